@@ -73,6 +73,9 @@ ISR_NOERR 45   ; IRQ 13 - FPU
 ISR_NOERR 46   ; IRQ 14 - ATA Primary
 ISR_NOERR 47   ; IRQ 15 - ATA Secondary
 
+; System call gate (int 0x80, DPL 3)
+ISR_NOERR 128
+
 ; Catch-all stub for uninitialized IDT entries (vectors 48-255).
 ; Pushes dummy error code + vector 0xFF, then goes through the normal handler
 ; so isr_handler() can send EOI / log and return safely.
@@ -1,9 +1,12 @@
 use core::mem;
 use crate::arch::x86_64::cpu;
 use crate::arch::x86_64::gdt::GDT_KERNEL_CODE;
+use crate::arch::x86_64::syscall::{self, SYSCALL_VECTOR};
+use crate::arch::x86_64::{pit, timer};
 
 // IDT gate types
 const IDT_INTERRUPT_GATE: u8 = 0x8E;
+const IDT_USER_TRAP_GATE: u8 = 0xEF;   // DPL 3: reachable with int from ring 3
 const IDT_ENTRIES: usize = 256;
 
 #[repr(C, packed)]
@@ -60,6 +63,9 @@ pub struct InterruptFrame {
 }
 
 pub type IrqHandler = fn(&mut InterruptFrame);
+/// Returns true if the exception was dealt with and execution may resume;
+/// false falls through to the default handling.
+pub type ExceptionHandler = fn(&mut InterruptFrame) -> bool;
 
 #[repr(C, align(16))]
 struct IdtTable {
@@ -72,6 +78,7 @@ static mut IDT: IdtTable = IdtTable {
 
 static mut IDTR: IdtDescriptor = IdtDescriptor { limit: 0, base: 0 };
 static mut IRQ_HANDLERS: [Option<IrqHandler>; 16] = [None; 16];
+static mut EXCEPTION_HANDLERS: [Option<ExceptionHandler>; 32] = [None; 32];
 
 // ISR stubs from isr.asm
 extern "C" {
@@ -87,6 +94,7 @@ extern "C" {
     fn isr_stub_36(); fn isr_stub_37(); fn isr_stub_38(); fn isr_stub_39();
     fn isr_stub_40(); fn isr_stub_41(); fn isr_stub_42(); fn isr_stub_43();
     fn isr_stub_44(); fn isr_stub_45(); fn isr_stub_46(); fn isr_stub_47();
+    fn isr_stub_128();
     fn unhandled_interrupt_stub();
 }
 
@@ -134,11 +142,17 @@ pub fn idt_init() {
         idt_set_entry(i as u8, catch_all, 0, IDT_INTERRUPT_GATE);
     }
 
+    // System calls (int 0x80)
+    idt_set_entry(SYSCALL_VECTOR, isr_stub_128 as *const () as u64, 0, IDT_USER_TRAP_GATE);
+
     unsafe {
         IDTR.limit = (mem::size_of::<[IdtEntry; IDT_ENTRIES]>() - 1) as u16;
         IDTR.base = IDT.entries.as_ptr() as u64;
         core::arch::asm!("lidt [{}]", in(reg) &IDTR, options(nostack, preserves_flags));
     }
+
+    // User-mode #DE/#UD/#GP kill the faulting process instead of halting
+    crate::proc::process::process_init_fault_handlers();
 }
 
 pub fn irq_register_handler(irq: u8, handler: IrqHandler) {
@@ -149,6 +163,14 @@ pub fn irq_register_handler(irq: u8, handler: IrqHandler) {
     }
 }
 
+pub fn exception_register_handler(vector: u8, handler: ExceptionHandler) {
+    if (vector as usize) < 32 {
+        unsafe {
+            EXCEPTION_HANDLERS[vector as usize] = Some(handler);
+        }
+    }
+}
+
 static EXCEPTION_NAMES: [&str; 32] = [
     "Division Error", "Debug", "NMI", "Breakpoint",
     "Overflow", "Bound Range", "Invalid Opcode", "Device Not Available",
@@ -160,6 +182,33 @@ static EXCEPTION_NAMES: [&str; 32] = [
     "Hypervisor Injection", "VMM Communication", "Security Exception", "Reserved",
 ];
 
+// Page-fault error code bits
+const PF_PRESENT: u64 = 1 << 0;   // 0 = page not present, 1 = protection violation
+const PF_WRITE: u64   = 1 << 1;
+const PF_USER: u64    = 1 << 2;
+const PF_RSVD: u64    = 1 << 3;
+const PF_FETCH: u64   = 1 << 4;
+
+/// Log a page fault; a ring-3 fault kills only the faulting process.
+/// Kernel faults return so the caller's panic path halts the machine.
+fn page_fault(frame: &InterruptFrame, from_user: bool) {
+    let addr = unsafe { cpu::read_cr2() };
+    let err = frame.error_code;
+    let pid = crate::proc::process::process_get_current().map(|p| p.pid).unwrap_or(0);
+    crate::serial_println!(
+        "[FAULT] Page fault at {:#x} (rip {:#x}, pid {}): {} {} from {} mode{}{}",
+        addr, frame.rip, pid,
+        if err & PF_FETCH != 0 { "fetch" } else if err & PF_WRITE != 0 { "write" } else { "read" },
+        if err & PF_PRESENT != 0 { "protection violation" } else { "of non-present page" },
+        if err & PF_USER != 0 { "user" } else { "kernel" },
+        if err & PF_RSVD != 0 { ", reserved bit set" } else { "" },
+        if from_user { ", killing process" } else { "" });
+
+    if from_user {
+        crate::proc::process::process_exit_current(-1);
+    }
+}
+
 /// Called from isr_common_stub in isr.asm
 #[no_mangle]
 pub extern "C" fn isr_handler(frame: *mut InterruptFrame) {
@@ -170,6 +219,16 @@ pub extern "C" fn isr_handler(frame: *mut InterruptFrame) {
         // CPU exception
         let from_user = (frame.cs & 3) != 0;
 
+        unsafe {
+            if let Some(handler) = EXCEPTION_HANDLERS[vector as usize] {
+                if handler(frame) { return; }
+            }
+        }
+
+        if vector == 14 {
+            page_fault(frame, from_user);
+        }
+
         if from_user {
             // Ring 3 fault — will be handled properly when process mgmt is implemented
             unsafe {
@@ -207,11 +266,22 @@ pub extern "C" fn isr_handler(frame: *mut InterruptFrame) {
     } else if vector >= 32 && vector < 48 {
         // Hardware IRQ
         let irq = (vector - 32) as u8;
+        crate::crypto::entropy::entropy_add_event(irq as u64);
+        if irq == 0 {
+            timer::timer_tick(pit::pit_get_uptime_ms());
+        }
         unsafe {
             if let Some(handler) = IRQ_HANDLERS[irq as usize] {
                 handler(frame);
             }
         }
+        if irq == 0 {
+            // Last, once the PIT handler has acknowledged the tick: this may
+            // switch to another process
+            crate::proc::scheduler::scheduler_tick();
+        }
+    } else if vector == SYSCALL_VECTOR as u64 {
+        syscall::syscall_dispatch(frame);
     } else {
         // Unhandled vector (48-255) — send EOI if it might be a spurious IRQ
         unsafe {
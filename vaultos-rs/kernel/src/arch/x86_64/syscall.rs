@@ -0,0 +1,96 @@
+// System call interface for VaultOS-RS
+//
+// User processes enter the kernel with `int 0x80` (a DPL 3 trap gate).
+// ABI: rax = syscall number (shared syscall_numbers), rdi/rsi/rdx = arguments;
+// the result comes back in rax, >= 0 on success or a negative VOS_ERR_* code.
+//
+//   SYS_DB_QUERY  (sql, len)   run one SQL statement -> rows in the result
+//   SYS_PROC_EXIT (code)       terminate the caller; does not return
+//   SYS_IO_WRITE  (buf, len)   write bytes to the serial console -> len
+//
+// Pointer arguments are user addresses. Every page of a buffer must lie in
+// the user half and be mapped in the caller's page table before it is read.
+
+use crate::arch::x86_64::idt::InterruptFrame;
+use crate::db::query::query_execute;
+use crate::drivers::serial;
+use crate::mm::layout::{USER_CODE_BASE, USER_STACK_TOP};
+use crate::mm::paging::{paging_virt_to_phys, PAGE_SIZE};
+use crate::proc::process;
+use vaultos_shared::error_codes::*;
+use vaultos_shared::syscall_numbers::{SYS_DB_QUERY, SYS_IO_WRITE, SYS_PROC_EXIT};
+
+pub const SYSCALL_VECTOR: u8 = 0x80;
+
+const SYS_WRITE_MAX: usize = 4096;
+const SYS_QUERY_MAX: usize = 512;
+
+/// True if [ptr, ptr+len) is user space and every page of it is mapped in `pml4`.
+fn user_range_ok(pml4: u64, ptr: u64, len: usize) -> bool {
+    let end = match ptr.checked_add(len as u64) {
+        Some(e) => e,
+        None => return false,
+    };
+    if ptr < USER_CODE_BASE || end > USER_STACK_TOP { return false; }
+
+    let mut page = ptr & !(PAGE_SIZE - 1);
+    while page < end {
+        if paging_virt_to_phys(pml4, page) == 0 { return false; }
+        page += PAGE_SIZE;
+    }
+    true
+}
+
+/// Borrow a validated user buffer of the current process.
+fn user_slice(ptr: u64, len: usize) -> Option<&'static [u8]> {
+    let proc = process::process_get_current()?;
+    if !user_range_ok(proc.page_table, ptr, len) { return None; }
+    // The caller's address space is still active inside the syscall
+    Some(unsafe { core::slice::from_raw_parts(ptr as *const u8, len) })
+}
+
+fn sys_exit(code: i32) -> i64 {
+    process::process_exit_current(code)
+}
+
+fn sys_write(buf: u64, len: usize) -> i64 {
+    if len > SYS_WRITE_MAX { return VOS_ERR_INVAL as i64; }
+    let bytes = match user_slice(buf, len) {
+        Some(b) => b,
+        None => return VOS_ERR_INVAL as i64,
+    };
+    for &b in bytes { unsafe { serial::serial_putchar(b); } }
+    len as i64
+}
+
+fn sys_query(sql: u64, len: usize) -> i64 {
+    if len == 0 || len > SYS_QUERY_MAX { return VOS_ERR_INVAL as i64; }
+    let bytes = match user_slice(sql, len) {
+        Some(b) => b,
+        None => return VOS_ERR_INVAL as i64,
+    };
+
+    // Copy out before parsing so the user can't change it mid-query
+    let mut buf = [0u8; SYS_QUERY_MAX];
+    buf[..len].copy_from_slice(bytes);
+    let text = match core::str::from_utf8(&buf[..len]) {
+        Ok(s) => s,
+        Err(_) => return VOS_ERR_INVAL as i64,
+    };
+
+    let pid = process::process_get_current().map(|p| p.pid).unwrap_or(0);
+    let result = query_execute(text, pid);
+    if result.error_code != VOS_OK { return result.error_code as i64; }
+    result.rows.len() as i64
+}
+
+/// Entry point from isr_handler for vector 0x80.
+pub fn syscall_dispatch(frame: &mut InterruptFrame) {
+    let ret = match frame.rax {
+        SYS_DB_QUERY  => sys_query(frame.rdi, frame.rsi as usize),
+        SYS_PROC_EXIT => sys_exit(frame.rdi as i32),
+        SYS_IO_WRITE  => sys_write(frame.rdi, frame.rsi as usize),
+        _ => VOS_ERR_NOSYS as i64,
+    };
+    frame.rax = ret as u64;
+}
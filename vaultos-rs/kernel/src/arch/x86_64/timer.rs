@@ -0,0 +1,104 @@
+// Periodic callbacks on the PIT tick
+//
+// Subsystems register a fn() with an interval; isr_handler calls
+// timer_tick() once per tick and every callback whose interval has elapsed
+// runs. Callbacks execute in interrupt context: they must be short, must
+// not block and must not allocate.
+//
+// Work that needs the heap or takes longer registers as deferred instead.
+// The tick only marks a deferred callback due; timer_run_deferred() runs
+// it later from task context, where the heap is safe to use.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use vaultos_shared::error_codes::*;
+
+pub type TimerCallback = fn();
+
+const MAX_TIMER_CALLBACKS: usize = 8;
+
+#[derive(Clone, Copy)]
+struct TimerSlot {
+    interval_ms: u64,
+    next_ms: u64,
+    callback: TimerCallback,
+    deferred: bool,
+    due: bool,
+}
+
+static mut TIMER_SLOTS: [Option<TimerSlot>; MAX_TIMER_CALLBACKS] = [None; MAX_TIMER_CALLBACKS];
+static mut TIMER_NOW_MS: u64 = 0;
+// Set while timer_run_deferred is running, so a process switched in
+// halfway through does not start the same callbacks again
+static DEFERRED_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Run `callback` every `interval_ms` (at least 1 ms) from the timer tick.
+/// Returns VOS_ERR_FULL when all slots are taken.
+pub fn pit_register_callback(interval_ms: u64, callback: TimerCallback) -> i32 {
+    register(interval_ms, callback, false)
+}
+
+/// Like pit_register_callback, but `callback` runs from timer_run_deferred
+/// rather than in interrupt context.
+pub fn pit_register_deferred(interval_ms: u64, callback: TimerCallback) -> i32 {
+    register(interval_ms, callback, true)
+}
+
+fn register(interval_ms: u64, callback: TimerCallback, deferred: bool) -> i32 {
+    let interval_ms = interval_ms.max(1);
+    unsafe {
+        for slot in TIMER_SLOTS.iter_mut() {
+            if slot.is_none() {
+                *slot = Some(TimerSlot {
+                    interval_ms,
+                    next_ms: TIMER_NOW_MS + interval_ms,
+                    callback,
+                    deferred,
+                    due: false,
+                });
+                return VOS_OK;
+            }
+        }
+    }
+    VOS_ERR_FULL
+}
+
+/// Called by isr_handler on every IRQ0, before the PIT handler runs and
+/// before the scheduler gets a chance to switch away.
+pub fn timer_tick(now_ms: u64) {
+    unsafe {
+        TIMER_NOW_MS = now_ms;
+        for slot in TIMER_SLOTS.iter_mut() {
+            let s = match slot {
+                Some(s) => s,
+                None => continue,
+            };
+            if now_ms < s.next_ms { continue; }
+            // Schedule from now so a missed tick doesn't cause a burst
+            s.next_ms = now_ms + s.interval_ms;
+            if s.deferred {
+                s.due = true;
+            } else {
+                (s.callback)();
+            }
+        }
+    }
+}
+
+/// Run the deferred callbacks the tick has marked due. Call from task
+/// context only, never from an interrupt handler.
+pub fn timer_run_deferred() {
+    if DEFERRED_RUNNING.swap(true, Ordering::Acquire) { return; }
+    for i in 0..MAX_TIMER_CALLBACKS {
+        let callback = unsafe {
+            match TIMER_SLOTS[i].as_mut() {
+                Some(s) if s.due => {
+                    s.due = false;
+                    s.callback
+                }
+                _ => continue,
+            }
+        };
+        callback();
+    }
+    DEFERRED_RUNNING.store(false, Ordering::Release);
+}
@@ -0,0 +1,196 @@
+// Capability delegation chains
+//
+// A delegated capability is a child of the capability it was cut from: it
+// names the same object, carries a subset of the parent's rights, and
+// records the parent in parent_cap_id (the parent_id column of
+// CapabilityTable). Revoking a parent can then find and revoke everything
+// delegated from it.
+//
+// Delegating needs CAP_GRANT on the parent, and the child can never hold a
+// right the parent lacks. The one widening allowed is of scope, not rights:
+// a system capability, which covers every object, may be narrowed to a
+// single object, which is how the kernel's root capability hands out table
+// access.
+//
+// Revocation follows the chain the other way. cap_revoke_tree marks a
+// capability and everything delegated from it, directly or further down,
+// as revoked. Independently of that, cap_check_chain refuses any capability
+// with a revoked ancestor, so a child issued after its parent was revoked
+// is dead on arrival.
+
+use alloc::vec::Vec;
+use crate::cap;
+use crate::db::database::{db_insert, db_set_table_next_id};
+use crate::db::query::query_run_with;
+use crate::db::record::{FieldValue, Record};
+use crate::error::{vos_result, VosError, VosResult};
+use vaultos_shared::capability_types::{Capability, CapObjectType, CAP_GRANT};
+use vaultos_shared::db_types::TABLE_ID_CAPABILITY;
+
+/// Give `new_owner_pid` a child of capability `parent_cap_id` with
+/// `subset_rights`. Returns the new cap_id.
+pub fn cap_delegate(parent_cap_id: u64, new_owner_pid: u64, subset_rights: u32) -> VosResult<u64> {
+    let object_id = cap::cap_table_lookup(parent_cap_id).ok_or(VosError::NotFound)?.object_id;
+    cap_delegate_on(parent_cap_id, object_id, new_owner_pid, subset_rights)
+}
+
+/// cap_delegate for a specific object. Unless the parent is a system
+/// capability, `object_id` must be the parent's own object.
+pub fn cap_delegate_on(parent_cap_id: u64, object_id: u64, new_owner_pid: u64,
+                       subset_rights: u32) -> VosResult<u64> {
+    let parent = cap::cap_table_lookup(parent_cap_id).ok_or(VosError::NotFound)?;
+    if parent.revoked { return Err(VosError::CapRevoked); }
+    if !cap::cap_validate(parent) { return Err(VosError::CapInvalid); }
+    if parent.rights & CAP_GRANT == 0 { return Err(VosError::Perm); }
+    // No escalation: every requested right must already be held
+    if subset_rights & !parent.rights != 0 { return Err(VosError::Perm); }
+
+    let object_type = if object_id == parent.object_id {
+        parent.object_type
+    } else if parent.object_type == CapObjectType::System {
+        CapObjectType::Table
+    } else {
+        return Err(VosError::Perm);
+    };
+
+    let child = cap::cap_create(object_id, object_type, new_owner_pid, subset_rights, parent_cap_id);
+    vos_result(cap::cap_table_insert(&child))?;
+    cap_persist(&child)?;
+    Ok(child.cap_id)
+}
+
+/// A live capability owned by `pid` that may delegate `rights` on table
+/// `object_id`: one on the table itself, else a system capability.
+pub fn cap_find_grantable(pid: u64, object_id: u64, rights: u32) -> Option<u64> {
+    let needed = rights | CAP_GRANT;
+    let mut fallback = None;
+    for id in 1..=cap::CAP_TABLE_SIZE as u64 {
+        let c = match cap::cap_table_lookup(id) { Some(c) => c, None => continue };
+        if c.owner_pid != pid || c.revoked || c.rights & needed != needed { continue; }
+        if !cap::cap_validate(c) { continue; }
+        if c.object_id == object_id && c.object_type == CapObjectType::Table { return Some(id); }
+        if c.object_type == CapObjectType::System && fallback.is_none() { fallback = Some(id); }
+    }
+    fallback
+}
+
+/// True if neither `cap_id` nor any capability it was delegated from has
+/// been revoked. Parents missing from the table end the walk.
+pub fn cap_chain_live(cap_id: u64) -> bool {
+    let mut id = cap_id;
+    // Bounded, so a corrupt parent loop cannot hang the caller
+    for _ in 0..cap::CAP_TABLE_SIZE {
+        let c = match cap::cap_table_lookup(id) { Some(c) => c, None => return true };
+        if c.revoked { return false; }
+        if c.parent_cap_id == 0 || c.parent_cap_id == id { return true; }
+        id = c.parent_cap_id;
+    }
+    false
+}
+
+/// cap::cap_check that also rejects capabilities whose delegation chain
+/// has been revoked anywhere above them. Only a capability on the object
+/// itself (same type and id) or a system capability counts; ids of
+/// different object types overlap, so a process capability on pid 3 says
+/// nothing about table 3.
+pub fn cap_check_chain(pid: u64, object_type: CapObjectType, object_id: u64, rights: u32) -> bool {
+    if pid == 0 { return true; }
+    (1..=cap::CAP_TABLE_SIZE as u64).any(|id| {
+        let c = match cap::cap_table_lookup(id) { Some(c) => c, None => return false };
+        c.owner_pid == pid
+            && (c.object_type == CapObjectType::System
+                || (c.object_type == object_type && c.object_id == object_id))
+            && c.rights & rights == rights
+            && cap::cap_validate(c)
+            && cap_chain_live(id)
+    })
+}
+
+/// May `pid` revoke `cap_id`? The kernel may revoke anything; a process may
+/// revoke capabilities it owns or that were delegated from one it owns.
+pub fn cap_may_revoke(pid: u64, cap_id: u64) -> bool {
+    if pid == 0 { return true; }
+    let mut id = cap_id;
+    for _ in 0..cap::CAP_TABLE_SIZE {
+        let c = match cap::cap_table_lookup(id) { Some(c) => c, None => return false };
+        if c.owner_pid == pid { return true; }
+        if c.parent_cap_id == 0 || c.parent_cap_id == id { return false; }
+        id = c.parent_cap_id;
+    }
+    false
+}
+
+/// Revoke `cap_id` alone, in memory and in CapabilityTable.
+pub fn cap_revoke_one(cap_id: u64) -> VosResult<()> {
+    let c = cap::cap_table_lookup(cap_id).ok_or(VosError::NotFound)?;
+    c.revoked = true;
+    cap_persist_revoked(cap_id)
+}
+
+/// Revoke `cap_id` and every capability delegated from it, at any depth.
+/// Returns how many were newly revoked.
+pub fn cap_revoke_tree(cap_id: u64) -> VosResult<u32> {
+    if cap::cap_table_lookup(cap_id).is_none() { return Err(VosError::NotFound); }
+    let mut pending: Vec<u64> = Vec::new();
+    let mut seen: Vec<u64> = Vec::new();
+    pending.push(cap_id);
+    let mut revoked = 0u32;
+    while let Some(id) = pending.pop() {
+        // A corrupt parent loop would otherwise come back round forever
+        if seen.contains(&id) { continue; }
+        seen.push(id);
+        let c = match cap::cap_table_lookup(id) { Some(c) => c, None => continue };
+        // Already-revoked capabilities are still walked: their children may not be
+        if !c.revoked {
+            c.revoked = true;
+            revoked += 1;
+        }
+        cap_persist_revoked(id)?;
+        for child in 1..=cap::CAP_TABLE_SIZE as u64 {
+            if let Some(cc) = cap::cap_table_lookup(child) {
+                if cc.parent_cap_id == id && child != id { pending.push(child); }
+            }
+        }
+    }
+    Ok(revoked)
+}
+
+/// Revoke every capability `owner_pid` holds, each with everything
+/// delegated from it, for when the owner itself is going away. Returns how
+/// many were newly revoked.
+pub fn cap_revoke_owned(owner_pid: u64) -> VosResult<u32> {
+    let mut revoked = 0u32;
+    for id in 1..=cap::CAP_TABLE_SIZE as u64 {
+        let owned = cap::cap_table_lookup(id).map_or(false, |c| c.owner_pid == owner_pid);
+        if owned { revoked += cap_revoke_tree(id)?; }
+    }
+    Ok(revoked)
+}
+
+fn cap_persist_revoked(cap_id: u64) -> VosResult<()> {
+    query_run_with("UPDATE CapabilityTable SET revoked = true WHERE cap_id = ?",
+                   &[FieldValue::U64(cap_id)], 0)?;
+    Ok(())
+}
+
+/// Write `c` to CapabilityTable, parent link included. The row is keyed by
+/// cap_id and keeps the capability's own owner and creation time, so it
+/// goes straight to the record layer rather than through INSERT, which
+/// would stamp both with the caller's.
+fn cap_persist(c: &Capability) -> VosResult<()> {
+    let mut rec = Record::new(TABLE_ID_CAPABILITY);
+    rec.row_id = c.cap_id;
+    rec.table_id = TABLE_ID_CAPABILITY;
+    rec.field_count = 7;
+    rec.set_u64(0, c.cap_id);
+    rec.set_u64(1, c.object_id);
+    rec.set_u64(2, c.owner_pid);
+    rec.set_u32(3, c.rights);
+    rec.set_u64(4, c.parent_cap_id);
+    rec.fields[5] = Some(FieldValue::Bool(false));
+    rec.set_u64(6, c.created_at);
+    let stored = db_insert(TABLE_ID_CAPABILITY, &mut rec);
+    // Later rows from db_next_id_for must not land on this cap_id
+    db_set_table_next_id(TABLE_ID_CAPABILITY, c.cap_id.saturating_add(1));
+    stored.map(|_| ())
+}
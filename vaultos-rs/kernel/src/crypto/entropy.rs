@@ -0,0 +1,65 @@
+// Timing entropy for reseeding the RNG
+//
+// Each event (hardware IRQ, key press, mouse movement) contributes the TSC
+// at the moment it was seen; the low bits jitter with interrupt and device
+// latency. isr_handler records every hardware IRQ, the PIT tick included.
+// Samples are staged cheaply, then folded into a 32-byte pool with
+// HMAC-SHA256 outside interrupt context. Every full stage reseeds the RNG, so
+// the software xorshift128+ path keeps drawing on fresh jitter instead of
+// running deterministically from its boot seed.
+
+use core::arch::asm;
+use crate::crypto::hmac::hmac_sha256;
+use crate::crypto::random;
+
+const STAGE_SAMPLES: usize = 64;
+
+static mut STAGE: [u64; STAGE_SAMPLES] = [0; STAGE_SAMPLES];
+static mut STAGE_LEN: usize = 0;
+static mut POOL: [u8; 32] = [0; 32];
+static mut RESEEDS: u64 = 0;
+
+fn rdtsc() -> u64 {
+    let lo: u32;
+    let hi: u32;
+    unsafe { asm!("rdtsc", out("eax") lo, out("edx") hi, options(nomem, nostack)); }
+    ((hi as u64) << 32) | lo as u64
+}
+
+/// Record one event. `data` is any event detail (key code, coordinates);
+/// it is mixed with the TSC. Cheap enough for interrupt context.
+pub fn entropy_add_event(data: u64) {
+    unsafe {
+        if STAGE_LEN >= STAGE_SAMPLES { return; } // full until the next poll
+        STAGE[STAGE_LEN] = rdtsc() ^ data.rotate_left(32);
+        STAGE_LEN += 1;
+    }
+}
+
+/// Fold a full stage into the pool and reseed the RNG. Call from a
+/// non-interrupt loop.
+pub fn entropy_poll() {
+    unsafe {
+        if STAGE_LEN < STAGE_SAMPLES { return; }
+
+        let mut bytes = [0u8; STAGE_SAMPLES * 8];
+        for (i, s) in STAGE.iter().enumerate() {
+            bytes[i * 8..i * 8 + 8].copy_from_slice(&s.to_le_bytes());
+        }
+        let mut next = [0u8; 32];
+        hmac_sha256(&POOL, &bytes, &mut next);
+        POOL = next;
+        STAGE_LEN = 0;
+
+        random::random_reseed(&POOL);
+        RESEEDS += 1;
+
+        for b in bytes.iter_mut() { *b = 0; }
+        for b in next.iter_mut() { *b = 0; }
+    }
+}
+
+/// Number of reseeds performed since boot.
+pub fn entropy_reseed_count() -> u64 {
+    unsafe { RESEEDS }
+}
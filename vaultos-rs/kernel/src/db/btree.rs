@@ -49,7 +49,9 @@ pub struct Btree {
 unsafe impl Send for Btree {}
 unsafe impl Sync for Btree {}
 
-pub type BtreeIterFn = fn(u64, *mut u8, *mut u8);
+/// Scan callback: (key, value, ctx). Return true to keep going, false to
+/// stop the scan after this key.
+pub type BtreeIterFn = fn(u64, *mut u8, *mut u8) -> bool;
 
 pub fn btree_init(tree: &mut Btree, table_id: u32) {
     tree.root = BtreeNode::new(true);
@@ -129,54 +131,59 @@ unsafe fn btree_split_child(parent: *mut BtreeNode, index: u32) {
     nn.dirty = true;
 }
 
-unsafe fn btree_insert_nonfull(node: *mut BtreeNode, key: u64, value: *mut u8) {
+/// Store `value` in an existing slot. Returns true if the key had no live
+/// value (lazily deleted), i.e. the key is being added back.
+unsafe fn btree_replace_value(n: &mut BtreeNode, i: usize, value: *mut u8) -> bool {
+    let revived = n.values[i].is_null();
+    n.values[i] = value;
+    n.value_lbas[i] = 0;
+    n.dirty = true;
+    revived
+}
+
+/// Returns true if `key` was not present before (count must grow).
+unsafe fn btree_insert_nonfull(node: *mut BtreeNode, key: u64, value: *mut u8) -> bool {
     let n = &mut *node;
     let mut i = n.num_keys as i32 - 1;
 
     if n.is_leaf {
+        // Check for duplicate key (update in place)
+        let mut k = 0usize;
+        while k < n.num_keys as usize && n.keys[k] < key { k += 1; }
+        if k < n.num_keys as usize && n.keys[k] == key {
+            return btree_replace_value(n, k, value);
+        }
         while i >= 0 && key < n.keys[i as usize] {
             n.keys[(i + 1) as usize] = n.keys[i as usize];
             n.values[(i + 1) as usize] = n.values[i as usize];
             n.value_lbas[(i + 1) as usize] = n.value_lbas[i as usize];
             i -= 1;
         }
-        // Check for duplicate key (update in place)
-        if i >= 0 && n.keys[i as usize] == key {
-            n.values[i as usize] = value;
-            n.value_lbas[i as usize] = 0;
-            n.dirty = true;
-            return;
-        }
         n.keys[(i + 1) as usize] = key;
         n.values[(i + 1) as usize] = value;
         n.value_lbas[(i + 1) as usize] = 0;
         n.num_keys += 1;
         n.dirty = true;
+        true
     } else {
         while i >= 0 && key < n.keys[i as usize] { i -= 1; }
         if i >= 0 && n.keys[i as usize] == key {
-            n.values[i as usize] = value;
-            n.value_lbas[i as usize] = 0;
-            n.dirty = true;
-            return;
+            return btree_replace_value(n, i as usize, value);
         }
         i += 1;
         if (*n.children[i as usize]).num_keys == BTREE_MAX_KEYS as u32 {
             btree_split_child(node, i as u32);
             if key > n.keys[i as usize] { i += 1; }
             if key == n.keys[i as usize] {
-                n.values[i as usize] = value;
-                n.value_lbas[i as usize] = 0;
-                n.dirty = true;
-                return;
+                return btree_replace_value(n, i as usize, value);
             }
         }
-        btree_insert_nonfull(n.children[i as usize], key, value);
+        btree_insert_nonfull(n.children[i as usize], key, value)
     }
 }
 
 pub fn btree_insert(tree: &mut Btree, key: u64, value: *mut u8) -> i32 {
-    unsafe {
+    let added = unsafe {
         let root = tree.root;
         if (*root).num_keys == BTREE_MAX_KEYS as u32 {
             let new_root = BtreeNode::new(false);
@@ -187,15 +194,15 @@ pub fn btree_insert(tree: &mut Btree, key: u64, value: *mut u8) -> i32 {
 
             let i = if key > (*new_root).keys[0] { 1 } else { 0 };
             if key == (*new_root).keys[0] {
-                (*new_root).values[0] = value;
+                btree_replace_value(&mut *new_root, 0, value)
             } else {
-                btree_insert_nonfull((*new_root).children[i], key, value);
+                btree_insert_nonfull((*new_root).children[i], key, value)
             }
         } else {
-            btree_insert_nonfull(root, key, value);
+            btree_insert_nonfull(root, key, value)
         }
-    }
-    tree.count += 1;
+    };
+    if added { tree.count += 1; }
     0
 }
 
@@ -219,7 +226,9 @@ pub fn btree_delete(tree: &mut Btree, key: u64) -> i32 {
                     tree.count -= 1;
                     return 0;
                 }
-                // Internal node: mark value NULL (lazy)
+                // Internal node: mark value NULL (lazy). Already NULL means
+                // the key was deleted before and is not in the tree.
+                if n.values[i as usize].is_null() { return -1; }
                 n.values[i as usize] = core::ptr::null_mut();
                 n.value_lbas[i as usize] = 0;
                 n.dirty = true;
@@ -234,27 +243,85 @@ pub fn btree_delete(tree: &mut Btree, key: u64) -> i32 {
     -1
 }
 
-unsafe fn btree_scan_node(node: *mut BtreeNode, callback: BtreeIterFn, ctx: *mut u8) {
-    if node.is_null() { return; }
+/// In-order walk; false as soon as the callback asks to stop.
+unsafe fn btree_scan_node(node: *mut BtreeNode, callback: BtreeIterFn, ctx: *mut u8) -> bool {
+    if node.is_null() { return true; }
     let n = &*node;
 
     for i in 0..n.num_keys as usize {
-        if !n.is_leaf {
-            btree_scan_node(n.children[i], callback, ctx);
+        if !n.is_leaf && !btree_scan_node(n.children[i], callback, ctx) {
+            return false;
         }
-        if !n.values[i].is_null() {
-            callback(n.keys[i], n.values[i], ctx);
+        if !n.values[i].is_null() && !callback(n.keys[i], n.values[i], ctx) {
+            return false;
         }
     }
     if !n.is_leaf {
-        btree_scan_node(n.children[n.num_keys as usize], callback, ctx);
+        return btree_scan_node(n.children[n.num_keys as usize], callback, ctx);
     }
+    true
 }
 
+/// Visit every live key in order until the callback returns false.
 pub fn btree_scan(tree: &Btree, callback: BtreeIterFn, ctx: *mut u8) {
     unsafe { btree_scan_node(tree.root, callback, ctx); }
 }
 
+// Smallest/largest key that still has a value. Deletes from internal nodes
+// only null the value and leaves may be left empty, so this is an in-order
+// walk that stops at the first live key: O(log n) unless deletes left holes.
+unsafe fn btree_first_live(node: *mut BtreeNode) -> Option<u64> {
+    if node.is_null() { return None; }
+    let n = &*node;
+    for i in 0..n.num_keys as usize {
+        if !n.is_leaf {
+            if let Some(k) = btree_first_live(n.children[i]) { return Some(k); }
+        }
+        if !n.values[i].is_null() { return Some(n.keys[i]); }
+    }
+    if n.is_leaf { return None; }
+    btree_first_live(n.children[n.num_keys as usize])
+}
+
+unsafe fn btree_last_live(node: *mut BtreeNode) -> Option<u64> {
+    if node.is_null() { return None; }
+    let n = &*node;
+    if !n.is_leaf {
+        if let Some(k) = btree_last_live(n.children[n.num_keys as usize]) { return Some(k); }
+    }
+    for i in (0..n.num_keys as usize).rev() {
+        if !n.values[i].is_null() { return Some(n.keys[i]); }
+        if !n.is_leaf {
+            if let Some(k) = btree_last_live(n.children[i]) { return Some(k); }
+        }
+    }
+    None
+}
+
+/// Smallest key in the tree, or None if it is empty.
+pub fn btree_min_key(tree: &Btree) -> Option<u64> {
+    unsafe { btree_first_live(tree.root) }
+}
+
+/// Largest key in the tree, or None if it is empty.
+pub fn btree_max_key(tree: &Btree) -> Option<u64> {
+    unsafe { btree_last_live(tree.root) }
+}
+
+fn btree_recount_cb(_key: u64, _value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe { *(ctx as *mut u64) += 1; }
+    true
+}
+
+/// Recompute `count` from the live keys (e.g. after loading from disk).
+/// Returns the new count.
+pub fn btree_recount(tree: &mut Btree) -> u64 {
+    let mut n: u64 = 0;
+    btree_scan(tree, btree_recount_cb, &mut n as *mut u64 as *mut u8);
+    tree.count = n;
+    n
+}
+
 unsafe fn btree_destroy_node(node: *mut BtreeNode) {
     if node.is_null() { return; }
     let n = &*node;
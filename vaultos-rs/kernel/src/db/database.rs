@@ -6,18 +6,27 @@
 // domain separation.
 
 use alloc::boxed::Box;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::fmt::Write;
 
+use crate::arch::x86_64::timer;
 use crate::crypto::aes::{AesCtx, aes_init, aes_cbc_encrypt, aes_cbc_decrypt,
                           aes_padded_size, aes_pkcs7_pad, aes_pkcs7_unpad, AES_BLOCK_SIZE};
 use crate::crypto::hmac::{HmacCtx, hmac_ctx_init, hmac_ctx_compute, hmac_sha256, hmac_verify};
 use crate::crypto::random::random_bytes;
-use crate::db::btree::{Btree, btree_init, btree_insert, btree_search, btree_delete, btree_scan};
+use crate::drivers::rtc;
+use crate::cap::delegation;
+use crate::error::{vos_result, VosError, VosResult};
+use crate::db::btree::{Btree, BtreeNode, btree_init, btree_insert, btree_search, btree_delete, btree_scan,
+                        btree_destroy, btree_max_key, btree_recount};
+use crate::db::field::field_as_u64;
 use crate::db::record::{Record, EncryptedRecord, FieldValue};
 use crate::db::record_serde::{record_serialize, record_deserialize};
 use crate::db::schema::{TableSchema, ColumnDef};
-use crate::storage::db_persist;
+use crate::storage::{db_persist, page_io};
+use crate::sync::spinlock::SpinLock;
 use vaultos_shared::db_types::*;
 use vaultos_shared::error_codes::*;
 
@@ -31,11 +40,66 @@ static mut TABLE_AES_KEYS: [Option<AesCtx>; MAX_TABLES] = [const { None }; MAX_T
 static mut TABLE_MAC_CTXS: [Option<HmacCtx>; MAX_TABLES] = [const { None }; MAX_TABLES];
 static mut TABLE_COUNT: u32 = 0;
 static mut GLOBAL_ROW_ID: u64 = 1;
+// Next primary key / B-tree key per table (see db_next_id_for)
+static mut NEXT_IDS: [u64; MAX_TABLES] = [1; MAX_TABLES];
 static mut MASTER_DB_KEY: [u8; 32] = [0u8; 32];
+// Keys for persisted B-tree node pages (shared by all tables)
+static mut INDEX_AES_KEY: Option<AesCtx> = None;
+static mut INDEX_MAC_CTX: Option<HmacCtx> = None;
+
+// In-memory secondary indexes (rebuilt from the primary trees, never persisted)
+pub const MAX_SECONDARY_INDEXES: usize = 16;
+static mut SECONDARY_INDEXES: [Option<SecondaryIndex>; MAX_SECONDARY_INDEXES] =
+    [const { None }; MAX_SECONDARY_INDEXES];
+
+// Per-column INSERT defaults. Declared in memory at table registration, not
+// persisted with the schema, so register_table_schemas re-declares them on
+// every boot.
+static mut COLUMN_DEFAULTS: [[Option<FieldValue>; MAX_COLUMNS]; MAX_TABLES] =
+    [const { [const { None }; MAX_COLUMNS] }; MAX_TABLES];
+
+// Automatic DML auditing into AuditTable
+static mut AUDIT_ENABLED: bool = true;
+static mut AUDIT_CALLER_PID: u64 = 0;
+static mut AUDIT_SUPPRESS: bool = false; // set while an UPDATE runs its delete+insert
+// How long an audit row lives before the expiry sweep drops it, in seconds.
+// Every DML statement adds one, so without a limit AuditTable grows without
+// bound.
+pub const AUDIT_RETENTION_SECS: u64 = 24 * 60 * 60;
+
+// Record expiry: any table with a U64 column of this name is swept
+pub const EXPIRES_COLUMN: &str = "expires";
+pub const EXPIRE_SWEEP_INTERVAL_MS: u64 = 5000;
+static mut EXPIRY_HOOKED: bool = false;
+
+// Results of the last db_verify_all (per-table MAC failures, and whether
+// the tree's row count disagreed with its live keys)
+static mut VERIFY_DONE: bool = false;
+static mut VERIFY_FAILURES: [u64; MAX_TABLES] = [0; MAX_TABLES];
+static mut VERIFY_COUNT_DRIFT: [bool; MAX_TABLES] = [false; MAX_TABLES];
+
+// MAC failures on normal reads (records and index pages) since boot.
+// Kept in memory only: it resets on every boot, while the tampered data it
+// reports stays on disk and is found again by the next read or scan.
+static mut MAC_FAILURES: u64 = 0;
 
 // Shared single-threaded buffers for encrypt/decrypt pipeline
-static mut SERDE_BUF: [u8; MAX_RECORD_SIZE] = [0u8; MAX_RECORD_SIZE];
-static mut CRYPTO_BUF: [u8; MAX_RECORD_SIZE + 16] = [0u8; { MAX_RECORD_SIZE + 16 }];
+// Guards CRYPTO_BUF and MAC_BUF for a whole encrypt or decrypt
+// pass, so a query run from interrupt context or a preempting task can't
+// overwrite them mid-record.
+//
+// Lock order: this lock is a leaf and comes after everything else in the
+// db layer. There is no btree or table lock today; one added later must be
+// taken before CRYPTO_LOCK, never while holding it. Finish any btree lookup
+// before taking it, and never call into the btree, secondary indexes,
+// persistence or query_execute while holding it. Interrupts are off while
+// it is held, so don't allocate or free either: buffers are sized up front
+// and anything built from the plaintext is built after the lock drops.
+static CRYPTO_LOCK: SpinLock<()> = SpinLock::new(());
+const CRYPTO_BUF_LEN: usize = MAX_RECORD_SIZE + AES_BLOCK_SIZE;
+static mut CRYPTO_BUF: [u8; CRYPTO_BUF_LEN] = [0u8; CRYPTO_BUF_LEN];
+// IV || ciphertext, the HMAC input
+static mut MAC_BUF: [u8; AES_BLOCK_SIZE + CRYPTO_BUF_LEN] = [0u8; AES_BLOCK_SIZE + CRYPTO_BUF_LEN];
 
 // ---------------------------------------------------------------------------
 // Key derivation (byte-identical to C)
@@ -80,6 +144,77 @@ fn derive_table_key(table_id: u32) {
     for b in derived.iter_mut() { *b = 0; }
 }
 
+/// Derive the node-page keys, domain-separated from the table keys.
+///   AES key = HMAC-SHA256(master_key, "IDXAES") -> first 16 bytes
+///   MAC key = HMAC-SHA256(master_key, "IDXMAC") -> full 32 bytes
+fn derive_index_key() {
+    let mut derived = [0u8; 32];
+    unsafe {
+        hmac_sha256(&MASTER_DB_KEY, b"IDXAES", &mut derived);
+        let mut aes_key = [0u8; 16];
+        aes_key.copy_from_slice(&derived[..16]);
+        let mut ctx = AesCtx { round_key: [0u8; 176] };
+        aes_init(&mut ctx, &aes_key);
+        INDEX_AES_KEY = Some(ctx);
+        for b in aes_key.iter_mut() { *b = 0; }
+
+        hmac_sha256(&MASTER_DB_KEY, b"IDXMAC", &mut derived);
+        let mut mac_ctx = HmacCtx::new();
+        hmac_ctx_init(&mut mac_ctx, &derived);
+        INDEX_MAC_CTX = Some(mac_ctx);
+    }
+    for b in derived.iter_mut() { *b = 0; }
+}
+
+/// MAC over a node page: HMAC(header || IV || ciphertext).
+fn index_page_mac(header: &[u8], iv: &[u8; 16], body: &[u8], out: &mut [u8; 32]) -> bool {
+    let mac_ctx = match unsafe { INDEX_MAC_CTX.as_ref() } {
+        Some(c) => c,
+        None => return false,
+    };
+    let mut mac_input = Vec::with_capacity(header.len() + AES_BLOCK_SIZE + body.len());
+    mac_input.extend_from_slice(header);
+    mac_input.extend_from_slice(iv);
+    mac_input.extend_from_slice(body);
+    hmac_ctx_compute(mac_ctx, &mac_input, out);
+    true
+}
+
+/// Encrypt a node page body in place (length a multiple of the AES block)
+/// and authenticate it together with the page's plaintext header.
+pub fn db_index_seal(header: &[u8], body: &mut [u8], iv: &mut [u8; 16], mac: &mut [u8; 32]) -> i32 {
+    if body.len() % AES_BLOCK_SIZE != 0 { return VOS_ERR_INVAL; }
+    let aes_ctx = match unsafe { INDEX_AES_KEY.as_ref() } {
+        Some(c) => c,
+        None => return VOS_ERR_INVAL,
+    };
+    random_bytes(iv);
+    let mut ct = vec![0u8; body.len()];
+    aes_cbc_encrypt(aes_ctx, iv, body, &mut ct, body.len());
+    body.copy_from_slice(&ct);
+    if !index_page_mac(header, iv, body, mac) { return VOS_ERR_INVAL; }
+    VOS_OK
+}
+
+/// Verify a sealed node page and decrypt its body in place.
+pub fn db_index_open(header: &[u8], body: &mut [u8], iv: &[u8; 16], mac: &[u8; 32]) -> i32 {
+    if body.len() % AES_BLOCK_SIZE != 0 { return VOS_ERR_INVAL; }
+    let aes_ctx = match unsafe { INDEX_AES_KEY.as_ref() } {
+        Some(c) => c,
+        None => return VOS_ERR_INVAL,
+    };
+    let mut computed = [0u8; 32];
+    if !index_page_mac(header, iv, body, &mut computed) || !hmac_verify(mac, &computed, 32) {
+        unsafe { MAC_FAILURES += 1; }
+        return VOS_ERR_PERM;
+    }
+    let mut pt = vec![0u8; body.len()];
+    aes_cbc_decrypt(aes_ctx, iv, body, &mut pt, body.len());
+    body.copy_from_slice(&pt);
+    for b in pt.iter_mut() { *b = 0; }
+    VOS_OK
+}
+
 // ---------------------------------------------------------------------------
 // Table creation (internal)
 // ---------------------------------------------------------------------------
@@ -208,13 +343,16 @@ fn register_table_schemas(verbose: bool) {
         s.set_name("MessageTable");
         s.encrypted = true;
         s.system_table = true;
-        s.column_count = 6;
+        s.column_count = 7;
         s.columns[0] = make_col("msg_id", ColumnType::U64, true, false);
         s.columns[1] = make_col("src_pid", ColumnType::U64, false, false);
         s.columns[2] = make_col("dst_pid", ColumnType::U64, false, false);
         s.columns[3] = make_col("type", ColumnType::Str, false, false);
         s.columns[4] = make_col("payload", ColumnType::Str, false, false);
+        // Private between sender and receiver: no ranges or LIKE probes
+        s.columns[4].sensitive = true;
         s.columns[5] = make_col("delivered", ColumnType::Bool, false, false);
+        s.columns[6] = make_col(EXPIRES_COLUMN, ColumnType::U64, false, false);
         db_create_table_impl(&s, verbose);
     }
 
@@ -224,15 +362,18 @@ fn register_table_schemas(verbose: bool) {
         s.set_name("AuditTable");
         s.encrypted = true;
         s.system_table = true;
-        s.column_count = 6;
+        s.column_count = 7;
         s.columns[0] = make_col("audit_id", ColumnType::U64, true, false);
         s.columns[1] = make_col("timestamp", ColumnType::U64, false, false);
         s.columns[2] = make_col("pid", ColumnType::U64, false, false);
         s.columns[3] = make_col("action", ColumnType::Str, false, false);
         s.columns[4] = make_col("target_id", ColumnType::U64, false, false);
         s.columns[5] = make_col("result", ColumnType::Str, false, false);
+        s.columns[6] = make_col(EXPIRES_COLUMN, ColumnType::U64, false, false);
         db_create_table_impl(&s, verbose);
     }
+
+    apply_system_table_extras();
 }
 
 // ---------------------------------------------------------------------------
@@ -248,9 +389,16 @@ pub fn db_init() {
             INDEXES[i] = None;
             TABLE_AES_KEYS[i] = None;
             TABLE_MAC_CTXS[i] = None;
+            for d in COLUMN_DEFAULTS[i].iter_mut() { *d = None; }
+        }
+        for i in 0..MAX_SECONDARY_INDEXES {
+            if let Some(mut idx) = SECONDARY_INDEXES[i].take() {
+                sec_index_destroy(&mut idx);
+            }
         }
         TABLE_COUNT = 0;
         GLOBAL_ROW_ID = 1;
+        NEXT_IDS = [1; MAX_TABLES];
     }
     crate::serial_println!("[DB] Database engine initialized (Encrypt-then-MAC enabled)");
 }
@@ -260,29 +408,36 @@ pub fn db_generate_master_key() {
     let mut key = [0u8; 32];
     random_bytes(&mut key);
     unsafe { MASTER_DB_KEY.copy_from_slice(&key); }
+    derive_index_key();
 }
 
 pub fn db_set_master_key(key: &[u8; 32]) {
     unsafe { MASTER_DB_KEY.copy_from_slice(key); }
+    derive_index_key();
 }
 
 pub fn db_get_master_key(out: &mut [u8; 32]) {
     unsafe { out.copy_from_slice(&MASTER_DB_KEY); }
 }
 
-/// Re-derive all per-table AES/MAC keys from current master key.
+/// Re-derive all per-table AES/MAC keys (and the node-page keys) from current master key.
 pub fn db_rederive_table_keys() {
     unsafe {
         for i in 0..TABLE_COUNT {
             derive_table_key(i);
         }
     }
+    derive_index_key();
 }
 
 /// Cold boot: register schemas, derive keys, init btrees, insert boot metadata.
 pub fn db_init_system_tables() {
     register_table_schemas(true);
+    // Boot metadata is not a user action; keep it out of the audit log
+    db_set_audit_enabled(false);
     db_insert_boot_metadata();
+    db_set_audit_enabled(true);
+    db_hook_expiry();
     unsafe {
         crate::serial_println!("[DB] System tables initialized");
     }
@@ -291,53 +446,49 @@ pub fn db_init_system_tables() {
 /// Warm boot: register schemas + derive keys WITHOUT inserting data.
 pub fn db_register_system_tables() {
     register_table_schemas(false);
+    db_hook_expiry();
+    // Trees the loader installed before registration; db_set_index covers
+    // the ones it installs afterwards
+    db_reconcile_row_ids();
+    // Counts on disk may be stale if the last session crashed mid-write
+    db_recover_counts();
     unsafe {
         crate::serial_println!("[DB] Registered system table schemas (warm boot)");
     }
 }
 
+fn boot_meta_record(key: &str, value: &str) -> Record {
+    let mut rec = Record::new(TABLE_ID_SYSTEM);
+    rec.row_id = db_next_id_for(TABLE_ID_SYSTEM);
+    rec.table_id = TABLE_ID_SYSTEM;
+    rec.field_count = 4;
+    rec.set_u64(0, rec.row_id);
+    rec.set_str(1, key);
+    rec.set_str(2, value);
+    rec.set_u64(3, rtc::rtc_unix_time());
+    rec
+}
+
+/// Column defaults and secondary indexes the system tables rely on. Neither
+/// is stored with the data, so they are declared again whenever the tables
+/// are (re)installed. A declaration whose column is missing is skipped.
+fn apply_system_table_extras() {
+    db_set_column_default(TABLE_ID_CAPABILITY, "revoked", FieldValue::Bool(false));
+    db_set_column_default(TABLE_ID_MESSAGE, "delivered", FieldValue::Bool(false));
+    // Object lookups go by name (Object Inspector, Table Browser, rm/open)
+    db_create_index(TABLE_ID_OBJECT, "name");
+}
+
 /// Insert initial boot metadata into SystemTable (cold boot).
 pub fn db_insert_boot_metadata() {
-    // os.name
-    {
-        let mut rec = Record::new(TABLE_ID_SYSTEM);
-        rec.row_id = db_next_row_id();
-        rec.table_id = TABLE_ID_SYSTEM;
-        rec.field_count = 4;
-        rec.set_u64(0, rec.row_id);
-        rec.set_str(1, "os.name");
-        rec.set_str(2, "VaultOS");
-        rec.set_u64(3, 0);
-        db_insert_record(TABLE_ID_SYSTEM, &mut rec);
-    }
-
-    // os.version
-    {
-        let mut rec = Record::new(TABLE_ID_SYSTEM);
-        rec.row_id = db_next_row_id();
-        rec.table_id = TABLE_ID_SYSTEM;
-        rec.field_count = 4;
-        rec.set_u64(0, rec.row_id);
-        rec.set_str(1, "os.version");
-        rec.set_str(2, "0.1.0");
-        rec.set_u64(3, 0);
-        db_insert_record(TABLE_ID_SYSTEM, &mut rec);
-    }
-
-    // os.philosophy
-    {
-        let mut rec = Record::new(TABLE_ID_SYSTEM);
-        rec.row_id = db_next_row_id();
-        rec.table_id = TABLE_ID_SYSTEM;
-        rec.field_count = 4;
-        rec.set_u64(0, rec.row_id);
-        rec.set_str(1, "os.philosophy");
-        rec.set_str(2, "Everything is a database and all data is confidential");
-        rec.set_u64(3, 0);
-        db_insert_record(TABLE_ID_SYSTEM, &mut rec);
-    }
-
-    crate::serial_println!("[DB] Boot metadata inserted (3 records)");
+    let mut recs = [
+        boot_meta_record("os.name", "VaultOS"),
+        boot_meta_record("os.version", "0.1.0"),
+        boot_meta_record("os.philosophy", "Everything is a database and all data is confidential"),
+    ];
+    let (count, _) = db_insert_records(TABLE_ID_SYSTEM, &mut recs);
+
+    crate::serial_println!("[DB] Boot metadata inserted ({} records)", count);
 }
 
 // ---------------------------------------------------------------------------
@@ -385,13 +536,301 @@ pub fn db_get_index(table_id: u32) -> Option<&'static mut Btree> {
 
 pub fn db_set_index(table_id: u32, tree: Btree) {
     if (table_id as usize) < MAX_TABLES {
+        // Keep the allocator off every block the loaded tree still uses
+        let root_lba = if tree.root.is_null() { 0 } else { unsafe { (*tree.root).disk_lba } };
+        if page_io::page_reserve_tree(root_lba) != VOS_OK {
+            crate::serial_println!("[DB] Table {}: could not walk every block of the loaded tree", table_id);
+        }
         unsafe { INDEXES[table_id as usize] = Some(tree); }
+        // A freshly loaded tree invalidates any secondary index over it
+        db_rebuild_indexes(table_id);
+        db_reconcile_row_ids();
     }
 }
 
+// ---------------------------------------------------------------------------
+// Secondary indexes
+// ---------------------------------------------------------------------------
+//
+// A secondary index maps a non-PK column value to the row_ids holding it.
+// Records are encrypted at rest, so the index is keyed by a hash of the
+// plaintext value and lives only in memory; it is rebuilt whenever the
+// primary tree is (re)loaded. Each B-tree value is a boxed Vec<u64> of
+// row_ids, and hash collisions are resolved by the caller re-checking the
+// decrypted record against its WHERE clause.
+
+pub struct SecondaryIndex {
+    pub table_id: u32,
+    pub column: u32,
+    pub tree: Btree,
+}
+
+/// FNV-1a over the field's canonical bytes. Integer variants are widened
+/// to u64 so a U32 column and a U64 literal hash identically. Reals hash
+/// their bit pattern, with -0.0 folded into 0.0 since the two compare equal.
+fn index_key(fv: &FieldValue) -> Option<u64> {
+    let mut tmp = [0u8; 8];
+    let bytes: &[u8] = match fv {
+        FieldValue::Str(s) => s.as_str().as_bytes(),
+        FieldValue::U64(v) => { tmp = v.to_le_bytes(); &tmp }
+        FieldValue::I64(v) => { tmp = (*v as u64).to_le_bytes(); &tmp }
+        FieldValue::U32(v) => { tmp = (*v as u64).to_le_bytes(); &tmp }
+        FieldValue::U8(v)  => { tmp = (*v as u64).to_le_bytes(); &tmp }
+        FieldValue::Bool(v) => { tmp = (*v as u64).to_le_bytes(); &tmp }
+        FieldValue::F64(v) => { tmp = (*v + 0.0).to_bits().to_le_bytes(); &tmp }
+        _ => return None,
+    };
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Some(hash)
+}
+
+fn sec_index_add(idx: &mut SecondaryIndex, rec: &Record) {
+    let fv = match rec.fields.get(idx.column as usize) {
+        Some(Some(f)) => f,
+        _ => return,
+    };
+    let key = match index_key(fv) { Some(k) => k, None => return };
+
+    let existing = btree_search(&idx.tree, key);
+    if existing.is_null() {
+        let ids: Box<Vec<u64>> = Box::new(vec![rec.row_id]);
+        btree_insert(&mut idx.tree, key, Box::into_raw(ids) as *mut u8);
+    } else {
+        let ids = unsafe { &mut *(existing as *mut Vec<u64>) };
+        if !ids.contains(&rec.row_id) { ids.push(rec.row_id); }
+    }
+}
+
+fn sec_index_remove(idx: &mut SecondaryIndex, rec: &Record) {
+    let fv = match rec.fields.get(idx.column as usize) {
+        Some(Some(f)) => f,
+        _ => return,
+    };
+    let key = match index_key(fv) { Some(k) => k, None => return };
+
+    let existing = btree_search(&idx.tree, key);
+    if existing.is_null() { return; }
+    let ids = unsafe { &mut *(existing as *mut Vec<u64>) };
+    ids.retain(|&id| id != rec.row_id);
+    if ids.is_empty() {
+        btree_delete(&mut idx.tree, key);
+        unsafe { drop(Box::from_raw(existing as *mut Vec<u64>)); }
+    }
+}
+
+fn sec_index_free_value(_key: u64, value: *mut u8, _ctx: *mut u8) -> bool {
+    unsafe { drop(Box::from_raw(value as *mut Vec<u64>)); }
+    true
+}
+
+fn sec_index_destroy(idx: &mut SecondaryIndex) {
+    btree_scan(&idx.tree, sec_index_free_value, core::ptr::null_mut());
+    btree_destroy(&mut idx.tree);
+}
+
+fn sec_index_build_callback(_key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let idx = &mut *(ctx as *mut SecondaryIndex);
+        if let Some(rec) = db_decrypt_record(idx.table_id, value) {
+            sec_index_add(idx, &rec);
+        }
+    }
+    true
+}
+
+/// Populate an index from the current contents of its table.
+fn sec_index_build(idx: &mut SecondaryIndex) {
+    unsafe {
+        let tree = match INDEXES[idx.table_id as usize].as_ref() {
+            Some(t) => t as *const Btree,
+            None => return,
+        };
+        btree_scan(&*tree, sec_index_build_callback, idx as *mut SecondaryIndex as *mut u8);
+    }
+}
+
+/// Create an in-memory secondary index on `column` of `table_id`.
+pub fn db_create_index(table_id: u32, column: &str) -> i32 {
+    let schema = match db_get_schema_by_id(table_id) {
+        Some(s) => s,
+        None => return VOS_ERR_INVAL,
+    };
+    let col = find_column_index(schema, column);
+    if col < 0 { return VOS_ERR_NOTFOUND; }
+    if schema.columns[col as usize].primary_key { return VOS_ERR_INVAL; }
+    if db_has_index(table_id, col as u32) { return VOS_ERR_EXISTS; }
+
+    unsafe {
+        let slot = match SECONDARY_INDEXES.iter().position(|s| s.is_none()) {
+            Some(i) => i,
+            None => return VOS_ERR_FULL,
+        };
+        let mut tree = Btree { root: core::ptr::null_mut(), count: 0, table_id };
+        btree_init(&mut tree, table_id);
+        let mut idx = SecondaryIndex { table_id, column: col as u32, tree };
+        sec_index_build(&mut idx);
+        SECONDARY_INDEXES[slot] = Some(idx);
+    }
+    VOS_OK
+}
+
+/// True if `column` of `table_id` has a secondary index.
+pub fn db_has_index(table_id: u32, column: u32) -> bool {
+    unsafe {
+        SECONDARY_INDEXES.iter().flatten()
+            .any(|idx| idx.table_id == table_id && idx.column == column)
+    }
+}
+
+/// Declare the value INSERT uses for `column` of `table_id` when the
+/// statement leaves it out. The value's type must match the column's.
+pub fn db_set_column_default(table_id: u32, column: &str, value: FieldValue) -> i32 {
+    let schema = match db_get_schema_by_id(table_id) {
+        Some(s) => s,
+        None => return VOS_ERR_INVAL,
+    };
+    let col = find_column_index(schema, column);
+    if col < 0 { return VOS_ERR_NOTFOUND; }
+    let col = col as usize;
+    if schema.columns[col].primary_key { return VOS_ERR_INVAL; }
+    let matches = match (&value, schema.columns[col].col_type) {
+        (FieldValue::U64(_), ColumnType::U64)
+        | (FieldValue::I64(_), ColumnType::I64)
+        | (FieldValue::U32(_), ColumnType::U32)
+        | (FieldValue::U8(_), ColumnType::U8)
+        | (FieldValue::F64(_), ColumnType::F64)
+        | (FieldValue::Bool(_), ColumnType::Bool)
+        | (FieldValue::Str(_), ColumnType::Str)
+        | (FieldValue::Blob(_), ColumnType::Blob) => true,
+        _ => false,
+    };
+    if !matches { return VOS_ERR_INVAL; }
+    unsafe { COLUMN_DEFAULTS[table_id as usize][col] = Some(value); }
+    VOS_OK
+}
+
+/// Default declared for column index `col`, or None if it has none.
+pub fn db_column_default(table_id: u32, col: usize) -> Option<&'static FieldValue> {
+    if table_id as usize >= MAX_TABLES || col >= MAX_COLUMNS { return None; }
+    unsafe { COLUMN_DEFAULTS[table_id as usize][col].as_ref() }
+}
+
+/// True if column index `col` of `table_id` holds secrets, so queries only
+/// test it for equality, in constant time.
+pub fn db_column_sensitive(table_id: u32, col: usize) -> bool {
+    match db_get_schema_by_id(table_id) {
+        Some(s) => col < s.column_count as usize && s.columns[col].sensitive,
+        None => false,
+    }
+}
+
+/// Drop and repopulate every secondary index over `table_id`.
+pub fn db_rebuild_indexes(table_id: u32) {
+    unsafe {
+        for slot in SECONDARY_INDEXES.iter_mut() {
+            if let Some(ref mut idx) = slot {
+                if idx.table_id != table_id { continue; }
+                sec_index_destroy(idx);
+                btree_init(&mut idx.tree, table_id);
+                sec_index_build(idx);
+            }
+        }
+    }
+}
+
+/// Candidate row_ids whose `column` may equal `value`.
+/// Returns None when the column is not indexed. Candidates can include
+/// hash collisions, so callers must re-check the decrypted record.
+pub fn db_index_lookup(table_id: u32, column: u32, value: &FieldValue) -> Option<Vec<u64>> {
+    unsafe {
+        let idx = SECONDARY_INDEXES.iter().flatten()
+            .find(|idx| idx.table_id == table_id && idx.column == column)?;
+        let key = match index_key(value) { Some(k) => k, None => return Some(Vec::new()) };
+        let ids = btree_search(&idx.tree, key);
+        if ids.is_null() { return Some(Vec::new()); }
+        Some((*(ids as *const Vec<u64>)).clone())
+    }
+}
+
+fn sec_indexes_on_insert(table_id: u32, rec: &Record) {
+    unsafe {
+        for idx in SECONDARY_INDEXES.iter_mut().flatten() {
+            if idx.table_id == table_id { sec_index_add(idx, rec); }
+        }
+    }
+}
+
+fn sec_indexes_on_delete(table_id: u32, rec: &Record) {
+    unsafe {
+        for idx in SECONDARY_INDEXES.iter_mut().flatten() {
+            if idx.table_id == table_id { sec_index_remove(idx, rec); }
+        }
+    }
+}
+
+fn table_has_indexes(table_id: u32) -> bool {
+    unsafe { SECONDARY_INDEXES.iter().flatten().any(|idx| idx.table_id == table_id) }
+}
+
 // ---------------------------------------------------------------------------
 // Row ID management
 // ---------------------------------------------------------------------------
+//
+// Every table numbers its rows from its own counter, and the id it hands out
+// is both the B-tree key and, where the first column is the primary key, the
+// PK value, so db_get_record(table, pk) keeps working. Keys only need to be
+// unique within their own tree.
+//
+// The counter is seeded from the largest key whenever a tree is installed, so
+// it never reuses an id still on disk. Persisting it too (superblock or export
+// image) also keeps ids of deleted rows at the top of the table from coming
+// back after a reboot.
+//
+// GLOBAL_ROW_ID is the older counter shared by all tables. It is still
+// persisted and exported, but nothing in the db layer keys records by it any
+// more; use db_next_id_for for new rows.
+
+/// Take the next id for a new row of `table_id`.
+pub fn db_next_id_for(table_id: u32) -> u64 {
+    if table_id as usize >= MAX_TABLES { return db_next_row_id(); }
+    unsafe {
+        let id = NEXT_IDS[table_id as usize];
+        NEXT_IDS[table_id as usize] += 1;
+        id
+    }
+}
+
+pub fn db_get_table_next_id(table_id: u32) -> u64 {
+    if table_id as usize >= MAX_TABLES { return 0; }
+    unsafe { NEXT_IDS[table_id as usize] }
+}
+
+/// Restore a persisted counter. It never moves backwards, so a stale value
+/// can't undo the seeding from the tree.
+pub fn db_set_table_next_id(table_id: u32, next_id: u64) {
+    if table_id as usize >= MAX_TABLES { return; }
+    unsafe {
+        let slot = &mut NEXT_IDS[table_id as usize];
+        if next_id > *slot { *slot = next_id; }
+    }
+}
+
+/// Move the counter past the largest key in the table's tree.
+fn seed_next_id(table_id: u32) {
+    let max = unsafe {
+        match INDEXES.get(table_id as usize).and_then(|t| t.as_ref()) {
+            Some(tree) => btree_max_key(tree),
+            None => return,
+        }
+    };
+    if let Some(max) = max {
+        db_set_table_next_id(table_id, max.saturating_add(1));
+    }
+}
 
 pub fn db_next_row_id() -> u64 {
     unsafe {
@@ -409,99 +848,312 @@ pub fn db_set_global_row_id(row_id: u64) {
     unsafe { GLOBAL_ROW_ID = row_id; }
 }
 
+/// Move GLOBAL_ROW_ID and every per-table counter past the largest key in
+/// the loaded trees. Call once persisted tables are in place on warm boot:
+/// until then the counters start from 1 and the next insert would reuse a
+/// row id that is already stored. Counters only ever move forward.
+pub fn db_reconcile_row_ids() {
+    let mut global_max = 0u64;
+    unsafe {
+        for t in 0..MAX_TABLES as u32 {
+            seed_next_id(t);
+            if let Some(max) = INDEXES[t as usize].as_ref().and_then(btree_max_key) {
+                global_max = global_max.max(max);
+            }
+        }
+    }
+    let next = global_max.saturating_add(1);
+    if next > db_get_global_row_id() {
+        db_set_global_row_id(next);
+        crate::serial_println!("[DB] Row ids resume at {}", next);
+    }
+    db_restore_next_ids();
+}
+
+// ---------------------------------------------------------------------------
+// Row id counters on disk
+// ---------------------------------------------------------------------------
+//
+// db_flush writes every table's counter into SystemTable, one row per table
+// keyed next_id.<table_id>, so the counters are committed together with the
+// table data. Seeding from btree_max_key alone would hand out again the ids
+// of rows deleted from the end of a table. db_reconcile_row_ids reads them
+// back; until SystemTable is loaded and keyed that finds nothing.
+
+const NEXT_ID_KEY_PREFIX: &str = "next_id.";
+
+struct NextIdCtx {
+    // (table id, saved counter, SystemTable row)
+    rows: Vec<(u32, u64, Record)>,
+}
+
+fn next_id_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    let ctx = unsafe { &mut *(ctx as *mut NextIdCtx) };
+    let rec = match db_decrypt_record(TABLE_ID_SYSTEM, value) {
+        Some(r) => r,
+        None => return true,
+    };
+    let saved = match (&rec.fields[1], &rec.fields[2]) {
+        (Some(FieldValue::Str(k)), Some(FieldValue::Str(v))) => {
+            match k.as_str().strip_prefix(NEXT_ID_KEY_PREFIX) {
+                Some(t) => t.parse::<u32>().ok().zip(v.as_str().parse::<u64>().ok()),
+                None => None,
+            }
+        }
+        _ => None,
+    };
+    if let Some((t, next)) = saved {
+        ctx.rows.push((t, next, rec));
+    }
+    true
+}
+
+fn saved_next_ids() -> Vec<(u32, u64, Record)> {
+    let mut ctx = NextIdCtx { rows: Vec::new() };
+    if let Some(tree) = db_get_index(TABLE_ID_SYSTEM) {
+        btree_scan(tree, next_id_scan_callback, &mut ctx as *mut NextIdCtx as *mut u8);
+    }
+    ctx.rows
+}
+
+/// Apply the counters db_flush saved. Like every restore they only move
+/// forward.
+fn db_restore_next_ids() {
+    for (t, next, _) in saved_next_ids() {
+        db_set_table_next_id(t, next);
+    }
+}
+
+/// Write each table's counter to its SystemTable row, skipping the ones
+/// that have not moved since the last flush.
+fn db_save_next_ids() {
+    if db_get_index(TABLE_ID_SYSTEM).is_none() { return; }
+    let mut saved = saved_next_ids();
+    // Bookkeeping, not a user action; keep it out of the audit log
+    let audit_was = db_audit_enabled();
+    db_set_audit_enabled(false);
+    for t in 0..db_get_table_count() {
+        let next = db_get_table_next_id(t);
+        let mut value = String::new();
+        let _ = write!(value, "{}", next);
+        match saved.iter_mut().find(|(st, _, _)| *st == t) {
+            Some((_, old, _)) if *old == next => {}
+            Some((_, _, rec)) => {
+                rec.set_str(2, &value);
+                rec.set_u64(3, rtc::rtc_unix_time());
+                let row_id = rec.row_id;
+                db_update_encrypted(TABLE_ID_SYSTEM, row_id, rec);
+            }
+            None => {
+                let mut key = String::new();
+                let _ = write!(key, "{}{}", NEXT_ID_KEY_PREFIX, t);
+                let mut rec = boot_meta_record(&key, &value);
+                db_insert_record(TABLE_ID_SYSTEM, &mut rec);
+            }
+        }
+    }
+    db_set_audit_enabled(audit_was);
+}
+
 // ---------------------------------------------------------------------------
 // Encrypt-then-MAC pipeline
 // ---------------------------------------------------------------------------
 
 /// Insert a record: serialize -> pad -> encrypt -> MAC -> store in btree.
 pub fn db_insert_record(table_id: u32, rec: &mut Record) -> i32 {
+    let err = db_insert_record_impl(table_id, rec);
+    audit_log("INSERT", table_id, rec.row_id, err);
+    err
+}
+
+fn db_insert_record_impl(table_id: u32, rec: &mut Record) -> i32 {
+    let enc = match encrypt_record(table_id, rec) {
+        Ok(e) => e,
+        Err(code) => return code,
+    };
     unsafe {
-        if table_id >= TABLE_COUNT { return VOS_ERR_INVAL; }
+        // Step 6: Store in B-tree
+        let tree = match INDEXES[table_id as usize].as_mut() {
+            Some(t) => t,
+            None => return VOS_ERR_INVAL,
+        };
+        btree_insert(tree, rec.row_id, Box::into_raw(enc) as *mut u8);
+    }
+    sec_indexes_on_insert(table_id, rec);
+    VOS_OK
+}
+
+/// Steps 1-5 of the insert pipeline: serialize -> pad -> encrypt -> MAC.
+/// Uses the shared CRYPTO_BUF and zeroes it before returning.
+fn encrypt_record(table_id: u32, rec: &Record) -> Result<Box<EncryptedRecord>, i32> {
+    if table_id >= db_get_table_count() { return Err(VOS_ERR_INVAL); }
+    // Step 3 ahead of time: allocate the encrypted record at its largest
+    // size before CRYPTO_LOCK turns interrupts off, trim it afterwards
+    let mut enc = Box::new(EncryptedRecord::new());
+    enc.ciphertext = vec![0u8; CRYPTO_BUF_LEN];
+    let padded_len = seal_record(table_id, rec, &mut enc)?;
+    enc.ciphertext.truncate(padded_len);
+    enc.ciphertext.shrink_to_fit();
+    Ok(enc)
+}
 
-        // Step 1: Serialize
-        let plain_len = record_serialize(rec, &mut SERDE_BUF);
-        if plain_len == 0 { return VOS_ERR_INVAL; }
+/// Serialize, pad, encrypt and MAC `rec` into `enc`, whose
+/// ciphertext is already CRYPTO_BUF_LEN long. Returns the padded length.
+fn seal_record(table_id: u32, rec: &Record, enc: &mut EncryptedRecord) -> Result<usize, i32> {
+    unsafe {
+        let _buffers = CRYPTO_LOCK.lock();
+
+        // Step 1: Serialize, compressed when that is smaller
+        // (0 means the record does not fit MAX_RECORD_SIZE)
+        let plain_len = record_serialize(rec, &mut CRYPTO_BUF[..MAX_RECORD_SIZE]);
+        if plain_len == 0 {
+            for b in CRYPTO_BUF[..MAX_RECORD_SIZE].iter_mut() { *b = 0; }
+            return Err(VOS_ERR_OVERFLOW);
+        }
 
         // Step 2: PKCS7 pad
         let padded_len = aes_padded_size(plain_len);
-        if padded_len > CRYPTO_BUF.len() { return VOS_ERR_INVAL; }
-        CRYPTO_BUF[..plain_len].copy_from_slice(&SERDE_BUF[..plain_len]);
+        if padded_len > CRYPTO_BUF.len() {
+            for i in 0..plain_len { CRYPTO_BUF[i] = 0; }
+            return Err(VOS_ERR_INVAL);
+        }
         aes_pkcs7_pad(&mut CRYPTO_BUF, plain_len, padded_len);
 
-        // Step 3: Allocate encrypted record
-        let mut enc = Box::new(EncryptedRecord::new());
-        enc.ciphertext = vec![0u8; padded_len];
-        enc.ciphertext_len = padded_len as u32;
-        enc.row_id = rec.row_id;
-        enc.table_id = table_id;
+        let result = seal_buffer(table_id, rec.row_id, padded_len, enc).map(|_| padded_len);
 
-        // Step 4: Random IV + AES-CBC encrypt
-        random_bytes(&mut enc.iv);
-        let aes_ctx = match TABLE_AES_KEYS[table_id as usize].as_ref() {
-            Some(c) => c,
-            None => return VOS_ERR_INVAL,
-        };
-        aes_cbc_encrypt(aes_ctx, &enc.iv, &CRYPTO_BUF[..padded_len],
-                         &mut enc.ciphertext, padded_len);
+        // Zero plaintext from the shared buffer
+        for i in 0..padded_len { CRYPTO_BUF[i] = 0; }
+        result
+    }
+}
 
-        // Step 5: HMAC-SHA256(IV || ciphertext)
-        let mac_ctx = match TABLE_MAC_CTXS[table_id as usize].as_ref() {
-            Some(c) => c,
-            None => return VOS_ERR_INVAL,
-        };
-        {
-            let mac_input_len = match AES_BLOCK_SIZE.checked_add(padded_len) {
-                Some(len) => len,
-                None => return VOS_ERR_INVAL, // Overflow
+/// Encrypt and MAC the first `padded_len` bytes of CRYPTO_BUF into `enc`.
+/// Caller holds CRYPTO_LOCK.
+unsafe fn seal_buffer(table_id: u32, row_id: u64, padded_len: usize,
+                      enc: &mut EncryptedRecord) -> Result<(), i32> {
+    enc.ciphertext_len = padded_len as u32;
+    enc.row_id = row_id;
+    enc.table_id = table_id;
+
+    // Step 4: Random IV + AES-CBC encrypt
+    random_bytes(&mut enc.iv);
+    let aes_ctx = match TABLE_AES_KEYS[table_id as usize].as_ref() {
+        Some(c) => c,
+        None => return Err(VOS_ERR_INVAL),
+    };
+    aes_cbc_encrypt(aes_ctx, &enc.iv, &CRYPTO_BUF[..padded_len],
+                     &mut enc.ciphertext[..padded_len], padded_len);
+
+    // Step 5: HMAC-SHA256(IV || ciphertext)
+    let mac_ctx = match TABLE_MAC_CTXS[table_id as usize].as_ref() {
+        Some(c) => c,
+        None => return Err(VOS_ERR_INVAL),
+    };
+    let mac_input_len = AES_BLOCK_SIZE + padded_len;
+    MAC_BUF[..AES_BLOCK_SIZE].copy_from_slice(&enc.iv);
+    MAC_BUF[AES_BLOCK_SIZE..mac_input_len].copy_from_slice(&enc.ciphertext[..padded_len]);
+    hmac_ctx_compute(mac_ctx, &MAC_BUF[..mac_input_len], &mut enc.mac);
+    // Zero mac input
+    for i in 0..mac_input_len { MAC_BUF[i] = 0; }
+
+    Ok(())
+}
+
+/// Insert a batch of records into one table.
+/// Every record goes through the same encrypt+MAC pipeline as a single
+/// insert (same ciphertext format); the B-tree inserts are then done in
+/// row_id order once all records are sealed. Returns (inserted, first_error),
+/// where first_error is VOS_OK if every record went in.
+pub fn db_insert_records(table_id: u32, recs: &mut [Record]) -> (u32, i32) {
+    unsafe {
+        if table_id >= TABLE_COUNT { return (0, VOS_ERR_INVAL); }
+    }
+
+    let mut first_err = VOS_OK;
+    let mut sealed: Vec<(usize, Box<EncryptedRecord>)> = Vec::with_capacity(recs.len());
+    for (i, rec) in recs.iter().enumerate() {
+        match encrypt_record(table_id, rec) {
+            Ok(enc) => sealed.push((i, enc)),
+            Err(code) => {
+                if first_err == VOS_OK { first_err = code; }
+                audit_log("INSERT", table_id, rec.row_id, code);
+            }
+        }
+    }
+    sealed.sort_unstable_by_key(|(_, enc)| enc.row_id);
+
+    let mut inserted = 0u32;
+    for (i, enc) in sealed {
+        unsafe {
+            let tree = match INDEXES[table_id as usize].as_mut() {
+                Some(t) => t,
+                None => return (inserted, VOS_ERR_INVAL),
             };
-            let mut mac_input = vec![0u8; mac_input_len];
-            mac_input[..AES_BLOCK_SIZE].copy_from_slice(&enc.iv);
-            mac_input[AES_BLOCK_SIZE..].copy_from_slice(&enc.ciphertext[..padded_len]);
-            hmac_ctx_compute(mac_ctx, &mac_input, &mut enc.mac);
-            // Zero mac input
-            for b in mac_input.iter_mut() { *b = 0; }
+            btree_insert(tree, recs[i].row_id, Box::into_raw(enc) as *mut u8);
         }
+        sec_indexes_on_insert(table_id, &recs[i]);
+        audit_log("INSERT", table_id, recs[i].row_id, VOS_OK);
+        inserted += 1;
+    }
+    (inserted, first_err)
+}
 
-        // Step 6: Store in B-tree
-        let enc_ptr = Box::into_raw(enc) as *mut u8;
-        let tree = match INDEXES[table_id as usize].as_mut() {
-            Some(t) => t,
-            None => return VOS_ERR_INVAL,
+/// Recompute HMAC-SHA256(IV || ciphertext) and compare it with the stored MAC.
+/// Caller holds CRYPTO_LOCK.
+fn record_mac_ok(table_id: u32, enc: &EncryptedRecord) -> bool {
+    unsafe {
+        let mac_ctx = match TABLE_MAC_CTXS[table_id as usize].as_ref() {
+            Some(c) => c,
+            None => return false,
         };
-        btree_insert(tree, rec.row_id, enc_ptr);
+        let ct_len = enc.ciphertext_len as usize;
+        if ct_len > enc.ciphertext.len() || ct_len > CRYPTO_BUF_LEN { return false; }
+        let mac_input_len = AES_BLOCK_SIZE + ct_len;
 
-        // Zero plaintext from shared buffers
-        for i in 0..plain_len { SERDE_BUF[i] = 0; }
-        for i in 0..padded_len { CRYPTO_BUF[i] = 0; }
+        let mut computed_mac = [0u8; 32];
+        MAC_BUF[..AES_BLOCK_SIZE].copy_from_slice(&enc.iv);
+        MAC_BUF[AES_BLOCK_SIZE..mac_input_len].copy_from_slice(&enc.ciphertext[..ct_len]);
+        hmac_ctx_compute(mac_ctx, &MAC_BUF[..mac_input_len], &mut computed_mac);
+        for i in 0..mac_input_len { MAC_BUF[i] = 0; }
+
+        let ok = hmac_verify(&enc.mac, &computed_mac, 32);
+        for b in computed_mac.iter_mut() { *b = 0; }
+        ok
     }
-    VOS_OK
 }
 
 /// Verify-then-decrypt pipeline.
 /// The `encrypted_value` pointer comes from btree_search (points to EncryptedRecord).
 pub fn db_decrypt_record(table_id: u32, encrypted_value: *mut u8) -> Option<Record> {
+    if encrypted_value.is_null() || table_id >= db_get_table_count() { return None; }
+    // The plaintext leaves the shared buffers through here, so the Record
+    // (which allocates) is built after CRYPTO_LOCK is released
+    let mut plain = [0u8; MAX_RECORD_SIZE];
+    let result = match open_record(table_id, encrypted_value, &mut plain) {
+        Some(n) => record_deserialize(&plain[..n]),
+        None => None,
+    };
+    for b in plain.iter_mut() { *b = 0; }
+
+    match result {
+        Some((rec, _consumed)) => Some(rec),
+        None => None,
+    }
+}
+
+/// Verify, decrypt and unpad one record into `plain`.
+/// Returns the serialized length.
+fn open_record(table_id: u32, encrypted_value: *mut u8, plain: &mut [u8; MAX_RECORD_SIZE]) -> Option<usize> {
     unsafe {
-        if encrypted_value.is_null() || table_id >= TABLE_COUNT { return None; }
         let enc = &*(encrypted_value as *const EncryptedRecord);
+        let _buffers = CRYPTO_LOCK.lock();
 
         // Step 1: Verify HMAC
-        let mac_ctx = TABLE_MAC_CTXS[table_id as usize].as_ref()?;
-        let mut computed_mac = [0u8; 32];
-        {
-            let mac_input_len = AES_BLOCK_SIZE.checked_add(enc.ciphertext_len as usize)?;
-            let mut mac_input = vec![0u8; mac_input_len];
-            mac_input[..AES_BLOCK_SIZE].copy_from_slice(&enc.iv);
-            mac_input[AES_BLOCK_SIZE..].copy_from_slice(&enc.ciphertext[..enc.ciphertext_len as usize]);
-            hmac_ctx_compute(mac_ctx, &mac_input, &mut computed_mac);
-            for b in mac_input.iter_mut() { *b = 0; }
-        }
-
-        if !hmac_verify(&enc.mac, &computed_mac, 32) {
+        if !record_mac_ok(table_id, enc) {
             crate::serial_println!("[DB] MAC verification failed!");
-            for b in computed_mac.iter_mut() { *b = 0; }
+            MAC_FAILURES += 1;
             return None;
         }
-        for b in computed_mac.iter_mut() { *b = 0; }
 
         // Step 2: AES-CBC decrypt
         let ct_len = enc.ciphertext_len as usize;
@@ -517,14 +1169,14 @@ pub fn db_decrypt_record(table_id: u32, encrypted_value: *mut u8) -> Option<Reco
             return None;
         }
 
-        // Step 4: Deserialize
-        let result = record_deserialize(&CRYPTO_BUF[..plain_len]);
+        let len = if plain_len <= MAX_RECORD_SIZE {
+            plain[..plain_len].copy_from_slice(&CRYPTO_BUF[..plain_len]);
+            Some(plain_len)
+        } else {
+            None
+        };
         for i in 0..ct_len { CRYPTO_BUF[i] = 0; }
-
-        match result {
-            Some((rec, _consumed)) => Some(rec),
-            None => None,
-        }
+        len
     }
 }
 
@@ -541,8 +1193,22 @@ pub fn db_get_record(table_id: u32, row_id: u64) -> Option<Record> {
 
 /// Delete a record by row_id.
 pub fn db_delete_record(table_id: u32, row_id: u64) -> i32 {
+    let err = db_delete_record_impl(table_id, row_id);
+    audit_log("DELETE", table_id, row_id, err);
+    err
+}
+
+fn db_delete_record_impl(table_id: u32, row_id: u64) -> i32 {
     unsafe {
         if table_id >= TABLE_COUNT { return VOS_ERR_INVAL; }
+
+        // Secondary indexes are keyed by plaintext, so decrypt before removal
+        if table_has_indexes(table_id) {
+            if let Some(old) = db_get_record(table_id, row_id) {
+                sec_indexes_on_delete(table_id, &old);
+            }
+        }
+
         let tree = match INDEXES[table_id as usize].as_mut() {
             Some(t) => t,
             None => return VOS_ERR_INVAL,
@@ -554,7 +1220,8 @@ pub fn db_delete_record(table_id: u32, row_id: u64) -> i32 {
         btree_delete(tree, row_id);
 
         // Zero and free encrypted data
-        let enc = Box::from_raw(enc_ptr as *mut EncryptedRecord);
+        let mut enc = Box::from_raw(enc_ptr as *mut EncryptedRecord);
+        for b in enc.ciphertext.iter_mut() { *b = 0; }
         // ciphertext Vec is dropped automatically; mac/iv zeroed on drop
         drop(enc);
     }
@@ -567,17 +1234,806 @@ pub fn db_update_encrypted(table_id: u32, row_id: u64, modified: &mut Record) ->
         if table_id >= TABLE_COUNT { return VOS_ERR_INVAL; }
     }
 
-    let err = db_delete_record(table_id, row_id);
-    if err != VOS_OK { return err; }
+    unsafe { AUDIT_SUPPRESS = true; }
+    let mut err = db_delete_record(table_id, row_id);
+    if err == VOS_OK {
+        modified.row_id = row_id;
+        modified.table_id = table_id;
+        err = db_insert_record(table_id, modified);
+    }
+    unsafe { AUDIT_SUPPRESS = false; }
+
+    audit_log("UPDATE", table_id, row_id, err);
+    err
+}
+
+// ---------------------------------------------------------------------------
+// Compaction
+// ---------------------------------------------------------------------------
+
+fn compact_collect_callback(key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let live = &mut *(ctx as *mut Vec<(u64, *mut u8)>);
+        live.push((key, value));
+    }
+    true
+}
+
+/// What compaction needs from the tree it replaces.
+struct CompactWalk {
+    // Disk block of every node that has been written out
+    node_blocks: Vec<u64>,
+    // (key, record block) of each live key already on disk, sorted by key
+    live_lbas: Vec<(u64, u64)>,
+}
+
+unsafe fn compact_walk(node: *mut BtreeNode, walk: &mut CompactWalk) {
+    if node.is_null() { return; }
+    let n = &*node;
+    if n.disk_lba != 0 { walk.node_blocks.push(n.disk_lba); }
+    for i in 0..n.num_keys as usize {
+        if !n.values[i].is_null() && n.value_lbas[i] != 0 {
+            walk.live_lbas.push((n.keys[i], n.value_lbas[i]));
+        }
+    }
+    if !n.is_leaf {
+        for i in 0..=n.num_keys as usize {
+            compact_walk(n.children[i], walk);
+        }
+    }
+}
+
+/// Give the rebuilt tree's keys the record blocks they already had, so live
+/// records are not written out again. Returns the number of nodes.
+unsafe fn compact_restore_lbas(node: *mut BtreeNode, live_lbas: &[(u64, u64)]) -> u64 {
+    if node.is_null() { return 0; }
+    let n = &mut *node;
+    for i in 0..n.num_keys as usize {
+        if let Ok(j) = live_lbas.binary_search_by_key(&n.keys[i], |&(k, _)| k) {
+            n.value_lbas[i] = live_lbas[j].1;
+        }
+    }
+    let mut nodes = 1;
+    if !n.is_leaf {
+        for i in 0..=n.num_keys as usize {
+            nodes += compact_restore_lbas(n.children[i], live_lbas);
+        }
+    }
+    nodes
+}
+
+/// Queue the blocks the committed tree holds beyond the live records: every
+/// old node page, and each record chain those pages point at that no live
+/// key still uses (deleted or since-updated rows). Returns blocks queued.
+fn compact_release_blocks(walk: &CompactWalk) -> u64 {
+    let mut live: Vec<u64> = walk.live_lbas.iter().map(|&(_, lba)| lba).collect();
+    live.sort_unstable();
+    let mut queued = 0u64;
+    for &block in walk.node_blocks.iter() {
+        let mut page = unsafe { Box::from_raw(BtreeNode::new(true)) };
+        let mut page_table = 0u8;
+        if page_io::page_read_node(block, &mut page, &mut page_table) == VOS_OK {
+            for i in 0..page.num_keys as usize {
+                let lba = page.value_lbas[i];
+                if lba != 0 && live.binary_search(&lba).is_err() {
+                    queued += page_io::page_free_record_after_commit(lba);
+                }
+            }
+        }
+        if page_io::page_free_after_commit(block) { queued += 1; }
+    }
+    queued
+}
+
+/// Rebuild a table's B-tree from its live records only.
+/// Lazily deleted internal slots (NULL values) and the nodes holding them
+/// are dropped; the EncryptedRecords themselves are moved, not re-encrypted,
+/// and keep their record blocks. The disk blocks the old tree no longer
+/// needs are freed after the next successful db_flush. Returns the disk
+/// bytes given back (those blocks less the ones the new tree's nodes will
+/// take when flushed) and VOS_OK, or (0, error).
+pub fn db_compact_table(table_id: u32) -> (u64, i32) {
+    unsafe {
+        if table_id >= TABLE_COUNT { return (0, VOS_ERR_INVAL); }
+        let old = match INDEXES[table_id as usize].as_mut() {
+            Some(t) => t,
+            None => return (0, VOS_ERR_INVAL),
+        };
+
+        let mut walk = CompactWalk { node_blocks: Vec::new(), live_lbas: Vec::new() };
+        compact_walk(old.root, &mut walk);
+        walk.live_lbas.sort_unstable();
+
+        let mut live: Vec<(u64, *mut u8)> = Vec::new();
+        btree_scan(old, compact_collect_callback, &mut live as *mut Vec<(u64, *mut u8)> as *mut u8);
+
+        let mut tree = Btree { root: core::ptr::null_mut(), count: 0, table_id };
+        btree_init(&mut tree, table_id);
+        for &(key, value) in live.iter() {
+            btree_insert(&mut tree, key, value);
+        }
+        tree.count = live.len() as u64;
+        let new_nodes = compact_restore_lbas(tree.root, &walk.live_lbas);
+
+        let released = compact_release_blocks(&walk);
+
+        // Only the old nodes go; their values now belong to the new tree
+        btree_destroy(old);
+        INDEXES[table_id as usize] = Some(tree);
+        (released.saturating_sub(new_nodes) * page_io::PAGE_BLOCK_SIZE, VOS_OK)
+    }
+}
+
+/// Drop every record of a table at once: the EncryptedRecords are zeroed and
+/// freed, the B-tree is replaced with an empty one and its secondary indexes
+/// are rebuilt. Returns the number of records removed and VOS_OK, or
+/// (0, error). Callers decide whether system tables may be truncated.
+pub fn db_truncate_table(table_id: u32) -> (u64, i32) {
+    let removed = unsafe {
+        if table_id >= TABLE_COUNT { return (0, VOS_ERR_INVAL); }
+        let old = match INDEXES[table_id as usize].as_mut() {
+            Some(t) => t,
+            None => return (0, VOS_ERR_INVAL),
+        };
+        let removed = old.count;
+        btree_scan(old, free_encrypted_callback, core::ptr::null_mut());
+        btree_destroy(old);
+
+        let mut tree = Btree { root: core::ptr::null_mut(), count: 0, table_id };
+        btree_init(&mut tree, table_id);
+        INDEXES[table_id as usize] = Some(tree);
+        removed
+    };
+    db_rebuild_indexes(table_id);
+    audit_log("TRUNCATE", table_id, 0, VOS_OK);
+    (removed, VOS_OK)
+}
+
+// ---------------------------------------------------------------------------
+// Integrity check
+// ---------------------------------------------------------------------------
+
+struct VerifyCtx {
+    table_id: u32,
+    checked: u64,
+    failed: u64,
+}
+
+fn verify_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let ctx = &mut *(ctx as *mut VerifyCtx);
+        let enc = &*(value as *const EncryptedRecord);
+        ctx.checked += 1;
+        let _buffers = CRYPTO_LOCK.lock();
+        if !record_mac_ok(ctx.table_id, enc) { ctx.failed += 1; }
+    }
+    true
+}
+
+/// Check the HMAC of every stored record without decrypting any of them.
+/// Returns (checked, failed); per-table failures are kept for
+/// db_verify_failures(). Read-only: a row count that disagrees with the
+/// tree is reported through db_verify_count_drift, not corrected.
+pub fn db_verify_all() -> (u64, u64) {
+    let mut checked = 0u64;
+    let mut failed = 0u64;
+    unsafe {
+        for t in 0..TABLE_COUNT {
+            let mut ctx = VerifyCtx { table_id: t, checked: 0, failed: 0 };
+            let mut drift = false;
+            if let Some(tree) = INDEXES[t as usize].as_ref() {
+                btree_scan(tree, verify_scan_callback, &mut ctx as *mut VerifyCtx as *mut u8);
+                // The scan visits every live key, so it doubles as a count check
+                if tree.count != ctx.checked {
+                    crate::serial_println!("[DB] Table {} count drift: tree says {}, found {}",
+                                           t, tree.count, ctx.checked);
+                    drift = true;
+                }
+            }
+            VERIFY_FAILURES[t as usize] = ctx.failed;
+            VERIFY_COUNT_DRIFT[t as usize] = drift;
+            checked += ctx.checked;
+            failed += ctx.failed;
+        }
+        VERIFY_DONE = true;
+    }
+    crate::serial_println!("[DB] Integrity check: {} records, {} MAC failures", checked, failed);
+    (checked, failed)
+}
+
+/// Failed MAC checks on record and index-page reads since boot. Per-boot:
+/// the counter is not persisted. db_verify_all scans are not included, so
+/// repeated integrity checks don't inflate it; see db_verify_failures.
+pub fn db_mac_failure_count() -> u64 {
+    unsafe { MAC_FAILURES }
+}
+
+/// MAC failures found in `table_id` by the last db_verify_all,
+/// or None if no check has run yet.
+pub fn db_verify_failures(table_id: u32) -> Option<u64> {
+    unsafe {
+        if !VERIFY_DONE || table_id as usize >= MAX_TABLES { return None; }
+        Some(VERIFY_FAILURES[table_id as usize])
+    }
+}
+
+/// True if the last db_verify_all found `table_id`'s row count out of step
+/// with its live keys. db_recover_counts repairs it.
+pub fn db_verify_count_drift(table_id: u32) -> bool {
+    unsafe { VERIFY_DONE && (table_id as usize) < MAX_TABLES && VERIFY_COUNT_DRIFT[table_id as usize] }
+}
+
+/// Recovery step: recount every table's tree from its live keys, so
+/// row counts are right again after a crash or a stale on-disk count.
+/// Returns how many tables had drifted.
+pub fn db_recover_counts() -> u32 {
+    let mut fixed = 0u32;
+    unsafe {
+        for t in 0..TABLE_COUNT as usize {
+            if let Some(tree) = INDEXES[t].as_mut() {
+                let was = tree.count;
+                if btree_recount(tree) != was {
+                    crate::serial_println!("[DB] Table {} count recovered: {} -> {}", t, was, tree.count);
+                    fixed += 1;
+                }
+            }
+            VERIFY_COUNT_DRIFT[t] = false;
+        }
+    }
+    fixed
+}
+
+// ---------------------------------------------------------------------------
+// Record expiry
+// ---------------------------------------------------------------------------
+//
+// Tables opt in by having an `expires` U64 column holding RTC unix seconds,
+// so a deadline set before a reboot still holds after it. Zero means "never
+// expires". The sweep runs as a deferred timer callback: the PIT tick only
+// marks it due, and it runs later outside interrupt context, since it
+// decrypts and allocates.
+
+struct ExpireCtx {
+    table_id: u32,
+    column: usize,
+    now: u64,
+    expired: Vec<u64>,
+}
+
+fn expire_scan_callback(key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let ctx = &mut *(ctx as *mut ExpireCtx);
+        if let Some(rec) = db_decrypt_record(ctx.table_id, value) {
+            if let Some(FieldValue::U64(exp)) = rec.fields[ctx.column] {
+                if exp != 0 && exp < ctx.now {
+                    ctx.expired.push(key);
+                }
+            }
+        }
+    }
+    true
+}
+
+/// Delete every record whose `expires` is nonzero and earlier than `now`
+/// (RTC unix seconds). Returns the number of records removed across all tables.
+pub fn db_expire_sweep(now: u64) -> u32 {
+    let mut removed = 0u32;
+    for t in 0..db_get_table_count() {
+        let schema = match db_get_schema_by_id(t) {
+            Some(s) => s,
+            None => continue,
+        };
+        let col = find_column_index(schema, EXPIRES_COLUMN);
+        if col < 0 || schema.columns[col as usize].col_type != ColumnType::U64 { continue; }
+
+        let tree = match db_get_index(t) {
+            Some(tr) => tr as *const Btree,
+            None => continue,
+        };
+        let mut ctx = ExpireCtx { table_id: t, column: col as usize, now, expired: Vec::new() };
+        unsafe {
+            btree_scan(&*tree, expire_scan_callback, &mut ctx as *mut ExpireCtx as *mut u8);
+        }
+
+        // Collect first, then delete, so the tree is not mutated mid-scan
+        for &row_id in ctx.expired.iter() {
+            if db_delete_record(t, row_id) == VOS_OK { removed += 1; }
+        }
+    }
+    if removed > 0 {
+        crate::serial_println!("[DB] Expired {} record(s)", removed);
+    }
+    removed
+}
+
+fn expire_tick() {
+    db_expire_sweep(rtc::rtc_unix_time());
+}
+
+/// Sweep expired records every EXPIRE_SWEEP_INTERVAL_MS from here on.
+/// Both boot paths call this; only the first registers the callback.
+fn db_hook_expiry() {
+    unsafe {
+        if EXPIRY_HOOKED { return; }
+        EXPIRY_HOOKED = timer::pit_register_deferred(EXPIRE_SWEEP_INTERVAL_MS, expire_tick) == VOS_OK;
+        if !EXPIRY_HOOKED {
+            crate::serial_println!("[DB] No timer slot left for the expiry sweep");
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Cascade delete
+// ---------------------------------------------------------------------------
+//
+// Rows in one table can name a row of another by value (a capability's
+// owner_pid, a message's src_pid and dst_pid). There are no enforced foreign
+// keys; instead CASCADE_RULES lists the parent->child column pairs, and a
+// DELETE that asks for CASCADE removes the children of every row it deletes.
+// Nothing cascades unless the caller asks for it.
+//
+// CapabilityTable rows only mirror the live capability table. Deleting them
+// alone would leave the capabilities usable, so that rule also revokes
+// everything the owner holds, down each delegation tree.
+
+/// Deleting a `parent_table` row also deletes every `child_table` row whose
+/// `child_col` equals the parent's `parent_col`. Both columns are integer ids.
+/// With `revoke_owner`, `child_col` is a capability owner's pid and that
+/// owner's capabilities are revoked first, whether or not they have rows.
+pub struct CascadeRule {
+    pub parent_table: u32,
+    pub parent_col: &'static str,
+    pub child_table: u32,
+    pub child_col: &'static str,
+    pub revoke_owner: bool,
+}
+
+pub static CASCADE_RULES: [CascadeRule; 3] = [
+    CascadeRule { parent_table: TABLE_ID_PROCESS, parent_col: "pid",
+                  child_table: TABLE_ID_CAPABILITY, child_col: "owner_pid", revoke_owner: true },
+    CascadeRule { parent_table: TABLE_ID_PROCESS, parent_col: "pid",
+                  child_table: TABLE_ID_MESSAGE, child_col: "src_pid", revoke_owner: false },
+    CascadeRule { parent_table: TABLE_ID_PROCESS, parent_col: "pid",
+                  child_table: TABLE_ID_MESSAGE, child_col: "dst_pid", revoke_owner: false },
+];
+
+/// Bound on rule chains, in case a rule table ever loops back on itself.
+const CASCADE_DEPTH_MAX: u32 = 4;
+
+struct CascadeCtx {
+    table_id: u32,
+    column: usize,
+    value: u64,
+    children: Vec<Record>,
+}
+
+fn cascade_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let ctx = &mut *(ctx as *mut CascadeCtx);
+        if let Some(rec) = db_decrypt_record(ctx.table_id, value) {
+            if rec.fields[ctx.column].as_ref().and_then(field_as_u64) == Some(ctx.value) {
+                ctx.children.push(rec);
+            }
+        }
+    }
+    true
+}
+
+/// Delete the rows that depend on `row` of `table_id` under `rules`, and
+/// their own dependents in turn. `row` itself is left for the caller to
+/// delete. Returns the number of dependent rows removed.
+pub fn db_cascade_delete(table_id: u32, row: &Record, rules: &[CascadeRule]) -> u32 {
+    cascade_delete_depth(table_id, row, rules, 0)
+}
+
+fn cascade_delete_depth(table_id: u32, row: &Record, rules: &[CascadeRule], depth: u32) -> u32 {
+    if depth >= CASCADE_DEPTH_MAX { return 0; }
+    let mut removed = 0u32;
+    for rule in rules.iter().filter(|r| r.parent_table == table_id) {
+        let parent = match db_get_schema_by_id(table_id) { Some(s) => s, None => continue };
+        let child = match db_get_schema_by_id(rule.child_table) { Some(s) => s, None => continue };
+        let pcol = find_column_index(parent, rule.parent_col);
+        let ccol = find_column_index(child, rule.child_col);
+        if pcol < 0 || ccol < 0 { continue; }
+        let value = match row.fields[pcol as usize].as_ref().and_then(field_as_u64) {
+            Some(v) => v,
+            None => continue,
+        };
+        if rule.revoke_owner {
+            let _ = delegation::cap_revoke_owned(value);
+        }
+
+        let tree = match db_get_index(rule.child_table) {
+            Some(tr) => tr as *const Btree,
+            None => continue,
+        };
+        let mut ctx = CascadeCtx { table_id: rule.child_table, column: ccol as usize, value, children: Vec::new() };
+        unsafe {
+            btree_scan(&*tree, cascade_scan_callback, &mut ctx as *mut CascadeCtx as *mut u8);
+        }
+
+        // Collect first, then delete, so the tree is not mutated mid-scan.
+        // A row matched by two rules (a message to itself) is gone the second
+        // time round and reports NOTFOUND, so it is counted once.
+        for child_row in ctx.children.iter() {
+            removed += cascade_delete_depth(rule.child_table, child_row, rules, depth + 1);
+            if db_delete_record(rule.child_table, child_row.row_id) == VOS_OK { removed += 1; }
+        }
+    }
+    removed
+}
+
+// ---------------------------------------------------------------------------
+// Audit logging
+// ---------------------------------------------------------------------------
+
+/// Enable or disable automatic DML auditing (e.g. around bulk loads).
+pub fn db_set_audit_enabled(enabled: bool) {
+    unsafe { AUDIT_ENABLED = enabled; }
+}
+
+pub fn db_audit_enabled() -> bool {
+    unsafe { AUDIT_ENABLED }
+}
+
+/// Record the pid on whose behalf the following DML runs (set by
+/// query_execute) and return the one it replaces. Callers put that back
+/// when their statement finishes: statements nest (a cascade revoke runs
+/// its own UPDATE), and the outer one must go on auditing under its pid,
+/// not 0 or the inner one's.
+pub fn db_set_audit_caller(pid: u64) -> u64 {
+    unsafe { core::mem::replace(&mut AUDIT_CALLER_PID, pid) }
+}
+
+/// Append an AuditTable row for a DML operation.
+/// Writes to AuditTable itself are never audited, which also stops the
+/// audit insert from recursing into this function.
+fn audit_log(action: &str, table_id: u32, row_id: u64, err: i32) {
+    unsafe {
+        if !AUDIT_ENABLED || AUDIT_SUPPRESS { return; }
+        if table_id == TABLE_ID_AUDIT || TABLE_ID_AUDIT >= TABLE_COUNT { return; }
+    }
+
+    let table_name = match db_get_schema_by_id(table_id) {
+        Some(s) => s.name_str(),
+        None => "?",
+    };
+    let mut result = [0u8; MAX_STR_LEN];
+    let status: &[u8] = if err == VOS_OK { b"OK " } else { b"FAIL " };
+    let mut len = status.len();
+    result[..len].copy_from_slice(status);
+    let name_len = table_name.len().min(MAX_STR_LEN - len);
+    result[len..len + name_len].copy_from_slice(&table_name.as_bytes()[..name_len]);
+    len += name_len;
+
+    let mut rec = Record::new(TABLE_ID_AUDIT);
+    rec.row_id = db_next_id_for(TABLE_ID_AUDIT);
+    rec.table_id = TABLE_ID_AUDIT;
+    rec.field_count = 7;
+    rec.set_u64(0, rec.row_id);
+    rec.set_u64(1, rtc::rtc_unix_time());
+    rec.set_u64(2, unsafe { AUDIT_CALLER_PID });
+    rec.set_str(3, action);
+    rec.set_u64(4, row_id);
+    rec.set_str(5, core::str::from_utf8(&result[..len]).unwrap_or(""));
+    rec.set_u64(6, rtc::rtc_unix_time() + AUDIT_RETENTION_SECS);
+    db_insert_record_impl(TABLE_ID_AUDIT, &mut rec);
+}
+
+// ---------------------------------------------------------------------------
+// Export / import
+// ---------------------------------------------------------------------------
+//
+// The image carries ciphertext verbatim, so it is only readable under the
+// same master key; with a different key the import succeeds but every later
+// read fails MAC verification. Layout (integers little-endian):
+//
+//   "VDBX"  u32 version  u32 table_count  u64 global_row_id
+//   per table:
+//     name[MAX_TABLE_NAME]  u32 column_count  u8 encrypted  u8 system_table
+//     u64 next_id  (version 2 and later)
+//     column_count x { name[MAX_COLUMN_NAME]  u8 type  u8 pk  u8 not_null
+//                      u8 sensitive (version 3 and later) }
+//     u64 record_count
+//     record_count x { u64 row_id  iv[16]  mac[32]  u32 ct_len  ct[ct_len] }
+
+const EXPORT_MAGIC: &[u8; 4] = b"VDBX";
+const EXPORT_VERSION: u32 = 3;
+
+/// Column type on-disk tag (matches C column_type_t order; F64 is
+/// Rust-only and comes last).
+fn col_type_to_u8(t: ColumnType) -> u8 {
+    match t {
+        ColumnType::U64 => 0,
+        ColumnType::I64 => 1,
+        ColumnType::Str => 2,
+        ColumnType::Blob => 3,
+        ColumnType::Bool => 4,
+        ColumnType::U32 => 5,
+        ColumnType::U8 => 6,
+        ColumnType::F64 => 7,
+    }
+}
+
+fn col_type_from_u8(v: u8) -> Option<ColumnType> {
+    match v {
+        0 => Some(ColumnType::U64),
+        1 => Some(ColumnType::I64),
+        2 => Some(ColumnType::Str),
+        3 => Some(ColumnType::Blob),
+        4 => Some(ColumnType::Bool),
+        5 => Some(ColumnType::U32),
+        6 => Some(ColumnType::U8),
+        7 => Some(ColumnType::F64),
+        _ => None,
+    }
+}
+
+struct ExportCtx<'a> {
+    out: &'a mut Vec<u8>,
+    count: u64,
+}
+
+fn export_record_callback(key: u64, value: *mut u8, ctx: *mut u8) -> bool {
+    unsafe {
+        let ctx = &mut *(ctx as *mut ExportCtx);
+        let enc = &*(value as *const EncryptedRecord);
+        let ct_len = enc.ciphertext_len as usize;
+        ctx.out.extend_from_slice(&key.to_le_bytes());
+        ctx.out.extend_from_slice(&enc.iv);
+        ctx.out.extend_from_slice(&enc.mac);
+        ctx.out.extend_from_slice(&(ct_len as u32).to_le_bytes());
+        ctx.out.extend_from_slice(&enc.ciphertext[..ct_len]);
+        ctx.count += 1;
+    }
+    true
+}
+
+/// Serialize every schema and encrypted record into `out` without decrypting.
+pub fn db_export(out: &mut Vec<u8>) -> i32 {
+    out.clear();
+    out.extend_from_slice(EXPORT_MAGIC);
+    out.extend_from_slice(&EXPORT_VERSION.to_le_bytes());
+    unsafe {
+        out.extend_from_slice(&TABLE_COUNT.to_le_bytes());
+        out.extend_from_slice(&GLOBAL_ROW_ID.to_le_bytes());
+
+        for t in 0..TABLE_COUNT as usize {
+            let schema = match SCHEMAS[t].as_ref() {
+                Some(s) => s,
+                None => return VOS_ERR_GENERIC,
+            };
+            let tree = match INDEXES[t].as_ref() {
+                Some(tr) => tr,
+                None => return VOS_ERR_GENERIC,
+            };
+
+            out.extend_from_slice(&schema.name[..MAX_TABLE_NAME]);
+            out.extend_from_slice(&schema.column_count.to_le_bytes());
+            out.push(schema.encrypted as u8);
+            out.push(schema.system_table as u8);
+            out.extend_from_slice(&NEXT_IDS[t].to_le_bytes());
+            for c in 0..schema.column_count as usize {
+                let col = &schema.columns[c];
+                out.extend_from_slice(&col.name[..MAX_COLUMN_NAME]);
+                out.push(col_type_to_u8(col.col_type));
+                out.push(col.primary_key as u8);
+                out.push(col.not_null as u8);
+                out.push(col.sensitive as u8);
+            }
+
+            // Patch the record count in after the scan
+            let count_pos = out.len();
+            out.extend_from_slice(&0u64.to_le_bytes());
+            let mut ctx = ExportCtx { out: &mut *out, count: 0 };
+            btree_scan(tree, export_record_callback, &mut ctx as *mut ExportCtx as *mut u8);
+            let count = ctx.count;
+            out[count_pos..count_pos + 8].copy_from_slice(&count.to_le_bytes());
+        }
+    }
+    VOS_OK
+}
 
-    modified.row_id = row_id;
-    modified.table_id = table_id;
-    db_insert_record(table_id, modified)
+struct ImageReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
 }
 
-/// Flush all dirty tables to disk.
+impl<'a> ImageReader<'a> {
+    fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+        let end = self.pos.checked_add(n)?;
+        if end > self.buf.len() { return None; }
+        let s = &self.buf[self.pos..end];
+        self.pos = end;
+        Some(s)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let b = self.bytes(4)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        let b = self.bytes(8)?;
+        let mut v = [0u8; 8];
+        v.copy_from_slice(b);
+        Some(u64::from_le_bytes(v))
+    }
+}
+
+fn free_encrypted_callback(_key: u64, value: *mut u8, _ctx: *mut u8) -> bool {
+    unsafe {
+        let mut enc = Box::from_raw(value as *mut EncryptedRecord);
+        for b in enc.ciphertext.iter_mut() { *b = 0; }
+        drop(enc);
+    }
+    true
+}
+
+fn free_table_trees(trees: &mut [Option<Btree>]) {
+    for slot in trees.iter_mut() {
+        if let Some(mut tree) = slot.take() {
+            btree_scan(&tree, free_encrypted_callback, core::ptr::null_mut());
+            btree_destroy(&mut tree);
+        }
+    }
+}
+
+fn import_record(r: &mut ImageReader, table_id: u32) -> Option<Box<EncryptedRecord>> {
+    let row_id = r.u64()?;
+    let mut enc = Box::new(EncryptedRecord::new());
+    enc.iv.copy_from_slice(r.bytes(AES_BLOCK_SIZE)?);
+    enc.mac.copy_from_slice(r.bytes(32)?);
+    let ct_len = r.u32()? as usize;
+    if ct_len == 0 || ct_len % AES_BLOCK_SIZE != 0 || ct_len > MAX_RECORD_SIZE + AES_BLOCK_SIZE {
+        return None;
+    }
+    enc.ciphertext = r.bytes(ct_len)?.to_vec();
+    enc.ciphertext_len = ct_len as u32;
+    enc.row_id = row_id;
+    enc.table_id = table_id;
+    Some(enc)
+}
+
+/// Parse one table section into a schema, its saved next id (0 in a
+/// version 1 image) and a freshly built tree.
+fn import_table(r: &mut ImageReader, table_id: u32, version: u32) -> Option<(TableSchema, u64, Btree)> {
+    let mut s = TableSchema::zeroed();
+    s.name[..MAX_TABLE_NAME].copy_from_slice(r.bytes(MAX_TABLE_NAME)?);
+    s.name[MAX_TABLE_NAME - 1] = 0;
+    s.table_id = table_id;
+    s.column_count = r.u32()?;
+    if s.column_count as usize > MAX_COLUMNS { return None; }
+    s.encrypted = r.u8()? != 0;
+    s.system_table = r.u8()? != 0;
+    let next_id = if version >= 2 { r.u64()? } else { 0 };
+    for c in 0..s.column_count as usize {
+        let mut col = ColumnDef::zeroed();
+        col.name[..MAX_COLUMN_NAME].copy_from_slice(r.bytes(MAX_COLUMN_NAME)?);
+        col.name[MAX_COLUMN_NAME - 1] = 0;
+        col.col_type = col_type_from_u8(r.u8()?)?;
+        col.primary_key = r.u8()? != 0;
+        col.not_null = r.u8()? != 0;
+        col.sensitive = version >= 3 && r.u8()? != 0;
+        s.columns[c] = col;
+    }
+
+    let mut tree = Btree { root: core::ptr::null_mut(), count: 0, table_id };
+    btree_init(&mut tree, table_id);
+
+    let count = r.u64()?;
+    for _ in 0..count {
+        match import_record(r, table_id) {
+            Some(enc) => {
+                let row_id = enc.row_id;
+                btree_insert(&mut tree, row_id, Box::into_raw(enc) as *mut u8);
+            }
+            None => {
+                free_table_trees(&mut [Some(tree)]);
+                return None;
+            }
+        }
+    }
+    Some((s, next_id, tree))
+}
+
+/// Replace every table with the contents of an image produced by db_export.
+/// The image is fully parsed before any live state is touched, so a
+/// truncated or corrupt image leaves the database unchanged.
+pub fn db_import(bytes: &[u8]) -> i32 {
+    let mut r = ImageReader { buf: bytes, pos: 0 };
+    if r.bytes(4) != Some(&EXPORT_MAGIC[..]) { return VOS_ERR_INVAL; }
+    let version = match r.u32() {
+        Some(v) if v >= 1 && v <= EXPORT_VERSION => v,
+        _ => return VOS_ERR_INVAL,
+    };
+    let table_count = match r.u32() { Some(n) => n, None => return VOS_ERR_INVAL };
+    if table_count as usize > MAX_TABLES { return VOS_ERR_OVERFLOW; }
+    let next_row_id = match r.u64() { Some(n) => n, None => return VOS_ERR_INVAL };
+
+    let mut schemas: [Option<TableSchema>; MAX_TABLES] = [const { None }; MAX_TABLES];
+    let mut trees: [Option<Btree>; MAX_TABLES] = [const { None }; MAX_TABLES];
+    let mut next_ids = [0u64; MAX_TABLES];
+    for t in 0..table_count {
+        match import_table(&mut r, t, version) {
+            Some((s, next_id, tree)) => {
+                schemas[t as usize] = Some(s);
+                next_ids[t as usize] = next_id;
+                trees[t as usize] = Some(tree);
+            }
+            None => {
+                free_table_trees(&mut trees);
+                return VOS_ERR_INVAL;
+            }
+        }
+    }
+
+    unsafe {
+        free_table_trees(&mut INDEXES);
+        // Secondary indexes, column defaults and id counters all describe
+        // the tables being replaced; none of them carries over
+        for i in 0..MAX_SECONDARY_INDEXES {
+            if let Some(mut idx) = SECONDARY_INDEXES[i].take() {
+                sec_index_destroy(&mut idx);
+            }
+        }
+        for t in 0..MAX_TABLES {
+            SCHEMAS[t] = schemas[t].take();
+            INDEXES[t] = trees[t].take();
+            TABLE_AES_KEYS[t] = None;
+            TABLE_MAC_CTXS[t] = None;
+            for d in COLUMN_DEFAULTS[t].iter_mut() { *d = None; }
+        }
+        NEXT_IDS = [1; MAX_TABLES];
+        TABLE_COUNT = table_count;
+        if next_row_id > GLOBAL_ROW_ID { GLOBAL_ROW_ID = next_row_id; }
+        for t in 0..table_count {
+            derive_table_key(t);
+            db_set_table_next_id(t, next_ids[t as usize]);
+        }
+        // Keys are in place, so the system indexes can be built from the
+        // imported trees
+        apply_system_table_extras();
+        db_reconcile_row_ids();
+        crate::serial_println!("[DB] Imported {} tables", table_count);
+    }
+    VOS_OK
+}
+
+/// Flush all dirty tables to disk, row id counters included.
 pub fn db_flush() -> i32 {
-    db_persist::db_persist_commit()
+    db_save_next_ids();
+    let err = db_persist::db_persist_commit();
+    // The committed tree no longer points at blocks compaction gave up
+    if err == VOS_OK { page_io::page_release_pending(); }
+    err
+}
+
+// ---------------------------------------------------------------------------
+// Result-returning entry points for Rust callers. The i32 functions above
+// stay as thin codes-only versions for the C-port call sites.
+// ---------------------------------------------------------------------------
+
+/// db_insert_record; Ok carries the row id the record was stored under.
+pub fn db_insert(table_id: u32, rec: &mut Record) -> VosResult<u64> {
+    vos_result(db_insert_record(table_id, rec))?;
+    Ok(rec.row_id)
+}
+
+pub fn db_delete(table_id: u32, row_id: u64) -> VosResult<()> {
+    vos_result(db_delete_record(table_id, row_id))
+}
+
+pub fn db_update(table_id: u32, row_id: u64, modified: &mut Record) -> VosResult<()> {
+    vos_result(db_update_encrypted(table_id, row_id, modified))
+}
+
+pub fn db_commit() -> VosResult<()> {
+    vos_result(db_flush())
 }
 
 // ---------------------------------------------------------------------------
@@ -588,7 +2044,14 @@ pub struct QueryResult {
     pub rows: Vec<Record>,
     pub error_code: i32,
     pub error_msg: [u8; 256],
+    /// Kept for older callers; prefer column_names/column_types.
     pub schema: Option<&'static TableSchema>,
+    /// One entry per column of `rows`, in field order. Empty for
+    /// statements that return no rows (INSERT, DELETE, errors, ...).
+    pub column_names: Vec<&'static str>,
+    pub column_types: Vec<ColumnType>,
+    /// Rows an INSERT, UPDATE, DELETE or TRUNCATE changed; 0 for the rest.
+    pub rows_affected: u32,
 }
 
 impl QueryResult {
@@ -596,6 +2059,29 @@ impl QueryResult {
         let len = self.error_msg.iter().position(|&c| c == 0).unwrap_or(self.error_msg.len());
         core::str::from_utf8(&self.error_msg[..len]).unwrap_or("")
     }
+
+    /// error_code as a Result; error_msg_str still has the detail.
+    pub fn status(&self) -> VosResult<()> {
+        vos_result(self.error_code)
+    }
+
+    /// The typed error, or None if the statement succeeded.
+    pub fn error(&self) -> Option<VosError> {
+        VosError::from_code(self.error_code)
+    }
+
+    /// Describe the rows with `schema`'s columns (also sets `schema`).
+    pub fn set_columns(&mut self, schema: &'static TableSchema) {
+        let n = schema.column_count as usize;
+        self.schema = Some(schema);
+        self.column_names = schema.columns[..n].iter().map(|c| c.name_str()).collect();
+        self.column_types = schema.columns[..n].iter().map(|c| c.col_type).collect();
+    }
+
+    /// Field index of the column called `name`, if the result has one.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.column_names.iter().position(|&c| c == name)
+    }
 }
 
 pub fn db_result_create(capacity: u32) -> QueryResult {
@@ -604,6 +2090,9 @@ pub fn db_result_create(capacity: u32) -> QueryResult {
         error_code: VOS_OK,
         error_msg: [0u8; 256],
         schema: None,
+        column_names: Vec::new(),
+        column_types: Vec::new(),
+        rows_affected: 0,
     }
 }
 
@@ -652,6 +2141,23 @@ pub fn str_eq_ignore_case(a: &str, b: &str) -> bool {
     true
 }
 
+/// Inputs up to this long take the same time to compare in ct_eq.
+const CT_EQ_BOUND: usize = MAX_STR_LEN + 1;
+
+/// Byte equality that doesn't stop at the first difference: it always reads
+/// CT_EQ_BOUND bytes (or the longer input, if that is larger), so the time
+/// taken says nothing about where or whether `a` and `b` differ.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    let n = CT_EQ_BOUND.max(a.len()).max(b.len());
+    let mut diff = (a.len() != b.len()) as u8;
+    for i in 0..n {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+        diff |= x ^ y;
+    }
+    core::hint::black_box(diff) == 0
+}
+
 /// Find column index by name (case-insensitive).
 pub fn find_column_index(schema: &TableSchema, name: &str) -> i32 {
     for i in 0..schema.column_count as usize {
@@ -0,0 +1,16 @@
+// Helpers over FieldValue shared by the database, its callers and the GUI
+
+use crate::db::record::FieldValue;
+
+/// Any integer or bool widened to u64, whatever width the column was
+/// declared with. None for strings, blobs, reals and negative I64 values.
+pub fn field_as_u64(v: &FieldValue) -> Option<u64> {
+    match *v {
+        FieldValue::U64(v) => Some(v),
+        FieldValue::U32(v) => Some(v as u64),
+        FieldValue::U8(v) => Some(v as u64),
+        FieldValue::I64(v) if v >= 0 => Some(v as u64),
+        FieldValue::Bool(b) => Some(b as u64),
+        _ => None,
+    }
+}
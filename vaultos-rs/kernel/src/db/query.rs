@@ -2,27 +2,64 @@
 // Port of kernel/db/query.c — recursive-descent parser
 //
 // Supported statements:
-//   SELECT [cols|*] FROM table [WHERE col op val [AND ...]]
-//   INSERT INTO table (cols) VALUES (vals)
-//   DELETE FROM table [WHERE ...]
+//   SELECT [cols|*] FROM table [WHERE col op val [AND|OR ...]]
+//          (op: = != < > <= >= LIKE; LIKE takes % and _ wildcards;
+//           col BETWEEN a AND b is col >= a AND col <= b)
+//          [ORDER BY col [ASC|DESC]] [LIMIT n [OFFSET m]]
+//   SELECT COUNT(*) FROM table [WHERE ...]
+//   INSERT INTO table (cols) VALUES (vals)       -- vals may include x'DEADBEEF' blobs
+//   INSERT OR REPLACE INTO table (cols) VALUES (vals)
+//          (replaces rows with the same key: the primary key if it is
+//           listed, otherwise the first listed column)
+//   DELETE FROM table [WHERE ...] [CASCADE]
+//          (CASCADE also deletes dependent rows: a process's capabilities
+//           and the messages it sent or received)
 //   UPDATE table SET col=val [, ...] [WHERE ...]
+//   (NOW() may stand in for any number: current RTC time in Unix seconds)
+//   (a number with a decimal point, e.g. 2.75, is a real: it fills F64
+//    columns and compares numerically with integer columns)
 //   SHOW TABLES
 //   DESCRIBE table
+//   TRUNCATE TABLE table [FORCE]               -- FORCE is required for system tables
 //   GRANT rights ON object_id TO process_id
-//   REVOKE cap_id
+//          (delegates from a capability the caller holds with CAP_GRANT;
+//           rights beyond that capability's are refused)
+//   REVOKE cap_id [CASCADE]
+//          (CASCADE also revokes every capability delegated from it; either
+//           way, capabilities below a revoked one stop passing access checks)
+//
+// `--` starts a comment that runs to the end of the line.
+//
+// Access control: a caller other than the kernel (pid 0) needs a
+// capability on the table: CAP_READ for SELECT and DESCRIBE, CAP_WRITE for
+// INSERT, UPDATE, DELETE and TRUNCATE. Without one the statement fails
+// with VOS_ERR_PERM before any row is touched. On a table with an
+// owner_pid column, such a caller's SELECT only sees its own rows unless it
+// is an admin (CAP_ALL on SystemTable, which a system capability covers).
+//
+// Prepared statements: query_prepare lexes a statement once, with `?` in
+// place of any literal; query_execute_prepared replays the tokens with the
+// bound values spliced in. A bound string never goes through the lexer, so
+// it needs no quoting and may contain quotes.
 
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::fmt::Write;
 
 use crate::db::database::{
     self, QueryResult, db_get_schema_by_name, db_get_schema_by_id, db_get_index,
     db_insert_record, db_decrypt_record, db_delete_record, db_update_encrypted,
-    db_next_row_id, db_result_create, db_result_add_row, db_result_error,
+    db_next_id_for, db_result_create, db_result_add_row, db_result_error,
     db_get_table_count, find_column_index, str_eq_ignore_case,
+    db_get_record, db_index_lookup, db_has_index, db_column_default,
 };
 use crate::db::btree::btree_scan;
+use crate::cap::delegation;
+use crate::drivers::rtc;
+use crate::error::{VosError, VosResult};
 use crate::db::record::{Record, FieldValue, StrField};
 use crate::db::schema::TableSchema;
+use vaultos_shared::capability_types::{CapObjectType, CAP_ALL, CAP_READ, CAP_WRITE};
 use vaultos_shared::db_types::*;
 use vaultos_shared::error_codes::*;
 
@@ -30,16 +67,21 @@ use vaultos_shared::error_codes::*;
 // Token types
 // ---------------------------------------------------------------------------
 
+/// Characters kept from a numeric literal: digits and at most one `.`
+const MAX_NUMBER_LEN: usize = 32;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TokenType {
     Select, Insert, Into, Delete, Update,
-    From, Where, And, Set, Values,
-    Show, Tables, Describe,
+    From, Where, And, Or, Set, Values,
+    Show, Tables, Describe, Truncate,
     Grant, Revoke, On, To,
     Read, Write, All,
+    Limit, Offset, Order, By, Asc, Desc, Like, Between,
     Star, Comma, LParen, RParen,
     Eq, Neq, Lt, Gt, Le, Ge,
-    Ident, StringLit, Number,
+    Ident, StringLit, Number, Real, BlobLit,
+    Param, Now, // only from query_prepare: `?` and a deferred NOW()
     Eof, Error,
 }
 
@@ -78,8 +120,18 @@ impl Token {
 
 struct Parser<'a> {
     input: &'a [u8],
+    /// Byte offset into `input`, or token index when replaying
     pos: usize,
     current: Token,
+    /// Decoded bytes of the current BlobLit token (too large for Token.value)
+    blob: Vec<u8>,
+    /// Lexing for query_prepare: emit Param and Now instead of failing on
+    /// `?` and evaluating NOW() up front
+    preparing: bool,
+    /// Prepared tokens to replay instead of lexing `input`
+    replay: Option<&'a [PreparedToken]>,
+    params: &'a [FieldValue],
+    next_param: usize,
 }
 
 impl<'a> Parser<'a> {
@@ -88,18 +140,139 @@ impl<'a> Parser<'a> {
             input: input.as_bytes(),
             pos: 0,
             current: Token::new(),
+            blob: Vec::new(),
+            preparing: false,
+            replay: None,
+            params: &[],
+            next_param: 0,
+        };
+        p.next_token();
+        p
+    }
+
+    fn preparing(input: &'a str) -> Self {
+        let mut p = Parser {
+            input: input.as_bytes(),
+            pos: 0,
+            current: Token::new(),
+            blob: Vec::new(),
+            preparing: true,
+            replay: None,
+            params: &[],
+            next_param: 0,
         };
         p.next_token();
         p
     }
 
+    fn replaying(stmt: &'a PreparedStmt, params: &'a [FieldValue]) -> Self {
+        let mut p = Parser {
+            input: &[],
+            pos: 0,
+            current: Token::new(),
+            blob: Vec::new(),
+            preparing: false,
+            replay: Some(&stmt.tokens),
+            params,
+            next_param: 0,
+        };
+        p.next_token();
+        p
+    }
+
+    /// Advance to the next prepared token, substituting bound values.
+    fn next_replayed(&mut self, tokens: &[PreparedToken]) {
+        let pt = match tokens.get(self.pos) {
+            Some(t) => t,
+            None => {
+                self.current.ttype = TokenType::Eof;
+                return;
+            }
+        };
+        self.pos += 1;
+        match pt.token.ttype {
+            TokenType::Param => self.bind_param(),
+            TokenType::Now => self.set_number(rtc::rtc_unix_time()),
+            TokenType::BlobLit => {
+                self.blob.clear();
+                self.blob.extend_from_slice(&pt.blob);
+                self.current.value_len = 0;
+                self.current.ttype = TokenType::BlobLit;
+            }
+            _ => self.current = pt.token.clone(),
+        }
+    }
+
+    /// Make the current token the next bound value, as if it had been
+    /// written as a literal.
+    fn bind_param(&mut self) {
+        let params = self.params;
+        let value = match params.get(self.next_param) {
+            Some(v) => v,
+            None => {
+                self.current.ttype = TokenType::Error;
+                return;
+            }
+        };
+        self.next_param += 1;
+        match value {
+            FieldValue::Str(s) => {
+                self.current.set_value(s.as_str());
+                self.current.ttype = TokenType::StringLit;
+            }
+            FieldValue::U64(v) => self.set_number(*v),
+            FieldValue::U32(v) => self.set_number(*v as u64),
+            FieldValue::U8(v) => self.set_number(*v as u64),
+            // The grammar has no negative literals
+            FieldValue::I64(v) if *v >= 0 => self.set_number(*v as u64),
+            FieldValue::I64(_) => self.current.ttype = TokenType::Error,
+            // Bound reals skip the lexer, so unlike literals they may be negative
+            FieldValue::F64(v) if v.is_finite() => {
+                let mut s = String::new();
+                let _ = write!(s, "{:?}", v);
+                self.current.set_value(&s);
+                self.current.ttype = TokenType::Real;
+            }
+            FieldValue::F64(_) => self.current.ttype = TokenType::Error,
+            FieldValue::Bool(b) => {
+                self.current.set_value(if *b { "true" } else { "false" });
+                self.current.ttype = TokenType::Ident;
+            }
+            FieldValue::Blob(b) => {
+                self.blob.clear();
+                self.blob.extend_from_slice(b.as_bytes());
+                self.current.value_len = 0;
+                self.current.ttype = TokenType::BlobLit;
+            }
+        }
+    }
+
+    fn set_number(&mut self, v: u64) {
+        let len = write_u64_to_buf(&mut self.current.value[..20], v);
+        self.current.value[len] = 0;
+        self.current.value_len = len;
+        self.current.ttype = TokenType::Number;
+    }
+
+    /// Skip whitespace and `--` comments, which run to the end of the line.
+    /// Only called between tokens, so `--` inside a string literal is text.
     fn skip_whitespace(&mut self) {
-        while self.pos < self.input.len() && is_space(self.input[self.pos]) {
-            self.pos += 1;
+        loop {
+            while self.pos < self.input.len() && is_space(self.input[self.pos]) {
+                self.pos += 1;
+            }
+            if !self.input[self.pos..].starts_with(b"--") { return; }
+            while self.pos < self.input.len() && self.input[self.pos] != b'\n' {
+                self.pos += 1;
+            }
         }
     }
 
     fn next_token(&mut self) {
+        if let Some(tokens) = self.replay {
+            self.next_replayed(tokens);
+            return;
+        }
         self.skip_whitespace();
 
         if self.pos >= self.input.len() {
@@ -122,6 +295,7 @@ impl<'a> Parser<'a> {
         if c == b'(' { self.current.ttype = TokenType::LParen; self.pos += 1; return; }
         if c == b')' { self.current.ttype = TokenType::RParen; self.pos += 1; return; }
         if c == b'=' { self.current.ttype = TokenType::Eq; self.pos += 1; return; }
+        if c == b'?' && self.preparing { self.current.ttype = TokenType::Param; self.pos += 1; return; }
 
         // Two-char operators
         if c == b'!' && self.pos + 1 < self.input.len() && self.input[self.pos + 1] == b'=' {
@@ -160,17 +334,50 @@ impl<'a> Parser<'a> {
             return;
         }
 
-        // Number
+        // Hex blob literal: x'DEADBEEF'
+        if (c == b'x' || c == b'X') && self.pos + 1 < self.input.len()
+            && self.input[self.pos + 1] == b'\''
+        {
+            self.pos += 2;
+            self.blob.clear();
+            let mut ok = true;
+            while self.pos < self.input.len() && self.input[self.pos] != b'\'' {
+                let hi = hex_val(self.input[self.pos]);
+                let lo = if self.pos + 1 < self.input.len() { hex_val(self.input[self.pos + 1]) } else { None };
+                match (hi, lo) {
+                    (Some(h), Some(l)) => self.blob.push((h << 4) | l),
+                    _ => { ok = false; break; }
+                }
+                self.pos += 2;
+            }
+            if !ok || self.pos >= self.input.len() {
+                self.current.ttype = TokenType::Error;
+                return;
+            }
+            self.pos += 1; // closing quote
+            self.current.value_len = 0;
+            self.current.ttype = TokenType::BlobLit;
+            return;
+        }
+
+        // Number, or a real if a `.` and more digits follow
         if is_digit(c) {
             let mut i = 0usize;
-            while self.pos < self.input.len() && is_digit(self.input[self.pos]) && i < 20 {
-                self.current.value[i] = self.input[self.pos];
+            let mut real = false;
+            while self.pos < self.input.len() && i < MAX_NUMBER_LEN {
+                let b = self.input[self.pos];
+                if b == b'.' && !real && self.pos + 1 < self.input.len() && is_digit(self.input[self.pos + 1]) {
+                    real = true;
+                } else if !is_digit(b) {
+                    break;
+                }
+                self.current.value[i] = b;
                 i += 1;
                 self.pos += 1;
             }
             self.current.value[i] = 0;
             self.current.value_len = i;
-            self.current.ttype = TokenType::Number;
+            self.current.ttype = if real { TokenType::Real } else { TokenType::Number };
             return;
         }
 
@@ -187,6 +394,20 @@ impl<'a> Parser<'a> {
             }
             self.current.value[i] = 0;
             self.current.value_len = i;
+
+            // NOW() pseudo-function: lexes as the current RTC time in Unix seconds
+            if str_eq_ignore_case(self.current.value_str(), "NOW")
+                && self.input[self.pos..].starts_with(b"()")
+            {
+                self.pos += 2;
+                if self.preparing {
+                    self.current.ttype = TokenType::Now;
+                } else {
+                    self.set_number(rtc::rtc_unix_time());
+                }
+                return;
+            }
+
             self.current.ttype = check_keyword(self.current.value_str());
             return;
         }
@@ -219,11 +440,13 @@ fn check_keyword(word: &str) -> TokenType {
     if str_eq_ignore_case(word, "FROM")     { return TokenType::From; }
     if str_eq_ignore_case(word, "WHERE")    { return TokenType::Where; }
     if str_eq_ignore_case(word, "AND")      { return TokenType::And; }
+    if str_eq_ignore_case(word, "OR")       { return TokenType::Or; }
     if str_eq_ignore_case(word, "SET")      { return TokenType::Set; }
     if str_eq_ignore_case(word, "VALUES")   { return TokenType::Values; }
     if str_eq_ignore_case(word, "SHOW")     { return TokenType::Show; }
     if str_eq_ignore_case(word, "TABLES")   { return TokenType::Tables; }
     if str_eq_ignore_case(word, "DESCRIBE") { return TokenType::Describe; }
+    if str_eq_ignore_case(word, "TRUNCATE") { return TokenType::Truncate; }
     if str_eq_ignore_case(word, "GRANT")    { return TokenType::Grant; }
     if str_eq_ignore_case(word, "REVOKE")   { return TokenType::Revoke; }
     if str_eq_ignore_case(word, "ON")       { return TokenType::On; }
@@ -231,6 +454,14 @@ fn check_keyword(word: &str) -> TokenType {
     if str_eq_ignore_case(word, "READ")     { return TokenType::Read; }
     if str_eq_ignore_case(word, "WRITE")    { return TokenType::Write; }
     if str_eq_ignore_case(word, "ALL")      { return TokenType::All; }
+    if str_eq_ignore_case(word, "LIMIT")    { return TokenType::Limit; }
+    if str_eq_ignore_case(word, "OFFSET")   { return TokenType::Offset; }
+    if str_eq_ignore_case(word, "ORDER")    { return TokenType::Order; }
+    if str_eq_ignore_case(word, "BY")       { return TokenType::By; }
+    if str_eq_ignore_case(word, "ASC")      { return TokenType::Asc; }
+    if str_eq_ignore_case(word, "DESC")     { return TokenType::Desc; }
+    if str_eq_ignore_case(word, "LIKE")     { return TokenType::Like; }
+    if str_eq_ignore_case(word, "BETWEEN")  { return TokenType::Between; }
     TokenType::Ident
 }
 
@@ -246,11 +477,25 @@ fn is_digit(c: u8) -> bool { c >= b'0' && c <= b'9' }
 fn is_alpha(c: u8) -> bool { (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') }
 #[inline]
 fn is_alnum(c: u8) -> bool { is_alpha(c) || is_digit(c) }
+#[inline]
+fn hex_val(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
 
 // ---------------------------------------------------------------------------
-// Simple u64 parser from string
+// Simple u64 / f64 parsers from string
 // ---------------------------------------------------------------------------
 
+/// Value of a Real token. Literals round to the nearest f64.
+fn parse_f64(s: &str) -> f64 {
+    s.parse::<f64>().unwrap_or(0.0)
+}
+
 fn parse_u64(s: &str) -> u64 {
     let mut val: u64 = 0;
     for &b in s.as_bytes() {
@@ -260,6 +505,15 @@ fn parse_u64(s: &str) -> u64 {
     val
 }
 
+/// `true`/`false` (any case) at the current token, for BOOL columns.
+fn bool_literal(p: &Parser) -> Option<bool> {
+    if p.current.ttype != TokenType::Ident { return None; }
+    let v = p.current.value_str();
+    if str_eq_ignore_case(v, "true") { Some(true) }
+    else if str_eq_ignore_case(v, "false") { Some(false) }
+    else { None }
+}
+
 // ---------------------------------------------------------------------------
 // WHERE clause types and parsing
 // ---------------------------------------------------------------------------
@@ -269,11 +523,14 @@ struct WhereCond {
     column_len: usize,
     op: CmpOp,
     value: WhereValue,
+    /// Preceded by OR: starts a new AND-group (AND binds tighter than OR)
+    or_group: bool,
 }
 
 enum WhereValue {
     Str(StrField),
     U64(u64),
+    F64(f64),
 }
 
 impl WhereCond {
@@ -290,17 +547,36 @@ fn parse_op(p: &mut Parser) -> CmpOp {
         TokenType::Gt  => CmpOp::Gt,
         TokenType::Le  => CmpOp::Le,
         TokenType::Ge  => CmpOp::Ge,
+        TokenType::Like => CmpOp::Like,
         _ => CmpOp::Eq,
     };
     p.next_token();
     op
 }
 
+/// Literal operand of a WHERE condition; consumes it on success.
+fn parse_where_value(p: &mut Parser) -> Option<WhereValue> {
+    let value = if p.current.ttype == TokenType::StringLit {
+        WhereValue::Str(StrField::from_str(p.current.value_str()))
+    } else if p.current.ttype == TokenType::Number {
+        WhereValue::U64(parse_u64(p.current.value_str()))
+    } else if p.current.ttype == TokenType::Real {
+        WhereValue::F64(parse_f64(p.current.value_str()))
+    } else if let Some(b) = bool_literal(p) {
+        WhereValue::U64(b as u64)
+    } else {
+        return None;
+    };
+    p.next_token();
+    Some(value)
+}
+
 fn parse_where(p: &mut Parser) -> Vec<WhereCond> {
     let mut conds = Vec::new();
     if p.current.ttype != TokenType::Where { return conds; }
     p.next_token(); // skip WHERE
 
+    let mut after_or = false;
     while conds.len() < MAX_WHERE_CONDS {
         if p.current.ttype != TokenType::Ident { break; }
 
@@ -309,6 +585,7 @@ fn parse_where(p: &mut Parser) -> Vec<WhereCond> {
             column_len: 0,
             op: CmpOp::Eq,
             value: WhereValue::U64(0),
+            or_group: after_or,
         };
 
         // Copy column name
@@ -317,22 +594,34 @@ fn parse_where(p: &mut Parser) -> Vec<WhereCond> {
         let len = bytes.len().min(MAX_COLUMN_NAME - 1);
         cond.column[..len].copy_from_slice(&bytes[..len]);
         cond.column_len = len;
-
         p.next_token();
-        cond.op = parse_op(p);
 
-        // Value
-        if p.current.ttype == TokenType::StringLit {
-            cond.value = WhereValue::Str(StrField::from_str(p.current.value_str()));
-        } else if p.current.ttype == TokenType::Number {
-            cond.value = WhereValue::U64(parse_u64(p.current.value_str()));
+        // BETWEEN a AND b: two conditions in the same AND-group
+        if p.current.ttype == TokenType::Between {
+            if conds.len() + 2 > MAX_WHERE_CONDS { break; }
+            p.next_token();
+            let lo = match parse_where_value(p) { Some(v) => v, None => break };
+            if !p.expect(TokenType::And) { break; }
+            let hi = match parse_where_value(p) { Some(v) => v, None => break };
+            let upper = WhereCond {
+                column: cond.column,
+                column_len: cond.column_len,
+                op: CmpOp::Le,
+                value: hi,
+                or_group: false,
+            };
+            cond.op = CmpOp::Ge;
+            cond.value = lo;
+            conds.push(cond);
+            conds.push(upper);
         } else {
-            break;
+            cond.op = parse_op(p);
+            cond.value = match parse_where_value(p) { Some(v) => v, None => break };
+            conds.push(cond);
         }
-        p.next_token();
-        conds.push(cond);
 
-        if p.current.ttype == TokenType::And {
+        if p.current.ttype == TokenType::And || p.current.ttype == TokenType::Or {
+            after_or = p.current.ttype == TokenType::Or;
             p.next_token();
         } else {
             break;
@@ -346,6 +635,31 @@ fn parse_where(p: &mut Parser) -> Vec<WhereCond> {
 // Field matching for WHERE conditions
 // ---------------------------------------------------------------------------
 
+/// SQL LIKE: `%` matches any run of bytes, `_` exactly one; other bytes
+/// compare ASCII case-insensitively. Backtracks only to the last `%`.
+fn like_match(s: &[u8], pat: &[u8]) -> bool {
+    let (mut si, mut pi) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None; // (pattern pos of %, text pos it resumes at)
+    while si < s.len() {
+        if pi < pat.len() && pat[pi] == b'%' {
+            star = Some((pi, si));
+            pi += 1;
+        } else if pi < pat.len() && (pat[pi] == b'_' || pat[pi].eq_ignore_ascii_case(&s[si])) {
+            si += 1;
+            pi += 1;
+        } else if let Some((sp, ss)) = star {
+            // Let the last % swallow one more byte and retry
+            star = Some((sp, ss + 1));
+            pi = sp + 1;
+            si = ss + 1;
+        } else {
+            return false;
+        }
+    }
+    while pi < pat.len() && pat[pi] == b'%' { pi += 1; }
+    pi == pat.len()
+}
+
 fn match_field(field: &FieldValue, op: CmpOp, cond_val: &WhereValue) -> bool {
     match (field, cond_val) {
         (FieldValue::Str(fs), WhereValue::Str(cs)) => {
@@ -359,7 +673,7 @@ fn match_field(field: &FieldValue, op: CmpOp, cond_val: &WhereValue) -> bool {
                 CmpOp::Gt  => cmp > 0,
                 CmpOp::Le  => cmp <= 0,
                 CmpOp::Ge  => cmp >= 0,
-                _ => false,
+                CmpOp::Like => like_match(a.as_bytes(), b.as_bytes()),
             }
         }
         (FieldValue::U64(fv), WhereValue::U64(cv)) => {
@@ -402,6 +716,12 @@ fn match_field(field: &FieldValue, op: CmpOp, cond_val: &WhereValue) -> bool {
                 _ => false,
             }
         }
+        // Reals compare numerically with each other and with integers
+        (FieldValue::F64(fv), WhereValue::F64(cv)) => match_f64(*fv, op, *cv),
+        (FieldValue::F64(fv), WhereValue::U64(cv)) => match_f64(*fv, op, *cv as f64),
+        (FieldValue::U64(fv), WhereValue::F64(cv)) => match_f64(*fv as f64, op, *cv),
+        (FieldValue::U32(fv), WhereValue::F64(cv)) => match_f64(*fv as f64, op, *cv),
+        (FieldValue::I64(fv), WhereValue::F64(cv)) => match_f64(*fv as f64, op, *cv),
         // Cross-type: U64 field vs string condition (parse string as number)
         (FieldValue::U64(fv), WhereValue::Str(sv)) => {
             let cv = parse_u64(sv.as_str());
@@ -415,19 +735,69 @@ fn match_field(field: &FieldValue, op: CmpOp, cond_val: &WhereValue) -> bool {
     }
 }
 
+/// NaN compares unequal to everything, itself included.
+fn match_f64(fv: f64, op: CmpOp, cv: f64) -> bool {
+    match op {
+        CmpOp::Eq  => fv == cv,
+        CmpOp::Neq => fv != cv,
+        CmpOp::Lt  => fv < cv,
+        CmpOp::Gt  => fv > cv,
+        CmpOp::Le  => fv <= cv,
+        CmpOp::Ge  => fv >= cv,
+        _ => false,
+    }
+}
+
+/// match_field for a sensitive column: only = and != are allowed, and
+/// strings compare in constant time. Ordering and LIKE would leak the value
+/// one probe at a time, so they never match.
+fn match_sensitive(field: &FieldValue, op: CmpOp, cond_val: &WhereValue) -> bool {
+    match (field, cond_val, op) {
+        (FieldValue::Str(fs), WhereValue::Str(cs), CmpOp::Eq) =>
+            database::ct_eq(fs.as_str().as_bytes(), cs.as_str().as_bytes()),
+        (FieldValue::Str(fs), WhereValue::Str(cs), CmpOp::Neq) =>
+            !database::ct_eq(fs.as_str().as_bytes(), cs.as_str().as_bytes()),
+        (_, _, CmpOp::Eq) | (_, _, CmpOp::Neq) => match_field(field, op, cond_val),
+        _ => false,
+    }
+}
+
+/// True if every condition of at least one OR-separated group matches.
 fn record_matches(rec: &Record, schema: &TableSchema, conds: &[WhereCond]) -> bool {
+    let mut group_ok = true;
     for cond in conds {
+        if cond.or_group {
+            if group_ok { return true; }
+            group_ok = true;
+        }
+        if !group_ok { continue; }
         let col_idx = find_column_index(schema, cond.column_str());
-        if col_idx < 0 { return false; }
-        let field = match &rec.fields[col_idx as usize] {
-            Some(f) => f,
-            None => return false,
+        if col_idx < 0 { group_ok = false; continue; }
+        let sensitive = database::db_column_sensitive(schema.table_id, col_idx as usize);
+        group_ok = match &rec.fields[col_idx as usize] {
+            Some(field) if sensitive => match_sensitive(field, cond.op, &cond.value),
+            Some(field) => match_field(field, cond.op, &cond.value),
+            None => false,
         };
-        if !match_field(field, cond.op, &cond.value) {
-            return false;
-        }
     }
-    true
+    group_ok
+}
+
+/// Sort order for ORDER BY: NULLs first, then by value; mismatched types compare equal.
+fn field_order(a: &Option<FieldValue>, b: &Option<FieldValue>) -> core::cmp::Ordering {
+    use core::cmp::Ordering;
+    match (a, b) {
+        (Some(FieldValue::U64(x)), Some(FieldValue::U64(y))) => x.cmp(y),
+        (Some(FieldValue::I64(x)), Some(FieldValue::I64(y))) => x.cmp(y),
+        (Some(FieldValue::U32(x)), Some(FieldValue::U32(y))) => x.cmp(y),
+        (Some(FieldValue::U8(x)), Some(FieldValue::U8(y))) => x.cmp(y),
+        (Some(FieldValue::F64(x)), Some(FieldValue::F64(y))) => x.total_cmp(y),
+        (Some(FieldValue::Bool(x)), Some(FieldValue::Bool(y))) => x.cmp(y),
+        (Some(FieldValue::Str(x)), Some(FieldValue::Str(y))) => str_compare(x.as_str(), y.as_str()).cmp(&0),
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        _ => Ordering::Equal,
+    }
 }
 
 fn str_compare(a: &str, b: &str) -> i32 {
@@ -452,22 +822,39 @@ struct ScanCtx {
     schema: *const TableSchema,
     conds: *const Vec<WhereCond>,
     delete_mode: bool,
+    /// Matching rows still to skip before collecting (OFFSET)
+    offset: u64,
+    /// Maximum rows to collect (LIMIT); u64::MAX when unbounded
+    limit: u64,
 }
 
-fn select_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) {
+/// Collects matching rows; stops the scan as soon as LIMIT rows are held.
+fn select_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) -> bool {
     unsafe {
-        let sc = &*(ctx as *const ScanCtx);
+        let sc = &mut *(ctx as *mut ScanCtx);
         let schema = &*sc.schema;
+        let conds = &*sc.conds;
+
+        if (*sc.result).rows.len() as u64 >= sc.limit { return false; }
+        // Without a WHERE clause every row matches, so skipped rows stay sealed
+        if conds.is_empty() && sc.offset > 0 {
+            sc.offset -= 1;
+            return true;
+        }
 
         let rec = match db_decrypt_record(schema.table_id, value) {
             Some(r) => r,
-            None => return,
+            None => return true,
         };
 
-        let conds = &*sc.conds;
         if record_matches(&rec, schema, conds) {
-            db_result_add_row(&mut *sc.result, &rec);
+            if sc.offset > 0 {
+                sc.offset -= 1;
+            } else {
+                db_result_add_row(&mut *sc.result, &rec);
+            }
         }
+        ((*sc.result).rows.len() as u64) < sc.limit
     }
 }
 
@@ -478,6 +865,7 @@ fn select_scan_callback(_key: u64, value: *mut u8, ctx: *mut u8) {
 // Static schemas for virtual result sets
 static mut SHOW_SCHEMA: Option<TableSchema> = None;
 static mut DESC_SCHEMA: Option<TableSchema> = None;
+static mut COUNT_SCHEMA: Option<TableSchema> = None;
 
 fn get_show_schema() -> &'static TableSchema {
     unsafe {
@@ -506,7 +894,7 @@ fn get_desc_schema() -> &'static TableSchema {
         if DESC_SCHEMA.is_none() {
             let mut s = TableSchema::zeroed();
             s.set_name("Columns");
-            s.column_count = 4;
+            s.column_count = 6;
             set_col_name(&mut s.columns[0], "name");
             s.columns[0].col_type = ColumnType::Str;
             set_col_name(&mut s.columns[1], "type");
@@ -515,6 +903,10 @@ fn get_desc_schema() -> &'static TableSchema {
             s.columns[2].col_type = ColumnType::Str;
             set_col_name(&mut s.columns[3], "not_null");
             s.columns[3].col_type = ColumnType::Str;
+            set_col_name(&mut s.columns[4], "indexed");
+            s.columns[4].col_type = ColumnType::Str;
+            set_col_name(&mut s.columns[5], "table");
+            s.columns[5].col_type = ColumnType::Str;
             DESC_SCHEMA = Some(s);
         }
         // SAFETY: Some() was just assigned above if it was None
@@ -525,6 +917,34 @@ fn get_desc_schema() -> &'static TableSchema {
     }
 }
 
+fn get_count_schema() -> &'static TableSchema {
+    unsafe {
+        if COUNT_SCHEMA.is_none() {
+            let mut s = TableSchema::zeroed();
+            s.set_name("Count");
+            s.column_count = 1;
+            set_col_name(&mut s.columns[0], "count");
+            s.columns[0].col_type = ColumnType::U64;
+            COUNT_SCHEMA = Some(s);
+        }
+        // SAFETY: Some() was just assigned above if it was None
+        match COUNT_SCHEMA.as_ref() {
+            Some(s) => s,
+            None => unreachable!(),
+        }
+    }
+}
+
+fn count_result(n: u64) -> QueryResult {
+    let mut result = db_result_create(1);
+    let mut row = Record::new(0);
+    row.field_count = 1;
+    row.set_u64(0, n);
+    db_result_add_row(&mut result, &row);
+    result.set_columns(get_count_schema());
+    result
+}
+
 fn set_col_name(col: &mut crate::db::schema::ColumnDef, name: &str) {
     let bytes = name.as_bytes();
     let len = bytes.len().min(MAX_COLUMN_NAME - 1);
@@ -549,7 +969,7 @@ fn exec_show_tables() -> QueryResult {
         }
     }
 
-    result.schema = Some(get_show_schema());
+    result.set_columns(get_show_schema());
     result
 }
 
@@ -557,7 +977,50 @@ fn exec_show_tables() -> QueryResult {
 // DESCRIBE
 // ---------------------------------------------------------------------------
 
-fn exec_describe(p: &mut Parser) -> QueryResult {
+/// Fail unless `pid` holds `rights` on the table. The kernel always passes.
+fn check_table_access(pid: u64, schema: &TableSchema, rights: u32) -> Result<(), QueryResult> {
+    let table = schema.table_id as u64;
+    if pid == 0 || delegation::cap_check_chain(pid, CapObjectType::Table, table, rights) {
+        return Ok(());
+    }
+    let mut msg = String::from("Permission denied: no ");
+    msg.push_str(if rights & CAP_WRITE != 0 { "WRITE" } else { "READ" });
+    msg.push_str(" capability on ");
+    msg.push_str(schema.name_str());
+    Err(db_result_error(VOS_ERR_PERM, &msg))
+}
+
+/// Callers that see every row of owner-scoped tables: holders of a full
+/// system capability.
+fn is_admin(pid: u64) -> bool {
+    pid == 0 || delegation::cap_check_chain(pid, CapObjectType::System, 0, CAP_ALL)
+}
+
+/// Limit `conds` to rows owned by `pid`: `owner_pid = pid` is ANDed into
+/// every OR group, so `a OR b` becomes `(owner AND a) OR (owner AND b)`.
+fn scope_to_owner(conds: &mut Vec<WhereCond>, pid: u64) {
+    let owner_cond = |or_group: bool| {
+        let name = b"owner_pid";
+        let mut column = [0u8; MAX_COLUMN_NAME];
+        column[..name.len()].copy_from_slice(name);
+        WhereCond { column, column_len: name.len(), op: CmpOp::Eq, value: WhereValue::U64(pid), or_group }
+    };
+    if conds.is_empty() {
+        conds.push(owner_cond(false));
+        return;
+    }
+    let mut scoped = Vec::with_capacity(conds.len() * 2);
+    for (i, mut cond) in conds.drain(..).enumerate() {
+        if i == 0 || cond.or_group {
+            scoped.push(owner_cond(cond.or_group));
+            cond.or_group = false;
+        }
+        scoped.push(cond);
+    }
+    *conds = scoped;
+}
+
+fn exec_describe(p: &mut Parser, pid: u64) -> QueryResult {
     if p.current.ttype != TokenType::Ident {
         return db_result_error(VOS_ERR_SYNTAX, "Expected table name");
     }
@@ -567,13 +1030,21 @@ fn exec_describe(p: &mut Parser) -> QueryResult {
         Some(s) => s,
         None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
     };
+    if let Err(denied) = check_table_access(pid, schema, CAP_READ) { return denied; }
 
     let mut result = db_result_create(schema.column_count);
+    // Table-wide flags, repeated on each row so any line tells the whole story
+    let table_flags = match (schema.encrypted, schema.system_table) {
+        (true, true)   => "ENCRYPTED SYSTEM",
+        (true, false)  => "ENCRYPTED",
+        (false, true)  => "PLAIN SYSTEM",
+        (false, false) => "PLAIN",
+    };
 
     for i in 0..schema.column_count as usize {
         let mut row = Record::new(0);
         row.row_id = i as u64;
-        row.field_count = 4;
+        row.field_count = 6;
         row.set_str(0, schema.columns[i].name_str());
 
         let type_str = match schema.columns[i].col_type {
@@ -584,14 +1055,21 @@ fn exec_describe(p: &mut Parser) -> QueryResult {
             ColumnType::Bool => "BOOL",
             ColumnType::U32  => "U32",
             ColumnType::U8   => "U8",
+            ColumnType::F64  => "F64",
         };
         row.set_str(1, type_str);
         row.set_str(2, if schema.columns[i].primary_key { "YES" } else { "NO" });
         row.set_str(3, if schema.columns[i].not_null { "YES" } else { "NO" });
+        // The primary key is the B-tree key itself; others only via db_create_index
+        let indexed = if schema.columns[i].primary_key { "PK" }
+            else if db_has_index(schema.table_id, i as u32) { "YES" }
+            else { "NO" };
+        row.set_str(4, indexed);
+        row.set_str(5, table_flags);
         db_result_add_row(&mut result, &row);
     }
 
-    result.schema = Some(get_desc_schema());
+    result.set_columns(get_desc_schema());
     result
 }
 
@@ -599,9 +1077,16 @@ fn exec_describe(p: &mut Parser) -> QueryResult {
 // SELECT
 // ---------------------------------------------------------------------------
 
-fn exec_select(p: &mut Parser, _pid: u64) -> QueryResult {
+fn exec_select(p: &mut Parser, pid: u64) -> QueryResult {
     // SELECT * FROM table [WHERE ...]
-    if p.current.ttype == TokenType::Star {
+    let mut count_only = false;
+    if p.current.ttype == TokenType::Ident && str_eq_ignore_case(p.current_value_str(), "COUNT") {
+        p.next_token();
+        if !(p.expect(TokenType::LParen) && p.expect(TokenType::Star) && p.expect(TokenType::RParen)) {
+            return db_result_error(VOS_ERR_SYNTAX, "Expected COUNT(*)");
+        }
+        count_only = true;
+    } else if p.current.ttype == TokenType::Star {
         p.next_token();
     } else {
         // Skip column list for now - always select all
@@ -624,38 +1109,227 @@ fn exec_select(p: &mut Parser, _pid: u64) -> QueryResult {
         Some(s) => s,
         None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
     };
+    if let Err(denied) = check_table_access(pid, schema, CAP_READ) { return denied; }
     p.next_token();
 
-    let conds = parse_where(p);
+    let mut conds = parse_where(p);
+    if !is_admin(pid) && find_column_index(schema, "owner_pid") >= 0 {
+        scope_to_owner(&mut conds, pid);
+    }
+    let order = match parse_order_by(p, schema) {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+    let (offset, limit) = match parse_limit(p) {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
+    // The tree already knows its size, so an unfiltered count decrypts nothing
+    if count_only && conds.is_empty() {
+        return match db_get_index(schema.table_id) {
+            Some(t) => count_result(t.count),
+            None => db_result_error(VOS_ERR_INVAL, "No index for table"),
+        };
+    }
+    // ORDER BY and COUNT(*) must see every match before OFFSET/LIMIT can be applied
+    let (mut scan_offset, scan_limit) = if order.is_some() || count_only {
+        (0, u64::MAX)
+    } else {
+        (offset, limit)
+    };
 
     let mut result = db_result_create(16);
-    result.schema = Some(schema);
+    result.set_columns(schema);
+
+    // Equality on an indexed column: decrypt only the candidate rows
+    if let Some(row_ids) = indexed_candidates(schema, &conds) {
+        for row_id in row_ids {
+            if result.rows.len() as u64 >= scan_limit { break; }
+            if let Some(rec) = db_get_record(schema.table_id, row_id) {
+                if record_matches(&rec, schema, &conds) {
+                    if scan_offset > 0 {
+                        scan_offset -= 1;
+                    } else {
+                        db_result_add_row(&mut result, &rec);
+                    }
+                }
+            }
+        }
+    } else {
+        let index = match db_get_index(schema.table_id) {
+            Some(t) => t as *mut crate::db::btree::Btree,
+            None => return db_result_error(VOS_ERR_INVAL, "No index for table"),
+        };
 
-    let index = match db_get_index(schema.table_id) {
-        Some(t) => t as *mut crate::db::btree::Btree,
-        None => return db_result_error(VOS_ERR_INVAL, "No index for table"),
-    };
+        let mut ctx = ScanCtx {
+            result: &mut result as *mut QueryResult,
+            schema: schema as *const TableSchema,
+            conds: &conds as *const Vec<WhereCond>,
+            delete_mode: false,
+            offset: scan_offset,
+            limit: scan_limit,
+        };
 
-    let ctx = ScanCtx {
-        result: &mut result as *mut QueryResult,
-        schema: schema as *const TableSchema,
-        conds: &conds as *const Vec<WhereCond>,
-        delete_mode: false,
-    };
+        unsafe {
+            btree_scan(&*index, select_scan_callback, &mut ctx as *mut ScanCtx as *mut u8);
+        }
+    }
 
-    unsafe {
-        btree_scan(&*index, select_scan_callback, &ctx as *const ScanCtx as *mut u8);
+    if count_only {
+        return count_result(result.rows.len() as u64);
+    }
+
+    if let Some((col, desc)) = order {
+        result.rows.sort_by(|a, b| {
+            let ord = field_order(&a.fields[col], &b.fields[col]);
+            if desc { ord.reverse() } else { ord }
+        });
+        let start = (offset as usize).min(result.rows.len());
+        result.rows.drain(..start);
+        if (result.rows.len() as u64) > limit {
+            result.rows.truncate(limit as usize);
+        }
     }
 
     result
 }
 
+/// Optional `ORDER BY col [ASC|DESC]`. Returns the column index and descending flag.
+fn parse_order_by(p: &mut Parser, schema: &TableSchema) -> Result<Option<(usize, bool)>, QueryResult> {
+    if p.current.ttype != TokenType::Order { return Ok(None); }
+    p.next_token();
+    if !p.expect(TokenType::By) {
+        return Err(db_result_error(VOS_ERR_SYNTAX, "Expected BY after ORDER"));
+    }
+    if p.current.ttype != TokenType::Ident {
+        return Err(db_result_error(VOS_ERR_SYNTAX, "Expected column after ORDER BY"));
+    }
+    let col = find_column_index(schema, p.current_value_str());
+    if col < 0 {
+        return Err(db_result_error(VOS_ERR_NOTFOUND, "ORDER BY column not found"));
+    }
+    p.next_token();
+
+    let mut desc = false;
+    if p.current.ttype == TokenType::Desc {
+        desc = true;
+        p.next_token();
+    } else if p.current.ttype == TokenType::Asc {
+        p.next_token();
+    }
+    Ok(Some((col as usize, desc)))
+}
+
+/// Optional `LIMIT n [OFFSET m]` tail of a SELECT. Returns (offset, limit).
+fn parse_limit(p: &mut Parser) -> Result<(u64, u64), QueryResult> {
+    if p.current.ttype != TokenType::Limit { return Ok((0, u64::MAX)); }
+    p.next_token();
+    if p.current.ttype != TokenType::Number {
+        return Err(db_result_error(VOS_ERR_SYNTAX, "Expected number after LIMIT"));
+    }
+    let limit = parse_u64(p.current_value_str());
+    p.next_token();
+
+    let mut offset = 0;
+    if p.current.ttype == TokenType::Offset {
+        p.next_token();
+        if p.current.ttype != TokenType::Number {
+            return Err(db_result_error(VOS_ERR_SYNTAX, "Expected number after OFFSET"));
+        }
+        offset = parse_u64(p.current_value_str());
+        p.next_token();
+    }
+    Ok((offset, limit))
+}
+
+/// Row ids to visit when some `col = val` condition hits a secondary index.
+/// Returns None when no condition can use an index (caller falls back to a scan).
+fn indexed_candidates(schema: &TableSchema, conds: &[WhereCond]) -> Option<Vec<u64>> {
+    // With OR, rows outside any one condition's index bucket can still match
+    if conds.iter().any(|c| c.or_group) { return None; }
+    for cond in conds {
+        if !matches!(cond.op, CmpOp::Eq) { continue; }
+        let ci = find_column_index(schema, cond.column_str());
+        if ci < 0 { continue; }
+        // Secrets are only matched by the constant-time scan
+        if database::db_column_sensitive(schema.table_id, ci as usize) { continue; }
+        let col_type = schema.columns[ci as usize].col_type;
+        // Only probe when the literal hashes like the stored field would
+        let key = match (&cond.value, col_type) {
+            (WhereValue::Str(s), ColumnType::Str) => FieldValue::Str(s.clone()),
+            (WhereValue::U64(v), ColumnType::U64 | ColumnType::U32
+                | ColumnType::U8 | ColumnType::I64) => FieldValue::U64(*v),
+            (WhereValue::F64(v), ColumnType::F64) => FieldValue::F64(*v),
+            _ => continue,
+        };
+        if let Some(ids) = db_index_lookup(schema.table_id, ci as u32, &key) {
+            return Some(ids);
+        }
+    }
+    None
+}
+
 // ---------------------------------------------------------------------------
 // INSERT
 // ---------------------------------------------------------------------------
 
+/// Row ids whose column `ci` equals `value`: the rows an INSERT OR REPLACE
+/// supersedes. None if the value can't be compared (blobs, bools).
+fn rows_with_key(schema: &'static TableSchema, ci: usize, value: &FieldValue) -> Option<Vec<u64>> {
+    let value = match value {
+        FieldValue::Str(s) => WhereValue::Str(s.clone()),
+        FieldValue::U64(v) => WhereValue::U64(*v),
+        FieldValue::U32(v) => WhereValue::U64(*v as u64),
+        FieldValue::U8(v)  => WhereValue::U64(*v as u64),
+        FieldValue::F64(v) => WhereValue::F64(*v),
+        _ => return None,
+    };
+    let mut cond = WhereCond {
+        column: [0u8; MAX_COLUMN_NAME],
+        column_len: 0,
+        op: CmpOp::Eq,
+        value,
+        or_group: false,
+    };
+    let name = schema.columns[ci].name_str().as_bytes();
+    cond.column_len = name.len().min(MAX_COLUMN_NAME - 1);
+    cond.column[..cond.column_len].copy_from_slice(&name[..cond.column_len]);
+    let conds = alloc::vec![cond];
+
+    if let Some(ids) = indexed_candidates(schema, &conds) {
+        return Some(ids.into_iter()
+            .filter(|&id| db_get_record(schema.table_id, id)
+                .map_or(false, |r| record_matches(&r, schema, &conds)))
+            .collect());
+    }
+
+    let mut matches = db_result_create(4);
+    let index = db_get_index(schema.table_id)? as *mut crate::db::btree::Btree;
+    let mut ctx = ScanCtx {
+        result: &mut matches as *mut QueryResult,
+        schema: schema as *const TableSchema,
+        conds: &conds as *const Vec<WhereCond>,
+        delete_mode: false,
+        offset: 0,
+        limit: u64::MAX,
+    };
+    unsafe {
+        btree_scan(&*index, select_scan_callback, &mut ctx as *mut ScanCtx as *mut u8);
+    }
+    Some(matches.rows.iter().map(|r| r.row_id).collect())
+}
+
 fn exec_insert(p: &mut Parser, pid: u64) -> QueryResult {
-    // INSERT INTO table (cols) VALUES (vals)
+    // INSERT [OR REPLACE] INTO table (cols) VALUES (vals)
+    let mut replace = false;
+    if p.current.ttype == TokenType::Or {
+        p.next_token();
+        if p.current.ttype != TokenType::Ident || !str_eq_ignore_case(p.current_value_str(), "REPLACE") {
+            return db_result_error(VOS_ERR_SYNTAX, "Expected REPLACE after INSERT OR");
+        }
+        p.next_token();
+        replace = true;
+    }
     if !p.expect(TokenType::Into) {
         return db_result_error(VOS_ERR_SYNTAX, "Expected INTO");
     }
@@ -669,6 +1343,7 @@ fn exec_insert(p: &mut Parser, pid: u64) -> QueryResult {
         Some(s) => s,
         None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
     };
+    if let Err(denied) = check_table_access(pid, schema, CAP_WRITE) { return denied; }
     p.next_token();
 
     // Parse column names
@@ -703,7 +1378,7 @@ fn exec_insert(p: &mut Parser, pid: u64) -> QueryResult {
 
     // Build record
     let mut rec = Record::new(schema.table_id);
-    rec.row_id = db_next_row_id();
+    rec.row_id = db_next_id_for(schema.table_id);
     rec.table_id = schema.table_id;
 
     // Set primary key if first column is PK
@@ -728,15 +1403,36 @@ fn exec_insert(p: &mut Parser, pid: u64) -> QueryResult {
 
         if p.current.ttype == TokenType::StringLit {
             rec.set_str(ci as u32, p.current.value_str());
+        } else if p.current.ttype == TokenType::BlobLit {
+            if p.blob.len() > MAX_BLOB_LEN {
+                return db_result_error(VOS_ERR_OVERFLOW, "Blob exceeds MAX_BLOB_LEN");
+            }
+            rec.set_blob(ci as u32, &p.blob);
+        } else if p.current.ttype == TokenType::Error {
+            return db_result_error(VOS_ERR_SYNTAX, "Malformed literal");
+        } else if let Some(b) = bool_literal(p) {
+            rec.fields[ci as usize] = Some(FieldValue::Bool(b));
         } else if p.current.ttype == TokenType::Number {
             let v = parse_u64(p.current.value_str());
-            if schema.columns[ci as usize].col_type == ColumnType::U64 {
+            if schema.columns[ci as usize].col_type == ColumnType::Bool {
+                rec.fields[ci as usize] = Some(FieldValue::Bool(v != 0));
+            } else if schema.columns[ci as usize].col_type == ColumnType::U64 {
                 rec.set_u64(ci as u32, v);
             } else if schema.columns[ci as usize].col_type == ColumnType::U32 {
                 rec.set_u32(ci as u32, v as u32);
+            } else if schema.columns[ci as usize].col_type == ColumnType::F64 {
+                rec.fields[ci as usize] = Some(FieldValue::F64(v as f64));
             } else {
                 rec.set_str(ci as u32, p.current.value_str());
             }
+        } else if p.current.ttype == TokenType::Real {
+            if schema.columns[ci as usize].col_type == ColumnType::F64 {
+                rec.fields[ci as usize] = Some(FieldValue::F64(parse_f64(p.current.value_str())));
+            } else if schema.columns[ci as usize].col_type == ColumnType::Str {
+                rec.set_str(ci as u32, p.current.value_str());
+            } else {
+                return db_result_error(VOS_ERR_INVAL, "Real value for an integer column");
+            }
         } else {
             break;
         }
@@ -747,42 +1443,114 @@ fn exec_insert(p: &mut Parser, pid: u64) -> QueryResult {
 
     rec.field_count = schema.column_count;
 
-    // Set owner_pid if column exists
+    // Set owner_pid if column exists and the statement left it out. Only
+    // the kernel (pid 0) may name an owner other than itself
     let owner_idx = find_column_index(schema, "owner_pid");
     if owner_idx >= 0 {
-        rec.set_u64(owner_idx as u32, pid);
+        match rec.fields[owner_idx as usize] {
+            None => rec.set_u64(owner_idx as u32, pid),
+            Some(FieldValue::U64(owner)) if pid != 0 && owner != pid => {
+                return db_result_error(VOS_ERR_PERM, "Permission denied: owner_pid must be the caller");
+            }
+            _ => {}
+        }
     }
 
-    // Set created timestamp if column exists
+    // Set created timestamp likewise. Like AuditTable.timestamp it is RTC
+    // unix seconds, so it survives reboots and compares against NOW
     let created_idx = find_column_index(schema, "created");
-    if created_idx >= 0 {
-        rec.set_u64(created_idx as u32, crate::arch::x86_64::pit::pit_get_ticks());
+    if created_idx >= 0 && rec.fields[created_idx as usize].is_none() {
+        rec.set_u64(created_idx as u32, rtc::rtc_unix_time());
     }
 
-    // Set size if column exists and data column exists
-    let size_idx = find_column_index(schema, "size");
-    let data_idx = find_column_index(schema, "data");
-    if size_idx >= 0 && data_idx >= 0 {
-        if let Some(FieldValue::Str(ref s)) = rec.fields[data_idx as usize] {
-            rec.set_u64(size_idx as u32, s.length as u64);
+    // Columns the statement left out take their declared default
+    for ci in 0..schema.column_count as usize {
+        if rec.fields[ci].is_none() {
+            if let Some(d) = db_column_default(schema.table_id, ci) {
+                rec.fields[ci] = Some(d.clone());
+            }
         }
     }
 
+    set_size_from_data(schema, &mut rec);
+
+    for ci in 0..schema.column_count as usize {
+        if schema.columns[ci].not_null && rec.fields[ci].is_none() {
+            let mut msg = String::from("Column ");
+            msg.push_str(schema.columns[ci].name_str());
+            msg.push_str(" is NOT NULL");
+            return db_result_error(VOS_ERR_INVAL, &msg);
+        }
+    }
+
+    // OR REPLACE keys on the primary key when it was given, else the first column
+    let mut superseded: Vec<u64> = Vec::new();
+    if replace {
+        if col_names.is_empty() {
+            return db_result_error(VOS_ERR_SYNTAX, "INSERT OR REPLACE needs a column list");
+        }
+        let first = {
+            let name = &col_names[0];
+            let len = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            core::str::from_utf8(&name[..len]).unwrap_or("")
+        };
+        let pk_listed = schema.columns[0].primary_key && col_names.iter().any(|n| {
+            let len = n.iter().position(|&c| c == 0).unwrap_or(n.len());
+            str_eq_ignore_case(core::str::from_utf8(&n[..len]).unwrap_or(""), schema.columns[0].name_str())
+        });
+        let key_ci = if pk_listed { 0 } else { find_column_index(schema, first) };
+        if key_ci < 0 {
+            return db_result_error(VOS_ERR_INVAL, "Unknown column");
+        }
+        let key_ci = key_ci as usize;
+        let key = match rec.fields[key_ci] {
+            Some(ref v) => v,
+            None => return db_result_error(VOS_ERR_INVAL, "INSERT OR REPLACE key has no value"),
+        };
+        superseded = match rows_with_key(schema, key_ci, key) {
+            Some(ids) => ids,
+            None => return db_result_error(VOS_ERR_INVAL, "INSERT OR REPLACE key must be a string or integer"),
+        };
+    }
+
     let row_id = rec.row_id;
     let err = db_insert_record(schema.table_id, &mut rec);
+    if err == VOS_ERR_OVERFLOW {
+        return db_result_error(err, "Record exceeds MAX_RECORD_SIZE");
+    }
     if err != VOS_OK {
         return db_result_error(err, "Insert failed");
     }
 
+    // The new row is in place before the old ones go, so a failed insert
+    // never loses the row it was meant to replace
+    for &old in superseded.iter() {
+        db_delete_record(schema.table_id, old);
+    }
+
     let mut result = db_result_create(0);
-    set_result_msg_insert(&mut result, row_id);
+    set_result_msg_insert(&mut result, row_id, !superseded.is_empty());
+    result.rows_affected = 1;
     result
 }
 
-fn set_result_msg_insert(result: &mut QueryResult, row_id: u64) {
-    // Format: "1 row inserted (row_id=N)"
+/// Set the size column from the data column's length, if the table has both.
+fn set_size_from_data(schema: &TableSchema, rec: &mut Record) {
+    let size_idx = find_column_index(schema, "size");
+    let data_idx = find_column_index(schema, "data");
+    if size_idx >= 0 && data_idx >= 0 {
+        match rec.fields[data_idx as usize] {
+            Some(FieldValue::Str(ref s)) => rec.set_u64(size_idx as u32, s.length as u64),
+            Some(FieldValue::Blob(ref b)) => rec.set_u64(size_idx as u32, b.as_bytes().len() as u64),
+            _ => {}
+        }
+    }
+}
+
+fn set_result_msg_insert(result: &mut QueryResult, row_id: u64, replaced: bool) {
+    // Format: "1 row inserted (row_id=N)" or "1 row replaced (row_id=N)"
     let mut msg = [0u8; 256];
-    let prefix = b"1 row inserted (row_id=";
+    let prefix: &[u8] = if replaced { b"1 row replaced (row_id=" } else { b"1 row inserted (row_id=" };
     msg[..prefix.len()].copy_from_slice(prefix);
     let mut pos = prefix.len();
     pos += write_u64_to_buf(&mut msg[pos..], row_id);
@@ -800,12 +1568,21 @@ fn set_result_msg_count(result: &mut QueryResult, prefix: &str, count: u32) {
     result.error_msg = msg;
 }
 
+fn append_result_msg_count(result: &mut QueryResult, prefix: &str, count: u32) {
+    let mut pos = result.error_msg.iter().position(|&b| b == 0).unwrap_or(255);
+    let bytes = prefix.as_bytes();
+    let plen = bytes.len().min(255 - pos);
+    result.error_msg[pos..pos + plen].copy_from_slice(&bytes[..plen]);
+    pos += plen;
+    write_u32_to_buf(&mut result.error_msg[pos..255], count);
+}
+
 // ---------------------------------------------------------------------------
 // DELETE
 // ---------------------------------------------------------------------------
 
-fn exec_delete(p: &mut Parser, _pid: u64) -> QueryResult {
-    // DELETE FROM table [WHERE ...]
+fn exec_delete(p: &mut Parser, pid: u64) -> QueryResult {
+    // DELETE FROM table [WHERE ...] [CASCADE]
     if !p.expect(TokenType::From) {
         return db_result_error(VOS_ERR_SYNTAX, "Expected FROM");
     }
@@ -819,10 +1596,22 @@ fn exec_delete(p: &mut Parser, _pid: u64) -> QueryResult {
         Some(s) => s,
         None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
     };
+    if let Err(denied) = check_table_access(pid, schema, CAP_WRITE) { return denied; }
     p.next_token();
 
     let conds = parse_where(p);
 
+    // CASCADE also removes dependent rows (database::CASCADE_RULES), which
+    // needs write access to each child table as well
+    let cascade = p.current.ttype == TokenType::Ident && str_eq_ignore_case(p.current_value_str(), "CASCADE");
+    if cascade {
+        for rule in database::CASCADE_RULES.iter().filter(|r| r.parent_table == schema.table_id) {
+            if let Some(child) = db_get_schema_by_id(rule.child_table) {
+                if let Err(denied) = check_table_access(pid, child, CAP_WRITE) { return denied; }
+            }
+        }
+    }
+
     // Find matching rows via scan
     let mut matches = db_result_create(16);
     let index = match db_get_index(schema.table_id) {
@@ -830,28 +1619,69 @@ fn exec_delete(p: &mut Parser, _pid: u64) -> QueryResult {
         None => return db_result_error(VOS_ERR_INVAL, "No index for table"),
     };
 
-    let ctx = ScanCtx {
+    let mut ctx = ScanCtx {
         result: &mut matches as *mut QueryResult,
         schema: schema as *const TableSchema,
         conds: &conds as *const Vec<WhereCond>,
         delete_mode: true,
+        offset: 0,
+        limit: u64::MAX,
     };
 
     unsafe {
-        btree_scan(&*index, select_scan_callback, &ctx as *const ScanCtx as *mut u8);
+        btree_scan(&*index, select_scan_callback, &mut ctx as *mut ScanCtx as *mut u8);
     }
 
-    // Delete matched rows
+    // Delete matched rows (the scan has finished, so the tree can change)
     let mut deleted: u32 = 0;
-    // Collect row IDs first to avoid mutating tree during iteration
-    let row_ids: Vec<u64> = matches.rows.iter().map(|r| r.row_id).collect();
-    for row_id in row_ids {
-        db_delete_record(schema.table_id, row_id);
+    let mut cascaded: u32 = 0;
+    for row in matches.rows.iter() {
+        if cascade {
+            cascaded += database::db_cascade_delete(schema.table_id, row, &database::CASCADE_RULES);
+        }
+        db_delete_record(schema.table_id, row.row_id);
         deleted += 1;
     }
 
     let mut result = db_result_create(0);
     set_result_msg_count(&mut result, "row(s) deleted: ", deleted);
+    result.rows_affected = deleted;
+    if cascade { append_result_msg_count(&mut result, ", dependent row(s) deleted: ", cascaded); }
+    result
+}
+
+// ---------------------------------------------------------------------------
+// TRUNCATE
+// ---------------------------------------------------------------------------
+
+fn exec_truncate(p: &mut Parser, pid: u64) -> QueryResult {
+    // TRUNCATE TABLE table [FORCE]
+    if p.current.ttype != TokenType::Ident || !str_eq_ignore_case(p.current_value_str(), "TABLE") {
+        return db_result_error(VOS_ERR_SYNTAX, "Expected TABLE after TRUNCATE");
+    }
+    p.next_token();
+    if p.current.ttype != TokenType::Ident {
+        return db_result_error(VOS_ERR_SYNTAX, "Expected table name");
+    }
+    let schema = match db_get_schema_by_name(p.current_value_str()) {
+        Some(s) => s,
+        None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
+    };
+    if let Err(denied) = check_table_access(pid, schema, CAP_WRITE) { return denied; }
+    p.next_token();
+
+    let force = p.current.ttype == TokenType::Ident && str_eq_ignore_case(p.current_value_str(), "FORCE");
+    if schema.system_table && !force {
+        return db_result_error(VOS_ERR_PERM, "System table: use TRUNCATE TABLE name FORCE");
+    }
+
+    let (removed, err) = database::db_truncate_table(schema.table_id);
+    if err != VOS_OK {
+        return db_result_error(err, "Truncate failed");
+    }
+    let mut result = db_result_create(0);
+    set_result_msg_count(&mut result, "row(s) truncated: ", removed as u32);
+    result.rows_affected = removed as u32;
     result
 }
 
@@ -859,7 +1689,7 @@ fn exec_delete(p: &mut Parser, _pid: u64) -> QueryResult {
 // UPDATE
 // ---------------------------------------------------------------------------
 
-fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
+fn exec_update(p: &mut Parser, pid: u64) -> QueryResult {
     // UPDATE table SET col=val [, ...] [WHERE ...]
     if p.current.ttype != TokenType::Ident {
         return db_result_error(VOS_ERR_SYNTAX, "Expected table name");
@@ -870,6 +1700,7 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
         Some(s) => s,
         None => return db_result_error(VOS_ERR_NOTFOUND, "Table not found"),
     };
+    if let Err(denied) = check_table_access(pid, schema, CAP_WRITE) { return denied; }
     p.next_token();
 
     if !p.expect(TokenType::Set) {
@@ -886,6 +1717,8 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
     enum SetValue {
         Str(StrField),
         U64(u64),
+        F64(f64),
+        Blob(Vec<u8>),
     }
 
     let mut assignments: Vec<SetAssign> = Vec::new();
@@ -910,6 +1743,17 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
             sa.value = SetValue::Str(StrField::from_str(p.current.value_str()));
         } else if p.current.ttype == TokenType::Number {
             sa.value = SetValue::U64(parse_u64(p.current.value_str()));
+        } else if p.current.ttype == TokenType::Real {
+            sa.value = SetValue::F64(parse_f64(p.current.value_str()));
+        } else if let Some(b) = bool_literal(p) {
+            sa.value = SetValue::U64(b as u64);
+        } else if p.current.ttype == TokenType::BlobLit {
+            if p.blob.len() > MAX_BLOB_LEN {
+                return db_result_error(VOS_ERR_OVERFLOW, "Blob exceeds MAX_BLOB_LEN");
+            }
+            sa.value = SetValue::Blob(p.blob.clone());
+        } else if p.current.ttype == TokenType::Error {
+            return db_result_error(VOS_ERR_SYNTAX, "Malformed literal");
         } else {
             break;
         }
@@ -920,6 +1764,21 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
 
     let conds = parse_where(p);
 
+    // Check every assignment against the schema before any row changes, so
+    // a bad one fails the statement instead of stopping it halfway
+    for sa in &assignments {
+        let col_str = core::str::from_utf8(&sa.col_name[..sa.col_name_len]).unwrap_or("");
+        let ci = find_column_index(schema, col_str);
+        if ci < 0 {
+            return db_result_error(VOS_ERR_INVAL, "Unknown column");
+        }
+        if let SetValue::F64(_) = sa.value {
+            if schema.columns[ci as usize].col_type != ColumnType::F64 {
+                return db_result_error(VOS_ERR_INVAL, "Real value for a non-F64 column");
+            }
+        }
+    }
+
     // Find matching rows
     let mut matches = db_result_create(16);
     let index = match db_get_index(schema.table_id) {
@@ -927,17 +1786,26 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
         None => return db_result_error(VOS_ERR_INVAL, "No index for table"),
     };
 
-    let ctx = ScanCtx {
+    let mut ctx = ScanCtx {
         result: &mut matches as *mut QueryResult,
         schema: schema as *const TableSchema,
         conds: &conds as *const Vec<WhereCond>,
         delete_mode: false,
+        offset: 0,
+        limit: u64::MAX,
     };
 
     unsafe {
-        btree_scan(&*index, select_scan_callback, &ctx as *const ScanCtx as *mut u8);
+        btree_scan(&*index, select_scan_callback, &mut ctx as *mut ScanCtx as *mut u8);
     }
 
+    // size follows data, as on insert
+    let data_idx = find_column_index(schema, "data");
+    let data_set = data_idx >= 0 && assignments.iter().any(|sa| {
+        let col_str = core::str::from_utf8(&sa.col_name[..sa.col_name_len]).unwrap_or("");
+        find_column_index(schema, col_str) == data_idx
+    });
+
     // Update: modify matched records and re-encrypt
     let mut updated: u32 = 0;
     for i in 0..matches.rows.len() {
@@ -952,31 +1820,48 @@ fn exec_update(p: &mut Parser, _pid: u64) -> QueryResult {
         for sa in &assignments {
             let col_str = core::str::from_utf8(&sa.col_name[..sa.col_name_len]).unwrap_or("");
             let ci = find_column_index(schema, col_str);
-            if ci < 0 { continue; }
             match &sa.value {
                 SetValue::Str(s) => {
                     modified.fields[ci as usize] = Some(FieldValue::Str(s.clone()));
                 }
+                SetValue::U64(v) if schema.columns[ci as usize].col_type == ColumnType::Bool => {
+                    modified.fields[ci as usize] = Some(FieldValue::Bool(*v != 0));
+                }
+                SetValue::U64(v) if schema.columns[ci as usize].col_type == ColumnType::F64 => {
+                    modified.fields[ci as usize] = Some(FieldValue::F64(*v as f64));
+                }
                 SetValue::U64(v) => {
                     modified.fields[ci as usize] = Some(FieldValue::U64(*v));
                 }
+                SetValue::F64(v) => {
+                    modified.fields[ci as usize] = Some(FieldValue::F64(*v));
+                }
+                SetValue::Blob(b) => {
+                    modified.set_blob(ci as u32, b);
+                }
             }
         }
 
-        db_update_encrypted(schema.table_id, modified.row_id, &mut modified);
-        updated += 1;
+        if data_set {
+            set_size_from_data(schema, &mut modified);
+        }
+
+        if db_update_encrypted(schema.table_id, modified.row_id, &mut modified) == VOS_OK {
+            updated += 1;
+        }
     }
 
     let mut result = db_result_create(0);
     set_result_msg_count(&mut result, "row(s) updated: ", updated);
+    result.rows_affected = updated;
     result
 }
 
 // ---------------------------------------------------------------------------
-// GRANT (stub - parse and return message)
+// GRANT
 // ---------------------------------------------------------------------------
 
-fn exec_grant(p: &mut Parser, _pid: u64) -> QueryResult {
+fn exec_grant(p: &mut Parser, pid: u64) -> QueryResult {
     let mut rights: u32 = 0;
 
     while p.current.ttype == TokenType::Read
@@ -986,11 +1871,11 @@ fn exec_grant(p: &mut Parser, _pid: u64) -> QueryResult {
     {
         let val = p.current.value_str();
         if p.current.ttype == TokenType::Read || str_eq_ignore_case(val, "READ") {
-            rights |= 0x01; // CAP_READ
+            rights |= CAP_READ;
         } else if p.current.ttype == TokenType::Write || str_eq_ignore_case(val, "WRITE") {
-            rights |= 0x02; // CAP_WRITE
+            rights |= CAP_WRITE;
         } else if p.current.ttype == TokenType::All || str_eq_ignore_case(val, "ALL") {
-            rights = 0xFF; // CAP_ALL
+            rights = CAP_ALL;
         }
         p.next_token();
         if p.current.ttype == TokenType::Comma { p.next_token(); } else { break; }
@@ -1015,54 +1900,68 @@ fn exec_grant(p: &mut Parser, _pid: u64) -> QueryResult {
     }
     let target_pid = parse_u64(p.current.value_str());
 
+    // The new capability is delegated from one the caller holds, so it can
+    // carry no more than that one does and is revoked along with it
+    let parent = match delegation::cap_find_grantable(pid, object_id, rights) {
+        Some(id) => id,
+        None => return db_result_error(VOS_ERR_PERM, "No capability to grant those rights on that object"),
+    };
+    let cap_id = match delegation::cap_delegate_on(parent, object_id, target_pid, rights) {
+        Ok(id) => id,
+        Err(e) => return db_result_error(e as i32, e.as_str()),
+    };
+
     let mut result = db_result_create(0);
-    // Format grant message
     let mut msg = [0u8; 256];
-    let prefix = b"GRANT rights=0x";
+    let prefix = b"GRANT cap_id=";
     msg[..prefix.len()].copy_from_slice(prefix);
     let mut pos = prefix.len();
+    pos += write_u64_to_buf(&mut msg[pos..], cap_id);
+    let rights_str = b" rights=0x";
+    msg[pos..pos + rights_str.len()].copy_from_slice(rights_str);
+    pos += rights_str.len();
     pos += write_hex_to_buf(&mut msg[pos..], rights as u64);
-    let on_str = b" on obj=";
-    if pos + on_str.len() < 256 {
-        msg[pos..pos + on_str.len()].copy_from_slice(on_str);
-        pos += on_str.len();
-    }
-    pos += write_u64_to_buf(&mut msg[pos..], object_id);
     let to_str = b" to pid=";
-    if pos + to_str.len() < 256 {
-        msg[pos..pos + to_str.len()].copy_from_slice(to_str);
-        pos += to_str.len();
-    }
+    msg[pos..pos + to_str.len()].copy_from_slice(to_str);
+    pos += to_str.len();
     pos += write_u64_to_buf(&mut msg[pos..], target_pid);
-    let note = b" (cap system not yet wired)";
-    if pos + note.len() < 256 {
-        msg[pos..pos + note.len()].copy_from_slice(note);
-    }
+    let parent_str = b" (from cap ";
+    msg[pos..pos + parent_str.len()].copy_from_slice(parent_str);
+    pos += parent_str.len();
+    pos += write_u64_to_buf(&mut msg[pos..], parent);
+    msg[pos] = b')';
     result.error_msg = msg;
     result
 }
 
 // ---------------------------------------------------------------------------
-// REVOKE (stub - parse and return message)
+// REVOKE
 // ---------------------------------------------------------------------------
 
-fn exec_revoke(p: &mut Parser, _pid: u64) -> QueryResult {
+fn exec_revoke(p: &mut Parser, pid: u64) -> QueryResult {
+    // REVOKE cap_id [CASCADE]
     if p.current.ttype != TokenType::Number {
         return db_result_error(VOS_ERR_SYNTAX, "Expected cap_id");
     }
     let cap_id = parse_u64(p.current.value_str());
+    p.next_token();
+    let cascade = p.current.ttype == TokenType::Ident && str_eq_ignore_case(p.current_value_str(), "CASCADE");
 
-    let mut result = db_result_create(0);
-    let mut msg = [0u8; 256];
-    let prefix = b"REVOKE cap_id=";
-    msg[..prefix.len()].copy_from_slice(prefix);
-    let mut pos = prefix.len();
-    pos += write_u64_to_buf(&mut msg[pos..], cap_id);
-    let note = b" (cap system not yet wired)";
-    if pos + note.len() < 256 {
-        msg[pos..pos + note.len()].copy_from_slice(note);
+    if !delegation::cap_may_revoke(pid, cap_id) {
+        return db_result_error(VOS_ERR_PERM, "Permission denied: not your capability");
     }
-    result.error_msg = msg;
+    let outcome = if cascade {
+        delegation::cap_revoke_tree(cap_id)
+    } else {
+        delegation::cap_revoke_one(cap_id).map(|_| 1)
+    };
+    let count = match outcome {
+        Ok(n) => n,
+        Err(e) => return db_result_error(e as i32, e.as_str()),
+    };
+
+    let mut result = db_result_create(0);
+    set_result_msg_count(&mut result, "capability(s) revoked: ", count);
     result
 }
 
@@ -1072,8 +1971,38 @@ fn exec_revoke(p: &mut Parser, _pid: u64) -> QueryResult {
 
 /// Execute a SQL-subset query. Returns a QueryResult.
 pub fn query_execute(input: &str, caller_pid: u64) -> QueryResult {
-    let mut p = Parser::new(input);
+    let outer_caller = database::db_set_audit_caller(caller_pid);
+    let result = query_dispatch(&mut Parser::new(input), caller_pid);
+    database::db_set_audit_caller(outer_caller);
+    result
+}
 
+/// query_execute_with without the capability check, for kernel services
+/// that enforce their own rules (the IPC mailbox only touches the caller's
+/// own messages). Audit entries still name `on_behalf_of`.
+pub fn query_execute_trusted_with(sql: &str, params: &[FieldValue], on_behalf_of: u64) -> QueryResult {
+    let stmt = match query_prepare(sql) {
+        Ok(stmt) => stmt,
+        Err(e) => return db_result_error(e as i32, "Malformed statement"),
+    };
+    if params.len() != stmt.param_count {
+        return db_result_error(VOS_ERR_INVAL, "Wrong number of parameters");
+    }
+    let outer_caller = database::db_set_audit_caller(on_behalf_of);
+    let result = query_dispatch(&mut Parser::replaying(&stmt, params), 0);
+    database::db_set_audit_caller(outer_caller);
+    result
+}
+
+/// query_execute for Rust callers: Err when the statement failed.
+/// Callers that need the message should use query_execute and `status()`.
+pub fn query_run(input: &str, caller_pid: u64) -> VosResult<QueryResult> {
+    let result = query_execute(input, caller_pid);
+    result.status()?;
+    Ok(result)
+}
+
+fn query_dispatch(p: &mut Parser, caller_pid: u64) -> QueryResult {
     match p.current.ttype {
         TokenType::Show => {
             p.next_token();
@@ -1084,39 +2013,112 @@ pub fn query_execute(input: &str, caller_pid: u64) -> QueryResult {
         }
         TokenType::Describe => {
             p.next_token();
-            exec_describe(&mut p)
+            exec_describe(p, caller_pid)
         }
         TokenType::Select => {
             p.next_token();
-            exec_select(&mut p, caller_pid)
+            exec_select(p, caller_pid)
         }
         TokenType::Insert => {
             p.next_token();
-            exec_insert(&mut p, caller_pid)
+            exec_insert(p, caller_pid)
         }
         TokenType::Delete => {
             p.next_token();
-            exec_delete(&mut p, caller_pid)
+            exec_delete(p, caller_pid)
         }
         TokenType::Update => {
             p.next_token();
-            exec_update(&mut p, caller_pid)
+            exec_update(p, caller_pid)
         }
         TokenType::Grant => {
             p.next_token();
-            exec_grant(&mut p, caller_pid)
+            exec_grant(p, caller_pid)
         }
         TokenType::Revoke => {
             p.next_token();
-            exec_revoke(&mut p, caller_pid)
+            exec_revoke(p, caller_pid)
+        }
+        TokenType::Truncate => {
+            p.next_token();
+            exec_truncate(p, caller_pid)
         }
         _ => {
             db_result_error(VOS_ERR_SYNTAX,
-                "Unknown command. Use: SELECT, INSERT, DELETE, UPDATE, SHOW TABLES, DESCRIBE, GRANT, REVOKE")
+                "Unknown command. Use: SELECT, INSERT, DELETE, UPDATE, TRUNCATE, SHOW TABLES, DESCRIBE, GRANT, REVOKE")
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Prepared statements
+// ---------------------------------------------------------------------------
+
+struct PreparedToken {
+    token: Token,
+    /// Decoded bytes when token is a BlobLit
+    blob: Vec<u8>,
+}
+
+/// A statement lexed once by query_prepare, run any number of times with
+/// different values for its `?` placeholders.
+pub struct PreparedStmt {
+    tokens: Vec<PreparedToken>,
+    param_count: usize,
+}
+
+impl PreparedStmt {
+    /// Number of `?` placeholders; query_execute_prepared needs exactly this
+    /// many values.
+    pub fn param_count(&self) -> usize {
+        self.param_count
+    }
+}
+
+/// Lex `sql` into a reusable statement. `?` may stand wherever a literal
+/// may. Fails with Syntax on a character the lexer rejects.
+pub fn query_prepare(sql: &str) -> VosResult<PreparedStmt> {
+    let mut p = Parser::preparing(sql);
+    let mut stmt = PreparedStmt { tokens: Vec::new(), param_count: 0 };
+    while p.current.ttype != TokenType::Eof {
+        match p.current.ttype {
+            TokenType::Error => return Err(VosError::Syntax),
+            TokenType::Param => stmt.param_count += 1,
+            _ => {}
         }
+        let blob = if p.current.ttype == TokenType::BlobLit { p.blob.clone() } else { Vec::new() };
+        stmt.tokens.push(PreparedToken { token: p.current.clone(), blob });
+        p.next_token();
+    }
+    Ok(stmt)
+}
+
+/// Run a prepared statement with `params` bound to its placeholders in order.
+pub fn query_execute_prepared(stmt: &PreparedStmt, params: &[FieldValue], caller_pid: u64) -> QueryResult {
+    if params.len() != stmt.param_count {
+        return db_result_error(VOS_ERR_INVAL, "Wrong number of parameters");
+    }
+    let outer_caller = database::db_set_audit_caller(caller_pid);
+    let result = query_dispatch(&mut Parser::replaying(stmt, params), caller_pid);
+    database::db_set_audit_caller(outer_caller);
+    result
+}
+
+/// Prepare and run `sql` once: for one-off statements with bound values.
+pub fn query_execute_with(sql: &str, params: &[FieldValue], caller_pid: u64) -> QueryResult {
+    match query_prepare(sql) {
+        Ok(stmt) => query_execute_prepared(&stmt, params, caller_pid),
+        Err(e) => db_result_error(e as i32, "Malformed statement"),
     }
 }
 
+/// query_execute_with as a Result, like query_run.
+pub fn query_run_with(sql: &str, params: &[FieldValue], caller_pid: u64) -> VosResult<QueryResult> {
+    let result = query_execute_with(sql, params, caller_pid);
+    result.status()?;
+    Ok(result)
+}
+
 // ---------------------------------------------------------------------------
 // Number-to-string helpers (no_std, no alloc for formatting)
 // ---------------------------------------------------------------------------
@@ -0,0 +1,314 @@
+// Record serialization for the encrypt-then-MAC pipeline
+// Port of kernel/db/record_serde.c, plus body compression
+//
+// Wire format:
+//   [row_id:8][table_id:4][field_count:4]
+//   Per field: [type:4][data...]
+//     U64/I64/F64: 8 bytes (F64 as its IEEE-754 bits)
+//     U32: 4 bytes
+//     U8/Bool: 1 byte
+//     Str: [length:2][data:length]
+//     Blob: [length:4][data:length]
+//     NULL (unset field): no data
+//
+// The body after the 16-byte header is LZ-compressed when that makes it
+// smaller; bit 31 of field_count marks a compressed body. Records written
+// before compression existed never have that bit set and read unchanged.
+//
+// Compressed body: a control byte c < 0x80 is followed by c+1 literal bytes;
+// c >= 0x80 copies (c & 0x7F) + 3 bytes from the u16 LE distance that follows.
+
+use crate::db::record::{Record, FieldValue, StrField};
+use vaultos_shared::db_types::*;
+
+const RECORD_HDR_LEN: usize = 16;
+const RECORD_FIELD_COUNT_OFF: usize = 12;
+const RECORD_COMPRESSED_FLAG: u32 = 0x8000_0000;
+
+// Type tags, matching column_type_t
+const TAG_U64: u32 = 0;
+const TAG_I64: u32 = 1;
+const TAG_STR: u32 = 2;
+const TAG_BLOB: u32 = 3;
+const TAG_BOOL: u32 = 4;
+const TAG_U32: u32 = 5;
+const TAG_U8: u32 = 6;
+const TAG_F64: u32 = 7;
+const TAG_NULL: u32 = 0xFFFF_FFFF;
+
+// ---------------------------------------------------------------------------
+// Little-endian writers / readers
+// ---------------------------------------------------------------------------
+
+/// Append `bytes` at *off. Returns false if they don't fit.
+fn wr(buf: &mut [u8], off: &mut usize, bytes: &[u8]) -> bool {
+    if *off + bytes.len() > buf.len() { return false; }
+    buf[*off..*off + bytes.len()].copy_from_slice(bytes);
+    *off += bytes.len();
+    true
+}
+
+/// Take `n` bytes at *off. Returns None if the buffer is too short.
+fn rd<'a>(buf: &'a [u8], off: &mut usize, n: usize) -> Option<&'a [u8]> {
+    if *off + n > buf.len() { return None; }
+    let s = &buf[*off..*off + n];
+    *off += n;
+    Some(s)
+}
+
+fn rd_u8(buf: &[u8], off: &mut usize) -> Option<u8> {
+    rd(buf, off, 1).map(|b| b[0])
+}
+
+fn rd_u16(buf: &[u8], off: &mut usize) -> Option<u16> {
+    rd(buf, off, 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn rd_u32(buf: &[u8], off: &mut usize) -> Option<u32> {
+    rd(buf, off, 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn rd_u64(buf: &[u8], off: &mut usize) -> Option<u64> {
+    let b = rd(buf, off, 8)?;
+    let mut v = [0u8; 8];
+    v.copy_from_slice(b);
+    Some(u64::from_le_bytes(v))
+}
+
+fn field_count_word(buf: &[u8]) -> u32 {
+    let o = RECORD_FIELD_COUNT_OFF;
+    u32::from_le_bytes([buf[o], buf[o + 1], buf[o + 2], buf[o + 3]])
+}
+
+fn set_field_count_word(buf: &mut [u8], v: u32) {
+    let o = RECORD_FIELD_COUNT_OFF;
+    buf[o..o + 4].copy_from_slice(&v.to_le_bytes());
+}
+
+// ---------------------------------------------------------------------------
+// Serialize / deserialize
+// ---------------------------------------------------------------------------
+
+/// Serialize `rec` into `buf`, compressing the body if that saves space.
+/// Returns bytes written, or 0 if the uncompressed form does not fit.
+///
+/// Doesn't allocate, so it is safe under CRYPTO_LOCK. The compressor's hash
+/// table is shared: callers serialize one record at a time under that lock.
+pub fn record_serialize(rec: &Record, buf: &mut [u8]) -> usize {
+    let len = serialize_plain(rec, buf);
+    if len <= RECORD_HDR_LEN { return len; }
+
+    let mut packed = [0u8; MAX_RECORD_SIZE];
+    let packed_len = lz_compress(&buf[RECORD_HDR_LEN..len], &mut packed);
+    let out = match packed_len {
+        Some(n) => {
+            buf[RECORD_HDR_LEN..RECORD_HDR_LEN + n].copy_from_slice(&packed[..n]);
+            for b in buf[RECORD_HDR_LEN + n..len].iter_mut() { *b = 0; }
+            let fc = field_count_word(buf);
+            set_field_count_word(buf, fc | RECORD_COMPRESSED_FLAG);
+            RECORD_HDR_LEN + n
+        }
+        None => len,
+    };
+    for b in packed.iter_mut() { *b = 0; }
+    out
+}
+
+fn serialize_plain(rec: &Record, buf: &mut [u8]) -> usize {
+    let mut off = 0;
+    if rec.field_count as usize > MAX_COLUMNS { return 0; }
+    if !wr(buf, &mut off, &rec.row_id.to_le_bytes())
+        || !wr(buf, &mut off, &rec.table_id.to_le_bytes())
+        || !wr(buf, &mut off, &rec.field_count.to_le_bytes()) {
+        return 0;
+    }
+
+    for f in rec.fields[..rec.field_count as usize].iter() {
+        let ok = match f {
+            None => wr(buf, &mut off, &TAG_NULL.to_le_bytes()),
+            Some(FieldValue::U64(v)) => wr(buf, &mut off, &TAG_U64.to_le_bytes())
+                && wr(buf, &mut off, &v.to_le_bytes()),
+            Some(FieldValue::I64(v)) => wr(buf, &mut off, &TAG_I64.to_le_bytes())
+                && wr(buf, &mut off, &v.to_le_bytes()),
+            Some(FieldValue::F64(v)) => wr(buf, &mut off, &TAG_F64.to_le_bytes())
+                && wr(buf, &mut off, &v.to_bits().to_le_bytes()),
+            Some(FieldValue::U32(v)) => wr(buf, &mut off, &TAG_U32.to_le_bytes())
+                && wr(buf, &mut off, &v.to_le_bytes()),
+            Some(FieldValue::U8(v)) => wr(buf, &mut off, &TAG_U8.to_le_bytes())
+                && wr(buf, &mut off, &[*v]),
+            Some(FieldValue::Bool(v)) => wr(buf, &mut off, &TAG_BOOL.to_le_bytes())
+                && wr(buf, &mut off, &[*v as u8]),
+            Some(FieldValue::Str(s)) => {
+                let data = s.as_str().as_bytes();
+                data.len() <= MAX_STR_LEN
+                    && wr(buf, &mut off, &TAG_STR.to_le_bytes())
+                    && wr(buf, &mut off, &(data.len() as u16).to_le_bytes())
+                    && wr(buf, &mut off, data)
+            }
+            Some(FieldValue::Blob(b)) => {
+                let data = b.as_bytes();
+                data.len() <= MAX_BLOB_LEN
+                    && wr(buf, &mut off, &TAG_BLOB.to_le_bytes())
+                    && wr(buf, &mut off, &(data.len() as u32).to_le_bytes())
+                    && wr(buf, &mut off, data)
+            }
+        };
+        if !ok { return 0; }
+    }
+    off
+}
+
+/// Deserialize a record, expanding a compressed body first.
+/// Returns the record and the bytes consumed, or None on malformed input.
+pub fn record_deserialize(buf: &[u8]) -> Option<(Record, usize)> {
+    if buf.len() < RECORD_HDR_LEN { return None; }
+    if field_count_word(buf) & RECORD_COMPRESSED_FLAG == 0 {
+        return deserialize_plain(buf);
+    }
+
+    let mut plain = [0u8; MAX_RECORD_SIZE];
+    plain[..RECORD_HDR_LEN].copy_from_slice(&buf[..RECORD_HDR_LEN]);
+    let fc = field_count_word(&plain);
+    set_field_count_word(&mut plain, fc & !RECORD_COMPRESSED_FLAG);
+    let result = lz_decompress(&buf[RECORD_HDR_LEN..], &mut plain[RECORD_HDR_LEN..])
+        .and_then(|n| deserialize_plain(&plain[..RECORD_HDR_LEN + n]))
+        .map(|(rec, _)| (rec, buf.len()));
+    for b in plain.iter_mut() { *b = 0; }
+    result
+}
+
+fn deserialize_plain(buf: &[u8]) -> Option<(Record, usize)> {
+    let mut off = 0;
+    let row_id = rd_u64(buf, &mut off)?;
+    let table_id = rd_u32(buf, &mut off)?;
+    let field_count = rd_u32(buf, &mut off)?;
+    if field_count as usize > MAX_COLUMNS { return None; }
+
+    let mut rec = Record::new(table_id);
+    rec.row_id = row_id;
+    for i in 0..field_count as usize {
+        rec.fields[i] = match rd_u32(buf, &mut off)? {
+            TAG_NULL => None,
+            TAG_U64 => Some(FieldValue::U64(rd_u64(buf, &mut off)?)),
+            TAG_I64 => Some(FieldValue::I64(rd_u64(buf, &mut off)? as i64)),
+            TAG_F64 => Some(FieldValue::F64(f64::from_bits(rd_u64(buf, &mut off)?))),
+            TAG_U32 => Some(FieldValue::U32(rd_u32(buf, &mut off)?)),
+            TAG_U8 => Some(FieldValue::U8(rd_u8(buf, &mut off)?)),
+            TAG_BOOL => Some(FieldValue::Bool(rd_u8(buf, &mut off)? != 0)),
+            TAG_STR => {
+                let len = rd_u16(buf, &mut off)? as usize;
+                if len > MAX_STR_LEN { return None; }
+                let s = core::str::from_utf8(rd(buf, &mut off, len)?).ok()?;
+                Some(FieldValue::Str(StrField::from_str(s)))
+            }
+            TAG_BLOB => {
+                let len = rd_u32(buf, &mut off)? as usize;
+                if len > MAX_BLOB_LEN { return None; }
+                let data = rd(buf, &mut off, len)?;
+                rec.set_blob(i as u32, data);
+                continue;
+            }
+            _ => return None,
+        };
+    }
+    rec.field_count = field_count;
+    Some((rec, off))
+}
+
+// ---------------------------------------------------------------------------
+// LZ compression
+// ---------------------------------------------------------------------------
+
+const LZ_MIN_MATCH: usize = 3;
+const LZ_MAX_MATCH: usize = 0x7F + LZ_MIN_MATCH;
+const LZ_MAX_LITERALS: usize = 0x80;
+const LZ_MAX_DIST: usize = 0xFFFF;
+const LZ_HASH_SIZE: usize = 4096;
+
+static mut LZ_HASH: [u32; LZ_HASH_SIZE] = [u32::MAX; LZ_HASH_SIZE];
+
+fn lz_hash(b: &[u8]) -> usize {
+    let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+    (v.wrapping_mul(2654435761) >> 20) as usize & (LZ_HASH_SIZE - 1)
+}
+
+/// Emit src[start..end] as literal runs. Returns false if dst is full.
+fn lz_emit_literals(src: &[u8], mut start: usize, end: usize, dst: &mut [u8], out: &mut usize) -> bool {
+    while start < end {
+        let n = (end - start).min(LZ_MAX_LITERALS);
+        if *out + 1 + n > dst.len() { return false; }
+        dst[*out] = (n - 1) as u8;
+        dst[*out + 1..*out + 1 + n].copy_from_slice(&src[start..start + n]);
+        *out += 1 + n;
+        start += n;
+    }
+    true
+}
+
+/// Compress src into dst. Returns None unless the result is strictly smaller.
+fn lz_compress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    if src.is_empty() { return None; }
+    let limit = dst.len().min(src.len() - 1);
+    let dst = &mut dst[..limit];
+    unsafe {
+        for h in LZ_HASH.iter_mut() { *h = u32::MAX; }
+
+        let mut out = 0;
+        let mut lit = 0;
+        let mut i = 0;
+        while i + LZ_MIN_MATCH <= src.len() {
+            let h = lz_hash(&src[i..]);
+            let cand = LZ_HASH[h] as usize;
+            LZ_HASH[h] = i as u32;
+
+            let mut len = 0;
+            if cand < i && i - cand <= LZ_MAX_DIST {
+                let max = (src.len() - i).min(LZ_MAX_MATCH);
+                while len < max && src[cand + len] == src[i + len] { len += 1; }
+            }
+            if len < LZ_MIN_MATCH {
+                i += 1;
+                continue;
+            }
+
+            if !lz_emit_literals(src, lit, i, dst, &mut out) { return None; }
+            if out + 3 > dst.len() { return None; }
+            let dist = i - cand;
+            dst[out] = 0x80 | (len - LZ_MIN_MATCH) as u8;
+            dst[out + 1] = dist as u8;
+            dst[out + 2] = (dist >> 8) as u8;
+            out += 3;
+            i += len;
+            lit = i;
+        }
+        if !lz_emit_literals(src, lit, src.len(), dst, &mut out) { return None; }
+        Some(out)
+    }
+}
+
+/// Decompress src into dst. Returns None on a malformed stream or overflow.
+fn lz_decompress(src: &[u8], dst: &mut [u8]) -> Option<usize> {
+    let mut i = 0;
+    let mut out = 0;
+    while i < src.len() {
+        let c = src[i] as usize;
+        i += 1;
+        if c < 0x80 {
+            let n = c + 1;
+            if i + n > src.len() || out + n > dst.len() { return None; }
+            dst[out..out + n].copy_from_slice(&src[i..i + n]);
+            i += n;
+            out += n;
+        } else {
+            if i + 2 > src.len() { return None; }
+            let len = (c & 0x7F) + LZ_MIN_MATCH;
+            let dist = src[i] as usize | (src[i + 1] as usize) << 8;
+            i += 2;
+            if dist == 0 || dist > out || out + len > dst.len() { return None; }
+            // Byte-wise: a match may overlap the bytes it produces
+            for k in 0..len { dst[out + k] = dst[out + k - dist]; }
+            out += len;
+        }
+    }
+    Some(out)
+}
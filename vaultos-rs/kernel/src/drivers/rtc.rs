@@ -0,0 +1,141 @@
+// CMOS real-time clock driver for VaultOS-RS
+//
+// Reads wall-clock time from the MC146818-compatible RTC behind ports
+// 0x70/0x71. The chip updates its registers once per second; reads are
+// only taken while the update-in-progress flag is clear and repeated until
+// two consecutive snapshots agree, so a value is never torn across an update.
+
+use core::arch::asm;
+
+const CMOS_ADDR: u16 = 0x70;
+const CMOS_DATA: u16 = 0x71;
+
+const RTC_SECONDS: u8 = 0x00;
+const RTC_MINUTES: u8 = 0x02;
+const RTC_HOURS: u8   = 0x04;
+const RTC_DAY: u8     = 0x07;
+const RTC_MONTH: u8   = 0x08;
+const RTC_YEAR: u8    = 0x09;
+const RTC_STATUS_A: u8 = 0x0A;
+const RTC_STATUS_B: u8 = 0x0B;
+
+const STATUS_A_UIP: u8 = 0x80;      // Update in progress
+const STATUS_B_24H: u8 = 0x02;      // Hours are 0-23
+const STATUS_B_BINARY: u8 = 0x04;   // Values are binary, not BCD
+const HOUR_PM: u8 = 0x80;           // PM bit in 12-hour mode
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct RawTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+}
+
+unsafe fn outb(port: u16, val: u8) {
+    asm!("out dx, al", in("dx") port, in("al") val, options(nomem, nostack, preserves_flags));
+}
+
+unsafe fn inb(port: u16) -> u8 {
+    let ret: u8;
+    asm!("in al, dx", out("al") ret, in("dx") port, options(nomem, nostack, preserves_flags));
+    ret
+}
+
+fn cmos_read(reg: u8) -> u8 {
+    unsafe {
+        // Bit 7 of the address port is NMI-disable; keep NMIs enabled
+        outb(CMOS_ADDR, reg & 0x7F);
+        inb(CMOS_DATA)
+    }
+}
+
+fn update_in_progress() -> bool {
+    cmos_read(RTC_STATUS_A) & STATUS_A_UIP != 0
+}
+
+fn read_raw() -> RawTime {
+    while update_in_progress() {}
+    RawTime {
+        second: cmos_read(RTC_SECONDS),
+        minute: cmos_read(RTC_MINUTES),
+        hour: cmos_read(RTC_HOURS),
+        day: cmos_read(RTC_DAY),
+        month: cmos_read(RTC_MONTH),
+        year: cmos_read(RTC_YEAR),
+    }
+}
+
+fn bcd_to_bin(v: u8) -> u8 {
+    (v & 0x0F) + (v >> 4) * 10
+}
+
+/// Read the current wall-clock time as (hour, minute, second, year, month, day).
+/// The RTC is assumed to hold UTC and a year in 2000-2099.
+pub fn rtc_read() -> (u8, u8, u8, u16, u8, u8) {
+    let mut t = read_raw();
+    loop {
+        let again = read_raw();
+        if again == t { break; }
+        t = again;
+    }
+
+    let status_b = cmos_read(RTC_STATUS_B);
+    let pm = t.hour & HOUR_PM != 0;
+    let mut hour = t.hour & !HOUR_PM;
+
+    if status_b & STATUS_B_BINARY == 0 {
+        t.second = bcd_to_bin(t.second);
+        t.minute = bcd_to_bin(t.minute);
+        hour = bcd_to_bin(hour);
+        t.day = bcd_to_bin(t.day);
+        t.month = bcd_to_bin(t.month);
+        t.year = bcd_to_bin(t.year);
+    }
+
+    if status_b & STATUS_B_24H == 0 {
+        // 12-hour mode: 12 AM is 0, 12 PM stays 12
+        if hour == 12 { hour = 0; }
+        if pm { hour += 12; }
+    }
+
+    (hour, t.minute, t.second, 2000 + t.year as u16, t.month, t.day)
+}
+
+/// Seconds since 1970-01-01 00:00:00 UTC.
+pub fn rtc_unix_time() -> u64 {
+    let (h, m, s, y, mo, d) = rtc_read();
+
+    // Days from civil date (Howard Hinnant's algorithm, proleptic Gregorian)
+    let y = if mo <= 2 { y as i64 - 1 } else { y as i64 };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (mo as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    (days as u64) * 86400 + h as u64 * 3600 + m as u64 * 60 + s as u64
+}
+
+/// Split unix seconds into (hour, minute, second, year, month, day), the
+/// inverse of rtc_unix_time.
+pub fn unix_to_civil(t: u64) -> (u8, u8, u8, u16, u8, u8) {
+    let secs = t % 86400;
+    let (h, m, s) = ((secs / 3600) as u8, ((secs / 60) % 60) as u8, (secs % 60) as u8);
+
+    // Civil from days (Howard Hinnant's algorithm, proleptic Gregorian)
+    let z = (t / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let mo = (if mp < 10 { mp + 3 } else { mp - 9 }) as u8;
+    let y = if mo <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 };
+
+    (h, m, s, y as u16, mo, d)
+}
@@ -0,0 +1,101 @@
+// Typed kernel errors
+//
+// VosError mirrors the VOS_ERR_* codes one-to-one, with the same values, so
+// `err as i32` is the integer the C-port call sites and the syscall ABI
+// expect. New Rust code should return Result<T, VosError> and convert
+// only where a raw code crosses an ABI boundary.
+
+use core::fmt;
+use vaultos_shared::error_codes::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum VosError {
+    Generic    = VOS_ERR_GENERIC,
+    NoMem      = VOS_ERR_NOMEM,
+    Inval      = VOS_ERR_INVAL,
+    NotFound   = VOS_ERR_NOTFOUND,
+    Perm       = VOS_ERR_PERM,
+    Exists     = VOS_ERR_EXISTS,
+    Full       = VOS_ERR_FULL,
+    Syntax     = VOS_ERR_SYNTAX,
+    CapInvalid = VOS_ERR_CAP_INVALID,
+    CapExpired = VOS_ERR_CAP_EXPIRED,
+    CapRevoked = VOS_ERR_CAP_REVOKED,
+    TxnAbort   = VOS_ERR_TXN_ABORT,
+    Io         = VOS_ERR_IO,
+    Overflow   = VOS_ERR_OVERFLOW,
+    Busy       = VOS_ERR_BUSY,
+    NoSys      = VOS_ERR_NOSYS,
+}
+
+pub type VosResult<T> = Result<T, VosError>;
+
+impl VosError {
+    /// The error for `code`, or None for VOS_OK.
+    pub fn from_code(code: i32) -> Option<VosError> {
+        match code {
+            VOS_OK              => None,
+            VOS_ERR_NOMEM       => Some(VosError::NoMem),
+            VOS_ERR_INVAL       => Some(VosError::Inval),
+            VOS_ERR_NOTFOUND    => Some(VosError::NotFound),
+            VOS_ERR_PERM        => Some(VosError::Perm),
+            VOS_ERR_EXISTS      => Some(VosError::Exists),
+            VOS_ERR_FULL        => Some(VosError::Full),
+            VOS_ERR_SYNTAX      => Some(VosError::Syntax),
+            VOS_ERR_CAP_INVALID => Some(VosError::CapInvalid),
+            VOS_ERR_CAP_EXPIRED => Some(VosError::CapExpired),
+            VOS_ERR_CAP_REVOKED => Some(VosError::CapRevoked),
+            VOS_ERR_TXN_ABORT   => Some(VosError::TxnAbort),
+            VOS_ERR_IO          => Some(VosError::Io),
+            VOS_ERR_OVERFLOW    => Some(VosError::Overflow),
+            VOS_ERR_BUSY        => Some(VosError::Busy),
+            VOS_ERR_NOSYS       => Some(VosError::NoSys),
+            _                   => Some(VosError::Generic),
+        }
+    }
+
+    /// Same text as the C vos_strerror.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VosError::Generic    => "Generic error",
+            VosError::NoMem      => "Out of memory",
+            VosError::Inval      => "Invalid argument",
+            VosError::NotFound   => "Not found",
+            VosError::Perm       => "Permission denied",
+            VosError::Exists     => "Already exists",
+            VosError::Full       => "Table full",
+            VosError::Syntax     => "Syntax error",
+            VosError::CapInvalid => "Invalid capability",
+            VosError::CapExpired => "Capability expired",
+            VosError::CapRevoked => "Capability revoked",
+            VosError::TxnAbort   => "Transaction aborted",
+            VosError::Io         => "I/O error",
+            VosError::Overflow   => "Buffer overflow",
+            VosError::Busy       => "Resource busy",
+            VosError::NoSys      => "Not implemented",
+        }
+    }
+}
+
+/// Unknown codes become Generic. VOS_OK is not an error and also maps to
+/// Generic; use vos_result when the code may be a success.
+impl From<i32> for VosError {
+    fn from(code: i32) -> Self {
+        VosError::from_code(code).unwrap_or(VosError::Generic)
+    }
+}
+
+impl fmt::Display for VosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Ok(()) for VOS_OK, the matching VosError otherwise.
+pub fn vos_result(code: i32) -> VosResult<()> {
+    match VosError::from_code(code) {
+        None => Ok(()),
+        Some(e) => Err(e),
+    }
+}
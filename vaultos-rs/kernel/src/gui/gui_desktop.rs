@@ -1,20 +1,30 @@
 /// Full graphical desktop — taskbar, start menu, and 12+ application windows.
 /// Port of the C `gui/desktop.c`.
 
+use alloc::vec;
+use alloc::vec::Vec;
 use core::fmt::Write;
 use crate::drivers::mouse;
 use crate::drivers::keyboard;
 use crate::drivers::serial;
+use crate::drivers::rtc;
 use crate::drivers::font::{FONT_WIDTH, FONT_HEIGHT};
 use crate::mm::{heap, pmm};
-use crate::db::database::{self, db_get_table_count, db_get_schema_by_id};
-use crate::db::query::query_execute;
-use crate::db::record::FieldValue;
-use crate::arch::x86_64::{pit, cpu};
-use crate::crypto::random;
+use crate::db::database::{self, QueryResult, db_get_table_count, db_get_schema_by_id};
+use crate::db::field::field_as_u64;
+use crate::db::query::{query_execute, query_execute_with};
+use crate::db::record::{FieldValue, Record, StrField};
+use crate::db::schema::TableSchema;
+use crate::arch::x86_64::{pit, cpu, timer};
+use crate::proc::ipc;
+use crate::proc::priority::PRIO_MAX;
+use crate::proc::process::{self, ProcState, ProcessInfo, MAX_PROCESSES};
+use crate::crypto::{aes_ctr, entropy, random};
 use crate::cap;
 use crate::serial_println;
-use crate::shell::shell_main;
+use crate::shell::{alias, friendly, history, script, shell_main};
+use vaultos_shared::db_types::{MAX_BLOB_LEN, MAX_STR_LEN};
+use vaultos_shared::error_codes::{VOS_OK, VOS_ERR_NOTFOUND, VOS_ERR_PERM};
 
 use super::graphics::*;
 use super::event::*;
@@ -86,6 +96,38 @@ fn fmt_u64(val: u64) -> FmtBuf {
     b
 }
 
+// Time and size formatting. Durations are milliseconds since boot; stored
+// timestamps (created, AuditTable.timestamp) are RTC unix seconds.
+
+/// Seconds with one decimal: `12.3s`.
+fn fmt_time(ms: u64) -> FmtBuf {
+    let mut b = FmtBuf::new();
+    let _ = write!(b, "{}.{}s", ms / 1000, (ms % 1000) / 100);
+    b
+}
+
+/// Wall-clock time from unix seconds: `2026-10-16 14:05:09` (UTC).
+fn fmt_datetime(unix: u64) -> FmtBuf {
+    let (h, m, s, y, mo, d) = rtc::unix_to_civil(unix);
+    let mut b = FmtBuf::new();
+    let _ = write!(b, "{}-{:02}-{:02} {:02}:{:02}:{:02}", y, mo, d, h, m, s);
+    b
+}
+
+/// `512 bytes`, `12 KB`, `3.4 MB` (1 KB = 1024 bytes).
+fn fmt_bytes(n: u64) -> FmtBuf {
+    let mut b = FmtBuf::new();
+    if n < 1024 {
+        let _ = write!(b, "{} bytes", n);
+    } else if n < 1024 * 1024 {
+        let _ = write!(b, "{} KB", n / 1024);
+    } else {
+        let tenths = n * 10 / (1024 * 1024);
+        let _ = write!(b, "{}.{} MB", tenths / 10, tenths % 10);
+    }
+    b
+}
+
 // ---- Helper: extract field value as string for listview ----
 fn field_to_str(fv: &FieldValue, buf: &mut FmtBuf) {
     match fv {
@@ -93,6 +135,7 @@ fn field_to_str(fv: &FieldValue, buf: &mut FmtBuf) {
         FieldValue::I64(v) => { let _ = write!(buf, "{}", v); }
         FieldValue::U32(v) => { let _ = write!(buf, "{}", v); }
         FieldValue::U8(v)  => { let _ = write!(buf, "{}", v); }
+        FieldValue::F64(v) => { let _ = write!(buf, "{}", v); }
         FieldValue::Bool(v) => { buf.push_str(if *v { "true" } else { "false" }); }
         FieldValue::Str(s) => {
             let txt = s.as_str();
@@ -103,9 +146,33 @@ fn field_to_str(fv: &FieldValue, buf: &mut FmtBuf) {
     }
 }
 
+/// Integer field of any width as u64; 0 when NULL or not an integer.
+fn field_u64(fv: &Option<FieldValue>) -> u64 {
+    fv.as_ref().and_then(field_as_u64).unwrap_or(0)
+}
+
+/// One listview line for a result row: the first 6 fields joined by " | ".
+const LV_MAX_FIELDS: usize = 6;
+
+fn format_row(row: &Record) -> FmtBuf {
+    let mut line = FmtBuf::new();
+    let max_fields = (row.field_count as usize).min(LV_MAX_FIELDS);
+    for f in 0..max_fields {
+        if let Some(ref fv) = row.fields[f] {
+            if f > 0 { line.push_str(" | "); }
+            field_to_str(fv, &mut line);
+        }
+    }
+    line
+}
+
 /// Populate a listview widget from a query result.
 fn populate_lv_from_query(w: &mut Widget, sql: &str) {
     let result = query_execute(sql, 0);
+    populate_lv_from_result(w, &result);
+}
+
+fn populate_lv_from_result(w: &mut Widget, result: &QueryResult) {
     listview_clear(w);
 
     if result.error_code != 0 {
@@ -113,16 +180,16 @@ fn populate_lv_from_query(w: &mut Widget, sql: &str) {
         return;
     }
 
-    for row in result.rows.iter() {
-        let mut line = FmtBuf::new();
-        let max_fields = if row.field_count > 6 { 6 } else { row.field_count };
-        for f in 0..max_fields as usize {
-            if let Some(ref fv) = row.fields[f] {
-                if f > 0 { line.push_str(" | "); }
-                field_to_str(fv, &mut line);
-            }
+    if !result.column_names.is_empty() {
+        let mut hdr = FmtBuf::new();
+        for (c, name) in result.column_names.iter().take(LV_MAX_FIELDS).enumerate() {
+            if c > 0 { hdr.push_str(" | "); }
+            hdr.push_str(name);
         }
-        listview_add_item(w, line.as_str());
+        listview_add_item(w, hdr.as_str());
+    }
+    for row in result.rows.iter() {
+        listview_add_item(w, format_row(row).as_str());
     }
 
     let mut summary = FmtBuf::new();
@@ -130,6 +197,67 @@ fn populate_lv_from_query(w: &mut Widget, sql: &str) {
     listview_add_item(w, summary.as_str());
 }
 
+/// CSV text for a result: a header line from the column names, then one line per
+/// row. Strings are double-quoted with embedded `"` doubled. Only whole lines
+/// are kept: it stops before the first row that would take the text past
+/// `limit` bytes.
+/// Returns the text and how many rows made it in.
+fn result_to_csv(result: &QueryResult, limit: usize) -> (Vec<u8>, usize) {
+    let mut text: Vec<u8> = Vec::new();
+    if !result.column_names.is_empty() {
+        for (c, name) in result.column_names.iter().enumerate() {
+            if c > 0 { text.push(b','); }
+            text.extend_from_slice(name.as_bytes());
+        }
+        text.push(b'\n');
+    }
+
+    let mut rows_written = 0usize;
+    let mut line: Vec<u8> = Vec::new();
+    for row in result.rows.iter() {
+        line.clear();
+        for f in 0..row.field_count as usize {
+            if f > 0 { line.push(b','); }
+            match row.fields[f] {
+                Some(FieldValue::Str(ref sv)) => {
+                    line.push(b'"');
+                    for b in sv.as_str().bytes() {
+                        if b == b'"' { line.push(b'"'); }
+                        line.push(b);
+                    }
+                    line.push(b'"');
+                }
+                Some(ref fv) => {
+                    let mut cell = FmtBuf::new();
+                    field_to_str(fv, &mut cell);
+                    line.extend_from_slice(cell.as_str().as_bytes());
+                }
+                None => {}
+            }
+        }
+        line.push(b'\n');
+        if text.len() + line.len() > limit { break; }
+        text.extend_from_slice(&line);
+        rows_written += 1;
+    }
+    (text, rows_written)
+}
+
+/// Store `data` as the ObjectTable object `name`: update it if it exists,
+/// otherwise insert it with type `otype`.
+fn object_put(name: &str, otype: &str, data: &str) -> QueryResult {
+    let exists = !query_execute_with("SELECT * FROM ObjectTable WHERE name = ?",
+                                     &[str_param(name)], 0).rows.is_empty();
+    let size = FieldValue::U64(data.len() as u64);
+    if exists {
+        query_execute_with("UPDATE ObjectTable SET data = ?, size = ? WHERE name = ?",
+                           &[str_param(data), size, str_param(name)], 0)
+    } else {
+        query_execute_with("INSERT INTO ObjectTable (name, type, data, size) VALUES (?, ?, ?, ?)",
+                           &[str_param(name), str_param(otype), str_param(data), size], 0)
+    }
+}
+
 fn col_type_name(ct: vaultos_shared::db_types::ColumnType) -> &'static str {
     use vaultos_shared::db_types::ColumnType;
     match ct {
@@ -140,6 +268,675 @@ fn col_type_name(ct: vaultos_shared::db_types::ColumnType) -> &'static str {
         ColumnType::Bool => "BOOL",
         ColumnType::U32  => "U32",
         ColumnType::U8   => "U8",
+        ColumnType::F64  => "F64",
+    }
+}
+
+// ---- Helper: fixed-size sample history + line graph ----
+const SAMPLE_RING_LEN: usize = 128;
+
+/// Bounded history of percentage samples (0..=100); oldest entries are overwritten.
+struct SampleRing {
+    buf: [u8; SAMPLE_RING_LEN],
+    next: usize,
+    len: usize,
+}
+
+impl SampleRing {
+    const fn new() -> Self { SampleRing { buf: [0; SAMPLE_RING_LEN], next: 0, len: 0 } }
+
+    fn push(&mut self, pct: u8) {
+        self.buf[self.next] = pct.min(100);
+        self.next = (self.next + 1) % SAMPLE_RING_LEN;
+        if self.len < SAMPLE_RING_LEN { self.len += 1; }
+    }
+
+    /// i-th sample, oldest first.
+    fn get(&self, i: usize) -> u8 {
+        let start = (self.next + SAMPLE_RING_LEN - self.len) % SAMPLE_RING_LEN;
+        self.buf[(start + i) % SAMPLE_RING_LEN]
+    }
+}
+
+/// Draw a ring's samples as a line graph inside (x, y, w, h), newest at the right.
+fn canvas_graph(win: &mut Window, x: i16, y: i16, w: i16, h: i16, ring: &SampleRing, color: u32) {
+    let cw = win.client_w;
+    let ch = win.client_h;
+    canvas_fill(&mut win.canvas, cw, ch, x, y, w, h, 0xFF0A0A1A);
+    canvas_rect(&mut win.canvas, cw, ch, x, y, w, h, 0xFF555577);
+    // 50% gridline
+    canvas_hline(&mut win.canvas, cw, ch, x + 1, y + h / 2, w - 2, 0xFF222244);
+
+    if ring.len == 0 || w < 4 || h < 4 { return; }
+    let inner_h = (h - 3) as i32;
+    let step = ((w - 2) as i32 / SAMPLE_RING_LEN as i32).max(1);
+    let visible = ring.len.min(((w - 2) as i32 / step) as usize);
+    let first = ring.len - visible;
+
+    let y_of = |pct: u8| -> i32 { y as i32 + 1 + inner_h - inner_h * pct as i32 / 100 };
+    let mut px = (x + w - 1) as i32 - visible as i32 * step;
+    let mut prev = y_of(ring.get(first));
+    for i in first..ring.len {
+        let cur = y_of(ring.get(i));
+        // Vertical span joining the previous point to this one
+        let top = prev.min(cur);
+        let span = (prev.max(cur) - top + 1) as i16;
+        canvas_fill(&mut win.canvas, cw, ch, px as i16, top as i16, step as i16, span, color);
+        prev = cur;
+        px += step;
+    }
+}
+
+// ---- Clipboard (shared by every textbox and the Terminal) ----
+// The keyboard driver folds Ctrl+letter into control codes 0x01-0x1A.
+const KEY_CTRL_C: u8 = 0x03;
+const KEY_CTRL_V: u8 = 0x16;
+const CLIPBOARD_MAX: usize = 4096; // room for a full screen of Terminal text
+
+static mut CLIPBOARD: [u8; CLIPBOARD_MAX] = [0; CLIPBOARD_MAX];
+static mut CLIPBOARD_LEN: usize = 0;
+
+fn clipboard_set(data: &[u8]) {
+    unsafe {
+        let len = data.len().min(CLIPBOARD_MAX);
+        CLIPBOARD[..len].copy_from_slice(&data[..len]);
+        CLIPBOARD_LEN = len;
+    }
+}
+
+fn clipboard_get() -> &'static [u8] {
+    unsafe { &CLIPBOARD[..CLIPBOARD_LEN] }
+}
+
+/// Ctrl+C / Ctrl+V on the focused textbox of a widget set. Returns true if handled.
+fn clipboard_key(ws: &mut WidgetSet, ev: &GuiEvent) -> bool {
+    if ev.etype != EventType::KeyDown || (ev.key != KEY_CTRL_C && ev.key != KEY_CTRL_V) {
+        return false;
+    }
+    let tb = match ws.widgets.iter_mut().flatten().find(|w| w.focused) {
+        Some(w) => w,
+        None => return false,
+    };
+
+    if ev.key == KEY_CTRL_C {
+        clipboard_set(widget_get_text(tb).as_bytes());
+        return true;
+    }
+
+    // Textboxes are single-line, so only printable bytes are pasted
+    let mut text = FmtBuf::new();
+    text.push_str(widget_get_text(tb));
+    for &b in clipboard_get() {
+        if (0x20..=0x7E).contains(&b) && text.pos < text.buf.len() {
+            text.buf[text.pos] = b;
+            text.pos += 1;
+        }
+    }
+    widget_set_text(tb, text.as_str());
+    true
+}
+
+// ===========================================================================
+// ---- Keyboard focus (shared by every widget set) ----
+// ===========================================================================
+// Tab / Shift+Tab move focus between textboxes and buttons, wrapping at
+// either end; Enter or Space on a focused button clicks it. Apps go through
+// widgets_dispatch/widgets_draw instead of calling the set directly.
+const FOCUS_RING: u32 = 0xFFFFCC00;
+
+fn widget_focusable(w: &Widget) -> bool {
+    matches!(w.wtype, WidgetType::Button | WidgetType::TextBox)
+}
+
+fn focus_step(ws: &mut WidgetSet, backward: bool) {
+    let n = ws.widgets.len();
+    if n == 0 { return; }
+    let cur = ws.widgets.iter().position(|w| matches!(w, Some(w) if w.focused));
+    let start = cur.unwrap_or(if backward { 0 } else { n - 1 });
+    for step in 1..=n {
+        let i = if backward { (start + n - step) % n } else { (start + step) % n };
+        if !matches!(ws.widgets[i], Some(ref w) if widget_focusable(w)) { continue; }
+        for w in ws.widgets.iter_mut().flatten() { w.focused = false; }
+        if let Some(ref mut w) = ws.widgets[i] { w.focused = true; }
+        return;
+    }
+}
+
+fn widgets_dispatch(ws: &mut WidgetSet, ev: &mut GuiEvent) -> WidgetAction {
+    if ev.etype == EventType::KeyDown {
+        if ev.key == b'\t' {
+            focus_step(ws, keyboard::keyboard_shift_held());
+            return WidgetAction::None;
+        }
+        if ev.key == b'\n' || ev.key == b' ' {
+            let btn = ws.widgets.iter().position(|w| {
+                matches!(w, Some(w) if w.focused && matches!(w.wtype, WidgetType::Button))
+            });
+            if let Some(i) = btn { return WidgetAction::Clicked(i); }
+        }
+    }
+    if ev.etype == EventType::MouseWheel {
+        lv_wheel(ws, ev);
+        return WidgetAction::None;
+    }
+    if ev.etype == EventType::MouseDown {
+        if let Some(i) = lv_index_at(ws, ev.mouse_x, ev.mouse_y) {
+            return lv_click(ws, i, ev.mouse_x, ev.mouse_y);
+        }
+    }
+    let action = ws.dispatch(ev);
+    if ev.etype == EventType::KeyDown && (ev.key == keyboard::KEY_UP || ev.key == keyboard::KEY_DOWN) {
+        for w in ws.widgets.iter_mut().flatten() {
+            if matches!(w.wtype, WidgetType::ListView) { lv_follow_selection(w); }
+        }
+    }
+    action
+}
+
+/// draw_all plus a ring around a focused button (textboxes show a caret)
+/// and a scrollbar on listviews with more items than fit.
+fn widgets_draw(ws: &WidgetSet, win: &mut Window) {
+    ws.draw_all(win);
+    let (cw, ch) = (win.client_w, win.client_h);
+    for w in ws.widgets.iter().flatten() {
+        if w.focused && matches!(w.wtype, WidgetType::Button) {
+            canvas_rect(&mut win.canvas, cw, ch, w.x - 2, w.y - 2, w.w + 4, w.h + 4, FOCUS_RING);
+        }
+        if matches!(w.wtype, WidgetType::ListView) && lv_overflows(w) {
+            let (tx, ty, th) = lv_track(w);
+            let (top, len) = lv_thumb(w);
+            canvas_fill(&mut win.canvas, cw, ch, tx, ty, LV_SCROLLBAR_W, th, LV_TRACK_BG);
+            canvas_fill(&mut win.canvas, cw, ch, tx + 1, top, LV_SCROLLBAR_W - 2, len, LV_THUMB);
+        }
+    }
+}
+
+// ===========================================================================
+// ---- Listview scrolling (shared by every widget set) ----
+// ===========================================================================
+// lv_scroll is the first item a listview shows. The wheel scrolls the
+// listview under the pointer, a click on the scrollbar track pages towards
+// the click, and Up/Down scroll to keep the selection in view. Clicks on
+// items go through the offset, so the item under the pointer is selected
+// however far the list is scrolled.
+const LV_ROW_H: i16 = 18; // item height in the listview draw path
+const LV_SCROLLBAR_W: i16 = 8;
+const LV_THUMB_MIN: i16 = 8;
+const LV_WHEEL_ROWS: i32 = 3;
+const LV_TRACK_BG: u32 = 0xFF10102A;
+const LV_THUMB: u32 = 0xFF555577;
+
+fn lv_visible(w: &Widget) -> i32 {
+    ((w.h - 2) / LV_ROW_H).max(1) as i32
+}
+
+fn lv_overflows(w: &Widget) -> bool {
+    w.lv_count as i32 > lv_visible(w)
+}
+
+fn lv_set_scroll(w: &mut Widget, first: i32) {
+    let max = (w.lv_count as i32 - lv_visible(w)).max(0);
+    w.lv_scroll = first.clamp(0, max) as i16;
+}
+
+/// Scroll just far enough to bring the selected item into view.
+fn lv_follow_selection(w: &mut Widget) {
+    let sel = w.lv_selected as i32;
+    if sel < 0 { return; }
+    let first = w.lv_scroll as i32;
+    if sel < first {
+        lv_set_scroll(w, sel);
+    } else if sel >= first + lv_visible(w) {
+        lv_set_scroll(w, sel - lv_visible(w) + 1);
+    }
+}
+
+/// Scrollbar track inside the right border: x, top and height.
+fn lv_track(w: &Widget) -> (i16, i16, i16) {
+    (w.x + w.w - 1 - LV_SCROLLBAR_W, w.y + 1, w.h - 2)
+}
+
+/// Thumb top and height, sized to the visible share of the items.
+fn lv_thumb(w: &Widget) -> (i16, i16) {
+    let (_, ty, th) = lv_track(w);
+    let count = (w.lv_count as i32).max(1);
+    let visible = lv_visible(w);
+    let len = ((th as i32 * visible / count) as i16).clamp(LV_THUMB_MIN.min(th), th);
+    let max = (count - visible).max(1);
+    let top = ty + ((th - len) as i32 * w.lv_scroll as i32 / max) as i16;
+    (top, len)
+}
+
+fn lv_index_at(ws: &WidgetSet, x: i16, y: i16) -> Option<usize> {
+    ws.widgets.iter().position(|w| {
+        matches!(w, Some(w) if matches!(w.wtype, WidgetType::ListView)
+            && x >= w.x && x < w.x + w.w && y >= w.y && y < w.y + w.h)
+    })
+}
+
+/// Wheel over a listview scrolls it; elsewhere it scrolls the set's first
+/// listview, which for most windows is the only one.
+fn lv_wheel(ws: &mut WidgetSet, ev: &GuiEvent) {
+    let i = match lv_index_at(ws, ev.mouse_x, ev.mouse_y).or_else(|| {
+        ws.widgets.iter().position(|w| matches!(w, Some(w) if matches!(w.wtype, WidgetType::ListView)))
+    }) {
+        Some(i) => i,
+        None => return,
+    };
+    if let Some(ref mut w) = ws.widgets[i] {
+        // Positive deltas are the wheel turning toward the user
+        let first = w.lv_scroll as i32 + ev.wheel_delta as i32 * LV_WHEEL_ROWS;
+        lv_set_scroll(w, first);
+    }
+}
+
+/// MouseDown inside listview `i`: page on the scrollbar track, otherwise
+/// select the item under the pointer.
+fn lv_click(ws: &mut WidgetSet, i: usize, x: i16, y: i16) -> WidgetAction {
+    for w in ws.widgets.iter_mut().flatten() {
+        if matches!(w.wtype, WidgetType::TextBox) { w.focused = false; }
+    }
+    let w = match ws.widgets[i] {
+        Some(ref mut w) => w,
+        None => return WidgetAction::None,
+    };
+    if lv_overflows(w) && x >= lv_track(w).0 {
+        let (top, len) = lv_thumb(w);
+        let page = lv_visible(w);
+        let first = w.lv_scroll as i32;
+        if y < top {
+            lv_set_scroll(w, first - page);
+        } else if y >= top + len {
+            lv_set_scroll(w, first + page);
+        }
+        return WidgetAction::None;
+    }
+    let idx = (y - w.y - 1) as i32 / LV_ROW_H as i32 + w.lv_scroll as i32;
+    if y <= w.y || idx >= w.lv_count as i32 { return WidgetAction::None; }
+    w.lv_selected = idx as i16;
+    WidgetAction::Selected(i, w.lv_selected)
+}
+
+// ===========================================================================
+// ---- Combo box (drop-down selector) ----
+// ===========================================================================
+// Lives beside a WidgetSet rather than in it: the app draws it after
+// draw_all() so the open list overlaps the widgets below, and dispatches it
+// before the set so an open list captures the click that closes it.
+const COMBO_MAX_OPTIONS: usize = 16;
+const COMBO_TEXT_MAX: usize = 32;
+const COMBO_H: i16 = 22;
+const COMBO_ROW_H: i16 = 18;
+
+enum ComboEvent {
+    Ignored,
+    Consumed,
+    Selected(usize),
+}
+
+struct ComboBox {
+    x: i16,
+    y: i16,
+    w: i16,
+    options: [[u8; COMBO_TEXT_MAX]; COMBO_MAX_OPTIONS],
+    lens: [usize; COMBO_MAX_OPTIONS],
+    count: usize,
+    selected: usize,
+    open: bool,
+}
+
+impl ComboBox {
+    const fn new() -> Self {
+        ComboBox {
+            x: 0, y: 0, w: 0,
+            options: [[0; COMBO_TEXT_MAX]; COMBO_MAX_OPTIONS],
+            lens: [0; COMBO_MAX_OPTIONS],
+            count: 0,
+            selected: 0,
+            open: false,
+        }
+    }
+
+    fn place(&mut self, x: i16, y: i16, w: i16) {
+        self.x = x;
+        self.y = y;
+        self.w = w;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.count = 0;
+        self.selected = 0;
+        self.open = false;
+    }
+
+    /// Options past COMBO_MAX_OPTIONS are dropped; text is truncated.
+    fn add_option(&mut self, text: &str) {
+        if self.count >= COMBO_MAX_OPTIONS { return; }
+        let len = text.len().min(COMBO_TEXT_MAX);
+        self.options[self.count][..len].copy_from_slice(&text.as_bytes()[..len]);
+        self.lens[self.count] = len;
+        self.count += 1;
+    }
+
+    fn option(&self, i: usize) -> &str {
+        if i >= self.count { return ""; }
+        core::str::from_utf8(&self.options[i][..self.lens[i]]).unwrap_or("")
+    }
+
+    fn selected(&self) -> usize { self.selected }
+
+    fn selected_str(&self) -> &str { self.option(self.selected) }
+
+    /// Select the option whose text is `text`, if present.
+    fn select_text(&mut self, text: &str) {
+        if let Some(i) = (0..self.count).find(|&i| self.option(i) == text) {
+            self.selected = i;
+        }
+    }
+
+    fn draw(&self, win: &mut Window) {
+        let cw = win.client_w;
+        let ch = win.client_h;
+        let bg = 0xFF0A0A1A;
+        canvas_fill(&mut win.canvas, cw, ch, self.x, self.y, self.w, COMBO_H, bg);
+        canvas_rect(&mut win.canvas, cw, ch, self.x, self.y, self.w, COMBO_H, 0xFF555577);
+        let ty = self.y + (COMBO_H - FONT_HEIGHT as i16) / 2;
+        canvas_text(&mut win.canvas, cw, ch, self.x + 4, ty, self.selected_str(), 0xFF00DDAA, bg);
+        let arrow = if self.open { "^" } else { "v" };
+        canvas_text(&mut win.canvas, cw, ch, self.x + self.w - FONT_WIDTH as i16 - 4, ty,
+            arrow, 0xFF808080, bg);
+
+        if !self.open { return; }
+        let list_y = self.y + COMBO_H;
+        let list_h = self.count as i16 * COMBO_ROW_H;
+        canvas_fill(&mut win.canvas, cw, ch, self.x, list_y, self.w, list_h, 0xFF1A1A3A);
+        canvas_rect(&mut win.canvas, cw, ch, self.x, list_y, self.w, list_h, 0xFF5588CC);
+        for i in 0..self.count {
+            let ry = list_y + i as i16 * COMBO_ROW_H;
+            let row_bg = if i == self.selected { 0xFF2A4466 } else { 0xFF1A1A3A };
+            canvas_fill(&mut win.canvas, cw, ch, self.x + 1, ry + 1, self.w - 2, COMBO_ROW_H - 1, row_bg);
+            canvas_text(&mut win.canvas, cw, ch, self.x + 4, ry + 1, self.option(i), 0xFFCCCCCC, row_bg);
+        }
+    }
+
+    /// Call before the WidgetSet's dispatch. While open, every click goes
+    /// to the combo box: on an option it selects, anywhere else it closes.
+    fn dispatch(&mut self, ev: &GuiEvent) -> ComboEvent {
+        if ev.etype != EventType::MouseDown { return ComboEvent::Ignored; }
+        let (mx, my) = (ev.mouse_x, ev.mouse_y);
+        let in_x = mx >= self.x && mx < self.x + self.w;
+
+        if self.open {
+            self.open = false;
+            let list_y = self.y + COMBO_H;
+            if in_x && my >= list_y && my < list_y + self.count as i16 * COMBO_ROW_H {
+                self.selected = ((my - list_y) / COMBO_ROW_H) as usize;
+                return ComboEvent::Selected(self.selected);
+            }
+            return ComboEvent::Consumed;
+        }
+
+        if in_x && my >= self.y && my < self.y + COMBO_H && self.count > 0 {
+            self.open = true;
+            return ComboEvent::Consumed;
+        }
+        ComboEvent::Ignored
+    }
+}
+
+// ===========================================================================
+// ---- Text area (multi-line editor) ----
+// ===========================================================================
+// Like the combo box it sits beside a WidgetSet: the app draws and
+// dispatches it itself. Text soft-wraps at the right edge; Enter inserts a
+// newline, so apps keep their own Execute/Save action.
+const TEXTAREA_MAX: usize = 1024;
+const TEXTAREA_PAD: i16 = 4;
+const TEXTAREA_MARK_BG: u32 = 0xFF665500;
+
+struct TextArea {
+    x: i16,
+    y: i16,
+    w: i16,
+    h: i16,
+    buf: [u8; TEXTAREA_MAX],
+    len: usize,
+    cursor: usize,
+    scroll: usize, // first visible row
+    focused: bool,
+    mark: (usize, usize), // highlighted (start, len), e.g. a find match; len 0 = none
+}
+
+impl TextArea {
+    const fn new() -> Self {
+        TextArea {
+            x: 0, y: 0, w: 0, h: 0,
+            buf: [0; TEXTAREA_MAX],
+            len: 0,
+            cursor: 0,
+            scroll: 0,
+            focused: false,
+            mark: (0, 0),
+        }
+    }
+
+    fn place(&mut self, x: i16, y: i16, w: i16, h: i16) {
+        self.x = x;
+        self.y = y;
+        self.w = w;
+        self.h = h;
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.cursor = 0;
+        self.scroll = 0;
+        self.mark = (0, 0);
+    }
+
+    fn text(&self) -> &str {
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("")
+    }
+
+    /// Replace the contents (truncated to capacity) and put the cursor at the end.
+    fn set_text(&mut self, text: &str) {
+        let len = text.len().min(TEXTAREA_MAX);
+        self.buf[..len].copy_from_slice(&text.as_bytes()[..len]);
+        self.len = len;
+        self.cursor = len;
+        self.scroll = 0;
+        self.mark = (0, 0);
+        self.scroll_to_cursor();
+    }
+
+    /// First occurrence of `pat` at or after `from`, wrapping to the start.
+    fn find(&self, pat: &[u8], from: usize, match_case: bool) -> Option<usize> {
+        if pat.is_empty() || pat.len() > self.len { return None; }
+        let last = self.len - pat.len();
+        let start = from.min(last + 1);
+        (start..=last).chain(0..start).find(|&i| {
+            let hay = &self.buf[i..i + pat.len()];
+            if match_case { hay == pat } else { hay.eq_ignore_ascii_case(pat) }
+        })
+    }
+
+    /// Replace `len` bytes at `start` with `with`, leaving the cursor after
+    /// it. False if the result would not fit.
+    fn replace_range(&mut self, start: usize, len: usize, with: &[u8]) -> bool {
+        if start + len > self.len || self.len - len + with.len() > TEXTAREA_MAX { return false; }
+        self.buf.copy_within(start + len..self.len, start + with.len());
+        self.buf[start..start + with.len()].copy_from_slice(with);
+        self.len = self.len - len + with.len();
+        self.cursor = start + with.len();
+        self.mark = (0, 0);
+        self.scroll_to_cursor();
+        true
+    }
+
+    fn cols(&self) -> usize {
+        ((self.w - 2 * TEXTAREA_PAD) / FONT_WIDTH as i16).max(1) as usize
+    }
+
+    fn visible_rows(&self) -> usize {
+        ((self.h - 2 * TEXTAREA_PAD) / FONT_HEIGHT as i16).max(1) as usize
+    }
+
+    /// Row and column of byte offset `off` after wrapping. A column equal to
+    /// cols() means the end of a full row.
+    fn pos_of(&self, off: usize) -> (usize, usize) {
+        let cols = self.cols();
+        let (mut r, mut c) = (0, 0);
+        for &b in &self.buf[..off] {
+            if b == b'\n' {
+                r += 1;
+                c = 0;
+                continue;
+            }
+            if c == cols { r += 1; c = 0; }
+            c += 1;
+        }
+        (r, c)
+    }
+
+    /// Offset closest to (row, col); past the end of a row it stops before
+    /// the row's newline or wrap point.
+    fn offset_at(&self, row: usize, col: usize) -> usize {
+        let cols = self.cols();
+        let (mut r, mut c) = (0, 0);
+        for i in 0..self.len {
+            let b = self.buf[i];
+            if b != b'\n' && c == cols {
+                if r == row { return i; }
+                r += 1;
+                c = 0;
+            }
+            if r > row || (r == row && c >= col) { return i; }
+            if b == b'\n' {
+                if r == row { return i; }
+                r += 1;
+                c = 0;
+            } else {
+                c += 1;
+            }
+        }
+        self.len
+    }
+
+    fn cursor_row(&self) -> usize { self.pos_of(self.cursor).0 }
+
+    fn row_count(&self) -> usize { self.pos_of(self.len).0 + 1 }
+
+    fn scroll_to_cursor(&mut self) {
+        let row = self.cursor_row();
+        let rows = self.visible_rows();
+        if row < self.scroll { self.scroll = row; }
+        if row >= self.scroll + rows { self.scroll = row + 1 - rows; }
+    }
+
+    fn insert(&mut self, bytes: &[u8]) {
+        let n = bytes.len().min(TEXTAREA_MAX - self.len);
+        if n == 0 { return; }
+        self.buf.copy_within(self.cursor..self.len, self.cursor + n);
+        self.buf[self.cursor..self.cursor + n].copy_from_slice(&bytes[..n]);
+        self.len += n;
+        self.cursor += n;
+    }
+
+    fn delete_at(&mut self, off: usize) {
+        if off >= self.len { return; }
+        self.buf.copy_within(off + 1..self.len, off);
+        self.len -= 1;
+    }
+
+    fn draw(&self, win: &mut Window) {
+        let cw = win.client_w;
+        let ch = win.client_h;
+        let bg = 0xFF0A0A1A;
+        let border = if self.focused { 0xFF5588CC } else { 0xFF555577 };
+        canvas_fill(&mut win.canvas, cw, ch, self.x, self.y, self.w, self.h, bg);
+        canvas_rect(&mut win.canvas, cw, ch, self.x, self.y, self.w, self.h, border);
+
+        let cols = self.cols();
+        let rows = self.visible_rows();
+        let cell = |r: usize, c: usize| -> Option<(i16, i16)> {
+            if r < self.scroll || r >= self.scroll + rows { return None; }
+            Some((self.x + TEXTAREA_PAD + (c * FONT_WIDTH as usize) as i16,
+                  self.y + TEXTAREA_PAD + ((r - self.scroll) * FONT_HEIGHT as usize) as i16))
+        };
+
+        let (mark_start, mark_len) = self.mark;
+        let (mut r, mut c) = (0, 0);
+        for (i, &b) in self.buf[..self.len].iter().enumerate() {
+            if b == b'\n' {
+                r += 1;
+                c = 0;
+                continue;
+            }
+            if c == cols { r += 1; c = 0; }
+            if let Some((px, py)) = cell(r, c) {
+                let ch_buf = [if (0x20..=0x7E).contains(&b) { b } else { b'?' }];
+                let s = core::str::from_utf8(&ch_buf).unwrap_or("?");
+                let cell_bg = if i >= mark_start && i < mark_start + mark_len { TEXTAREA_MARK_BG } else { bg };
+                canvas_text(&mut win.canvas, cw, ch, px, py, s, 0xFFCCCCCC, cell_bg);
+            }
+            c += 1;
+        }
+
+        if self.focused {
+            let (mut r, mut c) = self.pos_of(self.cursor);
+            if c == cols { r += 1; c = 0; }
+            if let Some((px, py)) = cell(r, c) {
+                canvas_fill(&mut win.canvas, cw, ch, px, py, 2, FONT_HEIGHT as i16, 0xFF00DDAA);
+            }
+        }
+    }
+
+    /// Returns true if the event was consumed. Clicks inside take focus;
+    /// clicks elsewhere drop it. Keys are only handled while focused.
+    fn dispatch(&mut self, ev: &GuiEvent) -> bool {
+        if ev.etype == EventType::MouseDown {
+            let inside = ev.mouse_x >= self.x && ev.mouse_x < self.x + self.w
+                && ev.mouse_y >= self.y && ev.mouse_y < self.y + self.h;
+            self.focused = inside;
+            if !inside { return false; }
+            let col = ((ev.mouse_x - self.x - TEXTAREA_PAD).max(0) / FONT_WIDTH as i16) as usize;
+            let row = ((ev.mouse_y - self.y - TEXTAREA_PAD).max(0) / FONT_HEIGHT as i16) as usize;
+            self.cursor = self.offset_at(self.scroll + row, col);
+            self.mark = (0, 0);
+            return true;
+        }
+        if ev.etype != EventType::KeyDown || !self.focused { return false; }
+
+        let (row, col) = self.pos_of(self.cursor);
+        match ev.key {
+            keyboard::KEY_LEFT => { if self.cursor > 0 { self.cursor -= 1; } }
+            keyboard::KEY_RIGHT => { if self.cursor < self.len { self.cursor += 1; } }
+            keyboard::KEY_UP => { if row > 0 { self.cursor = self.offset_at(row - 1, col); } }
+            keyboard::KEY_DOWN => { self.cursor = self.offset_at(row + 1, col); }
+            keyboard::KEY_HOME => { self.cursor = self.offset_at(row, 0); }
+            keyboard::KEY_END => { self.cursor = self.offset_at(row, usize::MAX); }
+            keyboard::KEY_DELETE => self.delete_at(self.cursor),
+            0x08 => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.delete_at(self.cursor);
+                }
+            }
+            KEY_CTRL_C => clipboard_set(self.text().as_bytes()),
+            KEY_CTRL_V => {
+                for &b in clipboard_get() {
+                    if b == b'\n' || (0x20..=0x7E).contains(&b) { self.insert(&[b]); }
+                }
+            }
+            b'\n' => self.insert(b"\n"),
+            k if (0x20..=0x7E).contains(&k) => self.insert(&[k]),
+            _ => return false,
+        }
+        self.mark = (0, 0);
+        self.scroll_to_cursor();
+        true
     }
 }
 
@@ -147,11 +944,26 @@ fn col_type_name(ct: vaultos_shared::db_types::ColumnType) -> &'static str {
 // ---- Query Console ----
 // ===========================================================================
 static mut QC_WIDGETS: WidgetSet = WidgetSet::new();
-const QC_TEXTBOX: usize = 0;
-const QC_EXEC_BTN: usize = 1;
-const QC_TMPL_BTN: usize = 2;
-const QC_LISTVIEW: usize = 3;
+static mut QC_EDITOR: TextArea = TextArea::new();
+const QC_EXEC_BTN: usize = 0;
+const QC_TMPL_BTN: usize = 1;
+const QC_LISTVIEW: usize = 2;
+const QC_NAME_BOX: usize = 3;
+const QC_EXPORT_BTN: usize = 4;
+const QC_SAVE_TMPL_BTN: usize = 5;
+const QC_EDITOR_H: i16 = 76;
+const QC_TMPL_PREFIX: &str = "query.tmpl.";
 static mut QC_TMPL_IDX: usize = 0;
+static mut QC_LAST_RESULT: Option<QueryResult> = None;
+
+// Executed-query history (ring buffer, most recent at QC_HIST_NEXT - 1)
+const QC_HIST_MAX: usize = 16;
+const QC_HIST_LEN: usize = 256;
+static mut QC_HIST: [[u8; QC_HIST_LEN]; QC_HIST_MAX] = [[0; QC_HIST_LEN]; QC_HIST_MAX];
+static mut QC_HIST_LENS: [usize; QC_HIST_MAX] = [0; QC_HIST_MAX];
+static mut QC_HIST_NEXT: usize = 0;
+static mut QC_HIST_COUNT: usize = 0;
+static mut QC_HIST_BROWSE: usize = 0; // 0 = editing a new line, n = n-th most recent
 
 const QC_TEMPLATES: [&str; 6] = [
     "SHOW TABLES",
@@ -162,9 +974,265 @@ const QC_TEMPLATES: [&str; 6] = [
     "DESCRIBE ProcessTable",
 ];
 
+/// Saved templates (SystemTable rows keyed query.tmpl.<name>), ordered by key.
+fn qc_saved_templates() -> QueryResult {
+    let mut sql = FmtBuf::new();
+    let _ = write!(sql, "SELECT * FROM SystemTable WHERE key LIKE '{}%' ORDER BY key", QC_TMPL_PREFIX);
+    query_execute(sql.as_str(), 0)
+}
+
+/// Load the next template into the editor: built-ins first, then saved ones.
+fn qc_next_template() {
+    unsafe {
+        let saved = qc_saved_templates();
+        let total = QC_TEMPLATES.len() + saved.rows.len();
+        let idx = QC_TMPL_IDX % total;
+        QC_TMPL_IDX = (idx + 1) % total;
+        if idx < QC_TEMPLATES.len() {
+            QC_EDITOR.set_text(QC_TEMPLATES[idx]);
+            return;
+        }
+
+        let row = &saved.rows[idx - QC_TEMPLATES.len()];
+        let key = match row.fields[1] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
+        let sql = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
+        QC_EDITOR.set_text(sql);
+        let mut msg = FmtBuf::new();
+        let _ = write!(msg, "Template '{}'", key.get(QC_TMPL_PREFIX.len()..).unwrap_or(key));
+        notify(msg.as_str(), TOAST_MS);
+    }
+}
+
+/// Save the editor text as template <name box> in SystemTable and flush it
+/// to disk so it is still there after a reboot.
+fn qc_save_template() {
+    unsafe {
+        let name = match QC_WIDGETS.widgets[QC_NAME_BOX] {
+            Some(ref tb) => widget_get_text(tb),
+            None => return,
+        };
+        let sql = QC_EDITOR.text().trim();
+        if name.is_empty() {
+            notify("Template: enter a name", TOAST_MS);
+            return;
+        }
+        if sql.is_empty() || sql.len() > MAX_STR_LEN {
+            notify("Template: SQL is empty or too long", TOAST_MS);
+            return;
+        }
+
+        let mut key = FmtBuf::new();
+        let _ = write!(key, "{}{}", QC_TMPL_PREFIX, name);
+        let res = query_execute_with("INSERT OR REPLACE INTO SystemTable (key, value) VALUES (?, ?)",
+                                     &[str_param(key.as_str()), str_param(sql)], 0);
+        let mut msg = FmtBuf::new();
+        if res.error_code != VOS_OK {
+            let _ = write!(msg, "Template save failed: {}", res.error_msg_str());
+        } else if database::db_flush() != VOS_OK {
+            let _ = write!(msg, "Template '{}' saved (not yet on disk)", name);
+        } else {
+            let _ = write!(msg, "Template '{}' saved", name);
+        }
+        notify(msg.as_str(), TOAST_MS);
+    }
+}
+
+/// The n-th most recent history entry (1-based).
+fn qc_hist_get(n: usize) -> &'static str {
+    unsafe {
+        let slot = (QC_HIST_NEXT + QC_HIST_MAX - n) % QC_HIST_MAX;
+        core::str::from_utf8(&QC_HIST[slot][..QC_HIST_LENS[slot]]).unwrap_or("")
+    }
+}
+
+fn qc_hist_push(sql: &str) {
+    unsafe {
+        QC_HIST_BROWSE = 0;
+        if QC_HIST_COUNT > 0 && qc_hist_get(1) == sql { return; }
+        let len = sql.len().min(QC_HIST_LEN);
+        QC_HIST[QC_HIST_NEXT][..len].copy_from_slice(&sql.as_bytes()[..len]);
+        QC_HIST_LENS[QC_HIST_NEXT] = len;
+        QC_HIST_NEXT = (QC_HIST_NEXT + 1) % QC_HIST_MAX;
+        if QC_HIST_COUNT < QC_HIST_MAX { QC_HIST_COUNT += 1; }
+    }
+}
+
+/// Up on the editor's first row / Down on its last row walks the history.
+/// Returns true if handled.
+fn qc_hist_key(key: u8) -> bool {
+    unsafe {
+        if !QC_EDITOR.focused { return false; }
+
+        let row = QC_EDITOR.cursor_row();
+        if key == keyboard::KEY_UP && row == 0 {
+            if QC_HIST_BROWSE < QC_HIST_COUNT { QC_HIST_BROWSE += 1; }
+        } else if key == keyboard::KEY_DOWN && row + 1 == QC_EDITOR.row_count() {
+            if QC_HIST_BROWSE > 0 { QC_HIST_BROWSE -= 1; }
+        } else {
+            return false;
+        }
+
+        let text = if QC_HIST_BROWSE == 0 { "" } else { qc_hist_get(QC_HIST_BROWSE) };
+        QC_EDITOR.set_text(text);
+        true
+    }
+}
+
+fn qc_execute() {
+    unsafe {
+        let sql = QC_EDITOR.text();
+        if sql.trim().is_empty() { return; }
+        qc_hist_push(sql);
+        let result = query_execute(sql, 0);
+        if let Some(ref mut lv) = QC_WIDGETS.widgets[QC_LISTVIEW] {
+            populate_lv_from_result(lv, &result);
+        }
+        if result.error_code == VOS_OK && !starts_with_ci(sql.trim_start(), "SELECT") {
+            notify("Statement executed", TOAST_MS);
+        }
+        QC_LAST_RESULT = Some(result);
+    }
+}
+
+/// Serialize the last result as CSV text (header line, then one line per row)
+/// and store it as an ObjectTable object named by the name box.
+fn qc_export() {
+    unsafe {
+        let name = match QC_WIDGETS.widgets[QC_NAME_BOX] {
+            Some(ref tb) => widget_get_text(tb),
+            None => return,
+        };
+        let result = match QC_LAST_RESULT {
+            Some(ref r) if r.error_code == 0 => r,
+            _ => { qc_export_report("Export: run a query first"); return; }
+        };
+        if name.is_empty() {
+            qc_export_report("Export: enter an object name");
+            return;
+        }
+
+        let (text, rows_written) = result_to_csv(result, MAX_STR_LEN);
+        let data = core::str::from_utf8(&text).unwrap_or("");
+        let res = object_put(name, "csv", data);
+        let mut msg = FmtBuf::new();
+        if res.error_code != 0 {
+            let _ = write!(msg, "Export failed: {}", res.error_msg_str());
+        } else if rows_written < result.rows.len() {
+            let _ = write!(msg, "Exported {} of {} row(s) to '{}' (truncated at {} bytes)",
+                           rows_written, result.rows.len(), name, MAX_STR_LEN);
+        } else {
+            let _ = write!(msg, "Exported {} row(s) to '{}'", rows_written, name);
+        }
+        qc_export_report(msg.as_str());
+    }
+}
+
+fn qc_export_report(msg: &str) {
+    unsafe {
+        if let Some(ref mut lv) = QC_WIDGETS.widgets[QC_LISTVIEW] {
+            listview_add_item(lv, msg);
+        }
+    }
+}
+
+// ---- Autocomplete (Ctrl+Space) ----
+// After FROM / INTO / UPDATE / DESCRIBE the word under the cursor completes
+// to a table name; anywhere else to a column of the table the statement
+// names, or to a table name if it names none yet.
+
+fn qc_is_table_keyword(word: &str) -> bool {
+    ["FROM", "INTO", "UPDATE", "DESCRIBE", "TABLE"].iter()
+        .any(|k| database::str_eq_ignore_case(word, k))
+}
+
+fn qc_words(sql: &str) -> impl Iterator<Item = &str> {
+    sql.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).filter(|w| !w.is_empty())
+}
+
+/// The first known table named right after a table keyword.
+fn qc_statement_table(sql: &str) -> Option<&'static TableSchema> {
+    let mut prev = "";
+    for w in qc_words(sql) {
+        if qc_is_table_keyword(prev) {
+            if let Some(schema) = database::db_get_schema_by_name(w) { return Some(schema); }
+        }
+        prev = w;
+    }
+    None
+}
+
+fn qc_complete() {
+    unsafe {
+        let text = QC_EDITOR.text();
+        let cursor = QC_EDITOR.cursor.min(text.len());
+        let bytes = text.as_bytes();
+        let mut word_start = cursor;
+        while word_start > 0 && (bytes[word_start - 1].is_ascii_alphanumeric() || bytes[word_start - 1] == b'_') {
+            word_start -= 1;
+        }
+        let prefix = &text[word_start..cursor];
+        let want_table = qc_words(&text[..word_start]).last().map_or(false, qc_is_table_keyword);
+
+        let mut cands: [&'static str; TERM_COMPLETE_MAX] = [""; TERM_COMPLETE_MAX];
+        let mut n = 0usize;
+        match qc_statement_table(text) {
+            Some(schema) if !want_table => {
+                for c in 0..schema.column_count as usize {
+                    term_complete_add(&mut cands, &mut n, schema.columns[c].name_str(), prefix);
+                }
+            }
+            _ => {
+                for t in 0..db_get_table_count() {
+                    if let Some(schema) = db_get_schema_by_id(t) {
+                        term_complete_add(&mut cands, &mut n, schema.name_str(), prefix);
+                    }
+                }
+            }
+        }
+        if n == 0 { return; }
+
+        // Longest common prefix of all candidates (case-insensitive)
+        let mut common = cands[0].len();
+        for c in &cands[1..n] {
+            let same = cands[0].bytes().zip(c.bytes())
+                .take_while(|(a, b)| a.eq_ignore_ascii_case(b)).count();
+            common = common.min(same);
+        }
+        if common > prefix.len() || n == 1 {
+            let word = &cands[0].as_bytes()[..common.max(prefix.len())];
+            QC_EDITOR.replace_range(word_start, prefix.len(), word);
+        }
+        if n == 1 { return; }
+
+        // Several left: list them under the editor
+        if let Some(ref mut lv) = QC_WIDGETS.widgets[QC_LISTVIEW] {
+            listview_clear(lv);
+            for c in &cands[..n] {
+                listview_add_item(lv, c);
+            }
+        }
+    }
+}
+
 fn qc_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { QC_WIDGETS.draw_all(win); }
+    unsafe {
+        widgets_draw(&QC_WIDGETS, win);
+        QC_EDITOR.draw(win);
+    }
+}
+
+/// Route an event to a text area ahead of its widget set. Returns true if
+/// the text area consumed it. Tab leaves the text area for the set's widgets.
+fn textarea_route(ta: &mut TextArea, ws: &mut WidgetSet, ev: &GuiEvent) -> bool {
+    if ev.etype == EventType::KeyDown && ev.key == b'\t' && ta.focused {
+        ta.focused = false;
+        return false;
+    }
+    if !ta.dispatch(ev) { return false; }
+    // Only one place holds the keyboard focus
+    for w in ws.widgets.iter_mut().flatten() { w.focused = false; }
+    true
 }
 
 fn qc_event(win: &mut Window, ev: &mut GuiEvent) {
@@ -172,63 +1240,271 @@ fn qc_event(win: &mut Window, ev: &mut GuiEvent) {
         wm_destroy_window(win.id);
         return;
     }
+    if ev.etype == EventType::KeyDown && qc_hist_key(ev.key) {
+        return;
+    }
     unsafe {
-        let action = QC_WIDGETS.dispatch(ev);
+        // Ctrl+Enter runs the query; plain Enter is a newline
+        if ev.etype == EventType::KeyDown && ev.key == b'\n'
+            && QC_EDITOR.focused && keyboard::keyboard_ctrl_held() {
+            qc_execute();
+            return;
+        }
+        if ev.etype == EventType::KeyDown && ev.key == b' '
+            && QC_EDITOR.focused && keyboard::keyboard_ctrl_held() {
+            qc_complete();
+            return;
+        }
+        if textarea_route(&mut QC_EDITOR, &mut QC_WIDGETS, ev) { return; }
+        if clipboard_key(&mut QC_WIDGETS, ev) { return; }
+        let action = widgets_dispatch(&mut QC_WIDGETS, ev);
         match action {
-            WidgetAction::Clicked(idx) if idx == QC_EXEC_BTN => {
-                if let Some(ref tb) = QC_WIDGETS.widgets[QC_TEXTBOX] {
-                    let sql = widget_get_text(tb);
-                    if !sql.is_empty() {
-                        if let Some(ref mut lv) = QC_WIDGETS.widgets[QC_LISTVIEW] {
-                            populate_lv_from_query(lv, sql);
-                        }
-                    }
-                }
+            WidgetAction::Clicked(idx) if idx == QC_EXEC_BTN => qc_execute(),
+            WidgetAction::Clicked(idx) if idx == QC_EXPORT_BTN => qc_export(),
+            WidgetAction::Clicked(idx) if idx == QC_TMPL_BTN => qc_next_template(),
+            WidgetAction::Clicked(idx) if idx == QC_SAVE_TMPL_BTN => qc_save_template(),
+            _ => {}
+        }
+    }
+}
+
+fn open_query_console() {
+    unsafe {
+        QC_WIDGETS.clear();
+        QC_TMPL_IDX = 0;
+        QC_HIST_BROWSE = 0;
+        QC_LAST_RESULT = None;
+    }
+    let id = match wm_create_window("Query Console", 100, 60, 540, 440, Some(qc_event), Some(qc_paint)) {
+        Some(v) => v,
+        None => return,
+    };
+    let cw;
+    if let Some(win) = wm_get_window(id) { cw = win.client_w; } else { return; }
+
+    unsafe {
+        QC_EDITOR.place(4, 4, cw as i16 - 160, QC_EDITOR_H);
+        QC_EDITOR.focused = true;
+        let below = 4 + QC_EDITOR_H + 4;
+        QC_WIDGETS.add_button(cw as i16 - 152, 4, 68, 24, "Execute");
+        QC_WIDGETS.add_button(cw as i16 - 80, 4, 76, 24, "Template");
+        QC_WIDGETS.add_listview(4, below + 30, cw as i16 - 8, 440 - 26 - (below + 36));
+        QC_WIDGETS.add_textbox(4, below, cw as i16 - 204, 24);
+        QC_WIDGETS.add_button(cw as i16 - 80, below, 76, 24, "Export");
+        QC_WIDGETS.add_button(cw as i16 - 196, below, 112, 24, "Save Template");
+    }
+}
+
+// ===========================================================================
+// ---- Table Browser ----
+// ===========================================================================
+static mut TB_WIDGETS: WidgetSet = WidgetSet::new();
+const TB_REFRESH_BTN: usize = 0;
+const TB_VIEWALL_BTN: usize = 1;
+const TB_LBL: usize = 2;
+const TB_SEARCH_BOX: usize = 3;
+const TB_SEARCH_BTN: usize = 4;
+const TB_TABLE_LIST: usize = 5;
+const TB_DETAIL_LIST: usize = 6;
+const TB_PREV_BTN: usize = 7;
+const TB_NEXT_BTN: usize = 8;
+const TB_PAGE_LABEL: usize = 9;
+const TB_EXPORT_BTN: usize = 10;
+const TB_PAGE_SIZE: u64 = 20;
+
+// "View All" paging: table being paged (u32::MAX = none) and current page
+static mut TB_PAGE_TABLE: u32 = u32::MAX;
+static mut TB_PAGE: u64 = 0;
+static mut TB_PAGE_FULL: bool = false;
+
+// Rows currently shown in the detail list (header line first, summary last)
+static mut TB_ROWS: Option<QueryResult> = None;
+static mut TB_SORT_COL: usize = usize::MAX;
+static mut TB_SORT_DESC: bool = false;
+
+/// Run a SELECT into the detail list with a clickable column header row.
+fn tb_show_rows(sql: &str) {
+    let result = query_execute(sql, 0);
+    unsafe {
+        TB_SORT_COL = usize::MAX;
+        TB_SORT_DESC = false;
+        if result.error_code != 0 || result.column_names.is_empty() {
+            if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
+                populate_lv_from_result(dl, &result);
             }
-            WidgetAction::Clicked(idx) if idx == QC_TMPL_BTN => {
-                let tmpl = QC_TEMPLATES[QC_TMPL_IDX];
-                QC_TMPL_IDX = (QC_TMPL_IDX + 1) % QC_TEMPLATES.len();
-                if let Some(ref mut tb) = QC_WIDGETS.widgets[QC_TEXTBOX] {
-                    widget_set_text(tb, tmpl);
-                }
+            TB_ROWS = None;
+            return;
+        }
+        TB_ROWS = Some(result);
+    }
+    tb_render_rows();
+}
+
+/// Show page TB_PAGE of TB_PAGE_TABLE; only that page's rows are decrypted.
+fn tb_show_page() {
+    unsafe {
+        let schema = match db_get_schema_by_id(TB_PAGE_TABLE) { Some(s) => s, None => return };
+        let mut sql = FmtBuf::new();
+        let _ = write!(sql, "SELECT * FROM {} LIMIT {} OFFSET {}",
+                       schema.name_str(), TB_PAGE_SIZE, TB_PAGE * TB_PAGE_SIZE);
+        tb_show_rows(sql.as_str());
+
+        let shown = TB_ROWS.as_ref().map(|r| r.rows.len() as u64).unwrap_or(0);
+        TB_PAGE_FULL = shown == TB_PAGE_SIZE;
+        let mut label = FmtBuf::new();
+        let _ = write!(label, "Page {}{}", TB_PAGE + 1, if TB_PAGE_FULL { "" } else { " (end)" });
+        if let Some(ref mut w) = TB_WIDGETS.widgets[TB_PAGE_LABEL] {
+            widget_set_text(w, label.as_str());
+            w.w = (label.pos as i16) * FONT_WIDTH as i16;
+        }
+    }
+}
+
+/// Leave paging mode (search results and schema views are not paged).
+fn tb_stop_paging() {
+    unsafe {
+        TB_PAGE_TABLE = u32::MAX;
+        TB_PAGE = 0;
+        TB_PAGE_FULL = false;
+        if let Some(ref mut w) = TB_WIDGETS.widgets[TB_PAGE_LABEL] {
+            widget_set_text(w, "");
+        }
+    }
+}
+
+fn tb_header_line() -> FmtBuf {
+    let mut hdr = FmtBuf::new();
+    unsafe {
+        let names = match TB_ROWS { Some(ref r) => &r.column_names, None => return hdr };
+        for (c, name) in names.iter().take(LV_MAX_FIELDS).enumerate() {
+            if c > 0 { hdr.push_str(" | "); }
+            hdr.push_str(name);
+            if c == TB_SORT_COL {
+                hdr.push_str(if TB_SORT_DESC { " v" } else { " ^" });
             }
-            _ => {}
+        }
+    }
+    hdr
+}
+
+fn tb_render_rows() {
+    unsafe {
+        let rows = match TB_ROWS { Some(ref r) => r, None => return };
+        if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
+            listview_clear(dl);
+            listview_add_item(dl, tb_header_line().as_str());
+            for row in rows.rows.iter() {
+                listview_add_item(dl, format_row(row).as_str());
+            }
+            let mut summary = FmtBuf::new();
+            let _ = write!(summary, "-- {} row(s) --", rows.rows.len());
+            listview_add_item(dl, summary.as_str());
         }
     }
 }
 
-fn open_query_console() {
-    unsafe {
-        QC_WIDGETS.clear();
-        QC_TMPL_IDX = 0;
+fn field_cmp(a: &Option<FieldValue>, b: &Option<FieldValue>) -> core::cmp::Ordering {
+    use core::cmp::Ordering;
+    match (a, b) {
+        (Some(FieldValue::U64(x)), Some(FieldValue::U64(y))) => x.cmp(y),
+        (Some(FieldValue::I64(x)), Some(FieldValue::I64(y))) => x.cmp(y),
+        (Some(FieldValue::U32(x)), Some(FieldValue::U32(y))) => x.cmp(y),
+        (Some(FieldValue::U8(x)), Some(FieldValue::U8(y))) => x.cmp(y),
+        (Some(FieldValue::F64(x)), Some(FieldValue::F64(y))) => x.total_cmp(y),
+        (Some(FieldValue::Bool(x)), Some(FieldValue::Bool(y))) => x.cmp(y),
+        (Some(FieldValue::Str(x)), Some(FieldValue::Str(y))) => x.as_str().cmp(y.as_str()),
+        // NULLs sort first; blobs and mixed types keep their order
+        (None, Some(_)) => Ordering::Less,
+        (Some(_), None) => Ordering::Greater,
+        _ => Ordering::Equal,
     }
-    let id = match wm_create_window("Query Console", 100, 60, 540, 400, Some(qc_event), Some(qc_paint)) {
-        Some(v) => v,
-        None => return,
-    };
-    let cw;
-    if let Some(win) = wm_get_window(id) { cw = win.client_w; } else { return; }
+}
 
+/// Header click at client x `mx`: sort by the column under it, toggling direction.
+fn tb_header_click(mx: i16) {
     unsafe {
-        QC_WIDGETS.add_textbox(4, 4, cw as i16 - 160, 24);
-        if let Some(ref mut w) = QC_WIDGETS.widgets[QC_TEXTBOX] { w.focused = true; }
-        QC_WIDGETS.add_button(cw as i16 - 152, 4, 68, 24, "Execute");
-        QC_WIDGETS.add_button(cw as i16 - 80, 4, 76, 24, "Template");
-        QC_WIDGETS.add_listview(4, 34, cw as i16 - 8, 400 - 26 - 40);
+        let text_x = match TB_WIDGETS.widgets[TB_DETAIL_LIST] {
+            Some(ref dl) => dl.x + 4,
+            None => return,
+        };
+        if mx < text_x { return; }
+        let ch = ((mx - text_x) / FONT_WIDTH as i16) as usize;
+
+        // Columns are separated by " | " in the header line
+        let hdr = tb_header_line();
+        let col = hdr.as_str().as_bytes().iter().take(ch).filter(|&&b| b == b'|').count();
+
+        let rows = match TB_ROWS { Some(ref mut r) => r, None => return };
+        let cols = rows.column_names.len().min(LV_MAX_FIELDS);
+        if col >= cols { return; }
+
+        if TB_SORT_COL == col {
+            TB_SORT_DESC = !TB_SORT_DESC;
+        } else {
+            TB_SORT_COL = col;
+            TB_SORT_DESC = false;
+        }
+        let desc = TB_SORT_DESC;
+        rows.rows.sort_by(|a, b| {
+            let ord = field_cmp(&a.fields[col], &b.fields[col]);
+            if desc { ord.reverse() } else { ord }
+        });
     }
+    tb_render_rows();
 }
 
-// ===========================================================================
-// ---- Table Browser ----
-// ===========================================================================
-static mut TB_WIDGETS: WidgetSet = WidgetSet::new();
-const TB_REFRESH_BTN: usize = 0;
-const TB_VIEWALL_BTN: usize = 1;
-const TB_LBL: usize = 2;
-const TB_SEARCH_BOX: usize = 3;
-const TB_SEARCH_BTN: usize = 4;
-const TB_TABLE_LIST: usize = 5;
-const TB_DETAIL_LIST: usize = 6;
+/// Export the table selected in the list as the CSV object `<table>.csv`,
+/// reporting in the detail list.
+fn tb_export_csv() {
+    unsafe {
+        let sel = TB_WIDGETS.widgets[TB_TABLE_LIST].as_ref().map(|w| w.lv_selected).unwrap_or(-1);
+        let schema = match if sel >= 0 { db_get_schema_by_id(sel as u32) } else { None } {
+            Some(s) => s,
+            None => {
+                if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
+                    listview_add_item(dl, "Export CSV: select a table first");
+                }
+                return;
+            }
+        };
+
+        let mut sql = FmtBuf::new();
+        let _ = write!(sql, "SELECT * FROM {}", schema.name_str());
+        let result = query_execute(sql.as_str(), 0);
+        let (full, _) = result_to_csv(&result, usize::MAX);
+        let (text, rows_written) = result_to_csv(&result, MAX_STR_LEN);
+        let data = core::str::from_utf8(&text).unwrap_or("");
+
+        let mut name = FmtBuf::new();
+        let _ = write!(name, "{}.csv", schema.name_str());
+        let res = object_put(name.as_str(), "csv", data);
+
+        TB_ROWS = None;
+        tb_stop_paging();
+        if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
+            listview_clear(dl);
+            let mut msg = FmtBuf::new();
+            if result.error_code != VOS_OK {
+                let _ = write!(msg, "Export failed: {}", result.error_msg_str());
+            } else if res.error_code != VOS_OK {
+                let _ = write!(msg, "Export failed: {}", res.error_msg_str());
+            } else {
+                let _ = write!(msg, "Exported {} row(s) to '{}'", rows_written, name.as_str());
+            }
+            listview_add_item(dl, msg.as_str());
+            if full.len() > MAX_STR_LEN {
+                let mut warn = FmtBuf::new();
+                let _ = write!(warn, "WARNING: CSV is {} bytes, over the {}-byte record limit;",
+                               full.len(), MAX_STR_LEN);
+                listview_add_item(dl, warn.as_str());
+                let mut warn = FmtBuf::new();
+                let _ = write!(warn, "  only {} of {} row(s) were kept", rows_written, result.rows.len());
+                listview_add_item(dl, warn.as_str());
+            }
+        }
+        if res.error_code == VOS_OK { notify("CSV exported", TOAST_MS); }
+    }
+}
 
 fn tb_refresh_tables() {
     unsafe {
@@ -246,7 +1522,7 @@ fn tb_refresh_tables() {
 
 fn tb_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { TB_WIDGETS.draw_all(win); }
+    unsafe { widgets_draw(&TB_WIDGETS, win); }
 }
 
 fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
@@ -255,7 +1531,8 @@ fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
         return;
     }
     unsafe {
-        let action = TB_WIDGETS.dispatch(ev);
+        if clipboard_key(&mut TB_WIDGETS, ev) { return; }
+        let action = widgets_dispatch(&mut TB_WIDGETS, ev);
         match action {
             WidgetAction::Clicked(idx) if idx == TB_REFRESH_BTN => {
                 tb_refresh_tables();
@@ -264,17 +1541,30 @@ fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
                 if let Some(ref tl) = TB_WIDGETS.widgets[TB_TABLE_LIST] {
                     let sel = tl.lv_selected;
                     if sel >= 0 {
-                        if let Some(s) = db_get_schema_by_id(sel as u32) {
-                            let mut sql = FmtBuf::new();
-                            let _ = write!(sql, "SELECT * FROM {}", s.name_str());
-                            if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
-                                populate_lv_from_query(dl, sql.as_str());
-                            }
+                        if db_get_schema_by_id(sel as u32).is_some() {
+                            TB_PAGE_TABLE = sel as u32;
+                            TB_PAGE = 0;
+                            tb_show_page();
                         }
                     }
                 }
             }
+            WidgetAction::Clicked(idx) if idx == TB_EXPORT_BTN => tb_export_csv(),
+            WidgetAction::Clicked(idx) if idx == TB_PREV_BTN => {
+                if TB_PAGE_TABLE != u32::MAX && TB_PAGE > 0 {
+                    TB_PAGE -= 1;
+                    tb_show_page();
+                }
+            }
+            WidgetAction::Clicked(idx) if idx == TB_NEXT_BTN => {
+                // A short page means there is nothing after it
+                if TB_PAGE_TABLE != u32::MAX && TB_PAGE_FULL {
+                    TB_PAGE += 1;
+                    tb_show_page();
+                }
+            }
             WidgetAction::Clicked(idx) if idx == TB_SEARCH_BTN => {
+                tb_stop_paging();
                 // Get selected table and search term
                 let sel = TB_WIDGETS.widgets[TB_TABLE_LIST].as_ref()
                     .map(|w| w.lv_selected).unwrap_or(-1);
@@ -294,10 +1584,9 @@ fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
                             } else {
                                 let _ = write!(sql, "SELECT * FROM {} WHERE {} = '{}'", s.name_str(), col, val);
                             }
-                            if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
-                                populate_lv_from_query(dl, sql.as_str());
-                            }
+                            tb_show_rows(sql.as_str());
                         } else {
+                            TB_ROWS = None;
                             if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
                                 listview_clear(dl);
                                 listview_add_item(dl, "Search format: column=value");
@@ -306,8 +1595,13 @@ fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
                     }
                 }
             }
+            WidgetAction::Selected(idx, 0) if idx == TB_DETAIL_LIST && TB_ROWS.is_some() => {
+                tb_header_click(ev.mouse_x);
+            }
             WidgetAction::Selected(idx, sel) if idx == TB_TABLE_LIST => {
                 // Show schema for selected table
+                TB_ROWS = None;
+                tb_stop_paging();
                 if let Some(schema) = db_get_schema_by_id(sel as u32) {
                     if let Some(ref mut dl) = TB_WIDGETS.widgets[TB_DETAIL_LIST] {
                         listview_clear(dl);
@@ -335,7 +1629,12 @@ fn tb_event(win: &mut Window, ev: &mut GuiEvent) {
 }
 
 fn open_table_browser() {
-    unsafe { TB_WIDGETS.clear(); }
+    unsafe {
+        TB_WIDGETS.clear();
+        TB_ROWS = None;
+        TB_PAGE_TABLE = u32::MAX;
+        TB_PAGE = 0;
+    }
     let id = match wm_create_window("Table Browser", 150, 80, 600, 400, Some(tb_event), Some(tb_paint)) {
         Some(v) => v,
         None => return,
@@ -351,7 +1650,11 @@ fn open_table_browser() {
         TB_WIDGETS.add_textbox(190, 2, cw as i16 - 270, 22);
         TB_WIDGETS.add_button(cw as i16 - 76, 2, 72, 22, "Search");
         TB_WIDGETS.add_listview(4, 28, 180, ch as i16 - 34);
-        TB_WIDGETS.add_listview(190, 28, cw as i16 - 196, ch as i16 - 34);
+        TB_WIDGETS.add_listview(190, 28, cw as i16 - 196, ch as i16 - 62);
+        TB_WIDGETS.add_button(190, ch as i16 - 28, 60, 22, "< Prev");
+        TB_WIDGETS.add_button(254, ch as i16 - 28, 60, 22, "Next >");
+        TB_WIDGETS.add_label(322, ch as i16 - 24, "", 0xFF808080, CLIENT_BG);
+        TB_WIDGETS.add_button(cw as i16 - 100, ch as i16 - 28, 96, 22, "Export CSV");
     }
     tb_refresh_tables();
 }
@@ -363,29 +1666,141 @@ static mut PM_WIDGETS: WidgetSet = WidgetSet::new();
 const PM_REFRESH_BTN: usize = 0;
 const PM_KILL_BTN: usize = 1;
 const PM_LISTVIEW: usize = 2;
+const PM_PRIO_UP_BTN: usize = 3;
+const PM_PRIO_DOWN_BTN: usize = 4;
+
+// Snapshot behind the list; item i is PM_PROCS[i]
+static mut PM_PROCS: [ProcessInfo; MAX_PROCESSES] = [const { ProcessInfo::empty() }; MAX_PROCESSES];
+static mut PM_PROC_COUNT: usize = 0;
 
 fn pm_refresh_list() {
+    unsafe {
+        PM_PROC_COUNT = process::process_list(&mut PM_PROCS);
+        // Join the live table with ProcessTable rows by pid for cap_root
+        let rows = query_execute("SELECT * FROM ProcessTable", 0);
+        let mut stack_total = 0u64;
+        if let Some(ref mut lv) = PM_WIDGETS.widgets[PM_LISTVIEW] {
+            listview_clear(lv);
+            for p in PM_PROCS[..PM_PROC_COUNT].iter() {
+                let mut line = FmtBuf::new();
+                let _ = write!(line, "{:>4}  {:<14} {:<8} p{}",
+                               p.pid, p.name_str(), state_name(p.state), p.priority);
+                if let Some(st) = process::process_get_stats(p.pid) {
+                    let _ = write!(line, " {:>3}K", st.kernel_stack / 1024);
+                    if st.is_user { let _ = write!(line, "+{}K", st.user_stack / 1024); }
+                    stack_total += st.kernel_stack + st.user_stack;
+                }
+                let row = rows.rows.iter().find(|r| field_u64(&r.fields[0]) == p.pid);
+                match row.map(|r| r.fields[4].as_ref().and_then(field_as_u64)) {
+                    Some(Some(cap)) => { let _ = write!(line, "  cap {}", cap); }
+                    Some(None) => {}
+                    None => line.push_str("  (no row)"),
+                }
+                listview_add_item(lv, line.as_str());
+            }
+            let mut summary = FmtBuf::new();
+            let _ = write!(summary, "-- {} process(es), {} KiB of stacks --", PM_PROC_COUNT, stack_total / 1024);
+            listview_add_item(lv, summary.as_str());
+        }
+    }
+}
+
+fn state_name(s: ProcState) -> &'static str {
+    match s {
+        ProcState::Free       => "free",
+        ProcState::Ready      => "ready",
+        ProcState::Running    => "running",
+        ProcState::Blocked    => "blocked",
+        ProcState::Terminated => "terminated",
+    }
+}
+
+/// Show a one-line message in place of the process list.
+fn pm_message(msg: &str) {
     unsafe {
         if let Some(ref mut lv) = PM_WIDGETS.widgets[PM_LISTVIEW] {
-            populate_lv_from_query(lv, "SELECT * FROM ProcessTable");
+            listview_clear(lv);
+            listview_add_item(lv, msg);
+        }
+    }
+}
+
+/// Move the selected process `delta` priority classes, keeping its
+/// ProcessTable row in step.
+fn pm_change_priority(delta: i8) {
+    let sel = unsafe {
+        match PM_WIDGETS.widgets[PM_LISTVIEW] {
+            Some(ref lv) => lv.lv_selected,
+            None => return,
+        }
+    };
+    if sel < 0 { return; }
+    let (pid, prio) = unsafe {
+        if sel as usize >= PM_PROC_COUNT { return; }
+        (PM_PROCS[sel as usize].pid, PM_PROCS[sel as usize].priority)
+    };
+
+    let new_prio = (prio as i16 + delta as i16).clamp(0, PRIO_MAX as i16) as u8;
+    if new_prio == prio { return; }
+    if process::process_set_priority(pid, new_prio) != VOS_OK {
+        pm_message("Process is gone; refresh the list");
+        return;
+    }
+    let mut sql = FmtBuf::new();
+    let _ = write!(sql, "UPDATE ProcessTable SET priority = {} WHERE pid = {}", new_prio, pid);
+    let _ = query_execute(sql.as_str(), 0);
+    pm_refresh_list();
+}
+
+/// Terminate the selected process and drop its ProcessTable row.
+fn pm_kill_selected() {
+    let sel = unsafe {
+        match PM_WIDGETS.widgets[PM_LISTVIEW] {
+            Some(ref lv) => lv.lv_selected,
+            None => return,
         }
+    };
+    if sel < 0 { return; }
+
+    // Items map 1:1 onto the snapshot (the trailing summary has no entry)
+    let pid = unsafe {
+        if sel as usize >= PM_PROC_COUNT { return; }
+        PM_PROCS[sel as usize].pid
+    };
+
+    let current = process::process_get_current().map(|p| p.pid).unwrap_or(0);
+    if pid == 0 {
+        pm_message("Refusing to kill pid 0 (kernel)");
+        return;
+    }
+    if pid == current {
+        pm_message("Refusing to kill the current process");
+        return;
     }
+
+    process::process_exit(pid, 0);
+    // Nobody else waits on it: reap the zombie so the slot is reusable
+    let _ = process::process_waitpid(pid);
+    let mut sql = FmtBuf::new();
+    let _ = write!(sql, "DELETE FROM ProcessTable WHERE pid = {}", pid);
+    let _ = query_execute(sql.as_str(), 0);
+    pm_refresh_list();
 }
 
 fn pm_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { PM_WIDGETS.draw_all(win); }
+    unsafe { widgets_draw(&PM_WIDGETS, win); }
 }
 
 fn pm_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
     unsafe {
-        let action = PM_WIDGETS.dispatch(ev);
+        let action = widgets_dispatch(&mut PM_WIDGETS, ev);
         match action {
             WidgetAction::Clicked(idx) if idx == PM_REFRESH_BTN => pm_refresh_list(),
-            WidgetAction::Clicked(idx) if idx == PM_KILL_BTN => {
-                // Kill not implemented in GUI for safety
-            }
+            WidgetAction::Clicked(idx) if idx == PM_KILL_BTN => pm_kill_selected(),
+            WidgetAction::Clicked(idx) if idx == PM_PRIO_UP_BTN => pm_change_priority(1),
+            WidgetAction::Clicked(idx) if idx == PM_PRIO_DOWN_BTN => pm_change_priority(-1),
             _ => {}
         }
     }
@@ -405,6 +1820,8 @@ fn open_process_manager() {
         PM_WIDGETS.add_button(4, 2, 72, 22, "Refresh");
         PM_WIDGETS.add_button(80, 2, 72, 22, "Kill");
         PM_WIDGETS.add_listview(4, 28, cw as i16 - 8, ch as i16 - 34);
+        PM_WIDGETS.add_button(156, 2, 72, 22, "Prio +");
+        PM_WIDGETS.add_button(232, 2, 72, 22, "Prio -");
     }
     pm_refresh_list();
 }
@@ -413,6 +1830,10 @@ fn open_process_manager() {
 // ---- System Status ----
 // ===========================================================================
 static mut SS_WIDGETS: WidgetSet = WidgetSet::new();
+const SS_COMPACT_BTN: usize = 10;
+const SS_COMPACT_LABEL: usize = 11;
+const SS_GRAPH_Y: i16 = 300;
+static mut SS_HEAP_HISTORY: SampleRing = SampleRing::new();
 
 fn ss_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
@@ -427,15 +1848,15 @@ fn ss_paint(win: &mut Window) {
 
         let ms = pit::pit_get_uptime_ms();
         let mut b = FmtBuf::new();
-        let _ = write!(b, "Uptime:       {}.{}s", ms / 1000, (ms % 1000) / 100);
+        let _ = write!(b, "Uptime:       {}", fmt_time(ms).as_str());
         update(1, b.as_str());
 
         b = FmtBuf::new();
-        let _ = write!(b, "Heap Used:    {} bytes", heap::heap_used());
+        let _ = write!(b, "Heap Used:    {}", fmt_bytes(heap::heap_used() as u64).as_str());
         update(2, b.as_str());
 
         b = FmtBuf::new();
-        let _ = write!(b, "Heap Free:    {} bytes", heap::heap_free());
+        let _ = write!(b, "Heap Free:    {}", fmt_bytes(heap::heap_free() as u64).as_str());
         update(3, b.as_str());
 
         b = FmtBuf::new();
@@ -462,18 +1883,53 @@ fn ss_paint(win: &mut Window) {
 
         update(8, "Encryption:   AES-128-CBC + HMAC-SHA256");
 
-        SS_WIDGETS.draw_all(win);
+        let used = heap::heap_used();
+        let total = used + heap::heap_free();
+        if total > 0 {
+            SS_HEAP_HISTORY.push((used * 100 / total) as u8);
+        }
+
+        widgets_draw(&SS_WIDGETS, win);
+
+        let gw = win.client_w as i16 - 24;
+        let gh = win.client_h as i16 - SS_GRAPH_Y - 8;
+        if gh > 8 {
+            canvas_text(&mut win.canvas, win.client_w, win.client_h, 12, SS_GRAPH_Y - 18,
+                        "Heap usage history", 0xFF808080, CLIENT_BG);
+            canvas_graph(win, 12, SS_GRAPH_Y, gw, gh, &SS_HEAP_HISTORY, 0xFF00DDAA);
+        }
+    }
+}
+
+/// Compact every table and report the disk bytes given back.
+fn ss_compact_all() {
+    let mut reclaimed = 0u64;
+    for t in 0..db_get_table_count() {
+        reclaimed += database::db_compact_table(t).0;
+    }
+
+    let mut b = FmtBuf::new();
+    let _ = write!(b, "Compacted:    {} reclaimed", fmt_bytes(reclaimed).as_str());
+    unsafe {
+        if let Some(ref mut w) = SS_WIDGETS.widgets[SS_COMPACT_LABEL] {
+            widget_set_text(w, b.as_str());
+            w.w = (b.as_str().len() as i16) * FONT_WIDTH as i16;
+        }
     }
 }
 
 fn ss_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
-    unsafe { SS_WIDGETS.dispatch(ev); }
+    unsafe {
+        if let WidgetAction::Clicked(idx) = widgets_dispatch(&mut SS_WIDGETS, ev) {
+            if idx == SS_COMPACT_BTN { ss_compact_all(); }
+        }
+    }
 }
 
 fn open_system_status() {
     unsafe { SS_WIDGETS.clear(); }
-    wm_create_window("System Status", 200, 100, 420, 340, Some(ss_event), Some(ss_paint));
+    wm_create_window("System Status", 200, 100, 420, 420, Some(ss_event), Some(ss_paint));
 
     let fg = 0xFF00DDAA;
     let bg = CLIENT_BG;
@@ -490,7 +1946,9 @@ fn open_system_status() {
         SS_WIDGETS.add_label(12, y, "Capabilities: ...", fg, bg); y += 22;
         SS_WIDGETS.add_label(12, y, "RNG Source:   ...", fg, bg); y += 22;
         SS_WIDGETS.add_label(12, y, "Encryption:   ...", 0xFF00CC66, bg); y += 28;
-        SS_WIDGETS.add_label(12, y, "CPU: x86-64 (qemu64)", 0xFF808080, bg);
+        SS_WIDGETS.add_label(12, y, "CPU: x86-64 (qemu64)", 0xFF808080, bg); y += 24;
+        SS_WIDGETS.add_button(12, y, 96, 22, "Compact DB");
+        SS_WIDGETS.add_label(116, y + 3, "", fg, bg);
     }
 }
 
@@ -499,6 +1957,7 @@ fn open_system_status() {
 // ===========================================================================
 static mut SD_WIDGETS: WidgetSet = WidgetSet::new();
 const SD_VIEW_AUDIT_BTN: usize = 14;
+const SD_VERIFY_BTN: usize = 15;
 
 fn sd_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
@@ -510,13 +1969,18 @@ fn sd_paint(win: &mut Window) {
             }
         };
 
-        // Update table SEALED labels (indices 3..9)
+        // Update table status labels (indices 3..9) from the last integrity check
         let tc = db_get_table_count();
         for i in 0..tc.min(6) {
             if let Some(s) = db_get_schema_by_id(i) {
                 let mut b = FmtBuf::new();
-                let _ = write!(b, "  {} SEALED", s.name_str());
+                let fg = match database::db_verify_failures(i) {
+                    None => { let _ = write!(b, "  {} SEALED (unverified)", s.name_str()); 0xFF00CC66 }
+                    Some(0) => { let _ = write!(b, "  {} SEALED, MAC OK", s.name_str()); 0xFF00CC66 }
+                    Some(n) => { let _ = write!(b, "  {} TAMPERED: {} MAC failure(s)", s.name_str(), n); 0xFFFF4444 }
+                };
                 update(3 + i as usize, b.as_str());
+                if let Some(ref mut w) = SD_WIDGETS.widgets[3 + i as usize] { w.fg = fg; }
             }
         }
 
@@ -524,9 +1988,11 @@ fn sd_paint(win: &mut Window) {
         let _ = write!(b, "Active Tokens: {}", cap::cap_table_count());
         update(11, b.as_str());
 
-        let rng_text = if random::random_hw_available() { "RNG: Hardware (RDRAND)" }
-            else { "RNG: Software (xorshift128+)" };
-        update(12, rng_text);
+        b = FmtBuf::new();
+        let _ = write!(b, "RNG: {}, {} reseeds",
+                       if random::random_hw_available() { "Hardware (RDRAND)" } else { "Software (xorshift128+)" },
+                       entropy::entropy_reseed_count());
+        update(12, b.as_str());
         if let Some(ref mut w) = SD_WIDGETS.widgets[12] {
             w.fg = if random::random_hw_available() { 0xFF00CC66 } else { 0xFFCCCC00 };
         }
@@ -537,16 +2003,25 @@ fn sd_paint(win: &mut Window) {
         let _ = write!(b, "Audit Events: {} logged", ar.rows.len());
         update(13, b.as_str());
 
-        SD_WIDGETS.draw_all(win);
+        let mac_failures = database::db_mac_failure_count();
+        b = FmtBuf::new();
+        let _ = write!(b, "MAC Failures: {} this boot", mac_failures);
+        update(16, b.as_str());
+        if let Some(ref mut w) = SD_WIDGETS.widgets[16] {
+            w.fg = if mac_failures == 0 { 0xFF00CC66 } else { 0xFFFF4444 };
+        }
+
+        widgets_draw(&SD_WIDGETS, win);
     }
 }
 
 fn sd_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
     unsafe {
-        let action = SD_WIDGETS.dispatch(ev);
+        let action = widgets_dispatch(&mut SD_WIDGETS, ev);
         if let WidgetAction::Clicked(idx) = action {
             if idx == SD_VIEW_AUDIT_BTN { open_audit_viewer(); }
+            if idx == SD_VERIFY_BTN { database::db_verify_all(); }
         }
     }
 }
@@ -572,6 +2047,8 @@ fn open_security_dashboard() {
         SD_WIDGETS.add_label(12, y, "RNG: ...", 0xFF00CC66, bg); y += 18; // 12
         SD_WIDGETS.add_label(12, y, "Audit Events: ...", 0xFF00DDAA, bg); y += 28; // 13
         SD_WIDGETS.add_button(12, y, 140, 24, "View Audit Log"); // 14
+        SD_WIDGETS.add_button(160, y, 140, 24, "Verify Integrity"); // 15
+        SD_WIDGETS.add_label(260, y - 28, "MAC Failures: ...", 0xFF00CC66, bg); // 16
     }
 }
 
@@ -583,27 +2060,95 @@ const AL_FILTER_BOX: usize = 0;
 const AL_REFRESH_BTN: usize = 1;
 const AL_LBL: usize = 2;
 const AL_LISTVIEW: usize = 3;
+const AL_FROM_BOX: usize = 5;
+const AL_TO_BOX: usize = 7;
+const SECS_PER_DAY: u64 = 86_400;
+
+/// A time-range bound as minutes past midnight: "" (open) or "14:30".
+/// Err for anything else.
+fn al_parse_bound(text: &str) -> Result<Option<u64>, ()> {
+    let text = text.trim();
+    if text.is_empty() { return Ok(None); }
+    let num = |s: &str| -> Result<u64, ()> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || s.len() > 9 { return Err(()); }
+        Ok(s.bytes().fold(0u64, |v, b| v * 10 + (b - b'0') as u64))
+    };
+    let i = text.find(':').ok_or(())?;
+    let (h, m) = (num(&text[..i])?, num(&text[i + 1..])?);
+    if h >= 24 || m >= 60 { return Err(()); }
+    Ok(Some(h * 60 + m))
+}
+
+fn al_box_text(idx: usize) -> &'static str {
+    unsafe {
+        match AL_WIDGETS.widgets[idx] {
+            Some(ref w) => widget_get_text(w),
+            None => "",
+        }
+    }
+}
 
 fn al_refresh() {
     unsafe {
-        let result = query_execute("SELECT * FROM AuditTable", 0);
+        // Filter text selects a single action, e.g. "DELETE"
+        let filter = al_box_text(AL_FILTER_BOX).trim();
+        let from = al_parse_bound(al_box_text(AL_FROM_BOX));
+        let to = al_parse_bound(al_box_text(AL_TO_BOX));
+        let (from, to) = match (from, to) {
+            (Ok(f), Ok(t)) => (f, t),
+            _ => {
+                if let Some(ref mut lv) = AL_WIDGETS.widgets[AL_LISTVIEW] {
+                    listview_clear(lv);
+                    listview_add_item(lv, "Time range: use HH:MM (14:30), or leave empty.");
+                }
+                return;
+            }
+        };
+
+        let mut sql = FmtBuf::new();
+        sql.push_str("SELECT * FROM AuditTable");
+        let mut joiner = " WHERE ";
+        if !filter.is_empty() {
+            // The lexer has no quote escaping, so quotes are dropped
+            sql.push_str(joiner);
+            sql.push_str("action = '");
+            for part in filter.split('\'') { sql.push_str(part); }
+            sql.push_str("'");
+            joiner = " AND ";
+        }
+        // Timestamps are unix seconds; bounds are times of day today (UTC),
+        // and the end minute is included whole
+        let now = rtc::rtc_unix_time();
+        let midnight = now - now % SECS_PER_DAY;
+        let lo = from.map(|m| midnight + m * 60);
+        let hi = to.map(|m| midnight + (m + 1) * 60 - 1);
+        match (lo, hi) {
+            (Some(a), Some(b)) => { let _ = write!(sql, "{}timestamp BETWEEN {} AND {}", joiner, a, b); }
+            (Some(a), None) => { let _ = write!(sql, "{}timestamp >= {}", joiner, a); }
+            (None, Some(b)) => { let _ = write!(sql, "{}timestamp <= {}", joiner, b); }
+            (None, None) => {}
+        }
+        let filtered = !filter.is_empty() || lo.is_some() || hi.is_some();
+        let result = query_execute(sql.as_str(), 0);
         if let Some(ref mut lv) = AL_WIDGETS.widgets[AL_LISTVIEW] {
             listview_clear(lv);
+            if result.error_code != 0 {
+                listview_add_item(lv, result.error_msg_str());
+                return;
+            }
             if result.rows.is_empty() {
-                listview_add_item(lv, "No audit events.");
+                listview_add_item(lv, if filtered { "No matching audit events." }
+                    else { "No audit events." });
                 return;
             }
             for row in result.rows.iter() {
-                let ts = match row.fields[1] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                let apid = match row.fields[2] { Some(FieldValue::U64(v)) => v, _ => 0 };
+                let ts = field_u64(&row.fields[1]);
+                let apid = field_u64(&row.fields[2]);
                 let action = match row.fields[3] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "?" };
                 let res_str = match row.fields[5] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
 
-                let secs = ts / 1000;
-                let mins = secs / 60;
-                let s = secs % 60;
                 let mut line = FmtBuf::new();
-                let _ = write!(line, "[{:02}:{:02}] {} PID:{} {}", mins, s, action, apid, res_str);
+                let _ = write!(line, "[{}] {} PID:{} {}", fmt_datetime(ts).as_str(), action, apid, res_str);
                 listview_add_item(lv, line.as_str());
             }
             let mut summary = FmtBuf::new();
@@ -615,13 +2160,14 @@ fn al_refresh() {
 
 fn al_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { AL_WIDGETS.draw_all(win); }
+    unsafe { widgets_draw(&AL_WIDGETS, win); }
 }
 
 fn al_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
     unsafe {
-        let action = AL_WIDGETS.dispatch(ev);
+        if clipboard_key(&mut AL_WIDGETS, ev) { return; }
+        let action = widgets_dispatch(&mut AL_WIDGETS, ev);
         if let WidgetAction::Clicked(idx) = action {
             if idx == AL_REFRESH_BTN { al_refresh(); }
         }
@@ -642,7 +2188,12 @@ fn open_audit_viewer() {
         AL_WIDGETS.add_textbox(4, 4, cw as i16 - 160, 24);
         AL_WIDGETS.add_button(cw as i16 - 152, 4, 72, 24, "Refresh");
         AL_WIDGETS.add_label(cw as i16 - 76, 8, "Filter", 0xFF808080, CLIENT_BG);
-        AL_WIDGETS.add_listview(4, 34, cw as i16 - 8, ch as i16 - 40);
+        AL_WIDGETS.add_listview(4, 64, cw as i16 - 8, ch as i16 - 70);
+        AL_WIDGETS.add_label(4, 38, "From", 0xFF808080, CLIENT_BG);
+        AL_WIDGETS.add_textbox(44, 34, 64, 24);
+        AL_WIDGETS.add_label(116, 38, "To", 0xFF808080, CLIENT_BG);
+        AL_WIDGETS.add_textbox(140, 34, 64, 24);
+        AL_WIDGETS.add_label(212, 38, "today, HH:MM (UTC)", 0xFF808080, CLIENT_BG);
     }
     al_refresh();
 }
@@ -654,8 +2205,95 @@ static mut CM_WIDGETS: WidgetSet = WidgetSet::new();
 const CM_REFRESH_BTN: usize = 0;
 const CM_REVOKE_BTN: usize = 1;
 const CM_LISTVIEW: usize = 2;
+const CM_DETAIL_LIST: usize = 3;
+const CM_REVOKE_TREE_BTN: usize = 4;
+const CM_DETAIL_H: i16 = 92;
+
+const CAP_RIGHT_NAMES: [(u32, &str); 5] = [
+    (0x01, "Read"), (0x02, "Write"), (0x04, "Execute"), (0x08, "Delete"), (0x10, "Grant"),
+];
+
+/// CapabilityTable rows in delegation order, each capability followed by
+/// the ones delegated from it, as (index into `result.rows`, depth).
+/// A capability whose parent is gone counts as a root.
+fn cm_tree(result: &QueryResult) -> Vec<(usize, usize)> {
+    let n = result.rows.len();
+    let ids: Vec<u64> = result.rows.iter().map(|r| field_u64(&r.fields[0])).collect();
+    let parents: Vec<u64> = result.rows.iter().map(|r| field_u64(&r.fields[4])).collect();
+    let mut placed = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    for root in (0..n).rev() {
+        if parents[root] == 0 || !ids.contains(&parents[root]) { stack.push((root, 0)); }
+    }
+    loop {
+        while let Some((i, depth)) = stack.pop() {
+            if placed[i] { continue; }
+            placed[i] = true;
+            order.push((i, depth));
+            for child in (0..n).rev() {
+                if !placed[child] && parents[child] == ids[i] { stack.push((child, depth + 1)); }
+            }
+        }
+        // Rows on a parent_id cycle have no root; list them flat at the end
+        match placed.iter().position(|&p| !p) {
+            Some(i) => stack.push((i, 0)),
+            None => break,
+        }
+    }
+    order
+}
+
+/// Index into `result.rows` of listview row `sel` (0 is the header).
+fn cm_row_index(result: &QueryResult, sel: usize) -> Option<usize> {
+    let i = sel.checked_sub(1)?;
+    cm_tree(result).get(i).map(|&(row, _)| row)
+}
+
+/// Spell out the capability on listview row `sel` (0 is the header).
+fn cm_show_detail(sel: usize) {
+    unsafe {
+        let dl = match CM_WIDGETS.widgets[CM_DETAIL_LIST] {
+            Some(ref mut w) => w,
+            None => return,
+        };
+        listview_clear(dl);
+        let result = query_execute("SELECT * FROM CapabilityTable", 0);
+        let row = match cm_row_index(&result, sel).and_then(|i| result.rows.get(i)) {
+            Some(r) => r,
+            None => { listview_add_item(dl, "Select a capability for details."); return; }
+        };
+        let cap_id = field_u64(&row.fields[0]);
+        let obj_id = field_u64(&row.fields[1]);
+        let own_pid = field_u64(&row.fields[2]);
+        let rights = field_u64(&row.fields[3]) as u32;
+        let parent = field_u64(&row.fields[4]);
+        let revoked = match row.fields[5] { Some(FieldValue::Bool(v)) => v, _ => false };
+
+        let mut line = FmtBuf::new();
+        let _ = write!(line, "Capability {} on object {}, {}", cap_id, obj_id,
+            if revoked { "REVOKED" } else { "ACTIVE" });
+        if parent != 0 { let _ = write!(line, ", delegated from {}", parent); }
+        listview_add_item(dl, line.as_str());
+
+        let mut line = FmtBuf::new();
+        let _ = write!(line, "Rights 0x{:08X}:", rights);
+        for &(bit, name) in CAP_RIGHT_NAMES.iter() {
+            let _ = write!(line, " {} {}", name, if rights & bit != 0 { "yes" } else { "no" });
+        }
+        listview_add_item(dl, line.as_str());
+
+        let mut line = FmtBuf::new();
+        match process::process_get_by_pid(own_pid) {
+            Some(p) => { let _ = write!(line, "Owner pid {} ({})", own_pid, p.name_str()); }
+            None => { let _ = write!(line, "Owner pid {} (not running)", own_pid); }
+        }
+        listview_add_item(dl, line.as_str());
+    }
+}
 
 fn cm_refresh() {
+    cm_show_detail(0);
     unsafe {
         if let Some(ref mut lv) = CM_WIDGETS.widgets[CM_LISTVIEW] {
             let result = query_execute("SELECT * FROM CapabilityTable", 0);
@@ -665,11 +2303,12 @@ fn cm_refresh() {
                 return;
             }
             listview_add_item(lv, "  CAP_ID  OBJ_ID  PID    RIGHTS  STATUS");
-            for row in result.rows.iter() {
-                let cap_id = match row.fields[0] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                let obj_id = match row.fields[1] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                let own_pid = match row.fields[2] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                let rights = match row.fields[3] { Some(FieldValue::U32(v)) => v, _ => 0 };
+            for (i, depth) in cm_tree(&result) {
+                let row = &result.rows[i];
+                let cap_id = field_u64(&row.fields[0]);
+                let obj_id = field_u64(&row.fields[1]);
+                let own_pid = field_u64(&row.fields[2]);
+                let rights = field_u64(&row.fields[3]) as u32;
                 let revoked = match row.fields[5] { Some(FieldValue::Bool(v)) => v, _ => false };
 
                 let mut r_str = FmtBuf::new();
@@ -681,7 +2320,11 @@ fn cm_refresh() {
                 if r_str.pos == 0 { r_str.push_str("NONE"); }
 
                 let mut line = FmtBuf::new();
-                let _ = write!(line, "  {} {} {} {} {}",
+                line.push_str("  ");
+                // Delegated capabilities sit under their parent
+                for _ in 0..depth.min(8) { line.push_str("  "); }
+                if depth > 0 { line.push_str("+-"); }
+                let _ = write!(line, "{} {} {} {} {}",
                     cap_id, obj_id, own_pid, r_str.as_str(),
                     if revoked { "REVOKED" } else { "ACTIVE" });
                 listview_add_item(lv, line.as_str());
@@ -693,36 +2336,47 @@ fn cm_refresh() {
     }
 }
 
+/// Revoke the selected capability, and with `subtree` everything
+/// delegated from it as well.
+fn cm_revoke_selected(subtree: bool) {
+    unsafe {
+        let sel = match CM_WIDGETS.widgets[CM_LISTVIEW] {
+            Some(ref lv) => lv.lv_selected,
+            None => return,
+        };
+        if sel <= 0 { return; } // 0 = header
+        let result = query_execute("SELECT * FROM CapabilityTable", 0);
+        let data_idx = match cm_row_index(&result, sel as usize) { Some(i) => i, None => return };
+        let cap_id = field_u64(&result.rows[data_idx].fields[0]);
+        let sql = if subtree { "REVOKE ? CASCADE" } else { "REVOKE ?" };
+        let res = query_execute_with(sql, &[FieldValue::U64(cap_id)], 0);
+        let mut msg = FmtBuf::new();
+        if res.error_code != VOS_OK {
+            let _ = write!(msg, "Revoke failed: {}", res.error_msg_str());
+        } else if subtree {
+            let _ = write!(msg, "Capability {} and its delegates revoked", cap_id);
+        } else {
+            let _ = write!(msg, "Capability {} revoked", cap_id);
+        }
+        notify(msg.as_str(), TOAST_MS);
+        cm_refresh();
+    }
+}
+
 fn cm_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { CM_WIDGETS.draw_all(win); }
+    unsafe { widgets_draw(&CM_WIDGETS, win); }
 }
 
 fn cm_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
     unsafe {
-        let action = CM_WIDGETS.dispatch(ev);
+        let action = widgets_dispatch(&mut CM_WIDGETS, ev);
         match action {
             WidgetAction::Clicked(idx) if idx == CM_REFRESH_BTN => cm_refresh(),
-            WidgetAction::Clicked(idx) if idx == CM_REVOKE_BTN => {
-                // Revoke selected capability
-                if let Some(ref lv) = CM_WIDGETS.widgets[CM_LISTVIEW] {
-                    let sel = lv.lv_selected;
-                    if sel > 0 { // 0 = header
-                        let result = query_execute("SELECT * FROM CapabilityTable", 0);
-                        let data_idx = (sel - 1) as usize;
-                        if data_idx < result.rows.len() {
-                            let cap_id = match result.rows[data_idx].fields[0] {
-                                Some(FieldValue::U64(v)) => v, _ => 0
-                            };
-                            let mut sql = FmtBuf::new();
-                            let _ = write!(sql, "REVOKE {}", cap_id);
-                            let _ = query_execute(sql.as_str(), 0);
-                            cm_refresh();
-                        }
-                    }
-                }
-            }
+            WidgetAction::Selected(idx, sel) if idx == CM_LISTVIEW => cm_show_detail(sel as usize),
+            WidgetAction::Clicked(idx) if idx == CM_REVOKE_BTN => cm_revoke_selected(false),
+            WidgetAction::Clicked(idx) if idx == CM_REVOKE_TREE_BTN => cm_revoke_selected(true),
             _ => {}
         }
     }
@@ -741,7 +2395,9 @@ fn open_cap_manager() {
     unsafe {
         CM_WIDGETS.add_button(4, 2, 72, 22, "Refresh");
         CM_WIDGETS.add_button(80, 2, 100, 22, "Revoke Sel.");
-        CM_WIDGETS.add_listview(4, 28, cw as i16 - 8, ch as i16 - 34);
+        CM_WIDGETS.add_listview(4, 28, cw as i16 - 8, ch as i16 - 38 - CM_DETAIL_H);
+        CM_WIDGETS.add_listview(4, ch as i16 - 6 - CM_DETAIL_H, cw as i16 - 8, CM_DETAIL_H);
+        CM_WIDGETS.add_button(184, 2, 100, 22, "Revoke Tree");
     }
     cm_refresh();
 }
@@ -750,14 +2406,52 @@ fn open_cap_manager() {
 // ---- Object Manager ----
 // ===========================================================================
 static mut OM_WIDGETS: WidgetSet = WidgetSet::new();
+static mut OM_TYPE_COMBO: ComboBox = ComboBox::new();
 const OM_REFRESH_BTN: usize = 0;
 const OM_DELETE_BTN: usize = 1;
 const OM_LISTVIEW: usize = 2;
+const OM_NAME_BOX: usize = 4;
+const OM_TYPE_BOX: usize = 6;
+const OM_DATA_BOX: usize = 8;
+const OM_NEW_BTN: usize = 9;
+const OM_ALL_TYPES: &str = "All types";
+const OM_DEFAULT_TYPE: &str = "text";
+
+/// Objects of the type picked in the combo box (all of them for option 0).
+fn om_query() -> QueryResult {
+    unsafe {
+        if OM_TYPE_COMBO.selected() == 0 {
+            return query_execute("SELECT * FROM ObjectTable", 0);
+        }
+        let mut sql = FmtBuf::new();
+        let _ = write!(sql, "SELECT * FROM ObjectTable WHERE type = '{}'", OM_TYPE_COMBO.selected_str());
+        query_execute(sql.as_str(), 0)
+    }
+}
+
+/// Rebuild the type list from the objects present, keeping the current pick.
+fn om_refresh_types() {
+    unsafe {
+        let mut current = FmtBuf::new();
+        current.push_str(OM_TYPE_COMBO.selected_str());
+        OM_TYPE_COMBO.clear();
+        OM_TYPE_COMBO.add_option(OM_ALL_TYPES);
+
+        let result = query_execute("SELECT * FROM ObjectTable", 0);
+        for row in result.rows.iter() {
+            let otype = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => continue };
+            let seen = (0..OM_TYPE_COMBO.count).any(|i| OM_TYPE_COMBO.option(i) == otype);
+            if !seen { OM_TYPE_COMBO.add_option(otype); }
+        }
+        OM_TYPE_COMBO.select_text(current.as_str());
+    }
+}
 
 fn om_refresh() {
+    om_refresh_types();
     unsafe {
         if let Some(ref mut lv) = OM_WIDGETS.widgets[OM_LISTVIEW] {
-            let result = query_execute("SELECT * FROM ObjectTable", 0);
+            let result = om_query();
             listview_clear(lv);
             if result.rows.is_empty() {
                 listview_add_item(lv, "No objects found.");
@@ -780,31 +2474,96 @@ fn om_refresh() {
     }
 }
 
+fn om_box_text(idx: usize) -> &'static str {
+    unsafe {
+        match OM_WIDGETS.widgets[idx] {
+            Some(ref w) => widget_get_text(w).trim(),
+            None => "",
+        }
+    }
+}
+
+/// A string bound to a `?` placeholder; no quoting needed.
+fn str_param(s: &str) -> FieldValue {
+    FieldValue::Str(StrField::from_str(s))
+}
+
+/// INSERT an object from the Name/Type/Data boxes and report the new row id.
+fn om_create() {
+    let name = om_box_text(OM_NAME_BOX);
+    let otype = match om_box_text(OM_TYPE_BOX) { "" => OM_DEFAULT_TYPE, t => t };
+    let data = om_box_text(OM_DATA_BOX);
+
+    let mut msg = FmtBuf::new();
+    if name.is_empty() {
+        msg.push_str("Create failed: name is required");
+    } else if name.len() > MAX_STR_LEN || data.len() > MAX_STR_LEN || otype.len() > MAX_STR_LEN {
+        let _ = write!(msg, "Create failed: values are limited to {} bytes", MAX_STR_LEN);
+    } else {
+        // INSERT fills size from data
+        let res = query_execute_with(
+            "INSERT INTO ObjectTable (name, type, data) VALUES (?, ?, ?)",
+            &[str_param(name), str_param(otype), str_param(data)], 0);
+        if res.error_code == VOS_OK {
+            // The INSERT message carries the row id: "1 row inserted (row_id=N)"
+            let _ = write!(msg, "Created '{}': {}", name, res.error_msg_str());
+            notify("Object created", TOAST_MS);
+            unsafe {
+                for idx in [OM_NAME_BOX, OM_TYPE_BOX, OM_DATA_BOX] {
+                    if let Some(ref mut w) = OM_WIDGETS.widgets[idx] { widget_set_text(w, ""); }
+                }
+            }
+        } else {
+            let _ = write!(msg, "Create failed: {}", res.error_msg_str());
+        }
+    }
+
+    om_refresh();
+    unsafe {
+        if let Some(ref mut lv) = OM_WIDGETS.widgets[OM_LISTVIEW] {
+            listview_add_item(lv, msg.as_str());
+        }
+    }
+}
+
 fn om_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { OM_WIDGETS.draw_all(win); }
+    unsafe {
+        widgets_draw(&OM_WIDGETS, win);
+        OM_TYPE_COMBO.draw(win);
+    }
 }
 
 fn om_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
     unsafe {
-        let action = OM_WIDGETS.dispatch(ev);
+        match OM_TYPE_COMBO.dispatch(ev) {
+            ComboEvent::Selected(_) => { om_refresh(); return; }
+            ComboEvent::Consumed => return,
+            ComboEvent::Ignored => {}
+        }
+        let action = widgets_dispatch(&mut OM_WIDGETS, ev);
         match action {
             WidgetAction::Clicked(idx) if idx == OM_REFRESH_BTN => om_refresh(),
+            WidgetAction::Clicked(idx) if idx == OM_NEW_BTN => om_create(),
             WidgetAction::Clicked(idx) if idx == OM_DELETE_BTN => {
                 if let Some(ref lv) = OM_WIDGETS.widgets[OM_LISTVIEW] {
                     let sel = lv.lv_selected;
                     if sel > 0 {
-                        let result = query_execute("SELECT * FROM ObjectTable", 0);
+                        let result = om_query();
                         let data_idx = (sel - 1) as usize;
                         if data_idx < result.rows.len() {
                             let name = match result.rows[data_idx].fields[1] {
                                 Some(FieldValue::Str(ref s)) => s.as_str(),
                                 _ => return,
                             };
-                            let mut sql = FmtBuf::new();
-                            let _ = write!(sql, "DELETE FROM ObjectTable WHERE name = '{}'", name);
-                            let _ = query_execute(sql.as_str(), 0);
+                            let res = query_execute_with(
+                                "DELETE FROM ObjectTable WHERE name = ?", &[str_param(name)], 0);
+                            if res.error_code == VOS_OK {
+                                let mut msg = FmtBuf::new();
+                                let _ = write!(msg, "Object '{}' deleted", name);
+                                notify(msg.as_str(), TOAST_MS);
+                            }
                             om_refresh();
                         }
                     }
@@ -828,7 +2587,16 @@ fn open_object_manager() {
     unsafe {
         OM_WIDGETS.add_button(4, 2, 72, 22, "Refresh");
         OM_WIDGETS.add_button(80, 2, 100, 22, "Delete Sel.");
-        OM_WIDGETS.add_listview(4, 28, cw as i16 - 8, ch as i16 - 34);
+        OM_WIDGETS.add_listview(4, 58, cw as i16 - 8, ch as i16 - 64);
+        OM_WIDGETS.add_label(4, 32, "Name", 0xFF808080, CLIENT_BG);
+        OM_WIDGETS.add_textbox(40, 28, 110, 24);
+        OM_WIDGETS.add_label(156, 32, "Type", 0xFF808080, CLIENT_BG);
+        OM_WIDGETS.add_textbox(192, 28, 70, 24);
+        OM_WIDGETS.add_label(268, 32, "Data", 0xFF808080, CLIENT_BG);
+        OM_WIDGETS.add_textbox(304, 28, cw as i16 - 380, 24);
+        OM_WIDGETS.add_button(cw as i16 - 70, 29, 66, 22, "New");
+        OM_TYPE_COMBO.place(184, 2, 160);
+        OM_TYPE_COMBO.add_option(OM_ALL_TYPES);
     }
     om_refresh();
 }
@@ -838,26 +2606,86 @@ fn open_object_manager() {
 // ===========================================================================
 static mut CALC_DISPLAY: [u8; 32] = [b'0', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
                                       0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+// Values are fixed-point, scaled by CALC_SCALE (three fractional digits)
+const CALC_SCALE: i64 = 1000;
+const CALC_FRAC_DIGITS: usize = 3;
 static mut CALC_VALUE: i64 = 0;
 static mut CALC_OPERAND: i64 = 0;
 static mut CALC_OP: u8 = 0;
 static mut CALC_NEW_INPUT: bool = true;
-static mut CALC_INPUT: [u8; 20] = [0; 20];
+static mut CALC_INPUT: [u8; 40] = [0; 40];
 static mut CALC_INPUT_LEN: usize = 0;
+// Scientific mode: the top row's operators become sqrt, x^2 and 1/x
+static mut CALC_SCI: bool = false;
+// Entry and display base: 10, 16 or 2. Values stay fixed-point underneath;
+// HEX and BIN show and accept integers only.
+static mut CALC_BASE: u32 = 10;
 
 const CALC_BTN_W: i16 = 48;
 const CALC_BTN_H: i16 = 36;
 const CALC_BTN_PAD: i16 = 4;
-const CALC_ROWS: usize = 5;
+const CALC_ROWS: usize = 7;
 const CALC_COLS: usize = 4;
 
-static CALC_BUTTONS: [[u8; 4]; 5] = [
+// Button codes: digits and operators are their own character; 'M' toggles
+// scientific mode, 's' is sqrt, 'q' is x^2, 'r' is 1/x, 'a'..'f' are the hex
+// digits and '#' cycles the base. A code repeated in the next cell of a row
+// is drawn as one wide key.
+static CALC_BUTTONS: [[u8; 4]; 7] = [
     [b'C', b'/', b'*', b'<'],
     [b'7', b'8', b'9', b'-'],
     [b'4', b'5', b'6', b'+'],
     [b'1', b'2', b'3', b'='],
-    [b'0', b' ', b' ', b'='],
+    [b'0', b'M', b'.', b'='],
+    [b'a', b'b', b'c', b'd'],
+    [b'e', b'f', b'#', b'#'],
 ];
+static CALC_SCI_TOP_ROW: [u8; 4] = [b'C', b's', b'q', b'r'];
+
+/// Button code at a grid cell in the current mode.
+fn calc_button_at(r: usize, c: usize) -> u8 {
+    if r == 0 && unsafe { CALC_SCI } { CALC_SCI_TOP_ROW[c] } else { CALC_BUTTONS[r][c] }
+}
+
+fn calc_button_label(btn: u8) -> &'static str {
+    match btn {
+        b's' => "sqrt",
+        b'q' => "x^2",
+        b'r' => "1/x",
+        b'M' => if unsafe { CALC_SCI } { "STD" } else { "SCI" },
+        b'#' => calc_base_name(),
+        b'a' => "A", b'b' => "B", b'c' => "C", b'd' => "D", b'e' => "E", b'f' => "F",
+        _ => "",
+    }
+}
+
+fn calc_base_name() -> &'static str {
+    match unsafe { CALC_BASE } {
+        16 => "HEX",
+        2 => "BIN",
+        _ => "DEC",
+    }
+}
+
+/// Value of digit button `btn` if it is valid in the current base.
+fn calc_digit_value(btn: u8) -> Option<u32> {
+    let v = match btn {
+        b'0'..=b'9' => (btn - b'0') as u32,
+        b'a'..=b'f' => (btn - b'a' + 10) as u32,
+        _ => return None,
+    };
+    if v < unsafe { CALC_BASE } { Some(v) } else { None }
+}
+
+/// Most digits an entry may have: enough for any value the fixed-point
+/// representation holds, and no more than the display shows.
+fn calc_max_digits() -> usize {
+    match unsafe { CALC_BASE } {
+        16 => 13,
+        2 => 30,
+        _ => 18,
+    }
+}
 
 fn calc_set_display(s: &str) {
     unsafe {
@@ -880,46 +2708,173 @@ fn calc_input_str() -> &'static str {
     }
 }
 
+/// Parse the input line into a fixed-point value (extra fraction digits are dropped).
 fn calc_input_to_i64() -> i64 {
     let s = calc_input_str();
+    let base = unsafe { CALC_BASE };
+    if base != 10 {
+        let mut int: i64 = 0;
+        let mut neg = false;
+        for (i, &b) in s.as_bytes().iter().enumerate() {
+            if i == 0 && b == b'-' { neg = true; continue; }
+            if let Some(d) = (b as char).to_digit(base) {
+                int = int.saturating_mul(base as i64).saturating_add(d as i64);
+            }
+        }
+        let val = int.saturating_mul(CALC_SCALE);
+        return if neg { -val } else { val };
+    }
     let mut val: i64 = 0;
     let mut neg = false;
+    let mut frac_digits: Option<usize> = None;
     for (i, &b) in s.as_bytes().iter().enumerate() {
         if i == 0 && b == b'-' { neg = true; continue; }
+        if b == b'.' {
+            if frac_digits.is_none() { frac_digits = Some(0); }
+            continue;
+        }
         if b >= b'0' && b <= b'9' {
-            val = val * 10 + (b - b'0') as i64;
+            if let Some(n) = frac_digits {
+                if n >= CALC_FRAC_DIGITS { continue; }
+                frac_digits = Some(n + 1);
+            }
+            val = val.saturating_mul(10).saturating_add((b - b'0') as i64);
         }
     }
+    for _ in frac_digits.unwrap_or(0)..CALC_FRAC_DIGITS {
+        val = val.saturating_mul(10);
+    }
     if neg { -val } else { val }
 }
 
+/// Integer division rounding half away from zero.
+fn calc_div_round(n: i128, d: i128) -> i64 {
+    let mut q = n / d;
+    let r = n % d;
+    if 2 * r.abs() >= d.abs() {
+        q += if (n < 0) != (d < 0) { -1 } else { 1 };
+    }
+    q.clamp(i64::MIN as i128, i64::MAX as i128) as i64
+}
+
+/// Format a fixed-point value with up to three fraction digits, trailing zeros
+/// trimmed. HEX and BIN show the integer part only.
+fn calc_format(val: i64) -> FmtBuf {
+    let mut b = FmtBuf::new();
+    let base = unsafe { CALC_BASE };
+    if base != 10 {
+        let int = val.unsigned_abs() / CALC_SCALE as u64;
+        if val < 0 && int != 0 { b.push_str("-"); }
+        if base == 16 { let _ = write!(b, "{:X}", int); } else { let _ = write!(b, "{:b}", int); }
+        if b.as_str().len() > 31 {
+            b = FmtBuf::new();
+            b.push_str("Overflow");
+        }
+        return b;
+    }
+    let mag = val.unsigned_abs();
+    let int = mag / CALC_SCALE as u64;
+    let mut frac = mag % CALC_SCALE as u64;
+    if val < 0 { b.push_str("-"); }
+    let _ = write!(b, "{}", int);
+    if frac != 0 {
+        let mut digits = CALC_FRAC_DIGITS;
+        while frac % 10 == 0 { frac /= 10; digits -= 1; }
+        let _ = write!(b, ".{:0width$}", frac, width = digits);
+    }
+    b
+}
+
 fn calc_execute_pending() {
     unsafe {
         match CALC_OP {
-            b'+' => CALC_VALUE += CALC_OPERAND,
-            b'-' => CALC_VALUE -= CALC_OPERAND,
-            b'*' => CALC_VALUE *= CALC_OPERAND,
+            b'+' => CALC_VALUE = CALC_VALUE.saturating_add(CALC_OPERAND),
+            b'-' => CALC_VALUE = CALC_VALUE.saturating_sub(CALC_OPERAND),
+            b'*' => CALC_VALUE = calc_div_round(
+                CALC_VALUE as i128 * CALC_OPERAND as i128, CALC_SCALE as i128),
             b'/' => {
-                if CALC_OPERAND != 0 { CALC_VALUE /= CALC_OPERAND; }
+                if CALC_OPERAND != 0 {
+                    CALC_VALUE = calc_div_round(
+                        CALC_VALUE as i128 * CALC_SCALE as i128, CALC_OPERAND as i128);
+                }
                 else { calc_set_display("Error: /0"); return; }
             }
-            _ => {}
+            _ => {}
+        }
+        calc_set_display(calc_format(CALC_VALUE).as_str());
+    }
+}
+
+/// Integer square root (Newton's method): the largest r with r*r <= n.
+fn calc_isqrt(n: u128) -> u128 {
+    if n < 2 { return n; }
+    let mut x = n;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
+
+/// Apply sqrt, x^2 or 1/x to the current entry and show the result, which
+/// becomes the entry a pending operator or `=` uses next.
+fn calc_apply_unary(btn: u8) {
+    unsafe {
+        let x = calc_input_to_i64();
+        let y = match btn {
+            // sqrt(x / SCALE) * SCALE == sqrt(x * SCALE)
+            b's' if x < 0 => None,
+            b's' => Some(calc_isqrt(x as u128 * CALC_SCALE as u128) as i64),
+            b'q' => Some(calc_div_round(x as i128 * x as i128, CALC_SCALE as i128)),
+            b'r' if x == 0 => None,
+            b'r' => Some(calc_div_round(CALC_SCALE as i128 * CALC_SCALE as i128, x as i128)),
+            _ => return,
+        };
+        CALC_NEW_INPUT = true;
+        match y {
+            Some(v) => {
+                let f = calc_format(v);
+                let fs = f.as_str();
+                CALC_INPUT_LEN = fs.len().min(CALC_INPUT.len() - 1);
+                CALC_INPUT[..CALC_INPUT_LEN].copy_from_slice(&fs.as_bytes()[..CALC_INPUT_LEN]);
+                calc_set_display(fs);
+            }
+            None => {
+                CALC_INPUT_LEN = 0;
+                calc_set_display("Error");
+            }
         }
-        let mut b = FmtBuf::new();
-        let _ = write!(b, "{}", CALC_VALUE);
-        calc_set_display(b.as_str());
     }
 }
 
 fn calc_handle_button(btn: u8) {
     unsafe {
-        if btn >= b'0' && btn <= b'9' {
+        if (btn >= b'0' && btn <= b'9') || (btn >= b'a' && btn <= b'f') {
+            if calc_digit_value(btn).is_none() { return; }
+            if CALC_NEW_INPUT {
+                CALC_INPUT_LEN = 0;
+                CALC_NEW_INPUT = false;
+            }
+            if CALC_INPUT_LEN < calc_max_digits() {
+                CALC_INPUT[CALC_INPUT_LEN] = btn.to_ascii_uppercase();
+                CALC_INPUT_LEN += 1;
+                CALC_INPUT[CALC_INPUT_LEN] = 0;
+            }
+            calc_set_display(calc_input_str());
+        } else if btn == b'.' {
+            if CALC_BASE != 10 { return; }
             if CALC_NEW_INPUT {
                 CALC_INPUT_LEN = 0;
                 CALC_NEW_INPUT = false;
             }
-            if CALC_INPUT_LEN < 18 {
-                CALC_INPUT[CALC_INPUT_LEN] = btn;
+            let has_dot = CALC_INPUT[..CALC_INPUT_LEN].contains(&b'.');
+            if !has_dot && CALC_INPUT_LEN < 17 {
+                if CALC_INPUT_LEN == 0 {
+                    CALC_INPUT[0] = b'0';
+                    CALC_INPUT_LEN = 1;
+                }
+                CALC_INPUT[CALC_INPUT_LEN] = b'.';
                 CALC_INPUT_LEN += 1;
                 CALC_INPUT[CALC_INPUT_LEN] = 0;
             }
@@ -948,18 +2903,32 @@ fn calc_handle_button(btn: u8) {
             }
             CALC_OP = btn;
             CALC_NEW_INPUT = true;
+        } else if btn == b's' || btn == b'q' || btn == b'r' {
+            calc_apply_unary(btn);
+        } else if btn == b'M' {
+            CALC_SCI = !CALC_SCI;
+        } else if btn == b'#' {
+            // Re-show the current entry in the next base
+            let x = calc_input_to_i64();
+            CALC_BASE = match CALC_BASE { 10 => 16, 16 => 2, _ => 10 };
+            let f = calc_format(x);
+            let fs = f.as_str();
+            CALC_INPUT_LEN = fs.len().min(CALC_INPUT.len() - 1);
+            CALC_INPUT[..CALC_INPUT_LEN].copy_from_slice(&fs.as_bytes()[..CALC_INPUT_LEN]);
+            CALC_NEW_INPUT = true;
+            calc_set_display(fs);
         } else if btn == b'=' {
             CALC_OPERAND = calc_input_to_i64();
             if CALC_OP != 0 { calc_execute_pending(); }
             else { CALC_VALUE = CALC_OPERAND; }
             CALC_OP = 0;
             CALC_NEW_INPUT = true;
-            let mut b = FmtBuf::new();
-            let _ = write!(b, "{}", CALC_VALUE);
-            calc_set_display(b.as_str());
+            if calc_display_str() != "Error: /0" {
+                calc_set_display(calc_format(CALC_VALUE).as_str());
+            }
             // Copy display to input
             let ds = calc_display_str();
-            CALC_INPUT_LEN = ds.len().min(19);
+            CALC_INPUT_LEN = ds.len().min(CALC_INPUT.len() - 1);
             CALC_INPUT[..CALC_INPUT_LEN].copy_from_slice(&ds.as_bytes()[..CALC_INPUT_LEN]);
         }
     }
@@ -984,7 +2953,7 @@ fn calc_paint(win: &mut Window) {
     let text_y = disp_y + (disp_h - FONT_HEIGHT as i16) / 2;
     canvas_text(&mut win.canvas, cw, ch, text_x, text_y, ds, 0xFFFFCC00, 0xFF0A0A1A);
 
-    // Operator indicator
+    // Operator and base indicators
     unsafe {
         if CALC_OP != 0 {
             let op_buf = [CALC_OP];
@@ -992,24 +2961,30 @@ fn calc_paint(win: &mut Window) {
             canvas_text(&mut win.canvas, cw, ch, disp_x + 6, text_y, op_str, 0xFF808080, 0xFF0A0A1A);
         }
     }
+    canvas_text(&mut win.canvas, cw, ch, disp_x + 6 + 2 * FONT_WIDTH as i16, text_y,
+                calc_base_name(), 0xFF5588CC, 0xFF0A0A1A);
 
     // Button grid
     let mut grid_y = disp_y + disp_h + CALC_BTN_PAD + 4;
     for r in 0..CALC_ROWS {
         let mut bx: i16 = 8;
-        for c in 0..CALC_COLS {
-            let ch_btn = CALC_BUTTONS[r][c];
-            if ch_btn == b' ' { bx += CALC_BTN_W + CALC_BTN_PAD; continue; }
-
-            let bw = if r == 4 && c == 0 { CALC_BTN_W * 3 + CALC_BTN_PAD * 2 } else { CALC_BTN_W };
-
-            let (btn_bg, btn_fg) = if ch_btn >= b'0' && ch_btn <= b'9' {
+        let mut c = 0;
+        while c < CALC_COLS {
+            let ch_btn = calc_button_at(r, c);
+            let mut span = 1;
+            while c + span < CALC_COLS && calc_button_at(r, c + span) == ch_btn { span += 1; }
+            let bw = CALC_BTN_W * span as i16 + CALC_BTN_PAD * (span as i16 - 1);
+            let is_digit = (ch_btn >= b'0' && ch_btn <= b'9') || (ch_btn >= b'a' && ch_btn <= b'f');
+
+            let (btn_bg, btn_fg) = if is_digit && calc_digit_value(ch_btn).is_none() {
+                (0xFF1E1E30, 0xFF555555u32) // not a digit in this base
+            } else if is_digit {
                 (0xFF2A2A4A, 0xFFFFFFFFu32)
             } else if ch_btn == b'C' {
                 (0xFF663333, 0xFFFFAAAAu32)
             } else if ch_btn == b'=' {
                 (0xFF1A4444, 0xFF00DDAAu32)
-            } else if ch_btn == b'<' {
+            } else if ch_btn == b'<' || ch_btn == b'M' || ch_btn == b'#' {
                 (0xFF333355, 0xFFCCCCCCu32)
             } else {
                 (0xFF333355, 0xFFFFCC00u32)
@@ -1019,12 +2994,16 @@ fn calc_paint(win: &mut Window) {
             canvas_rect(&mut win.canvas, cw, ch, bx, grid_y, bw, CALC_BTN_H, 0xFF555577);
 
             let btn_buf = [ch_btn];
-            let btn_str = core::str::from_utf8(&btn_buf).unwrap_or("");
-            let tx = bx + (bw - FONT_WIDTH as i16) / 2;
+            let btn_str = match calc_button_label(ch_btn) {
+                "" => core::str::from_utf8(&btn_buf).unwrap_or(""),
+                label => label,
+            };
+            let tx = bx + (bw - btn_str.len() as i16 * FONT_WIDTH as i16) / 2;
             let ty = grid_y + (CALC_BTN_H - FONT_HEIGHT as i16) / 2;
             canvas_text(&mut win.canvas, cw, ch, tx, ty, btn_str, btn_fg, btn_bg);
 
             bx += bw + CALC_BTN_PAD;
+            c += span;
         }
         grid_y += CALC_BTN_H + CALC_BTN_PAD;
     }
@@ -1035,11 +3014,17 @@ fn calc_event(win: &mut Window, ev: &mut GuiEvent) {
 
     if ev.etype == EventType::KeyDown {
         let key = ev.key;
-        if key >= b'0' && key <= b'9' { calc_handle_button(key); }
+        let hex = unsafe { CALC_BASE == 16 };
+        if (key >= b'0' && key <= b'9') || key == b'.' { calc_handle_button(key); }
+        // In HEX, A-F are digits (so C is too; Esc still clears)
+        else if hex && key.to_ascii_lowercase() >= b'a' && key.to_ascii_lowercase() <= b'f' {
+            calc_handle_button(key.to_ascii_lowercase());
+        }
         else if key == b'+' || key == b'-' || key == b'*' || key == b'/' { calc_handle_button(key); }
         else if key == b'\n' || key == b'=' { calc_handle_button(b'='); }
         else if key == 0x08 { calc_handle_button(b'<'); }
-        else if key == b'c' || key == b'C' { calc_handle_button(b'C'); }
+        else if key == b'c' || key == b'C' || key == 0x1B { calc_handle_button(b'C'); }
+        else if key == b's' || key == b'q' || key == b'r' { calc_handle_button(key); }
     }
 
     if ev.etype == EventType::MouseDown {
@@ -1047,8 +3032,7 @@ fn calc_event(win: &mut Window, ev: &mut GuiEvent) {
         let row = (ev.mouse_y - disp_h_total) / (CALC_BTN_H + CALC_BTN_PAD);
         let col = (ev.mouse_x - 8) / (CALC_BTN_W + CALC_BTN_PAD);
         if row >= 0 && (row as usize) < CALC_ROWS && col >= 0 && (col as usize) < CALC_COLS {
-            let btn = CALC_BUTTONS[row as usize][col as usize];
-            if btn != b' ' { calc_handle_button(btn); }
+            calc_handle_button(calc_button_at(row as usize, col as usize));
         }
     }
 }
@@ -1061,8 +3045,10 @@ fn open_calculator() {
         CALC_OP = 0;
         CALC_NEW_INPUT = true;
         CALC_INPUT_LEN = 0;
+        CALC_SCI = false;
+        CALC_BASE = 10;
     }
-    wm_create_window("Calculator", 200, 80, 260, 340, Some(calc_event), Some(calc_paint));
+    wm_create_window("Calculator", 200, 80, 260, 420, Some(calc_event), Some(calc_paint));
 }
 
 // ===========================================================================
@@ -1073,16 +3059,40 @@ const OI_REFRESH_BTN: usize = 0;
 const OI_FILTER_BOX: usize = 1;
 const OI_FILTER_BTN: usize = 2;
 const OI_OBJ_LIST: usize = 3;
+const OI_EDIT_BTN: usize = 4;
+const OI_EDIT_BOX: usize = 5;
+const OI_SAVE_BTN: usize = 6;
+const OI_FIND_BOX: usize = 7;
+const OI_FIND_BTN: usize = 8;
+const OI_NEXT_BTN: usize = 9;
+const OI_COMPARE_BTN: usize = 10;
+const OI_FIND_ROW_H: i16 = 28;
+const OI_MATCH_BG: u32 = 0xFF665500;
+const OI_DIFF_FG: u32 = 0xFFFF5555;
+const OI_ONLY_BG: u32 = 0xFF442222; // offset present in just one of the two objects
 
 static mut OI_OBJ_NAME: [u8; 64] = [0; 64];
 static mut OI_OBJ_TYPE: [u8; 64] = [0; 64];
-static mut OI_OBJ_DATA: [u8; 256] = [0; 256];
+// Raw bytes of the data column (a string or a blob, which may contain NULs)
+static mut OI_OBJ_DATA: [u8; MAX_BLOB_LEN] = [0; MAX_BLOB_LEN];
+static mut OI_OBJ_DATA_LEN: usize = 0;
 static mut OI_OBJ_ID: u64 = 0;
 static mut OI_OBJ_OWNER: u64 = 0;
 static mut OI_OBJ_CREATED: u64 = 0;
 static mut OI_HEX_SCROLL: i32 = 0;
+// Hex search: pattern bytes and the match currently highlighted
+static mut OI_FIND_PAT: [u8; 64] = [0; 64];
+static mut OI_FIND_LEN: usize = 0;
+static mut OI_MATCH_POS: Option<usize> = None;
+static mut OI_LIST_CLICKED: bool = false;
 static mut OI_HAS_SELECTION: bool = false;
 
+// Compare mode: a second object diffed byte-by-byte against the loaded one
+static mut OI_COMPARING: bool = false;
+static mut OI_CMP_NAME: [u8; 64] = [0; 64];
+static mut OI_CMP_DATA: [u8; MAX_BLOB_LEN] = [0; MAX_BLOB_LEN];
+static mut OI_CMP_DATA_LEN: usize = 0;
+
 fn oi_str(buf: &[u8]) -> &str {
     let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
     core::str::from_utf8(&buf[..len]).unwrap_or("")
@@ -1106,7 +3116,7 @@ fn oi_refresh() {
 
 fn oi_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    unsafe { OI_WIDGETS.draw_all(win); }
+    unsafe { widgets_draw(&OI_WIDGETS, win); }
 
     unsafe {
         if !OI_HAS_SELECTION { return; }
@@ -1140,11 +3150,8 @@ fn oi_paint(win: &mut Window) {
         canvas_text(&mut win.canvas, cw, ch, px, py, b.as_str(), 0xFF00DDAA, CLIENT_BG);
         py += FONT_HEIGHT as i16 + 2;
 
-        let secs = OI_OBJ_CREATED / 1000;
-        let mins = secs / 60;
-        let s = secs % 60;
         b = FmtBuf::new();
-        let _ = write!(b, "Created: {:02}:{:02}", mins, s);
+        let _ = write!(b, "Created: {}", fmt_datetime(OI_OBJ_CREATED).as_str());
         canvas_text(&mut win.canvas, cw, ch, px, py, b.as_str(), 0xFF00DDAA, CLIENT_BG);
         py += FONT_HEIGHT as i16 + 6;
 
@@ -1154,14 +3161,32 @@ fn oi_paint(win: &mut Window) {
 
         // Hex dump
         canvas_text(&mut win.canvas, cw, ch, px, py, "HEX DUMP", 0xFFFFCC00, CLIENT_BG);
+        let mut lx = px + 10 * FONT_WIDTH as i16;
+        if OI_COMPARING {
+            b = FmtBuf::new();
+            let _ = write!(b, "vs {}: {} differ", oi_str(&OI_CMP_NAME), oi_diff_count());
+            canvas_text(&mut win.canvas, cw, ch, lx, py, b.as_str(), OI_DIFF_FG, CLIENT_BG);
+            lx += (b.as_str().len() as i16 + 2) * FONT_WIDTH as i16;
+        }
+        if let Some(m) = OI_MATCH_POS {
+            b = FmtBuf::new();
+            let _ = write!(b, "match at {:04X}", m);
+            canvas_text(&mut win.canvas, cw, ch, lx, py, b.as_str(), 0xFFCCCCCC, CLIENT_BG);
+        }
         py += 22;
 
-        let data = oi_str(&OI_OBJ_DATA);
-        let data_bytes = data.as_bytes();
-        let data_len = data_bytes.len();
+        let data_len = OI_OBJ_DATA_LEN;
+        let data_bytes = &OI_OBJ_DATA[..data_len];
+        let cmp_len = if OI_COMPARING { OI_CMP_DATA_LEN } else { 0 };
+        let cmp_bytes = &OI_CMP_DATA[..cmp_len];
+        let dump_len = data_len.max(cmp_len);
         let bytes_per_row = 8;
-        let total_rows = if data_len == 0 { 1 } else { (data_len + bytes_per_row - 1) / bytes_per_row };
-        let visible_rows = ((ch as i16 - py - 4) / FONT_HEIGHT as i16) as i32;
+        let total_rows = if dump_len == 0 { 1 } else { (dump_len + bytes_per_row - 1) / bytes_per_row };
+        let visible_rows = ((ch as i16 - OI_FIND_ROW_H - py - 4) / FONT_HEIGHT as i16) as i32;
+        let in_match = |i: usize| match OI_MATCH_POS {
+            Some(m) => i >= m && i < m + OI_FIND_LEN,
+            None => false,
+        };
 
         for r in 0..visible_rows {
             let dr = r + OI_HEX_SCROLL;
@@ -1177,10 +3202,26 @@ fn oi_paint(win: &mut Window) {
             // Hex bytes
             let mut hx = px + 5 * FONT_WIDTH as i16 + 4;
             for byte in 0..bytes_per_row {
-                if offset + byte < data_len {
+                let i = offset + byte;
+                if i < data_len {
                     b = FmtBuf::new();
-                    let _ = write!(b, "{:02X} ", data_bytes[offset + byte]);
-                    canvas_text(&mut win.canvas, cw, ch, hx, ry, b.as_str(), 0xFF00DDAA, CLIENT_BG);
+                    let _ = write!(b, "{:02X}", data_bytes[i]);
+                    let fg = if i < cmp_len && cmp_bytes[i] != data_bytes[i] {
+                        OI_DIFF_FG
+                    } else {
+                        0xFF00DDAA
+                    };
+                    let bg = if in_match(i) {
+                        OI_MATCH_BG
+                    } else if OI_COMPARING && i >= cmp_len {
+                        OI_ONLY_BG
+                    } else {
+                        CLIENT_BG
+                    };
+                    canvas_text(&mut win.canvas, cw, ch, hx, ry, b.as_str(), fg, bg);
+                } else if i < cmp_len {
+                    // Only the comparison object reaches this far
+                    canvas_text(&mut win.canvas, cw, ch, hx, ry, "--", OI_DIFF_FG, OI_ONLY_BG);
                 }
                 hx += 3 * FONT_WIDTH as i16;
                 if byte == 3 { hx += FONT_WIDTH as i16; }
@@ -1194,108 +3235,342 @@ fn oi_paint(win: &mut Window) {
                 if offset + byte >= data_len { break; }
                 let c = data_bytes[offset + byte];
                 let ch_byte = if c >= 0x20 && c < 0x7F { c } else { b'.' };
-                let fg = if c >= 0x20 && c < 0x7F { 0xFFCCCCCC } else { 0xFF555555 };
+                let differs = offset + byte < cmp_len && cmp_bytes[offset + byte] != c;
+                let fg = if differs {
+                    OI_DIFF_FG
+                } else if c >= 0x20 && c < 0x7F {
+                    0xFFCCCCCC
+                } else {
+                    0xFF555555
+                };
                 let asc_buf = [ch_byte];
                 let asc_str = core::str::from_utf8(&asc_buf).unwrap_or(".");
-                canvas_text(&mut win.canvas, cw, ch, acx, ry, asc_str, fg, CLIENT_BG);
+                let bg = if in_match(offset + byte) { OI_MATCH_BG } else { CLIENT_BG };
+                canvas_text(&mut win.canvas, cw, ch, acx, ry, asc_str, fg, bg);
                 acx += FONT_WIDTH as i16;
             }
             canvas_text(&mut win.canvas, cw, ch, acx, ry, "|", 0xFF555577, CLIENT_BG);
         }
 
-        if data_len == 0 {
+        if dump_len == 0 {
             canvas_text(&mut win.canvas, cw, ch, px, py, "(empty)", 0xFF666666, CLIENT_BG);
         }
     }
 }
 
+const OI_WHEEL_LINES: i32 = 3;
+
+/// Offsets where the two objects differ, counting bytes only one of them has.
+fn oi_diff_count() -> usize {
+    unsafe {
+        let a = &OI_OBJ_DATA[..OI_OBJ_DATA_LEN];
+        let b = &OI_CMP_DATA[..OI_CMP_DATA_LEN];
+        let common = a.iter().zip(b.iter()).filter(|(x, y)| x != y).count();
+        common + a.len().max(b.len()) - a.len().min(b.len())
+    }
+}
+
+/// Parse the search box into OI_FIND_PAT: `0x` then hex bytes (spaces
+/// allowed, e.g. "0x DE AD BE EF"), otherwise the text itself. False if the
+/// pattern is empty or the hex is malformed.
+fn oi_parse_pattern(text: &str) -> bool {
+    unsafe {
+        OI_FIND_LEN = 0;
+        let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+        match hex {
+            Some(digits) => {
+                let mut hi: Option<u8> = None;
+                for c in digits.bytes() {
+                    if c == b' ' { continue; }
+                    let v = match c {
+                        b'0'..=b'9' => c - b'0',
+                        b'a'..=b'f' => c - b'a' + 10,
+                        b'A'..=b'F' => c - b'A' + 10,
+                        _ => return false,
+                    };
+                    match hi.take() {
+                        None => hi = Some(v),
+                        Some(h) => {
+                            if OI_FIND_LEN >= OI_FIND_PAT.len() { return false; }
+                            OI_FIND_PAT[OI_FIND_LEN] = (h << 4) | v;
+                            OI_FIND_LEN += 1;
+                        }
+                    }
+                }
+                if hi.is_some() { OI_FIND_LEN = 0; }
+            }
+            None => {
+                let len = text.len().min(OI_FIND_PAT.len());
+                OI_FIND_PAT[..len].copy_from_slice(&text.as_bytes()[..len]);
+                OI_FIND_LEN = len;
+            }
+        }
+        OI_FIND_LEN > 0
+    }
+}
+
+/// Highlight the first match at or after `from`, wrapping to the start,
+/// and scroll the hex view to it.
+fn oi_find_from(from: usize) {
+    unsafe {
+        let data = &OI_OBJ_DATA[..OI_OBJ_DATA_LEN];
+        let pat = &OI_FIND_PAT[..OI_FIND_LEN];
+        let found = if pat.is_empty() || pat.len() > data.len() {
+            None
+        } else {
+            let last = data.len() - pat.len();
+            let start = from.min(last + 1);
+            (start..=last).chain(0..start).find(|&i| &data[i..i + pat.len()] == pat)
+        };
+        OI_MATCH_POS = found;
+        match found {
+            Some(pos) => OI_HEX_SCROLL = (pos / 8) as i32,
+            None => notify("No match", TOAST_MS),
+        }
+    }
+}
+
+fn oi_find(next: bool) {
+    unsafe {
+        if !OI_HAS_SELECTION { return; }
+        let text = match OI_WIDGETS.widgets[OI_FIND_BOX] {
+            Some(ref tb) => widget_get_text(tb),
+            None => return,
+        };
+        if !oi_parse_pattern(text) {
+            OI_MATCH_POS = None;
+            notify("Search: text, or 0x and hex bytes", TOAST_MS);
+            return;
+        }
+        let from = match OI_MATCH_POS {
+            Some(m) if next => m + 1,
+            _ => 0,
+        };
+        oi_find_from(from);
+    }
+}
+
+/// Move the hex view by `lines` rows; negative scrolls up, stopping at the top.
+fn oi_scroll(lines: i32) {
+    unsafe {
+        OI_HEX_SCROLL += lines;
+        if OI_HEX_SCROLL < 0 { OI_HEX_SCROLL = 0; }
+    }
+}
+
 fn oi_event(win: &mut Window, ev: &mut GuiEvent) {
     if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
 
     unsafe {
         if ev.etype == EventType::KeyDown {
             match ev.key {
-                keyboard::KEY_DOWN | keyboard::KEY_PGDN => {
-                    OI_HEX_SCROLL += if ev.key == keyboard::KEY_PGDN { 10 } else { 1 };
-                    return;
-                }
-                keyboard::KEY_UP | keyboard::KEY_PGUP => {
-                    OI_HEX_SCROLL -= if ev.key == keyboard::KEY_PGUP { 10 } else { 1 };
-                    if OI_HEX_SCROLL < 0 { OI_HEX_SCROLL = 0; }
-                    return;
-                }
+                keyboard::KEY_DOWN => { oi_scroll(1); return; }
+                keyboard::KEY_PGDN => { oi_scroll(10); return; }
+                keyboard::KEY_UP => { oi_scroll(-1); return; }
+                keyboard::KEY_PGUP => { oi_scroll(-10); return; }
                 _ => {}
             }
         }
+        if ev.etype == EventType::MouseWheel {
+            // Positive deltas are the wheel turning toward the user
+            oi_scroll(ev.wheel_delta as i32 * OI_WHEEL_LINES);
+            return;
+        }
+        // A click selects an object; a double-click on the list opens it
+        if ev.etype == EventType::DoubleClick {
+            if OI_LIST_CLICKED { oi_open_selected(); }
+            return;
+        }
+        if ev.etype == EventType::MouseDown { OI_LIST_CLICKED = false; }
 
-        let action = OI_WIDGETS.dispatch(ev);
+        if clipboard_key(&mut OI_WIDGETS, ev) { return; }
+        let action = widgets_dispatch(&mut OI_WIDGETS, ev);
         match action {
             WidgetAction::Clicked(idx) if idx == OI_REFRESH_BTN || idx == OI_FILTER_BTN => {
                 oi_refresh();
             }
-            WidgetAction::Selected(idx, sel) if idx == OI_OBJ_LIST => {
-                // Parse name from "[type] name" and load
-                if let Some(ref lv) = OI_WIDGETS.widgets[OI_OBJ_LIST] {
-                    let item_str = {
-                        let buf = &lv.lv_items[sel as usize];
-                        let len = buf.iter().position(|&b| b == 0).unwrap_or(LISTVIEW_ITEM_MAX);
-                        core::str::from_utf8(&buf[..len]).unwrap_or("")
-                    };
-                    if let Some(bracket_end) = item_str.find(']') {
-                        let name = &item_str[bracket_end + 2..]; // skip "] "
-                        let mut sql = FmtBuf::new();
-                        let _ = write!(sql, "SELECT * FROM ObjectTable WHERE name = '{}'", name);
-                        let result = query_execute(sql.as_str(), 0);
-                        if !result.rows.is_empty() {
-                            let row = &result.rows[0];
-                            OI_OBJ_ID = match row.fields[0] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                            let name_fv = match row.fields[1] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
-                            let type_fv = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
-                            let data_fv = match row.fields[3] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
-                            OI_OBJ_OWNER = match row.fields[4] { Some(FieldValue::U64(v)) => v, _ => 0 };
-                            OI_OBJ_CREATED = match row.fields[6] { Some(FieldValue::U64(v)) => v, _ => 0 };
-
-                            OI_OBJ_NAME = [0; 64];
-                            let len = name_fv.len().min(63);
-                            OI_OBJ_NAME[..len].copy_from_slice(&name_fv.as_bytes()[..len]);
-
-                            OI_OBJ_TYPE = [0; 64];
-                            let len = type_fv.len().min(63);
-                            OI_OBJ_TYPE[..len].copy_from_slice(&type_fv.as_bytes()[..len]);
-
-                            OI_OBJ_DATA = [0; 256];
-                            let len = data_fv.len().min(255);
-                            OI_OBJ_DATA[..len].copy_from_slice(&data_fv.as_bytes()[..len]);
-
-                            OI_HEX_SCROLL = 0;
-                            OI_HAS_SELECTION = true;
-                        }
-                    }
-                }
+            WidgetAction::Selected(idx, _) if idx == OI_OBJ_LIST => {
+                OI_LIST_CLICKED = true;
             }
+            WidgetAction::Clicked(idx) if idx == OI_EDIT_BTN => oi_begin_edit(),
+            WidgetAction::Clicked(idx) if idx == OI_SAVE_BTN => oi_save_edit(),
+            WidgetAction::Clicked(idx) if idx == OI_FIND_BTN => oi_find(false),
+            WidgetAction::Clicked(idx) if idx == OI_NEXT_BTN => oi_find(true),
+            WidgetAction::Clicked(idx) if idx == OI_COMPARE_BTN => oi_toggle_compare(),
             _ => {}
         }
     }
 }
 
+/// Name of the object selected in the list, whose items read "[type] name".
+fn oi_selected_name() -> Option<&'static str> {
+    unsafe {
+        let lv = match OI_WIDGETS.widgets[OI_OBJ_LIST] {
+            Some(ref lv) => lv,
+            None => return None,
+        };
+        let sel = lv.lv_selected;
+        if sel < 0 { return None; }
+        let item_str = {
+            let buf = &lv.lv_items[sel as usize];
+            let len = buf.iter().position(|&b| b == 0).unwrap_or(LISTVIEW_ITEM_MAX);
+            core::str::from_utf8(&buf[..len]).unwrap_or("")
+        };
+        item_str.find(']').map(|bracket_end| &item_str[bracket_end + 2..]) // skip "] "
+    }
+}
+
+/// Load the object selected in the list.
+fn oi_open_selected() {
+    if let Some(name) = oi_selected_name() {
+        oi_load_object(name);
+    }
+}
+
+/// Compare: diff the loaded object against the one selected in the list.
+/// Pressed again, it leaves compare mode.
+fn oi_toggle_compare() {
+    unsafe {
+        if OI_COMPARING {
+            OI_COMPARING = false;
+            return;
+        }
+        if !OI_HAS_SELECTION {
+            notify("Open an object first", TOAST_MS);
+            return;
+        }
+        let name = match oi_selected_name() {
+            Some(n) => n,
+            None => { notify("Select an object to compare with", TOAST_MS); return; }
+        };
+        let result = query_execute_with(
+            "SELECT * FROM ObjectTable WHERE name = ?", &[str_param(name)], 0);
+        if result.rows.is_empty() { return; }
+
+        let data_fv: &[u8] = match result.rows[0].fields[3] {
+            Some(FieldValue::Str(ref s)) => s.as_str().as_bytes(),
+            Some(FieldValue::Blob(ref b)) => b.as_bytes(),
+            _ => &[],
+        };
+        OI_CMP_NAME = [0; 64];
+        let len = name.len().min(63);
+        OI_CMP_NAME[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        OI_CMP_DATA = [0; MAX_BLOB_LEN];
+        let len = data_fv.len().min(MAX_BLOB_LEN);
+        OI_CMP_DATA[..len].copy_from_slice(&data_fv[..len]);
+        OI_CMP_DATA_LEN = len;
+        OI_COMPARING = true;
+    }
+}
+
+/// Load an object's metadata and data into the inspector by name.
+fn oi_load_object(name: &str) {
+    let result = query_execute_with(
+        "SELECT * FROM ObjectTable WHERE name = ?", &[str_param(name)], 0);
+    if result.rows.is_empty() { return; }
+
+    unsafe {
+        let row = &result.rows[0];
+        OI_OBJ_ID = field_u64(&row.fields[0]);
+        let name_fv = match row.fields[1] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
+        let type_fv = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
+        let data_fv: &[u8] = match row.fields[3] {
+            Some(FieldValue::Str(ref s)) => s.as_str().as_bytes(),
+            Some(FieldValue::Blob(ref b)) => b.as_bytes(),
+            _ => &[],
+        };
+        OI_OBJ_OWNER = field_u64(&row.fields[4]);
+        OI_OBJ_CREATED = field_u64(&row.fields[6]);
+
+        OI_OBJ_NAME = [0; 64];
+        let len = name_fv.len().min(63);
+        OI_OBJ_NAME[..len].copy_from_slice(&name_fv.as_bytes()[..len]);
+
+        OI_OBJ_TYPE = [0; 64];
+        let len = type_fv.len().min(63);
+        OI_OBJ_TYPE[..len].copy_from_slice(&type_fv.as_bytes()[..len]);
+
+        OI_OBJ_DATA = [0; MAX_BLOB_LEN];
+        let len = data_fv.len().min(MAX_BLOB_LEN);
+        OI_OBJ_DATA[..len].copy_from_slice(&data_fv[..len]);
+        OI_OBJ_DATA_LEN = len;
+
+        OI_HEX_SCROLL = 0;
+        OI_MATCH_POS = None;
+        OI_HAS_SELECTION = true;
+    }
+}
+
+/// Edit: copy the selected object's data into the edit box.
+fn oi_begin_edit() {
+    unsafe {
+        if !OI_HAS_SELECTION { return; }
+        // Binary blobs cannot round-trip through a text box
+        let text = core::str::from_utf8(&OI_OBJ_DATA[..OI_OBJ_DATA_LEN]).unwrap_or("");
+        if let Some(ref mut tb) = OI_WIDGETS.widgets[OI_EDIT_BOX] {
+            widget_set_text(tb, text);
+            tb.focused = true;
+        }
+    }
+}
+
+/// Save: write the edit box back as the object's data.
+fn oi_save_edit() {
+    unsafe {
+        if !OI_HAS_SELECTION { return; }
+        let text = match OI_WIDGETS.widgets[OI_EDIT_BOX] {
+            Some(ref tb) => widget_get_text(tb),
+            None => return,
+        };
+
+        let mut end = text.len().min(MAX_STR_LEN);
+        while !text.is_char_boundary(end) { end -= 1; }
+        let data = &text[..end];
+
+        // UPDATE recomputes size from the new data
+        let res = query_execute_with("UPDATE ObjectTable SET data = ? WHERE name = ?",
+            &[str_param(data), str_param(oi_str(&OI_OBJ_NAME))], 0);
+        if res.error_code == VOS_OK {
+            notify("Object saved", TOAST_MS);
+        }
+
+        let mut name = [0u8; 64];
+        name.copy_from_slice(&OI_OBJ_NAME);
+        oi_refresh();
+        oi_load_object(oi_str(&name));
+    }
+}
+
 fn open_object_inspector() {
     unsafe {
         OI_WIDGETS.clear();
         OI_HAS_SELECTION = false;
         OI_HEX_SCROLL = 0;
+        OI_MATCH_POS = None;
+        OI_COMPARING = false;
     }
     let id = match wm_create_window("Object Inspector", 80, 50, 620, 420, Some(oi_event), Some(oi_paint)) {
         Some(v) => v,
         None => return,
     };
+    let cw;
     let ch;
-    if let Some(win) = wm_get_window(id) { ch = win.client_h; } else { return; }
+    if let Some(win) = wm_get_window(id) { cw = win.client_w; ch = win.client_h; } else { return; }
 
     unsafe {
         OI_WIDGETS.add_button(4, 2, 72, 22, "Refresh");
         OI_WIDGETS.add_textbox(80, 2, 180, 22);
         OI_WIDGETS.add_button(264, 2, 60, 22, "Filter");
         OI_WIDGETS.add_listview(4, 28, 152, ch as i16 - 34);
+        OI_WIDGETS.add_button(328, 2, 48, 22, "Edit");
+        OI_WIDGETS.add_textbox(380, 2, cw as i16 - 444, 22);
+        OI_WIDGETS.add_button(cw as i16 - 60, 2, 56, 22, "Save");
+        let fy = ch as i16 - OI_FIND_ROW_H + 2;
+        OI_WIDGETS.add_textbox(168, fy, cw as i16 - 168 - 204, 22);
+        OI_WIDGETS.add_button(cw as i16 - 124, fy, 58, 22, "Find");
+        OI_WIDGETS.add_button(cw as i16 - 62, fy, 58, 22, "Next");
+        OI_WIDGETS.add_button(cw as i16 - 200, fy, 72, 22, "Compare");
     }
     oi_refresh();
 }
@@ -1323,7 +3598,8 @@ fn draw_taskbar() {
     for &id in z {
         if let Some(win) = wm_get_window(id) {
             let bg = if win.focused { 0xFF2A4466 } else { 0xFF1A1A3A };
-            let border = if win.focused { 0xFF5588CC } else { 0xFF444466 };
+            let border = if id == switcher_candidate() { 0xFFFFCC00 }
+                else if win.focused { 0xFF5588CC } else { 0xFF444466 };
             let text_color = if win.minimized { 0xFF666666 } else { 0xFFCCCCCC };
             gfx_fill_rect(bx, ty + 2, 120, TASKBAR_HEIGHT - 4, bg);
             gfx_draw_rect(bx, ty + 2, 120, TASKBAR_HEIGHT - 4, border);
@@ -1335,11 +3611,17 @@ fn draw_taskbar() {
         }
     }
 
-    // Right side: status
+    // Right side: wall clock, then uptime/heap status
     let ms = pit::pit_get_uptime_ms();
     let mut status = FmtBuf::new();
-    let _ = write!(status, "{}.{}s  {}KB", ms / 1000, (ms % 1000) / 100, heap::heap_used() / 1024);
-    let sx = sw as i16 - (status.pos as i16) * FONT_WIDTH as i16 - 8;
+    let _ = write!(status, "{}  {}", fmt_time(ms).as_str(), fmt_bytes(heap::heap_used() as u64).as_str());
+    let (h, m, s, _, _, _) = rtc::rtc_read();
+    let mut clock = FmtBuf::new();
+    let _ = write!(clock, "{:02}:{:02}:{:02}", h, m, s);
+    let cx = sw as i16 - (clock.pos as i16) * FONT_WIDTH as i16 - 8;
+    gfx_draw_text(cx, ty + 6, clock.as_str(), 0xFFCCCCCC, TASKBAR_BG);
+
+    let sx = cx - (status.pos as i16) * FONT_WIDTH as i16 - 12;
     gfx_draw_text(sx, ty + 6, status.as_str(), 0xFF808080, TASKBAR_BG);
 
     // Security badges
@@ -1392,6 +3674,328 @@ fn menu_hit_test(mx: i16, my: i16) -> i32 {
     if idx < MENU_ITEMS && !menu_is_separator(idx) { idx as i32 } else { -1 }
 }
 
+// ===========================================================================
+// ---- Notifications (toasts) ----
+// ===========================================================================
+// Banners stacked in the bottom-right corner, drawn after comp_render so they
+// float above every window. The compositor repaints the whole screen each
+// frame, so an expired toast simply stops being drawn.
+const TOAST_MAX: usize = 4;
+const TOAST_TEXT_MAX: usize = 40;
+const TOAST_W: u16 = (TOAST_TEXT_MAX as u16) * FONT_WIDTH as u16 + 16;
+const TOAST_H: u16 = 24;
+const TOAST_GAP: u16 = 4;
+const TOAST_BG: u32 = 0xFF1A2A1A;
+const TOAST_BORDER: u32 = 0xFF00CC66;
+const TOAST_MS: u64 = 2500;
+
+#[derive(Clone, Copy)]
+struct Toast {
+    text: [u8; TOAST_TEXT_MAX],
+    len: usize,
+    expires_ms: u64,
+}
+
+/// Oldest first; TOAST_COUNT entries are live.
+static mut TOASTS: [Toast; TOAST_MAX] =
+    [Toast { text: [0; TOAST_TEXT_MAX], len: 0, expires_ms: 0 }; TOAST_MAX];
+static mut TOAST_COUNT: usize = 0;
+
+fn toast_expire(now: u64) {
+    unsafe {
+        let mut kept = 0;
+        for i in 0..TOAST_COUNT {
+            if TOASTS[i].expires_ms > now {
+                TOASTS[kept] = TOASTS[i];
+                kept += 1;
+            }
+        }
+        TOAST_COUNT = kept;
+    }
+}
+
+/// Show `text` for `ms` milliseconds. When the stack is full the oldest goes.
+fn notify(text: &str, ms: u64) {
+    let now = pit::pit_get_uptime_ms();
+    toast_expire(now);
+    unsafe {
+        if TOAST_COUNT == TOAST_MAX {
+            TOASTS.copy_within(1.., 0);
+            TOAST_COUNT -= 1;
+        }
+        let t = &mut TOASTS[TOAST_COUNT];
+        let len = text.len().min(TOAST_TEXT_MAX);
+        t.text[..len].copy_from_slice(&text.as_bytes()[..len]);
+        t.len = len;
+        t.expires_ms = now + ms;
+        TOAST_COUNT += 1;
+    }
+}
+
+fn draw_toasts() {
+    toast_expire(pit::pit_get_uptime_ms());
+    let count = unsafe { TOAST_COUNT };
+    if count == 0 { return; }
+
+    let x = gfx_width() as i16 - TOAST_W as i16 - 8;
+    let bottom = gfx_height() as i16 - TASKBAR_HEIGHT as i16 - 8;
+    // Newest sits lowest; older ones are pushed up
+    for i in 0..count {
+        let t = unsafe { &TOASTS[i] };
+        let y = bottom - ((count - i) as i16) * (TOAST_H + TOAST_GAP) as i16;
+        gfx_fill_rect(x, y, TOAST_W, TOAST_H, TOAST_BG);
+        gfx_draw_rect(x, y, TOAST_W, TOAST_H, TOAST_BORDER);
+        let text = core::str::from_utf8(&t.text[..t.len]).unwrap_or("");
+        gfx_draw_text(x + 8, y + 4, text, 0xFFFFFFFF, TOAST_BG);
+    }
+
+    let top = bottom - (count as i16) * (TOAST_H + TOAST_GAP) as i16;
+    gfx_flip_rect(x, top, TOAST_W, (bottom - top) as u16);
+}
+
+// ===========================================================================
+// ---- Window resize (right/bottom border grips) ----
+// ===========================================================================
+const RESIZE_GRIP: i16 = 4;
+const MIN_WIN_W: u16 = 160;
+const MIN_WIN_H: u16 = 100;
+
+static mut RESIZE_WIN: u32 = 0;
+static mut RESIZE_RIGHT: bool = false;
+static mut RESIZE_BOTTOM: bool = false;
+
+/// Topmost visible window whose right or bottom edge is under (mx, my).
+fn resize_hit_test(mx: i16, my: i16) -> Option<(u32, bool, bool)> {
+    let z = wm_get_z_order();
+    for &id in z.iter().rev() {
+        let win = match wm_get_window(id) { Some(w) => w, None => continue };
+        if !win.visible || win.minimized { continue; }
+        let right = win.x + win.width as i16;
+        let bottom = win.y + win.height as i16;
+        let inside = mx >= win.x && mx < right + RESIZE_GRIP && my >= win.y && my < bottom + RESIZE_GRIP;
+        if !inside { continue; }
+        let on_right = mx >= right - RESIZE_GRIP;
+        let on_bottom = my >= bottom - RESIZE_GRIP;
+        if on_right || on_bottom {
+            return Some((id, on_right, on_bottom));
+        }
+        // The point is over this window's body, so nothing below can be hit
+        return None;
+    }
+    None
+}
+
+/// Set a window's outer size and reallocate its client canvas.
+fn wm_resize_window(win: &mut Window, w: u16, h: u16) {
+    let w = w.max(MIN_WIN_W);
+    let h = h.max(MIN_WIN_H);
+    if w == win.width && h == win.height { return; }
+    win.width = w;
+    win.height = h;
+    win.client_w = w - 2 * BORDER_WIDTH as u16;
+    win.client_h = h - TITLEBAR_HEIGHT as u16 - 2 * BORDER_WIDTH as u16;
+    win.canvas = vec![CLIENT_BG; win.client_w as usize * win.client_h as usize];
+}
+
+/// Returns true if the event was consumed by a resize drag.
+fn resize_handle_event(ev: &GuiEvent) -> bool {
+    unsafe {
+        match ev.etype {
+            EventType::MouseDown => {
+                if let Some((id, right, bottom)) = resize_hit_test(ev.mouse_x, ev.mouse_y) {
+                    RESIZE_WIN = id;
+                    RESIZE_RIGHT = right;
+                    RESIZE_BOTTOM = bottom;
+                    wm_bring_to_front(id);
+                    return true;
+                }
+                false
+            }
+            EventType::MouseMove if RESIZE_WIN != 0 => {
+                if let Some(win) = wm_get_window_mut(RESIZE_WIN) {
+                    let mut w = win.width;
+                    let mut h = win.height;
+                    if RESIZE_RIGHT { w = (ev.mouse_x - win.x).max(0) as u16; }
+                    if RESIZE_BOTTOM { h = (ev.mouse_y - win.y).max(0) as u16; }
+                    wm_resize_window(win, w, h);
+                } else {
+                    RESIZE_WIN = 0;
+                }
+                true
+            }
+            EventType::MouseUp if RESIZE_WIN != 0 => {
+                RESIZE_WIN = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// ===========================================================================
+// ---- Double-click synthesis ----
+// ===========================================================================
+// A second MouseDown of the same button, close to the first and soon after
+// it, queues a DoubleClick behind it. Both MouseDowns are still delivered,
+// so single-click handlers see every click.
+const DOUBLE_CLICK_MS: u64 = 400;
+const DOUBLE_CLICK_RADIUS: i16 = 4;
+
+static mut LAST_CLICK_MS: u64 = 0;
+static mut LAST_CLICK_X: i16 = 0;
+static mut LAST_CLICK_Y: i16 = 0;
+static mut LAST_CLICK_BTN: u8 = 0;
+
+fn dblclick_track(ev: &GuiEvent) {
+    if ev.etype != EventType::MouseDown { return; }
+    let now = pit::pit_get_uptime_ms();
+    unsafe {
+        let near = (ev.mouse_x - LAST_CLICK_X).abs() <= DOUBLE_CLICK_RADIUS
+            && (ev.mouse_y - LAST_CLICK_Y).abs() <= DOUBLE_CLICK_RADIUS;
+        if LAST_CLICK_BTN == ev.mouse_button && near && now - LAST_CLICK_MS <= DOUBLE_CLICK_MS {
+            let mut dbl = GuiEvent::empty();
+            dbl.etype = EventType::DoubleClick;
+            dbl.mouse_x = ev.mouse_x;
+            dbl.mouse_y = ev.mouse_y;
+            dbl.mouse_button = ev.mouse_button;
+            dbl.mouse_buttons = ev.mouse_buttons;
+            event_push(&dbl);
+            // A third click starts a new pair rather than doubling again
+            LAST_CLICK_BTN = 0;
+            return;
+        }
+        LAST_CLICK_MS = now;
+        LAST_CLICK_X = ev.mouse_x;
+        LAST_CLICK_Y = ev.mouse_y;
+        LAST_CLICK_BTN = ev.mouse_button;
+    }
+}
+
+// ===========================================================================
+// ---- Window maximize (title bar double-click) ----
+// ===========================================================================
+const MAX_SAVED_RECTS: usize = 16;
+
+#[derive(Clone, Copy)]
+struct SavedRect { id: u32, x: i16, y: i16, w: u16, h: u16 }
+
+static mut SAVED_RECTS: [Option<SavedRect>; MAX_SAVED_RECTS] = [None; MAX_SAVED_RECTS];
+
+/// Topmost visible window whose title bar is under (mx, my).
+fn titlebar_hit_test(mx: i16, my: i16) -> Option<u32> {
+    let z = wm_get_z_order();
+    for &id in z.iter().rev() {
+        let win = match wm_get_window(id) { Some(w) => w, None => continue };
+        if !win.visible || win.minimized { continue; }
+        let inside = mx >= win.x && mx < win.x + win.width as i16
+            && my >= win.y && my < win.y + win.height as i16;
+        if !inside { continue; }
+        if my < win.y + TITLEBAR_HEIGHT as i16 { return Some(id); }
+        return None;
+    }
+    None
+}
+
+/// Toggle a window between its saved geometry and filling the screen above the taskbar.
+fn wm_toggle_maximize(id: u32) {
+    let max_w = gfx_width();
+    let max_h = gfx_height() - TASKBAR_HEIGHT;
+    let win = match wm_get_window_mut(id) { Some(w) => w, None => return };
+
+    unsafe {
+        let slot = SAVED_RECTS.iter().position(|r| matches!(r, Some(r) if r.id == id));
+        // A saved rect only counts if the window still has the maximized
+        // geometry; anything else is stale (moved, resized or a reused id).
+        let is_max = win.x == 0 && win.y == 0 && win.width == max_w && win.height == max_h;
+
+        if let (Some(i), true) = (slot, is_max) {
+            let r = SAVED_RECTS[i].take().unwrap();
+            win.x = r.x;
+            win.y = r.y;
+            wm_resize_window(win, r.w, r.h);
+            return;
+        }
+
+        let i = match slot.or_else(|| SAVED_RECTS.iter().position(|r| r.is_none())) {
+            Some(i) => i,
+            None => return,
+        };
+        SAVED_RECTS[i] = Some(SavedRect { id, x: win.x, y: win.y, w: win.width, h: win.height });
+        win.x = 0;
+        win.y = 0;
+        wm_resize_window(win, max_w, max_h);
+    }
+}
+
+/// Returns true if the event was a double-click on a title bar.
+fn maximize_handle_event(ev: &GuiEvent) -> bool {
+    if ev.etype != EventType::DoubleClick { return false; }
+    let id = match titlebar_hit_test(ev.mouse_x, ev.mouse_y) {
+        Some(id) => id,
+        None => return false,
+    };
+    wm_toggle_maximize(id);
+    wm_bring_to_front(id);
+    true
+}
+
+// ===========================================================================
+// ---- Window switcher (Ctrl+Tab) ----
+// ===========================================================================
+// The keyboard driver reports no key releases, so the switch commits when a
+// poll of the main loop finds Ctrl no longer held.
+static mut SWITCH_ORDER: [u32; MAX_WINDOWS] = [0; MAX_WINDOWS];
+static mut SWITCH_COUNT: usize = 0;
+static mut SWITCH_IDX: usize = 0;
+static mut SWITCH_ACTIVE: bool = false;
+
+/// Ctrl+Tab steps through the windows, most recently raised first.
+/// Returns true if the event was consumed.
+fn switcher_handle_event(ev: &GuiEvent) -> bool {
+    if ev.etype != EventType::KeyDown || ev.key != b'\t' || !keyboard::keyboard_ctrl_held() {
+        return false;
+    }
+    unsafe {
+        if !SWITCH_ACTIVE {
+            // Snapshot top-first so the cycle order is stable while Ctrl is held
+            SWITCH_COUNT = 0;
+            for &id in wm_get_z_order().iter().rev() {
+                if SWITCH_COUNT < MAX_WINDOWS && wm_get_window(id).is_some() {
+                    SWITCH_ORDER[SWITCH_COUNT] = id;
+                    SWITCH_COUNT += 1;
+                }
+            }
+            if SWITCH_COUNT < 2 { return true; }
+            SWITCH_ACTIVE = true;
+            SWITCH_IDX = 0;
+        }
+        SWITCH_IDX = (SWITCH_IDX + 1) % SWITCH_COUNT;
+    }
+    true
+}
+
+/// Window currently selected by the switcher, or 0 when it is not active.
+fn switcher_candidate() -> u32 {
+    unsafe { if SWITCH_ACTIVE { SWITCH_ORDER[SWITCH_IDX] } else { 0 } }
+}
+
+/// Once Ctrl is released, restore and raise the selected window.
+fn switcher_poll() {
+    unsafe {
+        if !SWITCH_ACTIVE || keyboard::keyboard_ctrl_held() { return; }
+        SWITCH_ACTIVE = false;
+        let id = SWITCH_ORDER[SWITCH_IDX];
+        // The window may have closed while the switcher was open
+        if let Some(win) = wm_get_window_mut(id) {
+            if win.minimized {
+                win.visible = true;
+                win.minimized = false;
+            }
+            wm_bring_to_front(id);
+        }
+    }
+}
+
 // ===========================================================================
 // ---- Main GUI Loop ----
 // ===========================================================================
@@ -1434,6 +4038,11 @@ pub fn gui_main() {
         // Process events
         let mut ev = GuiEvent::empty();
         while event_poll(&mut ev) {
+            // Input timing feeds the RNG reseed pool
+            entropy::entropy_add_event((ev.key as u64) << 32
+                | (ev.mouse_x as u16 as u64) << 16 | ev.mouse_y as u16 as u64);
+            dblclick_track(&ev);
+
             // Check taskbar clicks
             if ev.etype == EventType::MouseDown {
                 let sh = gfx_height();
@@ -1475,8 +4084,8 @@ pub fn gui_main() {
                                 0  => open_terminal(),
                                 1  => open_query_console(),
                                 2  => open_table_browser(),
-                                3  => open_data_grid_stub(),
-                                5  => open_vaultpad_stub(),
+                                3  => open_data_grid(),
+                                5  => open_vaultpad(),
                                 6  => open_calculator(),
                                 7  => open_object_inspector(),
                                 9  => open_security_dashboard(),
@@ -1499,19 +4108,41 @@ pub fn gui_main() {
                 unsafe { if MENU_OPEN { MENU_OPEN = false; } }
             }
 
+            // Border grips: resize takes the mouse until the button is released
+            if resize_handle_event(&ev) {
+                continue;
+            }
+
+            // Title bar double-click toggles maximize
+            if maximize_handle_event(&ev) {
+                continue;
+            }
+
+            // Ctrl+Tab window switching
+            if switcher_handle_event(&ev) {
+                continue;
+            }
+
             // Forward to window manager
             wm_dispatch_event(&mut ev);
         }
+        switcher_poll();
 
         // Render
         comp_render();
         draw_taskbar();
         draw_menu();
+        draw_toasts();
         // Flip taskbar+menu area
         let sh = gfx_height();
         gfx_flip_rect(0, sh as i16 - TASKBAR_HEIGHT as i16 - (MENU_ITEMS as i16) * 24 - 10,
                        gfx_width(), TASKBAR_HEIGHT + (MENU_ITEMS as u16) * 24 + 10);
 
+        // Housekeeping: timer work deferred out of the PIT tick (expired
+        // messages/audit entries)
+        timer::timer_run_deferred();
+        entropy::entropy_poll();
+
         // Idle
         unsafe { cpu::hlt(); }
     }
@@ -1521,6 +4152,65 @@ pub fn gui_main() {
     unsafe { crate::drivers::framebuffer::fb_clear(); }
 }
 
+// ===========================================================================
+// ---- Data Grid ----
+// ===========================================================================
+static mut DG_WIDGETS: WidgetSet = WidgetSet::new();
+static mut DG_TABLE_COMBO: ComboBox = ComboBox::new();
+const DG_LISTVIEW: usize = 0;
+
+fn dg_load_table() {
+    unsafe {
+        let mut sql = FmtBuf::new();
+        let _ = write!(sql, "SELECT * FROM {}", DG_TABLE_COMBO.selected_str());
+        if let Some(ref mut lv) = DG_WIDGETS.widgets[DG_LISTVIEW] {
+            populate_lv_from_query(lv, sql.as_str());
+        }
+    }
+}
+
+fn dg_paint(win: &mut Window) {
+    wm_clear_canvas(win, CLIENT_BG);
+    unsafe {
+        widgets_draw(&DG_WIDGETS, win);
+        DG_TABLE_COMBO.draw(win);
+    }
+}
+
+fn dg_event(win: &mut Window, ev: &mut GuiEvent) {
+    if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
+    unsafe {
+        match DG_TABLE_COMBO.dispatch(ev) {
+            ComboEvent::Selected(_) => { dg_load_table(); return; }
+            ComboEvent::Consumed => return,
+            ComboEvent::Ignored => {}
+        }
+        let _ = widgets_dispatch(&mut DG_WIDGETS, ev);
+    }
+}
+
+fn open_data_grid() {
+    unsafe { DG_WIDGETS.clear(); }
+    let id = match wm_create_window("Data Grid", 40, 30, 560, 340, Some(dg_event), Some(dg_paint)) {
+        Some(v) => v,
+        None => return,
+    };
+    let cw;
+    let ch;
+    if let Some(win) = wm_get_window(id) { cw = win.client_w; ch = win.client_h; } else { return; }
+
+    unsafe {
+        DG_WIDGETS.add_listview(4, 28, cw as i16 - 8, ch as i16 - 34);
+        DG_TABLE_COMBO.place(4, 2, 200);
+        for t in 0..db_get_table_count() {
+            if let Some(schema) = db_get_schema_by_id(t) {
+                DG_TABLE_COMBO.add_option(schema.name_str());
+            }
+        }
+    }
+    dg_load_table();
+}
+
 // ===========================================================================
 // ---- Terminal (VaultShell in a GUI window) ----
 // ===========================================================================
@@ -1537,17 +4227,114 @@ struct TermCell {
     fg: u32,
 }
 
-const BLANK_CELL: TermCell = TermCell { ch: b' ', fg: 0xFF00DDAA };
+const BLANK_CELL: TermCell = TermCell { ch: b' ', fg: 0xFF00DDAA };
+
+static mut TERM_BUF: [[TermCell; TERM_COLS]; TERM_ROWS] = [[BLANK_CELL; TERM_COLS]; TERM_ROWS];
+static mut TERM_CX: usize = 0;
+static mut TERM_CY: usize = 0;
+static mut TERM_CMD: [u8; 512] = [0; 512];
+static mut TERM_CMD_LEN: usize = 0;
+static mut TERM_WIN_ID: u32 = 0;
+static mut TERM_BACKUP: Option<Vec<u8>> = None;
+
+// Command history (ring buffer, most recent at TERM_HIST_NEXT - 1)
+const TERM_HIST_MAX: usize = 16;
+static mut TERM_HIST: [[u8; 512]; TERM_HIST_MAX] = [[0; 512]; TERM_HIST_MAX];
+static mut TERM_HIST_LENS: [usize; TERM_HIST_MAX] = [0; TERM_HIST_MAX];
+static mut TERM_HIST_NEXT: usize = 0;
+static mut TERM_HIST_COUNT: usize = 0;
+static mut TERM_HIST_BROWSE: usize = 0; // 0 = editing a new line, n = n-th most recent
+
+// Scrollback (ring of rows scrolled off the top, oldest at SB_NEXT - SB_COUNT)
+const TERM_SB_LINES: usize = 500;
+static mut TERM_SB: [[TermCell; TERM_COLS]; TERM_SB_LINES] = [[BLANK_CELL; TERM_COLS]; TERM_SB_LINES];
+static mut TERM_SB_NEXT: usize = 0;
+static mut TERM_SB_COUNT: usize = 0;
+static mut TERM_VIEW: usize = 0; // rows scrolled back; 0 = live view
+
+// ANSI SGR colors: ESC [ <n>;<n>... m sets the pen for following output.
+// 30-37 and 90-97 pick from the palette, 0 and 39 restore TERM_FG; any other
+// escape sequence is consumed and ignored.
+const TERM_PALETTE: [u32; 8] = [
+    0xFF555555, 0xFFFF5555, 0xFF55DD55, 0xFFFFCC00,
+    0xFF5599FF, 0xFFDD66DD, 0xFF00DDAA, 0xFFCCCCCC,
+];
+const TERM_PALETTE_BRIGHT: [u32; 8] = [
+    0xFF888888, 0xFFFF8888, 0xFF88FF88, 0xFFFFEE66,
+    0xFF88BBFF, 0xFFFF99FF, 0xFF66FFDD, 0xFFFFFFFF,
+];
+
+#[derive(Copy, Clone, PartialEq)]
+enum EscState { None, Esc, Csi }
+
+// Mouse selection, in view cells (row, col). Runs in reading order from the
+// earlier end to the later one; any new output or scrolling drops it.
+const TERM_MARGIN: i16 = 4;
+const TERM_SEL_BG: u32 = 0xFF2A4A6A;
+static mut TERM_SEL_ANCHOR: (usize, usize) = (0, 0);
+static mut TERM_SEL_END: (usize, usize) = (0, 0);
+static mut TERM_SEL_ACTIVE: bool = false;
+static mut TERM_SELECTING: bool = false;
+
+const SGR_OK: &str = "\x1b[92m";
+const SGR_ERR: &str = "\x1b[91m";
+const SGR_RESET: &str = "\x1b[0m";
+
+// Output capture for `cmd > object`: while set, output goes here, not on screen
+static mut TERM_CAPTURE: Option<Vec<u8>> = None;
+
+static mut TERM_PEN: u32 = TERM_FG;
+static mut TERM_ESC: EscState = EscState::None;
+static mut TERM_ESC_PARAM: u32 = 0;
+
+fn term_sgr(code: u32) {
+    unsafe {
+        TERM_PEN = match code {
+            0 | 39 => TERM_FG,
+            30..=37 => TERM_PALETTE[(code - 30) as usize],
+            90..=97 => TERM_PALETTE_BRIGHT[(code - 90) as usize],
+            _ => TERM_PEN,
+        };
+    }
+}
 
-static mut TERM_BUF: [[TermCell; TERM_COLS]; TERM_ROWS] = [[BLANK_CELL; TERM_COLS]; TERM_ROWS];
-static mut TERM_CX: usize = 0;
-static mut TERM_CY: usize = 0;
-static mut TERM_CMD: [u8; 512] = [0; 512];
-static mut TERM_CMD_LEN: usize = 0;
-static mut TERM_WIN_ID: u32 = 0;
+/// Feed one byte to the escape parser. Returns true if it was part of a sequence.
+fn term_escape(c: u8) -> bool {
+    unsafe {
+        match TERM_ESC {
+            EscState::None => {
+                if c != 0x1B { return false; }
+                TERM_ESC = EscState::Esc;
+            }
+            EscState::Esc => {
+                // Only CSI is understood; a lone ESC + byte is dropped
+                TERM_ESC = if c == b'[' { EscState::Csi } else { EscState::None };
+                TERM_ESC_PARAM = 0;
+            }
+            EscState::Csi => match c {
+                b'0'..=b'9' => {
+                    TERM_ESC_PARAM = TERM_ESC_PARAM.saturating_mul(10).saturating_add((c - b'0') as u32);
+                }
+                b';' => {
+                    term_sgr(TERM_ESC_PARAM);
+                    TERM_ESC_PARAM = 0;
+                }
+                0x40..=0x7E => {
+                    if c == b'm' { term_sgr(TERM_ESC_PARAM); }
+                    TERM_ESC = EscState::None;
+                }
+                _ => {}
+            },
+        }
+        true
+    }
+}
 
 fn term_scroll_up() {
     unsafe {
+        TERM_SB[TERM_SB_NEXT] = TERM_BUF[0];
+        TERM_SB_NEXT = (TERM_SB_NEXT + 1) % TERM_SB_LINES;
+        if TERM_SB_COUNT < TERM_SB_LINES { TERM_SB_COUNT += 1; }
         for r in 1..TERM_ROWS {
             TERM_BUF[r - 1] = TERM_BUF[r];
         }
@@ -1557,6 +4344,13 @@ fn term_scroll_up() {
 
 fn term_putchar(c: u8) {
     unsafe {
+        TERM_VIEW = 0;
+        TERM_SEL_ACTIVE = false;
+        if term_escape(c) { return; }
+        if let Some(ref mut out) = TERM_CAPTURE {
+            out.push(c);
+            return;
+        }
         match c {
             b'\n' => {
                 TERM_CX = 0;
@@ -1591,7 +4385,7 @@ fn term_putchar(c: u8) {
                         TERM_CY += 1;
                         if TERM_CY >= TERM_ROWS { term_scroll_up(); TERM_CY = TERM_ROWS - 1; }
                     }
-                    TERM_BUF[TERM_CY][TERM_CX] = TermCell { ch: c, fg: TERM_FG };
+                    TERM_BUF[TERM_CY][TERM_CX] = TermCell { ch: c, fg: TERM_PEN };
                     TERM_CX += 1;
                     if TERM_CX >= TERM_COLS {
                         TERM_CX = 0;
@@ -1616,6 +4410,10 @@ fn term_print(s: &str) {
 fn term_print_prompt() {
     unsafe {
         let prompt = "vault> ";
+        TERM_VIEW = 0;
+        // A command that left a color set must not tint the next input line
+        TERM_PEN = TERM_FG;
+        TERM_ESC = EscState::None;
         for &b in prompt.as_bytes() {
             if TERM_CY >= TERM_ROWS { term_scroll_up(); TERM_CY = TERM_ROWS - 1; }
             if TERM_CX >= TERM_COLS {
@@ -1634,6 +4432,104 @@ fn term_clear() {
         TERM_BUF = [[BLANK_CELL; TERM_COLS]; TERM_ROWS];
         TERM_CX = 0;
         TERM_CY = 0;
+        TERM_SB_NEXT = 0;
+        TERM_SB_COUNT = 0;
+        TERM_VIEW = 0;
+        TERM_PEN = TERM_FG;
+        TERM_ESC = EscState::None;
+    }
+}
+
+/// Row `r` of the window as currently scrolled: scrollback first, then TERM_BUF.
+fn term_view_row(r: usize) -> &'static [TermCell; TERM_COLS] {
+    unsafe {
+        let line = TERM_SB_COUNT - TERM_VIEW + r;
+        if line < TERM_SB_COUNT {
+            let oldest = (TERM_SB_NEXT + TERM_SB_LINES - TERM_SB_COUNT) % TERM_SB_LINES;
+            &TERM_SB[(oldest + line) % TERM_SB_LINES]
+        } else {
+            &TERM_BUF[line - TERM_SB_COUNT]
+        }
+    }
+}
+
+/// Page through the scrollback; positive `delta` moves toward older output.
+fn term_scroll_view(delta: isize) {
+    unsafe {
+        let v = TERM_VIEW as isize + delta;
+        TERM_VIEW = v.clamp(0, TERM_SB_COUNT as isize) as usize;
+        TERM_SEL_ACTIVE = false;
+    }
+}
+
+// ---- Selection ----
+
+/// Cell under a client-area point, clamped to the grid.
+fn term_cell_at(x: i16, y: i16) -> (usize, usize) {
+    let col = ((x - TERM_MARGIN).max(0) / FONT_WIDTH as i16) as usize;
+    let row = ((y - TERM_MARGIN).max(0) / FONT_HEIGHT as i16) as usize;
+    (row.min(TERM_ROWS - 1), col.min(TERM_COLS - 1))
+}
+
+/// Selection ends in reading order.
+fn term_sel_range() -> ((usize, usize), (usize, usize)) {
+    unsafe {
+        if TERM_SEL_ANCHOR <= TERM_SEL_END {
+            (TERM_SEL_ANCHOR, TERM_SEL_END)
+        } else {
+            (TERM_SEL_END, TERM_SEL_ANCHOR)
+        }
+    }
+}
+
+fn term_cell_selected(r: usize, c: usize) -> bool {
+    if unsafe { !TERM_SEL_ACTIVE } { return false; }
+    let (start, end) = term_sel_range();
+    (r, c) >= start && (r, c) <= end
+}
+
+/// Copy the selected cells to the clipboard, one line per row with
+/// trailing blanks dropped.
+fn term_copy_selection() {
+    let (start, end) = term_sel_range();
+    let mut text = Vec::new();
+    for r in start.0..=end.0 {
+        let first = if r == start.0 { start.1 } else { 0 };
+        let last = if r == end.0 { end.1 } else { TERM_COLS - 1 };
+        let row = term_view_row(r);
+        let mut len = last + 1;
+        while len > first && row[len - 1].ch == b' ' { len -= 1; }
+        text.extend(row[first..len].iter().map(|cell| cell.ch));
+        if r != end.0 { text.push(b'\n'); }
+    }
+    clipboard_set(&text);
+}
+
+/// Mouse drag over the grid. Returns true if the event was a selection event.
+fn term_select_event(ev: &GuiEvent) -> bool {
+    unsafe {
+        match ev.etype {
+            EventType::MouseDown => {
+                TERM_SEL_ANCHOR = term_cell_at(ev.mouse_x, ev.mouse_y);
+                TERM_SEL_END = TERM_SEL_ANCHOR;
+                TERM_SEL_ACTIVE = false;
+                TERM_SELECTING = true;
+                true
+            }
+            EventType::MouseMove if TERM_SELECTING => {
+                TERM_SEL_END = term_cell_at(ev.mouse_x, ev.mouse_y);
+                TERM_SEL_ACTIVE = TERM_SEL_END != TERM_SEL_ANCHOR;
+                true
+            }
+            EventType::MouseUp if TERM_SELECTING => {
+                TERM_SELECTING = false;
+                TERM_SEL_END = term_cell_at(ev.mouse_x, ev.mouse_y);
+                TERM_SEL_ACTIVE = TERM_SEL_END != TERM_SEL_ANCHOR;
+                if TERM_SEL_ACTIVE { term_copy_selection(); }
+                true
+            }
+            _ => false,
+        }
     }
 }
 
@@ -1648,6 +4544,503 @@ fn gui_term_print(s: &str) {
     term_print(s);
 }
 
+// ---- Command history ----
+
+fn term_hist_push(cmd: &[u8]) {
+    unsafe {
+        TERM_HIST_BROWSE = 0;
+        if TERM_HIST_COUNT > 0 {
+            let last = (TERM_HIST_NEXT + TERM_HIST_MAX - 1) % TERM_HIST_MAX;
+            if &TERM_HIST[last][..TERM_HIST_LENS[last]] == cmd { return; }
+        }
+        let len = cmd.len().min(512);
+        TERM_HIST[TERM_HIST_NEXT][..len].copy_from_slice(&cmd[..len]);
+        TERM_HIST_LENS[TERM_HIST_NEXT] = len;
+        TERM_HIST_NEXT = (TERM_HIST_NEXT + 1) % TERM_HIST_MAX;
+        if TERM_HIST_COUNT < TERM_HIST_MAX { TERM_HIST_COUNT += 1; }
+    }
+}
+
+/// Erase the typed command from the screen, back to the prompt.
+fn term_erase_input() {
+    unsafe {
+        for _ in 0..TERM_CMD_LEN {
+            // Step back over a wrapped line
+            if TERM_CX == 0 && TERM_CY > 0 {
+                TERM_CY -= 1;
+                TERM_CX = TERM_COLS;
+            }
+            term_putchar(0x08);
+        }
+    }
+}
+
+/// Replace the current input line with `cmd` and echo it.
+fn term_set_input(cmd: &[u8]) {
+    term_erase_input();
+    unsafe {
+        let len = cmd.len().min(510);
+        TERM_CMD[..len].copy_from_slice(&cmd[..len]);
+        TERM_CMD_LEN = len;
+        for &b in &cmd[..len] { term_putchar(b); }
+    }
+}
+
+/// Up/Down: recall older/newer history entries into the input line.
+fn term_hist_recall(older: bool) {
+    unsafe {
+        if older {
+            if TERM_HIST_BROWSE >= TERM_HIST_COUNT { return; }
+            TERM_HIST_BROWSE += 1;
+        } else {
+            if TERM_HIST_BROWSE == 0 { return; }
+            TERM_HIST_BROWSE -= 1;
+        }
+        if TERM_HIST_BROWSE == 0 {
+            term_set_input(&[]);
+        } else {
+            let slot = (TERM_HIST_NEXT + TERM_HIST_MAX - TERM_HIST_BROWSE) % TERM_HIST_MAX;
+            let entry = TERM_HIST[slot];
+            term_set_input(&entry[..TERM_HIST_LENS[slot]]);
+        }
+    }
+}
+
+// ---- Tab completion ----
+
+/// Shell built-ins plus the terminal's own commands.
+static TERM_COMMANDS: &[&str] = &[
+    "help", "clear", "status", "gui", "security", "crypto", "audit", "inbox",
+    "scripts", "save", "run", "cat", "spawn", "kill", "msg",
+    "backup", "restore", "verify", "recount", "expire", "sleep", "wait", "recv", "alias", "unalias",
+    "history",
+];
+
+const TERM_COMPLETE_MAX: usize = 32;
+
+fn starts_with_ci(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && database::str_eq_ignore_case(&s[..prefix.len()], prefix)
+}
+
+fn term_complete_add(out: &mut [&'static str; TERM_COMPLETE_MAX], n: &mut usize,
+                     word: &'static str, prefix: &str) {
+    if !starts_with_ci(word, prefix) || *n >= TERM_COMPLETE_MAX { return; }
+    if out[..*n].iter().any(|&w| w == word) { return; }
+    out[*n] = word;
+    *n += 1;
+}
+
+/// Complete the word under the cursor: commands/verbs first, table names after.
+fn term_complete() {
+    unsafe {
+        let line = TERM_CMD;
+        let line = core::str::from_utf8(&line[..TERM_CMD_LEN]).unwrap_or("");
+        let word_start = line.rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let prefix = &line[word_start..];
+
+        let mut cands: [&'static str; TERM_COMPLETE_MAX] = [""; TERM_COMPLETE_MAX];
+        let mut n = 0usize;
+        if word_start == 0 {
+            for &w in TERM_COMMANDS.iter().chain(friendly::VERBS.iter()) {
+                term_complete_add(&mut cands, &mut n, w, prefix);
+            }
+        } else {
+            for t in 0..db_get_table_count() {
+                if let Some(schema) = db_get_schema_by_id(t) {
+                    term_complete_add(&mut cands, &mut n, schema.name_str(), prefix);
+                }
+            }
+            for alias in friendly::alias_names() {
+                term_complete_add(&mut cands, &mut n, alias, prefix);
+            }
+        }
+        if n == 0 { return; }
+
+        // Longest common prefix of all candidates (case-insensitive)
+        let mut common = cands[0].len();
+        for c in &cands[1..n] {
+            let same = cands[0].bytes().zip(c.bytes())
+                .take_while(|(a, b)| a.eq_ignore_ascii_case(b)).count();
+            common = common.min(same);
+        }
+
+        if common > prefix.len() || n == 1 {
+            let mut fill = [0u8; 512];
+            let mut len = word_start;
+            fill[..len].copy_from_slice(&line.as_bytes()[..len]);
+            let word = &cands[0].as_bytes()[..common.max(prefix.len())];
+            let wlen = word.len().min(510 - len);
+            fill[len..len + wlen].copy_from_slice(&word[..wlen]);
+            len += wlen;
+            if n == 1 && len < 510 {
+                fill[len] = b' ';
+                len += 1;
+            }
+            term_set_input(&fill[..len]);
+            return;
+        }
+
+        // Ambiguous: list the candidates and redraw the line
+        term_putchar(b'\n');
+        for c in &cands[..n] {
+            term_print("  ");
+            term_print(c);
+        }
+        term_putchar(b'\n');
+        term_print_prompt();
+        let saved = TERM_CMD;
+        let saved_len = TERM_CMD_LEN;
+        TERM_CMD_LEN = 0;
+        term_set_input(&saved[..saved_len]);
+    }
+}
+
+// ---- Terminal built-ins handled ahead of the shell ----
+
+/// `backup`: export the whole database (still encrypted) into memory and
+/// mirror it to serial as hex so it can be captured off-box.
+fn term_cmd_backup() {
+    let mut image = Vec::new();
+    let err = database::db_export(&mut image);
+    if err != 0 {
+        let mut fb = FmtBuf::new();
+        let _ = write!(fb, "  {}Backup failed (error {}){}\n", SGR_ERR, err, SGR_RESET);
+        term_print(fb.as_str());
+        return;
+    }
+
+    serial_println!("[BACKUP] begin {} bytes", image.len());
+    for chunk in image.chunks(32) {
+        let mut fb = FmtBuf::new();
+        for b in chunk {
+            let _ = write!(fb, "{:02x}", b);
+        }
+        serial_println!("{}", fb.as_str());
+    }
+    serial_println!("[BACKUP] end");
+
+    let mut fb = FmtBuf::new();
+    let _ = write!(fb, "  Backup: {} tables, {} bytes (encrypted)\n",
+        db_get_table_count(), image.len());
+    term_print(fb.as_str());
+    unsafe { TERM_BACKUP = Some(image); }
+}
+
+/// `restore`: replace the database with the last in-memory backup.
+fn term_cmd_restore() {
+    let image = unsafe { TERM_BACKUP.as_ref() };
+    let image = match image {
+        Some(i) => i,
+        None => { term_print("  No backup taken this session\n"); return; }
+    };
+    let err = database::db_import(image);
+    let mut fb = FmtBuf::new();
+    if err == 0 {
+        let _ = write!(fb, "  Restored {} tables from backup\n", db_get_table_count());
+    } else {
+        let _ = write!(fb, "  {}Restore failed (error {}){}, database unchanged\n",
+                       SGR_ERR, err, SGR_RESET);
+    }
+    term_print(fb.as_str());
+}
+
+/// `verify`: check every record's MAC and print a per-table summary.
+fn term_cmd_verify() {
+    let (checked, failed) = database::db_verify_all();
+    for t in 0..db_get_table_count() {
+        let name = db_get_schema_by_id(t).map(|s| s.name_str()).unwrap_or("?");
+        let bad = database::db_verify_failures(t).unwrap_or(0);
+        let drift = database::db_verify_count_drift(t);
+        let mut fb = FmtBuf::new();
+        if bad == 0 && !drift {
+            let _ = write!(fb, "  {:<18} {}OK{}\n", name, SGR_OK, SGR_RESET);
+        } else if bad == 0 {
+            let _ = write!(fb, "  {:<18} {}row count drift, run 'recount'{}\n", name, SGR_ERR, SGR_RESET);
+        } else {
+            let _ = write!(fb, "  {:<18} {}{} MAC failure(s){}\n", name, SGR_ERR, bad, SGR_RESET);
+        }
+        term_print(fb.as_str());
+    }
+    let mut fb = FmtBuf::new();
+    if failed == 0 {
+        let _ = write!(fb, "  {} records checked, 0 failed\n", checked);
+    } else {
+        let _ = write!(fb, "  {} records checked, {} failed {}-- TAMPERING DETECTED{}\n",
+                       checked, failed, SGR_ERR, SGR_RESET);
+    }
+    term_print(fb.as_str());
+    term_print(if aes_ctr::aes_ctr_selftest() {
+        "  AES-CTR known-answer test: \x1b[92mOK\x1b[0m\n"
+    } else {
+        "  AES-CTR known-answer test: \x1b[91mFAILED\x1b[0m\n"
+    });
+}
+
+/// `recount`: rebuild every table's row count from its live keys.
+fn term_cmd_recount() {
+    let fixed = database::db_recover_counts();
+    let mut fb = FmtBuf::new();
+    if fixed == 0 {
+        let _ = write!(fb, "  All row counts were correct\n");
+    } else {
+        let _ = write!(fb, "  Recounted {} table(s)\n", fixed);
+    }
+    term_print(fb.as_str());
+}
+
+/// kill <name>: terminate the lowest-pid process with that exact name
+/// and reap it.
+fn term_cmd_kill(name: &str) {
+    let pid = process::process_find_by_name(name);
+    let mut fb = FmtBuf::new();
+    match process::process_kill_by_name(name) {
+        VOS_OK => {
+            let pid = pid.unwrap_or(0);
+            let mut sql = FmtBuf::new();
+            let _ = write!(sql, "DELETE FROM ProcessTable WHERE pid = {}", pid);
+            let _ = query_execute(sql.as_str(), 0);
+            match process::process_waitpid(pid) {
+                Some(code) => { let _ = write!(fb, "  Killed '{}' (pid {}, exit code {})\n", name, pid, code); }
+                None => { let _ = write!(fb, "  Killed '{}' (pid {})\n", name, pid); }
+            }
+        }
+        VOS_ERR_NOTFOUND => { let _ = write!(fb, "  No process named '{}'\n", name); }
+        VOS_ERR_PERM => { let _ = write!(fb, "  Refusing to kill '{}'\n", name); }
+        err => { let _ = write!(fb, "  kill failed ({})\n", err); }
+    }
+    term_print(fb.as_str());
+}
+
+/// wait <pid>: reap an exited process and print its exit code.
+fn term_cmd_wait(arg: &str) {
+    let pid = match arg.parse::<u64>() {
+        Ok(v) if v != 0 => v,
+        _ => { term_print("  Usage: wait <pid>\n"); return; }
+    };
+    let mut fb = FmtBuf::new();
+    match process::process_waitpid(pid) {
+        Some(code) => { let _ = write!(fb, "  pid {} exited with code {}\n", pid, code); }
+        None if process::process_get_by_pid(pid).is_some() => {
+            let _ = write!(fb, "  pid {} is still running\n", pid);
+        }
+        None => { let _ = write!(fb, "  No exited process with pid {}\n", pid); }
+    }
+    term_print(fb.as_str());
+}
+
+fn term_current_pid() -> u64 {
+    process::process_get_current().map(|p| p.pid).unwrap_or(0)
+}
+
+/// msg <pid> <text>: queue a 'user' message in MessageTable.
+fn term_cmd_msg(args: &str) {
+    let (pid_str, text) = match args.split_once(' ') {
+        Some((p, t)) => (p, t.trim()),
+        None => (args, ""),
+    };
+    let dst = pid_str.parse::<u64>().unwrap_or(0);
+    if dst == 0 || text.is_empty() {
+        term_print("  Usage: msg <pid> <text>\n");
+        return;
+    }
+    let mut fb = FmtBuf::new();
+    match ipc::ipc_send(term_current_pid(), dst, "user", text) {
+        VOS_OK => { let _ = write!(fb, "  Message sent to PID {}\n", dst); }
+        err => { let _ = write!(fb, "  Failed to send message ({})\n", err); }
+    }
+    term_print(fb.as_str());
+}
+
+/// recv: take the oldest undelivered message for this process.
+fn term_cmd_recv() {
+    let msg = match ipc::ipc_recv(term_current_pid()) {
+        Some(m) => m,
+        None => { term_print("  No new messages\n"); return; }
+    };
+    let mut fb = FmtBuf::new();
+    let _ = write!(fb, "  #{} from PID {} [{}]: {}\n",
+                   msg.msg_id, msg.src_pid, msg.msg_type, msg.payload);
+    term_print(fb.as_str());
+}
+
+/// sleep <ms>: block the current process and report how long it was out.
+fn term_cmd_sleep(arg: &str) {
+    let ms = match arg.parse::<u64>() {
+        Ok(v) => v,
+        Err(_) => { term_print("  Usage: sleep <ms>\n"); return; }
+    };
+    let start = pit::pit_get_uptime_ms();
+    process::process_sleep_ms(ms);
+    let mut fb = FmtBuf::new();
+    let _ = write!(fb, "  Resumed after {} ms\n", pit::pit_get_uptime_ms() - start);
+    term_print(fb.as_str());
+}
+
+/// run <object> [--continue]: execute the SQL script stored in an object.
+/// Returns false if there is no such object, leaving `run` to the shell.
+fn term_cmd_run(args: &str) -> bool {
+    let mut name = "";
+    let mut keep_going = false;
+    for word in args.split(' ').filter(|w| !w.is_empty()) {
+        if word == script::CONTINUE_FLAG { keep_going = true; } else { name = word; }
+    }
+    if name.is_empty() { return false; }
+
+    let mut report = |stmt: &str, result: &QueryResult| {
+        term_print(SGR_OK);
+        term_print("  > ");
+        term_print(stmt);
+        term_print(SGR_RESET);
+        term_putchar(b'\n');
+        if result.error_code != VOS_OK {
+            let mut fb = FmtBuf::new();
+            let _ = write!(fb, "  {}Error: {}{}\n", SGR_ERR, result.error_msg_str(), SGR_RESET);
+            term_print(fb.as_str());
+            return;
+        }
+        for row in result.rows.iter() {
+            term_print("    ");
+            term_print(format_row(row).as_str());
+            term_putchar(b'\n');
+        }
+    };
+    let summary = match script::script_run(name, keep_going, term_current_pid(), &mut report) {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+
+    let mut fb = FmtBuf::new();
+    let _ = write!(fb, "  {} of {} statement(s) ran", summary.ran, summary.total);
+    if summary.failed > 0 {
+        let _ = write!(fb, ", {}{} failed{}", SGR_ERR, summary.failed, SGR_RESET);
+    }
+    fb.push_str("\n");
+    term_print(fb.as_str());
+    true
+}
+
+/// Run one command line: terminal built-ins first, then the shell.
+fn term_run(cmd: &str) {
+    if cmd.is_empty() || term_builtin(cmd) { return; }
+    // Redirect shell output to terminal cell buffer
+    shell_main::set_output(gui_term_putch, gui_term_print);
+    let is_clear = shell_main::execute_command(cmd);
+    shell_main::restore_output();
+    if is_clear {
+        term_clear();
+    }
+}
+
+/// Split `cmd > name` into the command and the target object. The `>` must
+/// stand alone and the target be one word starting with a letter, so SQL
+/// comparisons such as `pid > 3` or `a >= b` are left alone.
+fn term_split_redirect(cmd: &str) -> (&str, Option<&str>) {
+    let (left, target) = match cmd.rsplit_once(" > ") {
+        Some((l, t)) => (l.trim(), t.trim()),
+        None => return (cmd, None),
+    };
+    let is_name = target.bytes().next().map_or(false, |b| b.is_ascii_alphabetic())
+        && target.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'.');
+    if !is_name || left.is_empty() || left.bytes().filter(|&b| b == b'\'').count() % 2 != 0 {
+        return (cmd, None);
+    }
+    (left, Some(target))
+}
+
+/// `cmd > name`: run `cmd` with its output captured, and store the text as
+/// object `name` (type 'text'), replacing its data if it exists.
+fn term_run_to_object(cmd: &str, name: &str) {
+    unsafe { TERM_CAPTURE = Some(Vec::new()); }
+    term_run(cmd);
+    let captured = unsafe { TERM_CAPTURE.take().unwrap_or_default() };
+
+    let mut data: Vec<u8> = captured.into_iter()
+        .filter(|&b| b == b'\n' || (0x20..=0x7E).contains(&b))
+        .collect();
+    let truncated = data.len() > MAX_STR_LEN;
+    data.truncate(MAX_STR_LEN);
+    let text = core::str::from_utf8(&data).unwrap_or("");
+
+    let res = object_put(name, "text", text);
+    let mut fb = FmtBuf::new();
+    if res.error_code != VOS_OK {
+        let _ = write!(fb, "  {}Redirect failed: {}{}\n", SGR_ERR, res.error_msg_str(), SGR_RESET);
+    } else if truncated {
+        let _ = write!(fb, "  Wrote {} bytes to '{}' (output cut off)\n", text.len(), name);
+    } else {
+        let _ = write!(fb, "  Wrote {} bytes to '{}'\n", text.len(), name);
+    }
+    term_print(fb.as_str());
+}
+
+/// Returns true if `cmd` was a terminal built-in.
+fn term_builtin(cmd: &str) -> bool {
+    let cmd = cmd.trim();
+    if database::str_eq_ignore_case(cmd, "backup") {
+        term_cmd_backup();
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "restore") {
+        term_cmd_restore();
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "verify") {
+        term_cmd_verify();
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "recount") {
+        term_cmd_recount();
+        return true;
+    }
+    // kill <pid> stays with the shell; a non-numeric argument is a name
+    if let Some(rest) = cmd.strip_prefix("kill ") {
+        let name = rest.trim();
+        if !name.is_empty() && name.parse::<u64>().is_err() {
+            term_cmd_kill(name);
+            return true;
+        }
+    }
+    if let Some(rest) = cmd.strip_prefix("msg ") {
+        term_cmd_msg(rest.trim());
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "recv") {
+        term_cmd_recv();
+        return true;
+    }
+    if let Some(rest) = cmd.strip_prefix("sleep ") {
+        term_cmd_sleep(rest.trim());
+        return true;
+    }
+    if let Some(rest) = cmd.strip_prefix("wait ") {
+        term_cmd_wait(rest.trim());
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "alias") || starts_with_ci(cmd, "alias ") {
+        alias::alias_command(&cmd[5..], term_print);
+        return true;
+    }
+    if starts_with_ci(cmd, "unalias ") {
+        alias::unalias_command(&cmd[8..], term_print);
+        return true;
+    }
+    if starts_with_ci(cmd, "run ") && term_cmd_run(&cmd[4..]) {
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "history") || starts_with_ci(cmd, "history ") {
+        history::history_command(&cmd[7..], term_print);
+        return true;
+    }
+    if database::str_eq_ignore_case(cmd, "expire") {
+        let n = database::db_expire_sweep(pit::pit_get_uptime_ms());
+        let mut fb = FmtBuf::new();
+        let _ = write!(fb, "  Expired {} record(s)\n", n);
+        term_print(fb.as_str());
+        return true;
+    }
+    false
+}
+
 fn term_paint(win: &mut Window) {
     let cw = win.client_w;
     let ch = win.client_h;
@@ -1657,8 +5050,8 @@ fn term_paint(win: &mut Window) {
         *pixel = TERM_BG;
     }
 
-    let mx: i16 = 4;
-    let my: i16 = 4;
+    let mx: i16 = TERM_MARGIN;
+    let my: i16 = TERM_MARGIN;
 
     unsafe {
         // Draw cells directly into canvas using safe indexing
@@ -1668,7 +5061,11 @@ fn term_paint(win: &mut Window) {
             for c in 0..TERM_COLS {
                 let px = mx + (c as i16) * FONT_WIDTH as i16;
                 if px + FONT_WIDTH as i16 > cw as i16 { break; }
-                let cell = &TERM_BUF[r][c];
+                let cell = &term_view_row(r)[c];
+                if term_cell_selected(r, c) {
+                    canvas_fill(&mut win.canvas, cw, ch, px, py,
+                                FONT_WIDTH as i16, FONT_HEIGHT as i16, TERM_SEL_BG);
+                }
                 if cell.ch != b' ' {
                     // Draw character glyph directly into canvas
                     let glyph = &crate::drivers::font::FONT_8X16[cell.ch as usize];
@@ -1691,6 +5088,15 @@ fn term_paint(win: &mut Window) {
             }
         }
 
+        // Scrolled back: show the position instead of the cursor
+        if TERM_VIEW > 0 {
+            let mut label = FmtBuf::new();
+            let _ = write!(label, "[-{} lines]", TERM_VIEW);
+            let lx = cw as i16 - mx - (label.as_str().len() * FONT_WIDTH as usize) as i16;
+            canvas_text(&mut win.canvas, cw, ch, lx, my, label.as_str(), TERM_PROMPT_FG, TERM_BG);
+            return;
+        }
+
         // Blinking block cursor
         let cx_px = mx + (TERM_CX as i16) * FONT_WIDTH as i16;
         let cy_px = my + (TERM_CY as i16) * FONT_HEIGHT as i16;
@@ -1717,27 +5123,56 @@ fn term_event(win: &mut Window, ev: &mut GuiEvent) {
         return;
     }
 
+    if term_select_event(ev) { return; }
     if ev.etype != EventType::KeyDown { return; }
 
     let key = ev.key;
+    if key == KEY_CTRL_C && unsafe { TERM_SEL_ACTIVE } {
+        term_copy_selection();
+        return;
+    }
+    match key {
+        keyboard::KEY_PGUP => { term_scroll_view((TERM_ROWS - 1) as isize); return; }
+        keyboard::KEY_PGDN => { term_scroll_view(-((TERM_ROWS - 1) as isize)); return; }
+        _ => unsafe { TERM_VIEW = 0; TERM_SEL_ACTIVE = false; }
+    }
     unsafe {
         match key {
             // Enter — execute command
             b'\n' | b'\r' => {
                 term_putchar(b'\n');
                 if TERM_CMD_LEN > 0 {
-                    let cmd = core::str::from_utf8(&TERM_CMD[..TERM_CMD_LEN]).unwrap_or("");
-                    // Redirect shell output to terminal cell buffer
-                    shell_main::set_output(gui_term_putch, gui_term_print);
-                    let is_clear = shell_main::execute_command(cmd);
-                    shell_main::restore_output();
-                    if is_clear {
-                        term_clear();
+                    let line = TERM_CMD;
+                    term_hist_push(&line[..TERM_CMD_LEN]);
+                    let typed = core::str::from_utf8(&TERM_CMD[..TERM_CMD_LEN]).unwrap_or("");
+                    history::history_record(typed);
+                    let expanded = alias::alias_expand(typed);
+                    let cmd = match expanded {
+                        Ok(Some(ref s)) => s.as_str(),
+                        Ok(None) => typed,
+                        Err(_) => { term_print("  Alias loop, command not run\n"); "" }
+                    };
+                    match term_split_redirect(cmd) {
+                        (left, Some(target)) => term_run_to_object(left, target),
+                        _ => term_run(cmd),
                     }
                 }
                 TERM_CMD_LEN = 0;
                 term_print_prompt();
             }
+            b'\t' => term_complete(),
+            KEY_CTRL_C => clipboard_set(&TERM_CMD[..TERM_CMD_LEN]),
+            KEY_CTRL_V => {
+                for &b in clipboard_get() {
+                    if (0x20..=0x7E).contains(&b) && TERM_CMD_LEN < 510 {
+                        TERM_CMD[TERM_CMD_LEN] = b;
+                        TERM_CMD_LEN += 1;
+                        term_putchar(b);
+                    }
+                }
+            }
+            keyboard::KEY_UP => term_hist_recall(true),
+            keyboard::KEY_DOWN => term_hist_recall(false),
             // Backspace
             0x08 | 0x7F => {
                 if TERM_CMD_LEN > 0 {
@@ -1772,6 +5207,13 @@ fn open_terminal() {
         // Clear buffer
         term_clear();
         TERM_CMD_LEN = 0;
+
+        // First open since boot: Up-arrow recall starts from the saved history
+        if TERM_HIST_COUNT == 0 {
+            for line in history::history_entries() {
+                term_hist_push(line.as_bytes());
+            }
+        }
     }
 
     let id = wm_create_window("Terminal", 30, 20, 660, 660,
@@ -1787,27 +5229,186 @@ fn open_terminal() {
     }
 }
 
-// Stub openers for apps that need more complex custom rendering
-// (Data Grid and VaultPad are too complex for the initial port — will be added later)
-fn open_data_grid_stub() {
-    let id = wm_create_window("Data Grid", 40, 30, 500, 300,
-        Some(stub_event), Some(stub_paint));
-    let _ = id;
+// ===========================================================================
+// ---- VaultPad (text objects in ObjectTable) ----
+// ===========================================================================
+static mut VP_WIDGETS: WidgetSet = WidgetSet::new();
+static mut VP_EDITOR: TextArea = TextArea::new();
+const VP_NAME_BOX: usize = 0;
+const VP_OPEN_BTN: usize = 1;
+const VP_SAVE_BTN: usize = 2;
+const VP_FIND_BOX: usize = 3;
+const VP_REPLACE_BOX: usize = 4;
+const VP_NEXT_BTN: usize = 5;
+const VP_REPLACE_BTN: usize = 6;
+const VP_CASE_BTN: usize = 7;
+const VP_STATUS_H: i16 = 20;
+const VP_FIND_H: i16 = 28;
+
+static mut VP_MATCH_CASE: bool = false;
+
+fn vp_name() -> &'static str {
+    unsafe {
+        match VP_WIDGETS.widgets[VP_NAME_BOX] {
+            Some(ref tb) => widget_get_text(tb),
+            None => "",
+        }
+    }
+}
+
+fn vp_open() {
+    let name = vp_name();
+    if name.is_empty() {
+        notify("Enter an object name", TOAST_MS);
+        return;
+    }
+    let result = query_execute_with(
+        "SELECT * FROM ObjectTable WHERE name = ?", &[str_param(name)], 0);
+    let row = match result.rows.first() {
+        Some(r) => r,
+        None => { notify("No such object", TOAST_MS); return; }
+    };
+    let data = match row.fields[3] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => "" };
+    unsafe {
+        VP_EDITOR.set_text(data);
+        VP_EDITOR.focused = true;
+    }
+}
+
+/// Store the editor text in the named object, creating it as type 'text'.
+fn vp_save() {
+    let name = vp_name();
+    if name.is_empty() {
+        notify("Enter an object name", TOAST_MS);
+        return;
+    }
+
+    let text = unsafe { VP_EDITOR.text() };
+    let mut cut = text.len().min(MAX_STR_LEN);
+    while !text.is_char_boundary(cut) { cut -= 1; }
+    let truncated = cut < text.len();
+    let data = &text[..cut];
+
+    let res = object_put(name, "text", data);
+    let mut msg = FmtBuf::new();
+    if res.error_code != VOS_OK {
+        let _ = write!(msg, "Save failed: {}", res.error_msg_str());
+    } else if truncated {
+        let _ = write!(msg, "Saved '{}' (cut to {} bytes)", name, MAX_STR_LEN);
+    } else {
+        let _ = write!(msg, "Saved '{}'", name);
+    }
+    notify(msg.as_str(), TOAST_MS);
+}
+
+fn vp_box_text(idx: usize) -> &'static str {
+    unsafe {
+        match VP_WIDGETS.widgets[idx] {
+            Some(ref tb) => widget_get_text(tb),
+            None => "",
+        }
+    }
+}
+
+/// Next: highlight the next match after the cursor, wrapping at the end.
+fn vp_find_next() -> bool {
+    let pat = vp_box_text(VP_FIND_BOX).as_bytes();
+    if pat.is_empty() { return false; }
+    unsafe {
+        match VP_EDITOR.find(pat, VP_EDITOR.cursor, VP_MATCH_CASE) {
+            Some(pos) => {
+                VP_EDITOR.mark = (pos, pat.len());
+                VP_EDITOR.cursor = pos + pat.len();
+                VP_EDITOR.scroll_to_cursor();
+                true
+            }
+            None => {
+                VP_EDITOR.mark = (0, 0);
+                notify("Not found", TOAST_MS);
+                false
+            }
+        }
+    }
 }
 
-fn open_vaultpad_stub() {
-    let id = wm_create_window("VaultPad Editor", 60, 40, 500, 300,
-        Some(stub_event), Some(stub_paint));
-    let _ = id;
+/// Replace: substitute the highlighted match, then move on to the next one.
+/// With nothing highlighted it only finds.
+fn vp_replace() {
+    let pat = vp_box_text(VP_FIND_BOX).as_bytes();
+    let with = vp_box_text(VP_REPLACE_BOX).as_bytes();
+    unsafe {
+        let (start, len) = VP_EDITOR.mark;
+        let still_matches = len == pat.len() && len > 0
+            && VP_EDITOR.find(pat, start, VP_MATCH_CASE) == Some(start);
+        if still_matches && !VP_EDITOR.replace_range(start, len, with) {
+            notify("Replace would overflow the editor", TOAST_MS);
+            return;
+        }
+    }
+    vp_find_next();
 }
 
-fn stub_paint(win: &mut Window) {
+fn vp_paint(win: &mut Window) {
     wm_clear_canvas(win, CLIENT_BG);
-    let cw = win.client_w;
-    let ch = win.client_h;
-    canvas_text(&mut win.canvas, cw, ch, 20, 20, "Coming soon...", 0xFF808080, CLIENT_BG);
+    unsafe {
+        widgets_draw(&VP_WIDGETS, win);
+        VP_EDITOR.draw(win);
+
+        let (row, col) = VP_EDITOR.pos_of(VP_EDITOR.cursor);
+        let mut status = FmtBuf::new();
+        let _ = write!(status, "Ln {}, Col {}   {}/{} bytes", row + 1, col + 1, VP_EDITOR.len, MAX_STR_LEN);
+        if VP_MATCH_CASE { status.push_str("   Match case"); }
+        let cw = win.client_w;
+        let ch = win.client_h;
+        canvas_text(&mut win.canvas, cw, ch, 6, ch as i16 - VP_STATUS_H + 2, status.as_str(),
+            if VP_EDITOR.len > MAX_STR_LEN { 0xFFFF6644 } else { 0xFF808080 }, CLIENT_BG);
+    }
+}
+
+fn vp_event(win: &mut Window, ev: &mut GuiEvent) {
+    if ev.etype == EventType::Close { wm_destroy_window(win.id); return; }
+    unsafe {
+        if textarea_route(&mut VP_EDITOR, &mut VP_WIDGETS, ev) { return; }
+        if clipboard_key(&mut VP_WIDGETS, ev) { return; }
+        match widgets_dispatch(&mut VP_WIDGETS, ev) {
+            WidgetAction::Clicked(idx) if idx == VP_OPEN_BTN => vp_open(),
+            WidgetAction::Clicked(idx) if idx == VP_SAVE_BTN => vp_save(),
+            WidgetAction::Clicked(idx) if idx == VP_NEXT_BTN => { vp_find_next(); }
+            WidgetAction::Clicked(idx) if idx == VP_REPLACE_BTN => vp_replace(),
+            WidgetAction::Clicked(idx) if idx == VP_CASE_BTN => {
+                VP_MATCH_CASE = !VP_MATCH_CASE;
+                VP_EDITOR.mark = (0, 0);
+            }
+            _ => {}
+        }
+    }
 }
 
-fn stub_event(win: &mut Window, ev: &mut GuiEvent) {
-    if ev.etype == EventType::Close { wm_destroy_window(win.id); }
+fn open_vaultpad() {
+    unsafe { VP_WIDGETS.clear(); }
+    let id = match wm_create_window("VaultPad Editor", 60, 40, 500, 340, Some(vp_event), Some(vp_paint)) {
+        Some(v) => v,
+        None => return,
+    };
+    let cw;
+    let ch;
+    if let Some(win) = wm_get_window(id) { cw = win.client_w; ch = win.client_h; } else { return; }
+
+    unsafe {
+        VP_WIDGETS.add_textbox(4, 4, cw as i16 - 160, 24);
+        VP_WIDGETS.add_button(cw as i16 - 152, 4, 72, 24, "Open");
+        VP_WIDGETS.add_button(cw as i16 - 76, 4, 72, 24, "Save");
+
+        // Find row: [find] [replace] Next Replace Aa
+        let fy = ch as i16 - VP_STATUS_H - VP_FIND_H + 2;
+        let bw = (cw as i16 - 196) / 2;
+        VP_WIDGETS.add_textbox(4, fy, bw, 22);
+        VP_WIDGETS.add_textbox(8 + bw, fy, bw, 22);
+        VP_WIDGETS.add_button(12 + 2 * bw, fy, 56, 22, "Next");
+        VP_WIDGETS.add_button(72 + 2 * bw, fy, 72, 22, "Replace");
+        VP_WIDGETS.add_button(148 + 2 * bw, fy, 40, 22, "Aa");
+
+        VP_EDITOR.place(4, 32, cw as i16 - 8, ch as i16 - 32 - VP_FIND_H - VP_STATUS_H - 4);
+        VP_EDITOR.focused = true;
+    }
 }
@@ -0,0 +1,88 @@
+// Inter-process messaging backed by MessageTable.
+//
+// A message is a MessageTable row (msg_id, src_pid, dst_pid, type, payload,
+// delivered). Sending inserts an undelivered row; receiving takes the oldest
+// undelivered row addressed to the pid and flags it delivered, so the
+// mailbox is as persistent and encrypted as any other table.
+//
+// Processes hold no capability on MessageTable; the mailbox is a kernel
+// service, so its queries skip the table check and are scoped to the
+// sender or recipient here instead.
+
+use alloc::string::String;
+use crate::db::query::query_execute_trusted_with;
+use crate::db::database::QueryResult;
+use crate::db::field::field_as_u64;
+use crate::error::VosResult;
+use crate::db::record::{FieldValue, Record, StrField};
+use vaultos_shared::error_codes::*;
+
+pub struct Message {
+    pub msg_id: u64,
+    pub src_pid: u64,
+    pub dst_pid: u64,
+    pub msg_type: String,
+    pub payload: String,
+}
+
+/// Queue a message for `dst_pid`. Returns VOS_OK or the INSERT's error code.
+pub fn ipc_send(src_pid: u64, dst_pid: u64, msg_type: &str, payload: &str) -> i32 {
+    if dst_pid == 0 { return VOS_ERR_INVAL; }
+
+    query_execute_trusted_with(
+        "INSERT INTO MessageTable (src_pid, dst_pid, type, payload, delivered) \
+         VALUES (?, ?, ?, ?, false)",
+        &[FieldValue::U64(src_pid), FieldValue::U64(dst_pid),
+          FieldValue::Str(StrField::from_str(msg_type)), FieldValue::Str(StrField::from_str(payload))],
+        src_pid).error_code
+}
+
+fn mailbox_query(sql: &str, params: &[FieldValue], pid: u64) -> VosResult<QueryResult> {
+    let result = query_execute_trusted_with(sql, params, pid);
+    result.status()?;
+    Ok(result)
+}
+
+/// The row's value in column `name`, looked up by name so the mailbox does
+/// not depend on MessageTable's column order.
+fn column<'a>(result: &QueryResult, row: &'a Record, name: &str) -> Option<&'a FieldValue> {
+    row.fields[result.column_index(name)?].as_ref()
+}
+
+fn field_u64(v: Option<&FieldValue>) -> u64 {
+    v.and_then(field_as_u64).unwrap_or(0)
+}
+
+fn field_string(v: Option<&FieldValue>) -> String {
+    match v { Some(FieldValue::Str(s)) => String::from(s.as_str()), _ => String::new() }
+}
+
+/// Take the oldest undelivered message addressed to `pid`, marking it delivered.
+///
+/// The claim is one UPDATE that only matches while the row is still
+/// undelivered. If another receiver got there between the SELECT and the
+/// UPDATE, nothing changes and the next candidate is tried, so a message is
+/// handed out at most once.
+pub fn ipc_recv(pid: u64) -> Option<Message> {
+    loop {
+        let result = mailbox_query(
+            "SELECT * FROM MessageTable WHERE dst_pid = ? AND delivered = false ORDER BY msg_id LIMIT 1",
+            &[FieldValue::U64(pid)], pid).ok()?;
+        let row = result.rows.first()?;
+
+        let msg = Message {
+            msg_id: field_u64(column(&result, row, "msg_id")),
+            src_pid: field_u64(column(&result, row, "src_pid")),
+            dst_pid: field_u64(column(&result, row, "dst_pid")),
+            msg_type: field_string(column(&result, row, "type")),
+            payload: field_string(column(&result, row, "payload")),
+        };
+
+        let claim = mailbox_query(
+            "UPDATE MessageTable SET delivered = true WHERE msg_id = ? AND delivered = false",
+            &[FieldValue::U64(msg.msg_id)], pid).ok()?;
+        if claim.rows_affected == 1 {
+            return Some(msg);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Priority selection for the scheduler
+//
+// Process.priority picks a class, 0 (lowest) to PRIO_MAX. On every
+// timeslice the scheduler asks sched_pick_next for the next slot: the
+// highest non-empty class among Ready processes (and the one still
+// Running) wins, and slots within a class take turns round-robin.
+//
+// Aging keeps a busy high-priority task from starving the rest. Each
+// timeslice a Ready process is passed over adds to its wait count; every
+// AGING_SLICES passes it gains one class of boost, up to PRIO_MAX. Being
+// picked clears the boost, so it drops back to its own class afterwards.
+//
+// That bounds the wait: after AGING_SLICES * PRIO_MAX passes a slot sits
+// in the top class, and the round-robin cursor reaches it within another
+// MAX_PROCESSES picks. Debug builds assert the bound on every pick.
+
+use super::process::{process_by_index, ProcState, MAX_PROCESSES};
+
+pub const PRIO_MIN: u8 = 0;
+pub const PRIO_MAX: u8 = 3;
+pub const PRIO_DEFAULT: u8 = 1;
+const PRIO_CLASSES: usize = PRIO_MAX as usize + 1;
+
+/// Timeslices a Ready process waits before it is boosted one class.
+const AGING_SLICES: u32 = 4;
+
+/// Most picks a runnable slot can be passed over in a row.
+const STARVATION_BOUND: u32 = AGING_SLICES * PRIO_MAX as u32 + MAX_PROCESSES as u32;
+
+static mut WAIT_SLICES: [u32; MAX_PROCESSES] = [0; MAX_PROCESSES];
+// Picks passed over since the slot last ran; unlike WAIT_SLICES never
+// cleared by a boost
+static mut SINCE_RUN: [u32; MAX_PROCESSES] = [0; MAX_PROCESSES];
+static mut BOOST: [u8; MAX_PROCESSES] = [0; MAX_PROCESSES];
+// Last slot picked in each class; the next search starts after it
+static mut RR_CURSOR: [usize; PRIO_CLASSES] = [MAX_PROCESSES - 1; PRIO_CLASSES];
+
+fn runnable(idx: usize) -> bool {
+    let state = process_by_index(idx).state;
+    state == ProcState::Ready || state == ProcState::Running
+}
+
+/// Class a slot competes in: its priority plus any aging boost.
+fn effective_priority(idx: usize) -> u8 {
+    let base = process_by_index(idx).priority.min(PRIO_MAX);
+    unsafe { base.saturating_add(BOOST[idx]).min(PRIO_MAX) }
+}
+
+/// Choose the slot to run for the next timeslice, or None if nothing is
+/// runnable. Call once per timeslice; it also ages the slots passed over.
+pub fn sched_pick_next() -> Option<usize> {
+    let class = (0..MAX_PROCESSES)
+        .filter(|&i| runnable(i))
+        .map(effective_priority)
+        .max()? as usize;
+
+    unsafe {
+        let start = RR_CURSOR[class];
+        let picked = (1..=MAX_PROCESSES)
+            .map(|off| (start + off) % MAX_PROCESSES)
+            .find(|&i| runnable(i) && effective_priority(i) as usize == class)?;
+        RR_CURSOR[class] = picked;
+
+        for i in 0..MAX_PROCESSES {
+            if i == picked || !runnable(i) {
+                WAIT_SLICES[i] = 0;
+                SINCE_RUN[i] = 0;
+                if i == picked { BOOST[i] = 0; }
+                continue;
+            }
+            SINCE_RUN[i] += 1;
+            debug_assert!(SINCE_RUN[i] <= STARVATION_BOUND,
+                          "sched: slot {} passed over {} times", i, SINCE_RUN[i]);
+            WAIT_SLICES[i] += 1;
+            if WAIT_SLICES[i] >= AGING_SLICES {
+                WAIT_SLICES[i] = 0;
+                if BOOST[i] < PRIO_MAX { BOOST[i] += 1; }
+            }
+        }
+        Some(picked)
+    }
+}
+
+/// Forget aging state for a slot that is being reused.
+pub fn sched_reset_slot(idx: usize) {
+    if idx >= MAX_PROCESSES { return; }
+    unsafe {
+        WAIT_SLICES[idx] = 0;
+        SINCE_RUN[idx] = 0;
+        BOOST[idx] = 0;
+    }
+}
@@ -6,11 +6,16 @@ use crate::mm::paging::*;
 use crate::mm::layout::*;
 use crate::mm::heap;
 use crate::cap;
-use crate::arch::x86_64::gdt;
+use crate::arch::x86_64::{cpu, gdt, pit, timer};
+use crate::arch::x86_64::idt::{self, InterruptFrame};
+use super::priority::{self, PRIO_DEFAULT, PRIO_MAX};
 use vaultos_shared::capability_types::*;
+use vaultos_shared::error_codes::*;
 
 pub const MAX_PROCESSES: usize = 64;
 pub const PROC_STACK_SIZE: usize = 64 * 1024; // 64 KiB
+/// Terminated processes kept for process_waitpid before the oldest is freed.
+pub const MAX_ZOMBIES: usize = 16;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -61,6 +66,7 @@ pub struct Process {
     pub cap_root: u64,
     pub is_user: bool,
     pub user_stack_base: u64,
+    pub exit_code: i32,
 }
 
 impl Process {
@@ -78,8 +84,50 @@ impl Process {
             cap_root: 0,
             is_user: false,
             user_stack_base: 0,
+            exit_code: 0,
         }
     }
+
+    /// Name up to the first NUL.
+    pub fn name_str(&self) -> &str {
+        name_as_str(&self.name)
+    }
+}
+
+/// Snapshot of one process-table slot, returned by `process_list`.
+#[derive(Clone, Copy)]
+pub struct ProcessInfo {
+    pub pid: u64,
+    pub name: [u8; 64],
+    pub state: ProcState,
+    pub priority: u8,
+}
+
+impl ProcessInfo {
+    pub const fn empty() -> Self {
+        ProcessInfo { pid: 0, name: [0u8; 64], state: ProcState::Free, priority: 0 }
+    }
+
+    /// Name up to the first NUL.
+    pub fn name_str(&self) -> &str {
+        name_as_str(&self.name)
+    }
+}
+
+/// Resource figures for one live process, returned by `process_get_stats`.
+#[derive(Clone, Copy)]
+pub struct ProcessStats {
+    pub pid: u64,
+    pub state: ProcState,
+    pub priority: u8,
+    pub is_user: bool,
+    pub kernel_stack: u64,     // bytes allocated for the kernel stack
+    pub user_stack: u64,       // bytes mapped for the user stack (0 for kernel tasks)
+}
+
+fn name_as_str(name: &[u8; 64]) -> &str {
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    core::str::from_utf8(&name[..len]).unwrap_or("?")
 }
 
 // ---------------------------------------------------------------------------
@@ -146,10 +194,11 @@ pub fn process_create(name: &str, entry: u64) -> Option<&'static mut Process> {
 
     unsafe {
         let proc = &mut PROCESSES[slot];
+        priority::sched_reset_slot(slot);
         proc.pid = pid;
         set_name(proc, name);
         proc.state = ProcState::Ready;
-        proc.priority = 1;
+        proc.priority = PRIO_DEFAULT;
         proc.is_user = false;
         proc.stack_virt = stack_virt;
         proc.stack_base = stack_virt;
@@ -233,10 +282,11 @@ pub fn process_create_user(name: &str, entry: u64) -> Option<&'static mut Proces
 
     unsafe {
         let proc = &mut PROCESSES[slot];
+        priority::sched_reset_slot(slot);
         proc.pid = pid;
         set_name(proc, name);
         proc.state = ProcState::Ready;
-        proc.priority = 1;
+        proc.priority = PRIO_DEFAULT;
         proc.is_user = true;
         proc.stack_virt = stack_virt;
         proc.stack_base = stack_virt;
@@ -270,31 +320,229 @@ pub fn process_create_user(name: &str, entry: u64) -> Option<&'static mut Proces
 // Process exit
 // ---------------------------------------------------------------------------
 
-/// Terminate a process: free stack, destroy user address space.
-pub fn process_exit(pid: u64, _exit_code: i32) {
+/// Terminate a process. The slot stays Terminated, holding `exit_code`,
+/// until process_waitpid reaps it or more than MAX_ZOMBIES processes are
+/// waiting to be reaped. Its stack and address space are freed at once,
+/// unless it is the running process: those are still in use until it has
+/// switched away, so they wait for process_release_exited.
+pub fn process_exit(pid: u64, exit_code: i32) {
+    let current = super::scheduler::current_pid();
     unsafe {
         for i in 0..MAX_PROCESSES {
-            if PROCESSES[i].pid == pid && PROCESSES[i].state != ProcState::Free {
+            let state = PROCESSES[i].state;
+            if PROCESSES[i].pid == pid && state != ProcState::Free && state != ProcState::Terminated {
                 PROCESSES[i].state = ProcState::Terminated;
-
-                // Free user address space if applicable
-                if PROCESSES[i].is_user && PROCESSES[i].page_table != 0 {
-                    vmm::vmm_destroy_user_space(PROCESSES[i].page_table);
+                PROCESSES[i].exit_code = exit_code;
+                if pid != current {
+                    release_resources(i);
                 }
+                reap_excess_zombies();
+                return;
+            }
+        }
+    }
+}
+
+/// Terminate the running process and switch away for good. Used when the
+/// process asks to exit or faults. Nothing is freed here: we are still on
+/// the kernel stack and address space being given up.
+pub fn process_exit_current(exit_code: i32) -> ! {
+    if let Some(proc) = process_get_current() {
+        process_exit(proc.pid, exit_code);
+    }
+    loop { super::scheduler::scheduler_yield(); }
+}
 
-                // Free kernel stack
-                // SAFETY: Layout is infallible for these constants (64 KiB, align 16)
-                if PROCESSES[i].stack_virt != 0 {
-                    alloc::alloc::dealloc(
-                        PROCESSES[i].stack_virt as *mut u8,
-                        alloc::alloc::Layout::from_size_align(PROC_STACK_SIZE, 16).unwrap(),
-                    );
+/// Free the kernel stack and user address space of slot `idx`.
+fn release_resources(idx: usize) {
+    unsafe {
+        // Free user address space if applicable
+        if PROCESSES[idx].is_user && PROCESSES[idx].page_table != 0 {
+            vmm::vmm_destroy_user_space(PROCESSES[idx].page_table);
+            PROCESSES[idx].page_table = 0;
+        }
+
+        // Free kernel stack
+        // SAFETY: Layout is infallible for these constants (64 KiB, align 16)
+        if PROCESSES[idx].stack_virt != 0 {
+            alloc::alloc::dealloc(
+                PROCESSES[idx].stack_virt as *mut u8,
+                alloc::alloc::Layout::from_size_align(PROC_STACK_SIZE, 16).unwrap(),
+            );
+            PROCESSES[idx].stack_virt = 0;
+            PROCESSES[idx].stack_base = 0;
+        }
+    }
+}
+
+/// Free the stacks and address spaces that exited processes left behind
+/// when they terminated themselves. Called by scheduler_yield, never from
+/// an IRQ; the running process is never touched.
+pub fn process_release_exited() {
+    let current = super::scheduler::current_pid();
+    unsafe {
+        for i in 0..MAX_PROCESSES {
+            if PROCESSES[i].state == ProcState::Terminated && PROCESSES[i].pid != current {
+                release_resources(i);
+            }
+        }
+    }
+}
+
+// Exceptions a user process can raise on its own; each kills only that process
+const USER_FAULT_VECTORS: [u8; 3] = [
+    0,  // Division Error
+    6,  // Invalid Opcode
+    13, // General Protection Fault
+];
+
+fn user_fault_handler(frame: &mut InterruptFrame) -> bool {
+    if frame.cs & 3 == 0 { return false; } // kernel fault: default path
+    let pid = process_get_current().map(|p| p.pid).unwrap_or(0);
+    crate::serial_println!("[FAULT] Exception {} at rip {:#x} in pid {}, killing process",
+                           frame.vector, frame.rip, pid);
+    process_exit_current(-1)
+}
+
+/// Route user-mode #DE/#UD/#GP to process termination instead of the halt path.
+pub fn process_init_fault_handlers() {
+    for &v in USER_FAULT_VECTORS.iter() {
+        idt::exception_register_handler(v, user_fault_handler);
+    }
+}
+
+/// Free the oldest (lowest-pid) zombies until at most MAX_ZOMBIES remain.
+/// A zombie that is still the running process is counted but never freed.
+fn reap_excess_zombies() {
+    let current = super::scheduler::current_pid();
+    unsafe {
+        loop {
+            let mut count = 0;
+            let mut oldest: Option<usize> = None;
+            for i in 0..MAX_PROCESSES {
+                if PROCESSES[i].state != ProcState::Terminated { continue; }
+                count += 1;
+                if PROCESSES[i].pid == current { continue; }
+                if oldest.map_or(true, |o| PROCESSES[i].pid < PROCESSES[o].pid) {
+                    oldest = Some(i);
                 }
+            }
+            match oldest {
+                Some(i) if count > MAX_ZOMBIES => free_slot(i),
+                _ => return,
+            }
+        }
+    }
+}
 
-                PROCESSES[i].state = ProcState::Free;
-                PROCESSES[i].pid = 0;
-                return;
+fn free_slot(idx: usize) {
+    release_resources(idx);
+    unsafe {
+        PROCESSES[idx].state = ProcState::Free;
+        PROCESSES[idx].pid = 0;
+        PROCESSES[idx].exit_code = 0;
+    }
+}
+
+/// Reap a terminated process: return its exit code and free the slot.
+/// None if `pid` is still running, was already reaped, or never existed.
+pub fn process_waitpid(pid: u64) -> Option<i32> {
+    // Still on its own stack until it has switched away
+    if pid == super::scheduler::current_pid() { return None; }
+    unsafe {
+        for i in 0..MAX_PROCESSES {
+            if PROCESSES[i].pid == pid && PROCESSES[i].state == ProcState::Terminated {
+                let code = PROCESSES[i].exit_code;
+                free_slot(i);
+                return Some(code);
+            }
+        }
+    }
+    None
+}
+
+/// Terminate the lowest-pid process whose name is exactly `name`.
+/// Refuses pid 0 and the calling process with VOS_ERR_PERM.
+pub fn process_kill_by_name(name: &str) -> i32 {
+    let pid = match process_find_by_name(name) {
+        Some(p) => p,
+        None => return VOS_ERR_NOTFOUND,
+    };
+    if pid == 0 || pid == super::scheduler::current_pid() {
+        return VOS_ERR_PERM;
+    }
+    process_exit(pid, 0);
+    VOS_OK
+}
+
+// ---------------------------------------------------------------------------
+// Sleep
+// ---------------------------------------------------------------------------
+
+#[derive(Clone, Copy)]
+struct Sleeper {
+    pid: u64,
+    wake_ms: u64,
+}
+
+// One entry per sleeping process; a process can only sleep once at a time
+static mut SLEEP_QUEUE: [Option<Sleeper>; MAX_PROCESSES] = [None; MAX_PROCESSES];
+static mut SLEEP_TIMER_REGISTERED: bool = false;
+
+fn sleep_tick() {
+    process_wake_sleepers(pit::pit_get_uptime_ms());
+}
+
+/// Block the current process for at least `ms` milliseconds. Returns once
+/// the timer tick has woken it.
+pub fn process_sleep_ms(ms: u64) {
+    let proc = match process_get_current() {
+        Some(p) => p,
+        None => return,
+    };
+    let wake_ms = pit::pit_get_uptime_ms() + ms;
+    unsafe {
+        // The wake-up check runs on every tick once anyone has slept
+        if !SLEEP_TIMER_REGISTERED {
+            if timer::pit_register_callback(1, sleep_tick) != VOS_OK { return; }
+            SLEEP_TIMER_REGISTERED = true;
+        }
+        let slot = match SLEEP_QUEUE.iter().position(|s| s.is_none()) {
+            Some(i) => i,
+            None => return,
+        };
+        // Block before queueing so a tick that fires in between can only
+        // wake us early, never leave us blocked with no queue entry
+        proc.state = ProcState::Blocked;
+        SLEEP_QUEUE[slot] = Some(Sleeper { pid: proc.pid, wake_ms });
+    }
+    // With nothing else runnable the scheduler comes straight back; wait
+    // here for sleep_tick rather than run on while still Blocked. The state
+    // changes under us from the IRQ, hence the volatile reads.
+    let blocked = || unsafe { core::ptr::read_volatile(&proc.state) } == ProcState::Blocked;
+    while blocked() {
+        super::scheduler::scheduler_yield();
+        if blocked() { cpu::hlt(); }
+    }
+}
+
+/// Move sleepers whose deadline has passed back to Ready.
+/// Runs from the timer tick via sleep_tick.
+pub fn process_wake_sleepers(now_ms: u64) {
+    unsafe {
+        for entry in SLEEP_QUEUE.iter_mut() {
+            let s = match *entry {
+                Some(s) => s,
+                None => continue,
+            };
+            if now_ms < s.wake_ms { continue; }
+            // A sleeper that exited meanwhile just drops out of the queue
+            if let Some(i) = process_index_of(s.pid) {
+                if PROCESSES[i].state == ProcState::Blocked {
+                    PROCESSES[i].state = ProcState::Ready;
+                }
             }
+            *entry = None;
         }
     }
 }
@@ -303,10 +551,30 @@ pub fn process_exit(pid: u64, _exit_code: i32) {
 // Lookup
 // ---------------------------------------------------------------------------
 
+/// In use and not yet exited. Terminated slots only answer process_waitpid.
+fn live(p: &Process) -> bool {
+    p.state != ProcState::Free && p.state != ProcState::Terminated
+}
+
+/// Lowest pid among live processes named exactly `name`.
+pub fn process_find_by_name(name: &str) -> Option<u64> {
+    let mut best: Option<u64> = None;
+    unsafe {
+        for i in 0..MAX_PROCESSES {
+            let p = &PROCESSES[i];
+            if !live(p) { continue; }
+            if name_as_str(&p.name) != name { continue; }
+            if best.map_or(true, |b| p.pid < b) { best = Some(p.pid); }
+        }
+    }
+    best
+}
+
+/// Live process with `pid`; None once it has exited.
 pub fn process_get_by_pid(pid: u64) -> Option<&'static mut Process> {
     unsafe {
         for i in 0..MAX_PROCESSES {
-            if PROCESSES[i].pid == pid && PROCESSES[i].state != ProcState::Free {
+            if PROCESSES[i].pid == pid && live(&PROCESSES[i]) {
                 return Some(&mut PROCESSES[i]);
             }
         }
@@ -325,14 +593,57 @@ pub fn process_by_index(idx: usize) -> &'static mut Process {
     unsafe { &mut PROCESSES[idx] }
 }
 
-/// Find table index for a pid.
+/// Find table index for a live pid.
 pub fn process_index_of(pid: u64) -> Option<usize> {
     unsafe {
         for i in 0..MAX_PROCESSES {
-            if PROCESSES[i].pid == pid && PROCESSES[i].state != ProcState::Free {
+            if PROCESSES[i].pid == pid && live(&PROCESSES[i]) {
                 return Some(i);
             }
         }
     }
     None
 }
+
+/// Set the scheduling class of `pid` (0..=PRIO_MAX). Takes effect at the
+/// next timeslice. VOS_ERR_INVAL for an out-of-range class.
+pub fn process_set_priority(pid: u64, prio: u8) -> i32 {
+    if prio > PRIO_MAX { return VOS_ERR_INVAL; }
+    match process_get_by_pid(pid) {
+        Some(p) => { p.priority = prio; VOS_OK }
+        None => VOS_ERR_NOTFOUND,
+    }
+}
+
+/// Stack allocation, state and priority of `pid`, or None if it is not live.
+pub fn process_get_stats(pid: u64) -> Option<ProcessStats> {
+    let p = process_get_by_pid(pid)?;
+    Some(ProcessStats {
+        pid: p.pid,
+        state: p.state,
+        priority: p.priority,
+        is_user: p.is_user,
+        kernel_stack: if p.stack_base != 0 { PROC_STACK_SIZE as u64 } else { 0 },
+        user_stack: if p.is_user && p.user_stack_base != 0 { USER_STACK_SIZE } else { 0 },
+    })
+}
+
+/// Copy every in-use slot into `out` (up to its length). Returns the count.
+pub fn process_list(out: &mut [ProcessInfo]) -> usize {
+    let mut n = 0;
+    unsafe {
+        for i in 0..MAX_PROCESSES {
+            if n >= out.len() { break; }
+            let p = &PROCESSES[i];
+            if p.state == ProcState::Free { continue; }
+            out[n] = ProcessInfo {
+                pid: p.pid,
+                name: p.name,
+                state: p.state,
+                priority: p.priority,
+            };
+            n += 1;
+        }
+    }
+    n
+}
@@ -0,0 +1,126 @@
+// Preemptive scheduler over the static process table
+//
+// Every TIMESLICE ticks, and whenever a process yields, sched_pick_next
+// chooses the slot to run next by priority class (see priority.rs). The
+// outgoing process goes back to Ready unless it blocked or exited on the
+// way in; the incoming one becomes Running and gets its kernel stack
+// installed as TSS RSP0 for ring 3 -> ring 0 transitions.
+//
+// schedule() runs with interrupts off: from IRQ0 they already are, and
+// scheduler_yield turns them off itself so a timer tick cannot re-enter
+// it halfway through a switch.
+
+use crate::arch::x86_64::{gdt, timer};
+use crate::sync::spinlock::{irq_restore, irq_save};
+use super::priority;
+use super::process::{process_by_index, process_release_exited, Context, ProcState, PROC_STACK_SIZE};
+
+/// Ticks per timeslice.
+const TIMESLICE: u64 = 10;
+
+static mut CURRENT: Option<usize> = None;
+static mut TICK_COUNT: u64 = 0;
+static mut ACTIVE: bool = false;
+// Boot stack state left behind when the first process is switched in
+static mut BOOT_CONTEXT: Context = Context::zero();
+
+extern "C" {
+    fn context_switch(current: *mut Context, next: *const Context);
+}
+
+pub fn scheduler_init() {
+    unsafe {
+        CURRENT = None;
+        TICK_COUNT = 0;
+        ACTIVE = false;
+    }
+    crate::serial_println!("[SCHED] Scheduler initialized");
+}
+
+/// Pid of the running process, or 0 before the scheduler has started.
+pub fn current_pid() -> u64 {
+    unsafe {
+        match CURRENT {
+            Some(i) => process_by_index(i).pid,
+            None => 0,
+        }
+    }
+}
+
+fn schedule() {
+    unsafe {
+        if !ACTIVE { return; }
+        let next = match priority::sched_pick_next() {
+            Some(i) => i,
+            None => return,
+        };
+        let prev = CURRENT;
+        if prev == Some(next) { return; } // same process, no switch needed
+
+        if let Some(p) = prev {
+            let proc = process_by_index(p);
+            if proc.state == ProcState::Running {
+                proc.state = ProcState::Ready;
+            }
+        }
+
+        let next_proc = process_by_index(next);
+        next_proc.state = ProcState::Running;
+        CURRENT = Some(next);
+
+        // Update TSS RSP0 for ring transitions
+        gdt::gdt_set_tss_rsp0(next_proc.stack_base + PROC_STACK_SIZE as u64);
+
+        if let Some(p) = prev {
+            context_switch(&mut process_by_index(p).context, &next_proc.context);
+        }
+    }
+}
+
+/// Called by isr_handler on every IRQ0, after the PIT handler.
+pub fn scheduler_tick() {
+    unsafe {
+        TICK_COUNT += 1;
+        if !ACTIVE { return; }
+        if TICK_COUNT % TIMESLICE == 0 {
+            schedule();
+        }
+    }
+}
+
+/// Give up the rest of the timeslice.
+pub fn scheduler_yield() {
+    // Never reached from an IRQ, so the heap is safe to use here
+    process_release_exited();
+    timer::timer_run_deferred();
+    let irq_was_on = irq_save();
+    schedule();
+    // Back in this process: the switch restored the rflags it saved
+    irq_restore(irq_was_on);
+}
+
+/// Run the first process picked and never return.
+pub fn scheduler_start() -> ! {
+    let first = match priority::sched_pick_next() {
+        Some(i) => i,
+        None => panic!("scheduler_start: no runnable process"),
+    };
+    let proc = process_by_index(first);
+    proc.state = ProcState::Running;
+    unsafe {
+        CURRENT = Some(first);
+        ACTIVE = true;
+    }
+    gdt::gdt_set_tss_rsp0(proc.stack_base + PROC_STACK_SIZE as u64);
+
+    crate::serial_println!("[SCHED] Starting first process: '{}' (pid={})",
+                           proc.name_str(), proc.pid);
+
+    // Switch in through the context process_create built: its rip is the
+    // entry point, rsp the top of its own stack, and rflags has IF set
+    unsafe {
+        context_switch(&mut BOOT_CONTEXT, &proc.context);
+    }
+
+    panic!("scheduler_start returned!");
+}
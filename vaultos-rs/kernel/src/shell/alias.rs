@@ -0,0 +1,198 @@
+// User-defined shell aliases
+//
+// `alias ll = "show objects"` makes `ll` stand for `show objects`. Only the
+// first word of a command line is looked up; the rest of the line is kept,
+// so `ll sort name` runs `show objects sort name`. An alias may start with
+// another alias, up to ALIAS_DEPTH_MAX levels; a cycle is an error rather
+// than a hang.
+//
+// Each alias is a SystemTable row keyed `alias.<name>`, so it is encrypted
+// and persisted like the rest of the configuration. The in-memory copy is
+// filled from SystemTable on first use.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use crate::db::database;
+use crate::db::query::{query_execute, query_execute_with};
+use crate::db::record::{FieldValue, StrField};
+use vaultos_shared::error_codes::*;
+
+pub const ALIAS_KEY_PREFIX: &str = "alias.";
+const ALIAS_NAME_MAX: usize = 32;
+const ALIAS_MAX: usize = 32;
+const ALIAS_DEPTH_MAX: usize = 8;
+
+static mut ALIASES: Vec<(String, String)> = Vec::new();
+static mut LOADED: bool = false;
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= ALIAS_NAME_MAX
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        && !database::str_eq_ignore_case(name, "alias")
+        && !database::str_eq_ignore_case(name, "unalias")
+}
+
+fn str_param(s: &str) -> FieldValue {
+    FieldValue::Str(StrField::from_str(s))
+}
+
+/// Read every `alias.*` row of SystemTable into memory, replacing what was
+/// there. Called at boot; lookups also call it once if boot did not.
+pub fn alias_load() {
+    let mut sql = String::new();
+    let _ = write!(sql, "SELECT * FROM SystemTable WHERE key LIKE '{}%' ORDER BY key", ALIAS_KEY_PREFIX);
+    let result = query_execute(&sql, 0);
+    unsafe {
+        ALIASES.clear();
+        for row in result.rows.iter() {
+            let key = match row.fields[1] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => continue };
+            let cmd = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => continue };
+            let name = &key[ALIAS_KEY_PREFIX.len().min(key.len())..];
+            if valid_name(name) && ALIASES.len() < ALIAS_MAX {
+                ALIASES.push((String::from(name), String::from(cmd)));
+            }
+        }
+        LOADED = true;
+    }
+}
+
+fn ensure_loaded() {
+    if unsafe { !LOADED } { alias_load(); }
+}
+
+fn lookup(name: &str) -> Option<&'static str> {
+    ensure_loaded();
+    unsafe {
+        ALIASES.iter()
+            .find(|(n, _)| database::str_eq_ignore_case(n, name))
+            .map(|(_, cmd)| cmd.as_str())
+    }
+}
+
+/// Define or redefine `name`, in memory and in SystemTable.
+pub fn alias_define(name: &str, cmd: &str) -> i32 {
+    let cmd = cmd.trim();
+    if !valid_name(name) || cmd.is_empty() { return VOS_ERR_INVAL; }
+    ensure_loaded();
+
+    let mut key = String::from(ALIAS_KEY_PREFIX);
+    key.push_str(name);
+    let res = query_execute_with("INSERT OR REPLACE INTO SystemTable (key, value) VALUES (?, ?)",
+                                 &[str_param(&key), str_param(cmd)], 0);
+    if res.error_code != VOS_OK { return res.error_code; }
+
+    unsafe {
+        match ALIASES.iter_mut().find(|(n, _)| database::str_eq_ignore_case(n, name)) {
+            Some(entry) => entry.1 = String::from(cmd),
+            None if ALIASES.len() < ALIAS_MAX => ALIASES.push((String::from(name), String::from(cmd))),
+            None => return VOS_ERR_FULL,
+        }
+    }
+    database::db_flush()
+}
+
+/// Forget `name`. VOS_ERR_NOTFOUND if it was not defined.
+pub fn alias_remove(name: &str) -> i32 {
+    ensure_loaded();
+    let idx = unsafe { ALIASES.iter().position(|(n, _)| database::str_eq_ignore_case(n, name)) };
+    let idx = match idx {
+        Some(i) => i,
+        None => return VOS_ERR_NOTFOUND,
+    };
+
+    let mut key = String::from(ALIAS_KEY_PREFIX);
+    unsafe { key.push_str(&ALIASES[idx].0); }
+    let res = query_execute_with("DELETE FROM SystemTable WHERE key = ?", &[str_param(&key)], 0);
+    if res.error_code != VOS_OK { return res.error_code; }
+    unsafe { ALIASES.remove(idx); }
+    database::db_flush()
+}
+
+/// Expand the first word of `line` if it is an alias, repeatedly.
+/// Ok(None) means no alias applied; Err(VOS_ERR_INVAL) reports a cycle.
+pub fn alias_expand(line: &str) -> Result<Option<String>, i32> {
+    let mut cur = String::from(line.trim());
+    let mut seen: Vec<String> = Vec::new();
+    loop {
+        let (first, rest) = match cur.split_once(' ') {
+            Some((f, r)) => (f, r),
+            None => (cur.as_str(), ""),
+        };
+        let cmd = match lookup(first) {
+            Some(c) => c,
+            None => break,
+        };
+        if seen.len() >= ALIAS_DEPTH_MAX
+            || seen.iter().any(|s| database::str_eq_ignore_case(s, first)) {
+            return Err(VOS_ERR_INVAL);
+        }
+        seen.push(String::from(first));
+
+        let mut next = String::from(cmd);
+        if !rest.is_empty() {
+            next.push(' ');
+            next.push_str(rest);
+        }
+        cur = next;
+    }
+    Ok(if seen.is_empty() { None } else { Some(cur) })
+}
+
+/// `alias` lists, `alias name` shows one, `alias name = "cmd"` defines.
+/// Output goes through `print`, as with friendly::write_help.
+pub fn alias_command(args: &str, print: fn(&str)) {
+    let args = args.trim();
+    if args.is_empty() {
+        ensure_loaded();
+        let aliases = unsafe { &ALIASES };
+        if aliases.is_empty() { print("  No aliases defined\n"); }
+        for (name, cmd) in aliases.iter() {
+            let mut line = String::new();
+            let _ = write!(line, "  {:<12} = \"{}\"\n", name, cmd);
+            print(&line);
+        }
+        return;
+    }
+
+    let (name, cmd) = match args.split_once('=') {
+        Some((n, c)) => (n.trim(), c.trim()),
+        None => {
+            let mut line = String::new();
+            match lookup(args) {
+                Some(cmd) => { let _ = write!(line, "  {} = \"{}\"\n", args, cmd); }
+                None => { let _ = write!(line, "  No alias '{}'\n", args); }
+            }
+            print(&line);
+            return;
+        }
+    };
+    let unquoted = cmd.strip_prefix('"').and_then(|c| c.strip_suffix('"'))
+        .or_else(|| cmd.strip_prefix('\'').and_then(|c| c.strip_suffix('\'')))
+        .unwrap_or(cmd);
+
+    match alias_define(name, unquoted) {
+        VOS_OK => {}
+        VOS_ERR_INVAL => print("  Usage: alias <name> = \"<command>\" (name: letters, digits, _ -)\n"),
+        VOS_ERR_FULL => print("  Too many aliases\n"),
+        err => {
+            let mut line = String::new();
+            let _ = write!(line, "  alias failed ({})\n", err);
+            print(&line);
+        }
+    }
+}
+
+/// `unalias name`
+pub fn unalias_command(args: &str, print: fn(&str)) {
+    let name = args.trim();
+    match alias_remove(name) {
+        VOS_OK => {}
+        VOS_ERR_NOTFOUND => print("  No such alias\n"),
+        err => {
+            let mut line = String::new();
+            let _ = write!(line, "  unalias failed ({})\n", err);
+            print(&line);
+        }
+    }
+}
@@ -13,6 +13,17 @@ static ALIASES: &[(&str, &str)] = &[
     ("sys",     "SystemTable"),
 ];
 
+/// Verbs understood by `translate` (used for completion).
+pub static VERBS: &[&str] = &[
+    "tables", "show", "list", "info", "count", "find", "add",
+    "del", "rm", "set", "create", "open", "cat", "ps", "help",
+];
+
+/// Alias names accepted wherever a table name is expected.
+pub fn alias_names() -> impl Iterator<Item = &'static str> {
+    ALIASES.iter().map(|&(alias, _)| alias)
+}
+
 /// Resolve alias to real table name, or return as-is.
 pub fn resolve_alias(name: &str) -> &str {
     for &(alias, real) in ALIASES {
@@ -23,8 +34,63 @@ pub fn resolve_alias(name: &str) -> &str {
     name
 }
 
-/// Try to translate a friendly command to SQL. Returns Some(sql_bytes_len) on success.
-pub fn translate(input: &[u8], sql: &mut [u8; SQL_MAX]) -> Option<usize> {
+/// Friendly verbs with their usage, as printed by `help <anything>`.
+static VERB_HELP: &[(&str, &str)] = &[
+    ("tables",                       "list all tables"),
+    ("show <table> [sort c] [limit n]", "list the rows of a table"),
+    ("find <table> col>=val [or] ...", "rows matching = != < > <= >="),
+    ("count <table> [col=val ...]",  "number of rows"),
+    ("info <table>",                 "column layout of a table"),
+    ("add <table> col=val ...",      "insert a row"),
+    ("del <table> col=val ...",      "delete matching rows"),
+    ("set <table> col=val where k=v", "update matching rows"),
+    ("create <type> <name> [text]",  "create an object"),
+    ("open <name>",                  "show an object"),
+    ("ps",                           "list processes"),
+];
+
+/// Result of `translate`.
+pub enum Translation {
+    /// SQL of the given length was written to the buffer
+    Sql(usize),
+    /// The user asked for help; print it with `write_help` instead of running SQL
+    Help,
+}
+
+/// Print the friendly verbs and table aliases through `print`.
+pub fn write_help(print: fn(&str)) {
+    print("  Friendly commands:\n");
+    for &(usage, what) in VERB_HELP {
+        print("    ");
+        print(usage);
+        for _ in usage.len()..32 { print(" "); }
+        print(what);
+        print("\n");
+    }
+    print("  Table aliases:\n");
+    for &(alias, real) in ALIASES {
+        print("    ");
+        print(alias);
+        for _ in alias.len()..10 { print(" "); }
+        print("-> ");
+        print(real);
+        print("\n");
+    }
+}
+
+/// Try to translate a friendly command. `help ...` yields `Translation::Help`
+/// (a bare `help` is taken by the shell's own command list first).
+pub fn translate(input: &[u8], sql: &mut [u8; SQL_MAX]) -> Option<Translation> {
+    let input_str = to_str(input).trim();
+    let verb = input_str.split(' ').next().unwrap_or("");
+    if eq_ci(verb, "help") {
+        return Some(Translation::Help);
+    }
+    translate_sql(input, sql).map(Translation::Sql)
+}
+
+/// Translate a friendly command to SQL. Returns Some(sql_bytes_len) on success.
+fn translate_sql(input: &[u8], sql: &mut [u8; SQL_MAX]) -> Option<usize> {
     let input_str = to_str(input);
     let trimmed = input_str.trim();
     if trimmed.is_empty() { return None; }
@@ -40,11 +106,14 @@ pub fn translate(input: &[u8], sql: &mut [u8; SQL_MAX]) -> Option<usize> {
         return write_sql(sql, b"SHOW TABLES");
     }
 
-    // show <table> → SELECT * FROM <table>
+    // show <table> [sort col [asc|desc]] [limit n] → SELECT * FROM <table> [ORDER BY ...] [LIMIT n]
     if eq_ci(verb, "show") || eq_ci(verb, "list") {
         if count >= 2 {
             let table = resolve_alias(tokens[1].0);
-            return write_fmt(sql, &[b"SELECT * FROM ", table.as_bytes()]);
+            let mut pos = write_to(sql, 0, b"SELECT * FROM ");
+            pos = write_to(sql, pos, table.as_bytes());
+            pos = append_modifiers(&tokens, 2, count, sql, pos);
+            return Some(pos);
         } else if eq_ci(verb, "list") {
             return write_sql(sql, b"SELECT * FROM ObjectTable");
         }
@@ -57,21 +126,29 @@ pub fn translate(input: &[u8], sql: &mut [u8; SQL_MAX]) -> Option<usize> {
         return write_fmt(sql, &[b"DESCRIBE ", table.as_bytes()]);
     }
 
-    // count <table> → SELECT * FROM <table> (result count shown by display)
+    // count <table> [col=val ...] → SELECT COUNT(*) FROM <table> [WHERE ...]
     if eq_ci(verb, "count") && count >= 2 {
         let table = resolve_alias(tokens[1].0);
-        return write_fmt(sql, &[b"SELECT * FROM ", table.as_bytes()]);
+        let mut pos = write_to(sql, 0, b"SELECT COUNT(*) FROM ");
+        pos = write_to(sql, pos, table.as_bytes());
+        if has_condition(&tokens, 2, count) {
+            pos = write_to(sql, pos, b" WHERE ");
+            pos = build_where(trimmed, &tokens, 2, count, sql, pos);
+        }
+        return Some(pos);
     }
 
-    // find <table> [col=val ...] → SELECT * FROM <table> WHERE ...
+    // find <table> [col=val ...] [sort col [asc|desc]] → SELECT * FROM <table> WHERE ...
     if eq_ci(verb, "find") && count >= 2 {
         let table = resolve_alias(tokens[1].0);
+        let conds_end = modifier_start(&tokens, 2, count);
         let mut pos = write_to(sql, 0, b"SELECT * FROM ");
         pos = write_to(sql, pos, table.as_bytes());
-        if count > 2 {
+        if has_condition(&tokens, 2, conds_end) {
             pos = write_to(sql, pos, b" WHERE ");
-            pos = build_where(trimmed, &tokens, 2, count, sql, pos);
+            pos = build_where(trimmed, &tokens, 2, conds_end, sql, pos);
         }
+        pos = append_modifiers(&tokens, conds_end, count, sql, pos);
         return Some(pos);
     }
 
@@ -163,15 +240,19 @@ fn build_where(
     start: usize, count: usize, sql: &mut [u8; SQL_MAX], mut pos: usize,
 ) -> usize {
     let mut first = true;
+    let mut joiner: &[u8] = b" AND ";
     for i in start..count {
         let tok = tokens[i].0;
         if eq_ci(tok, "where") { continue; }
-        if let Some(eq_pos) = tok.find('=') {
-            if !first { pos = write_to(sql, pos, b" AND "); }
-            let col = &tok[..eq_pos];
-            let val = &tok[eq_pos + 1..];
+        // `or` joins the next condition with OR; plain adjacency means AND
+        if eq_ci(tok, "or") { joiner = b" OR "; continue; }
+        if let Some((col, op, val)) = split_cond(tok) {
+            if !first { pos = write_to(sql, pos, joiner); }
+            joiner = b" AND ";
             pos = write_to(sql, pos, col.as_bytes());
-            pos = write_to(sql, pos, b" = ");
+            pos = write_to(sql, pos, b" ");
+            pos = write_to(sql, pos, op.as_bytes());
+            pos = write_to(sql, pos, b" ");
             pos = append_value(sql, pos, val);
             first = false;
         }
@@ -179,6 +260,78 @@ fn build_where(
     pos
 }
 
+/// Split a `col<op>val` token, where op is one of = != < > <= >=.
+fn split_cond(tok: &str) -> Option<(&str, &'static str, &str)> {
+    let bytes = tok.as_bytes();
+    let at = bytes.iter().position(|&b| b == b'=' || b == b'<' || b == b'>' || b == b'!')?;
+    if at == 0 { return None; }
+    let next_eq = bytes.get(at + 1) == Some(&b'=');
+    let (op, len) = match (bytes[at], next_eq) {
+        (b'<', true)  => ("<=", 2),
+        (b'>', true)  => (">=", 2),
+        (b'!', true)  => ("!=", 2),
+        (b'<', false) => ("<", 1),
+        (b'>', false) => (">", 1),
+        (b'=', _)     => ("=", 1),
+        _ => return None,
+    };
+    Some((&tok[..at], op, &tok[at + len..]))
+}
+
+/// Index of the first result modifier (`sort ...`, `limit ...`) at or after `start`.
+fn modifier_start(tokens: &[(&str, usize, usize); 32], start: usize, count: usize) -> usize {
+    (start..count)
+        .find(|&i| eq_ci(tokens[i].0, "sort") || eq_ci(tokens[i].0, "limit"))
+        .unwrap_or(count)
+}
+
+fn has_condition(tokens: &[(&str, usize, usize); 32], start: usize, end: usize) -> bool {
+    (start..end).any(|i| split_cond(tokens[i].0).is_some())
+}
+
+/// Append `ORDER BY col [DESC]` for `sort col [asc|desc]` and ` LIMIT n` for
+/// `limit n`, in the order SQL wants them. A non-numeric limit is ignored.
+fn append_modifiers(
+    tokens: &[(&str, usize, usize); 32], start: usize, count: usize,
+    sql: &mut [u8; SQL_MAX], mut pos: usize,
+) -> usize {
+    let mut sort: Option<(&str, bool)> = None;
+    let mut limit: Option<&str> = None;
+
+    let mut i = start;
+    while i < count {
+        if eq_ci(tokens[i].0, "sort") && i + 1 < count {
+            let mut desc = false;
+            let col = tokens[i + 1].0;
+            i += 2;
+            if i < count && (eq_ci(tokens[i].0, "desc") || eq_ci(tokens[i].0, "asc")) {
+                desc = eq_ci(tokens[i].0, "desc");
+                i += 1;
+            }
+            sort = Some((col, desc));
+            continue;
+        }
+        if eq_ci(tokens[i].0, "limit") && i + 1 < count {
+            let n = tokens[i + 1].0;
+            if is_numeric(n) && !n.starts_with('-') { limit = Some(n); }
+            i += 2;
+            continue;
+        }
+        i += 1;
+    }
+
+    if let Some((col, desc)) = sort {
+        pos = write_to(sql, pos, b" ORDER BY ");
+        pos = write_to(sql, pos, col.as_bytes());
+        if desc { pos = write_to(sql, pos, b" DESC"); }
+    }
+    if let Some(n) = limit {
+        pos = write_to(sql, pos, b" LIMIT ");
+        pos = write_to(sql, pos, n.as_bytes());
+    }
+    pos
+}
+
 fn build_insert(
     _input: &str, tokens: &[(&str, usize, usize); 32],
     start: usize, count: usize, table: &str, sql: &mut [u8; SQL_MAX],
@@ -0,0 +1,139 @@
+// Shell command history kept in SystemTable
+//
+// Every executed line is stored as a SystemTable row so Up-arrow recall
+// survives a reboot. Rows rotate through the keys history.00 .. history.99;
+// history.next holds the sequence number of the next line, so slot
+// (next % HISTORY_MAX) is the oldest once the ring has wrapped.
+//
+// `history` lines themselves are not recorded.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use crate::db::database;
+use crate::db::query::{query_execute, query_execute_with};
+use crate::db::record::{FieldValue, StrField};
+use vaultos_shared::error_codes::*;
+
+pub const HISTORY_MAX: usize = 100;
+const HISTORY_KEY_PREFIX: &str = "history.";
+const HISTORY_NEXT_KEY: &str = "history.next";
+
+static mut NEXT_SEQ: u64 = 0;
+static mut LOADED: bool = false;
+
+fn str_param(s: &str) -> FieldValue {
+    FieldValue::Str(StrField::from_str(s))
+}
+
+fn slot_key(slot: usize) -> String {
+    let mut key = String::new();
+    let _ = write!(key, "{}{:02}", HISTORY_KEY_PREFIX, slot);
+    key
+}
+
+fn put(key: &str, value: &str) -> i32 {
+    query_execute_with("INSERT OR REPLACE INTO SystemTable (key, value) VALUES (?, ?)",
+                       &[str_param(key), str_param(value)], 0).error_code
+}
+
+/// All history rows as (slot, line), plus the stored next sequence number.
+fn read_rows() -> (Vec<(usize, String)>, u64) {
+    let mut sql = String::new();
+    let _ = write!(sql, "SELECT * FROM SystemTable WHERE key LIKE '{}%'", HISTORY_KEY_PREFIX);
+    let result = query_execute(&sql, 0);
+    let mut rows = Vec::new();
+    let mut next = 0;
+    for row in result.rows.iter() {
+        let key = match row.fields[1] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => continue };
+        let value = match row.fields[2] { Some(FieldValue::Str(ref s)) => s.as_str(), _ => continue };
+        if key == HISTORY_NEXT_KEY {
+            next = value.parse::<u64>().unwrap_or(0);
+        } else if let Ok(slot) = key[HISTORY_KEY_PREFIX.len()..].parse::<usize>() {
+            if slot < HISTORY_MAX { rows.push((slot, String::from(value))); }
+        }
+    }
+    (rows, next)
+}
+
+fn ensure_loaded() {
+    unsafe {
+        if LOADED { return; }
+        NEXT_SEQ = read_rows().1;
+        LOADED = true;
+    }
+}
+
+fn is_history_command(line: &str) -> bool {
+    let verb = line.split(' ').next().unwrap_or("");
+    database::str_eq_ignore_case(verb, "history")
+}
+
+/// Store an executed line. Blank lines and `history` commands are skipped.
+pub fn history_record(line: &str) {
+    let line = line.trim();
+    if line.is_empty() || is_history_command(line) { return; }
+    ensure_loaded();
+    unsafe {
+        let slot = (NEXT_SEQ % HISTORY_MAX as u64) as usize;
+        if put(&slot_key(slot), line) != VOS_OK { return; }
+        NEXT_SEQ += 1;
+        let mut next = String::new();
+        let _ = write!(next, "{}", NEXT_SEQ);
+        let _ = put(HISTORY_NEXT_KEY, &next);
+    }
+    let _ = database::db_flush();
+}
+
+/// Stored lines, oldest first.
+pub fn history_entries() -> Vec<String> {
+    let (mut rows, next) = read_rows();
+    unsafe {
+        NEXT_SEQ = next;
+        LOADED = true;
+    }
+    // Slots from the oldest: next % MAX onward, then wrap
+    let oldest = (next % HISTORY_MAX as u64) as usize;
+    rows.sort_by_key(|&(slot, _)| (slot + HISTORY_MAX - oldest) % HISTORY_MAX);
+    rows.into_iter().map(|(_, line)| line).collect()
+}
+
+/// Delete every history row.
+pub fn history_clear() -> i32 {
+    let mut sql = String::new();
+    let _ = write!(sql, "DELETE FROM SystemTable WHERE key LIKE '{}%'", HISTORY_KEY_PREFIX);
+    let res = query_execute(&sql, 0);
+    if res.error_code != VOS_OK { return res.error_code; }
+    unsafe {
+        NEXT_SEQ = 0;
+        LOADED = true;
+    }
+    database::db_flush()
+}
+
+/// `history` prints the stored lines numbered; `history clear` empties it.
+pub fn history_command(args: &str, print: fn(&str)) {
+    let args = args.trim();
+    if database::str_eq_ignore_case(args, "clear") {
+        match history_clear() {
+            VOS_OK => print("  History cleared\n"),
+            err => {
+                let mut line = String::new();
+                let _ = write!(line, "  history clear failed ({})\n", err);
+                print(&line);
+            }
+        }
+        return;
+    }
+    if !args.is_empty() {
+        print("  Usage: history [clear]\n");
+        return;
+    }
+    let entries = history_entries();
+    if entries.is_empty() { print("  No history\n"); }
+    for (i, cmd) in entries.iter().enumerate() {
+        let mut line = String::new();
+        let _ = write!(line, "  {:>3}  {}\n", i + 1, cmd);
+        print(&line);
+    }
+}
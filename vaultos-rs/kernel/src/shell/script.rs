@@ -0,0 +1,82 @@
+// SQL scripts stored as objects
+//
+// `run <object>` executes the statements held in an ObjectTable object's
+// data, in order. Statements end at `;` or at a newline; a `;` or newline
+// inside a '...' literal does not split. Blank lines and lines starting
+// with `--` are skipped.
+//
+// By default the script stops at the first statement that fails; with
+// `--continue` it reports the failure and carries on.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::db::database::QueryResult;
+use crate::db::query::{query_execute, query_execute_with};
+use crate::db::record::{FieldValue, StrField};
+use vaultos_shared::error_codes::*;
+
+pub const CONTINUE_FLAG: &str = "--continue";
+
+pub struct ScriptSummary {
+    /// Statements in the script
+    pub total: usize,
+    /// Statements executed, including any that failed
+    pub ran: usize,
+    pub failed: usize,
+}
+
+/// Split script text into statements.
+pub fn script_statements(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut in_quote = false;
+    let mut start = 0;
+    for (i, b) in text.bytes().enumerate() {
+        match b {
+            b'\'' => in_quote = !in_quote,
+            b';' | b'\n' if !in_quote => {
+                push_statement(&mut out, &text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    push_statement(&mut out, &text[start..]);
+    out
+}
+
+fn push_statement<'a>(out: &mut Vec<&'a str>, stmt: &'a str) {
+    let stmt = stmt.trim();
+    if !stmt.is_empty() && !stmt.starts_with("--") { out.push(stmt); }
+}
+
+/// The data of object `name`, or None if there is no such object.
+pub fn script_load(name: &str) -> Option<String> {
+    let result = query_execute_with("SELECT * FROM ObjectTable WHERE name = ?",
+                                    &[FieldValue::Str(StrField::from_str(name))], 0);
+    let row = result.rows.first()?;
+    match row.fields[3] {
+        Some(FieldValue::Str(ref s)) => Some(String::from(s.as_str())),
+        Some(FieldValue::Blob(ref b)) => core::str::from_utf8(b.as_bytes()).ok().map(String::from),
+        _ => Some(String::new()),
+    }
+}
+
+/// Run the script in object `name` as `caller_pid`, handing each statement
+/// and its result to `report`. Err(VOS_ERR_NOTFOUND) if the object does not
+/// exist.
+pub fn script_run(name: &str, keep_going: bool, caller_pid: u64,
+                  report: &mut dyn FnMut(&str, &QueryResult)) -> Result<ScriptSummary, i32> {
+    let text = script_load(name).ok_or(VOS_ERR_NOTFOUND)?;
+    let stmts = script_statements(&text);
+    let mut summary = ScriptSummary { total: stmts.len(), ran: 0, failed: 0 };
+    for stmt in stmts {
+        let result = query_execute(stmt, caller_pid);
+        summary.ran += 1;
+        report(stmt, &result);
+        if result.error_code != VOS_OK {
+            summary.failed += 1;
+            if !keep_going { break; }
+        }
+    }
+    Ok(summary)
+}
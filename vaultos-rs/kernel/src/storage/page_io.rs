@@ -1,22 +1,36 @@
 // Page I/O for VaultOS CoW B-tree Persistence
 //
 // Serializes/deserializes B-tree nodes and encrypted records
-// to/from 4 KiB disk blocks.
+// to/from 4 KiB disk blocks. Node keys and child links are encrypted
+// with the index key; only the small page header stays in plaintext.
 
 use crate::db::btree::{BtreeNode, BTREE_MAX_KEYS, BTREE_ORDER};
+use crate::db::database::{db_index_open, db_index_seal};
 use crate::db::record::EncryptedRecord;
 use crate::storage::disk_alloc::*;
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
+use vaultos_shared::db_types::MAX_RECORD_SIZE;
 use vaultos_shared::error_codes::*;
 
-pub const PAGE_NODE_MAGIC: u32 = 0x4E4F4445;   // "NODE"
+pub const PAGE_NODE_MAGIC: u32 = 0x4E4F4433;   // "NOD3" (encrypted body, CRC32)
+/// Original node pages with an additive checksum; rejected on read.
+pub const PAGE_NODE_MAGIC_V1: u32 = 0x4E4F4445; // "NODE"
+/// Plaintext node pages with a CRC32 checksum; rejected on read.
+pub const PAGE_NODE_MAGIC_V2: u32 = 0x4E4F4432; // "NOD2"
 pub const PAGE_RECORD_MAGIC: u32 = 0x52454344;  // "RECD"
 
+/// Size of every node and record page, one disk block.
+pub const PAGE_BLOCK_SIZE: u64 = 4096;
+
 pub const RECORD_PAGE_PAYLOAD: usize = 4080;  // 4096 - 16 byte header
 /// Header in payload: row_id(8) + table_id(4) + ciphertext_len(4) + iv(16) + mac(32) = 64
 pub const RECORD_PAYLOAD_HDR: usize = 64;
+/// Longest chain a valid record can occupy: the payload header plus a
+/// padded MAX_RECORD_SIZE ciphertext. Longer chains mean disk corruption.
+pub const MAX_RECORD_CHAIN: usize =
+    (RECORD_PAYLOAD_HDR + MAX_RECORD_SIZE + 16 + RECORD_PAGE_PAYLOAD - 1) / RECORD_PAGE_PAYLOAD;
 
 /// On-disk node page layout (4096 bytes)
 #[repr(C)]
@@ -30,11 +44,20 @@ struct NodePage {
     keys: [u64; BTREE_MAX_KEYS],       // 63 * 8 = 504
     value_lbas: [u64; BTREE_MAX_KEYS],  // 63 * 8 = 504
     child_lbas: [u64; BTREE_ORDER],     // 64 * 8 = 512
-    reserved: [u8; 4096 - 1536],
+    iv: [u8; 16],
+    mac: [u8; 32],
+    reserved: [u8; 4096 - 1536 - 48],
 }
 
 const _: () = assert!(core::mem::size_of::<NodePage>() == 4096);
 
+/// Plaintext header covered by the MAC (magic .. table_id, before the checksum)
+const NODE_HDR_LEN: usize = 12;
+/// Encrypted body: keys, value_lbas and child_lbas (95 AES blocks)
+const NODE_BODY_START: usize = 16;
+const NODE_BODY_LEN: usize = 1520;
+const _: () = assert!(NODE_BODY_LEN % 16 == 0);
+
 /// On-disk record page layout (4096 bytes, chainable)
 #[repr(C)]
 struct RecordPage {
@@ -46,22 +69,42 @@ struct RecordPage {
 
 const _: () = assert!(core::mem::size_of::<RecordPage>() == 4096);
 
-/// Simple additive checksum over node page (excluding checksum field at offset 12..16).
+/// CRC-32 (IEEE 802.3, reflected polynomial 0xEDB88320) lookup table.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = CRC32_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+/// CRC32 over the node page, excluding the checksum field at offset 12..16.
 fn compute_node_checksum(pg: &NodePage) -> u32 {
     let data = unsafe {
         core::slice::from_raw_parts(pg as *const NodePage as *const u8, 4096)
     };
-    let mut sum: u32 = 0;
-    for i in 0..4096 {
-        if i >= 12 && i < 16 { continue; }
-        sum = sum.wrapping_add(data[i] as u32);
-    }
-    sum
+    let crc = crc32_update(0xFFFF_FFFF, &data[..12]);
+    !crc32_update(crc, &data[16..])
 }
 
 /// Write a B-tree node to a new disk block. Returns block index, 0 on failure.
 pub fn page_write_node(node: &mut BtreeNode, table_id: u32) -> u64 {
-    let block = disk_alloc_block();
+    let block = alloc_block();
     if block == 0 {
         crate::serial_println!("[PAGE] ERROR: Failed to allocate block for node");
         return 0;
@@ -84,6 +127,22 @@ pub fn page_write_node(node: &mut BtreeNode, table_id: u32) -> u64 {
         }
     }
 
+    let mut iv = [0u8; 16];
+    let mut mac = [0u8; 32];
+    {
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(&mut pg as *mut NodePage as *mut u8, 4096)
+        };
+        let (header, rest) = bytes.split_at_mut(NODE_BODY_START);
+        let body = &mut rest[..NODE_BODY_LEN];
+        if db_index_seal(&header[..NODE_HDR_LEN], body, &mut iv, &mut mac) != VOS_OK {
+            crate::serial_println!("[PAGE] ERROR: Failed to encrypt node page");
+            disk_free_block(block);
+            return 0;
+        }
+    }
+    pg.iv = iv;
+    pg.mac = mac;
     pg.checksum = compute_node_checksum(&pg);
 
     let buf = unsafe {
@@ -111,6 +170,10 @@ pub fn page_read_node(block: u64, node: &mut BtreeNode, table_id_out: &mut u8) -
         return VOS_ERR_IO;
     }
 
+    if pg.magic == PAGE_NODE_MAGIC_V1 || pg.magic == PAGE_NODE_MAGIC_V2 {
+        crate::serial_println!("[PAGE] ERROR: Legacy unencrypted node page, rejecting");
+        return VOS_ERR_INVAL;
+    }
     if pg.magic != PAGE_NODE_MAGIC {
         crate::serial_println!("[PAGE] ERROR: Bad node magic");
         return VOS_ERR_INVAL;
@@ -122,6 +185,23 @@ pub fn page_read_node(block: u64, node: &mut BtreeNode, table_id_out: &mut u8) -
         return VOS_ERR_INVAL;
     }
 
+    let iv = pg.iv;
+    let mac = pg.mac;
+    {
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(&mut pg as *mut NodePage as *mut u8, 4096)
+        };
+        let (header, rest) = bytes.split_at_mut(NODE_BODY_START);
+        let body = &mut rest[..NODE_BODY_LEN];
+        if db_index_open(&header[..NODE_HDR_LEN], body, &iv, &mac) != VOS_OK {
+            crate::serial_println!("[PAGE] ERROR: Node MAC verification failed");
+            return VOS_ERR_INVAL;
+        }
+    }
+    if pg.num_keys as usize > BTREE_MAX_KEYS {
+        return VOS_ERR_INVAL;
+    }
+
     node.num_keys = pg.num_keys;
     node.is_leaf = pg.is_leaf != 0;
     node.disk_lba = block;
@@ -161,74 +241,79 @@ pub fn page_write_record(enc: &EncryptedRecord) -> u64 {
     payload_buf[64..64 + enc.ciphertext_len as usize]
         .copy_from_slice(&enc.ciphertext[..enc.ciphertext_len as usize]);
 
-    let mut remaining = total_payload;
-    let mut offset = 0usize;
-    let mut first_block: u64 = 0;
-    let mut prev_block: u64 = 0;
-    let mut prev_pg: Option<RecordPage> = None;
-
-    while remaining > 0 {
-        let block = disk_alloc_block();
+    // Allocate the whole chain up front
+    let nblocks = (total_payload + RECORD_PAGE_PAYLOAD - 1) / RECORD_PAGE_PAYLOAD;
+    let mut blocks: Vec<u64> = Vec::with_capacity(nblocks);
+    for _ in 0..nblocks {
+        let block = alloc_block();
         if block == 0 {
             crate::serial_println!("[PAGE] ERROR: Failed to allocate block for record");
-            if first_block != 0 {
-                page_free_record_blocks(first_block);
-            }
+            for &b in &blocks { disk_free_block(b); }
             return 0;
         }
+        blocks.push(block);
+    }
+
+    // Write tail-first: the head block, which is the only one a B-tree node
+    // references, goes to disk last, so a crash mid-write never leaves a
+    // reachable head pointing at unwritten pages.
+    for i in (0..nblocks).rev() {
+        let offset = i * RECORD_PAGE_PAYLOAD;
+        let chunk = (total_payload - offset).min(RECORD_PAGE_PAYLOAD);
 
         let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
         pg.magic = PAGE_RECORD_MAGIC;
-
-        let chunk = remaining.min(RECORD_PAGE_PAYLOAD);
         pg.payload_len = chunk as u32;
-        pg.next_block = 0;
+        pg.next_block = if i + 1 < nblocks { blocks[i + 1] } else { 0 };
         pg.payload[..chunk].copy_from_slice(&payload_buf[offset..offset + chunk]);
 
-        // Link previous page to this one
-        if let Some(ref mut prev) = prev_pg {
-            prev.next_block = block;
-            let buf = unsafe {
-                core::slice::from_raw_parts(prev as *const RecordPage as *const u8, 4096)
-            };
-            let ret = disk_write_block(prev_block, buf);
-            if ret != VOS_OK {
-                crate::serial_println!("[PAGE] ERROR: Failed to write chained record block");
-                disk_free_block(block);
-                return 0;
-            }
-        }
-
-        if first_block == 0 { first_block = block; }
-
-        prev_pg = Some(pg);
-        prev_block = block;
-        offset += chunk;
-        remaining -= chunk;
-    }
-
-    // Write last page
-    if let Some(ref prev) = prev_pg {
         let buf = unsafe {
-            core::slice::from_raw_parts(prev as *const RecordPage as *const u8, 4096)
+            core::slice::from_raw_parts(&pg as *const RecordPage as *const u8, 4096)
         };
-        let ret = disk_write_block(prev_block, buf);
-        if ret != VOS_OK {
-            crate::serial_println!("[PAGE] ERROR: Failed to write final record block");
+        if disk_write_block(blocks[i], buf) != VOS_OK {
+            crate::serial_println!("[PAGE] ERROR: Failed to write record block");
+            for &b in &blocks { disk_free_block(b); }
             return 0;
         }
     }
 
-    first_block
+    blocks[0]
+}
+
+/// Tracks the blocks of one record chain so a corrupt `next_block` link that
+/// loops back, or a chain longer than any record, is caught instead of hanging.
+struct ChainGuard {
+    seen: [u64; MAX_RECORD_CHAIN],
+    count: usize,
+}
+
+impl ChainGuard {
+    fn new() -> Self {
+        ChainGuard { seen: [0; MAX_RECORD_CHAIN], count: 0 }
+    }
+
+    /// Record a visit to `block`. Returns false on a repeat or over the cap.
+    fn visit(&mut self, block: u64) -> bool {
+        if self.count >= MAX_RECORD_CHAIN { return false; }
+        if self.seen[..self.count].contains(&block) { return false; }
+        self.seen[self.count] = block;
+        self.count += 1;
+        true
+    }
 }
 
 /// Read an encrypted record from disk. Returns VOS_OK on success.
 pub fn page_read_record(block: u64, enc: &mut EncryptedRecord) -> i32 {
-    // First pass: count total payload length
+    // First pass: validate the chain and count total payload length
+    let mut guard = ChainGuard::new();
     let mut total_len: usize = 0;
     let mut cur = block;
 
     while cur != 0 {
+        if !guard.visit(cur) {
+            crate::serial_println!("[PAGE] ERROR: Record chain at block {} loops or is too long", block);
+            return VOS_ERR_INVAL;
+        }
         let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
         let buf = unsafe {
             core::slice::from_raw_parts_mut(&mut pg as *mut RecordPage as *mut u8, 4096)
@@ -236,17 +321,17 @@ pub fn page_read_record(block: u64, enc: &mut EncryptedRecord) -> i32 {
         let ret = disk_read_block(cur, buf);
         if ret != VOS_OK { return VOS_ERR_IO; }
         if pg.magic != PAGE_RECORD_MAGIC { return VOS_ERR_INVAL; }
+        if pg.payload_len as usize > RECORD_PAGE_PAYLOAD { return VOS_ERR_INVAL; }
         total_len += pg.payload_len as usize;
         cur = pg.next_block;
     }
 
     if total_len < RECORD_PAYLOAD_HDR { return VOS_ERR_INVAL; }
 
-    // Assemble full payload
+    // Assemble full payload from the blocks validated above
     let mut payload_buf = vec![0u8; total_len];
     let mut offset = 0usize;
-    cur = block;
-    while cur != 0 {
+    for &cur in &guard.seen[..guard.count] {
         let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
         let buf = unsafe {
             core::slice::from_raw_parts_mut(&mut pg as *mut RecordPage as *mut u8, 4096)
@@ -254,9 +339,9 @@ pub fn page_read_record(block: u64, enc: &mut EncryptedRecord) -> i32 {
         let ret = disk_read_block(cur, buf);
         if ret != VOS_OK { return VOS_ERR_IO; }
         let len = pg.payload_len as usize;
+        if len > RECORD_PAGE_PAYLOAD || offset + len > total_len { return VOS_ERR_INVAL; }
         payload_buf[offset..offset + len].copy_from_slice(&pg.payload[..len]);
         offset += len;
-        cur = pg.next_block;
     }
 
     // Parse header
@@ -270,7 +355,9 @@ pub fn page_read_record(block: u64, enc: &mut EncryptedRecord) -> i32 {
         Some(end) => end,
         None => return VOS_ERR_INVAL, // Overflow on corrupt data
     };
-    if ct_end > total_len {
+    // A chain always holds exactly the header plus ciphertext; anything
+    // else is a torn or stale chain
+    if ct_end != total_len {
         return VOS_ERR_INVAL;
     }
 
@@ -282,10 +369,16 @@ pub fn page_read_record(block: u64, enc: &mut EncryptedRecord) -> i32 {
     VOS_OK
 }
 
-/// Free all blocks in a record chain.
-pub fn page_free_record_blocks(block: u64) {
+/// Free all blocks in a record chain. Stops with VOS_ERR_INVAL, leaving the
+/// rest of the chain allocated, if a link loops back or the chain is too long.
+pub fn page_free_record_blocks(block: u64) -> i32 {
+    let mut guard = ChainGuard::new();
     let mut cur = block;
     while cur != 0 {
+        if !guard.visit(cur) {
+            crate::serial_println!("[PAGE] ERROR: Record chain at block {} loops or is too long", block);
+            return VOS_ERR_INVAL;
+        }
         let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
         let buf = unsafe {
             core::slice::from_raw_parts_mut(&mut pg as *mut RecordPage as *mut u8, 4096)
@@ -296,7 +389,148 @@ pub fn page_free_record_blocks(block: u64) {
         } else {
             0
         };
-        disk_free_block(cur);
+        page_free_block(cur);
         cur = next;
     }
+    VOS_OK
+}
+
+// ---------------------------------------------------------------------------
+// Blocks in use at boot
+// ---------------------------------------------------------------------------
+//
+// The allocator does not keep its free map on disk, so after a reboot it can
+// hand out a block that a loaded B-tree node or record chain still occupies.
+// Every tree the loader installs is walked once (page_reserve_tree) and its
+// blocks recorded here; alloc_block passes over them, leaving them allocated.
+// A block leaves the set when it is freed through page_free_block, after
+// which it really is free.
+
+static mut RESERVED: Vec<u64> = Vec::new(); // sorted
+
+fn is_reserved(block: u64) -> bool {
+    unsafe { RESERVED.binary_search(&block).is_ok() }
+}
+
+/// Add `block` to the set. False if it was already there.
+fn reserve(block: u64) -> bool {
+    unsafe {
+        match RESERVED.binary_search(&block) {
+            Ok(_) => false,
+            Err(i) => { RESERVED.insert(i, block); true }
+        }
+    }
+}
+
+/// disk_alloc_block that never returns a block a loaded tree still uses.
+fn alloc_block() -> u64 {
+    loop {
+        let block = disk_alloc_block();
+        if block == 0 || !is_reserved(block) { return block; }
+    }
+}
+
+/// Free a block that may belong to a tree loaded at boot. Anything that
+/// releases committed node or record blocks should come through here.
+pub fn page_free_block(block: u64) {
+    unsafe {
+        if let Ok(i) = RESERVED.binary_search(&block) { RESERVED.remove(i); }
+    }
+    disk_free_block(block);
+}
+
+/// Reserve every block of the record chain starting at `block`.
+fn reserve_record_chain(block: u64) -> i32 {
+    let mut guard = ChainGuard::new();
+    let mut cur = block;
+    while cur != 0 {
+        if !guard.visit(cur) { return VOS_ERR_INVAL; }
+        let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(&mut pg as *mut RecordPage as *mut u8, 4096)
+        };
+        if disk_read_block(cur, buf) != VOS_OK { return VOS_ERR_IO; }
+        if pg.magic != PAGE_RECORD_MAGIC { return VOS_ERR_INVAL; }
+        reserve(cur);
+        cur = pg.next_block;
+    }
+    VOS_OK
+}
+
+/// Reserve the node page at `block`, its record chains and every node below
+/// it. Must run for each tree loaded at boot, before any page_write_*.
+/// A block met twice is walked once, so a corrupt link cannot loop.
+pub fn page_reserve_tree(block: u64) -> i32 {
+    if block == 0 || !reserve(block) { return VOS_OK; }
+    let mut node = unsafe { Box::from_raw(BtreeNode::new(true)) };
+    let mut table_id = 0u8;
+    let err = page_read_node(block, &mut node, &mut table_id);
+    if err != VOS_OK { return err; }
+
+    for i in 0..node.num_keys as usize {
+        // Zero marks a value deleted in place
+        if node.value_lbas[i] != 0 {
+            let err = reserve_record_chain(node.value_lbas[i]);
+            if err != VOS_OK { return err; }
+        }
+    }
+    if !node.is_leaf {
+        for i in 0..=node.num_keys as usize {
+            let err = page_reserve_tree(node.child_lbas[i]);
+            if err != VOS_OK { return err; }
+        }
+    }
+    VOS_OK
+}
+
+// ---------------------------------------------------------------------------
+// Blocks released at the next commit
+// ---------------------------------------------------------------------------
+//
+// A block the committed tree still points at can't be reused before the
+// next commit has moved the on-disk root off it: a crash in between would
+// leave that root pointing at overwritten data. Such blocks are queued here
+// and stay allocated until page_release_pending frees them, once db_flush
+// has committed.
+
+static mut PENDING_FREE: Vec<u64> = Vec::new(); // sorted
+
+/// Queue `block` to be freed after the next commit. False if it already is.
+pub fn page_free_after_commit(block: u64) -> bool {
+    if block == 0 { return false; }
+    unsafe {
+        match PENDING_FREE.binary_search(&block) {
+            Ok(_) => false,
+            Err(i) => { PENDING_FREE.insert(i, block); true }
+        }
+    }
+}
+
+/// Queue every block of the record chain starting at `block`. Returns how
+/// many blocks were newly queued; a broken chain is queued up to the break.
+pub fn page_free_record_after_commit(block: u64) -> u64 {
+    let mut guard = ChainGuard::new();
+    let mut queued = 0u64;
+    let mut cur = block;
+    while cur != 0 {
+        if !guard.visit(cur) { break; }
+        let mut pg = unsafe { core::mem::zeroed::<RecordPage>() };
+        let buf = unsafe {
+            core::slice::from_raw_parts_mut(&mut pg as *mut RecordPage as *mut u8, 4096)
+        };
+        if disk_read_block(cur, buf) != VOS_OK || pg.magic != PAGE_RECORD_MAGIC { break; }
+        if page_free_after_commit(cur) { queued += 1; }
+        cur = pg.next_block;
+    }
+    queued
+}
+
+/// Free every queued block. Call only once a commit has succeeded.
+/// Returns the number of blocks freed.
+pub fn page_release_pending() -> u64 {
+    let pending = unsafe { core::mem::take(&mut PENDING_FREE) };
+    for &block in pending.iter() {
+        page_free_block(block);
+    }
+    pending.len() as u64
 }
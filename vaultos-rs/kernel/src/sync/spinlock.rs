@@ -0,0 +1,105 @@
+// Test-and-set spinlock for kernel state shared with interrupt context
+//
+// Taking the lock also disables interrupts, and dropping the guard restores
+// the interrupt flag as it was. On the single CPU this is what makes the
+// lock work: nothing can preempt the holder, so an IRQ handler, a timer
+// callback or a preempting task never sees the data half-updated. The
+// atomic flag keeps it correct if more CPUs are brought up later.
+//
+// Rules:
+//   - Hold a SpinLock only for short, bounded work; interrupts stay off.
+//   - Never block, sleep or yield while holding one.
+//   - Don't re-lock a lock you hold: with interrupts off on one CPU the
+//     second lock() spins forever. Use try_lock where re-entry is possible.
+
+use core::arch::asm;
+use core::cell::UnsafeCell;
+use core::hint::spin_loop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicBool, Ordering};
+use crate::arch::x86_64::cpu;
+
+const RFLAGS_IF: u64 = 1 << 9;
+
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    data: UnsafeCell<T>,
+}
+
+// SAFETY: access to `data` is serialized by `locked`
+unsafe impl<T: Send> Sync for SpinLock<T> {}
+
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+    irq_was_on: bool,
+}
+
+/// Disable interrupts and report whether they were enabled.
+pub fn irq_save() -> bool {
+    let rflags: u64;
+    unsafe {
+        asm!("pushfq", "pop {}", out(reg) rflags, options(nomem, preserves_flags));
+        cpu::cli();
+    }
+    rflags & RFLAGS_IF != 0
+}
+
+/// Re-enable interrupts if irq_save found them enabled.
+pub fn irq_restore(was_on: bool) {
+    if was_on { unsafe { cpu::sti(); } }
+}
+
+impl<T> SpinLock<T> {
+    pub const fn new(data: T) -> Self {
+        SpinLock { locked: AtomicBool::new(false), data: UnsafeCell::new(data) }
+    }
+
+    /// Disable interrupts and spin until the lock is ours.
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        let irq_was_on = irq_save();
+        while self.locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            spin_loop();
+        }
+        SpinLockGuard { lock: self, irq_was_on }
+    }
+
+    /// Take the lock only if it is free; interrupts are left alone on failure.
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        let irq_was_on = irq_save();
+        if self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return Some(SpinLockGuard { lock: self, irq_was_on });
+        }
+        irq_restore(irq_was_on);
+        None
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        irq_restore(self.irq_was_on);
+    }
+}